@@ -20,34 +20,35 @@ pub fn main() {
     } else {
         let file = File::open(&args[1]).unwrap();
         let image = MetadataImage::read(file).unwrap();
+        let cli_header = image.cli_header().unwrap();
 
         println!("CLI Header");
-        println!("  Size: {}", image.cli_header().header_size);
+        println!("  Size: {}", cli_header.header_size);
         println!(
             "  Runtime Version: {}.{}",
-            image.cli_header().major_runtime_version,
-            image.cli_header().minor_runtime_version
+            cli_header.major_runtime_version,
+            cli_header.minor_runtime_version
         );
-        println!("  Metadata: {}", image.cli_header().metadata);
-        println!("  Flags: {}", image.cli_header().flags);
+        println!("  Metadata: {}", cli_header.metadata);
+        println!("  Flags: {}", cli_header.flags);
         println!(
             "  Entrypoint Token: {}",
-            image.cli_header().entry_point_token
+            cli_header.entry_point_token
         );
-        println!("  Resources: {}", image.cli_header().resources);
-        println!("  Strong Name: {}", image.cli_header().strong_name);
+        println!("  Resources: {}", cli_header.resources);
+        println!("  Strong Name: {}", cli_header.strong_name);
         println!(
             "  Code Manager Table: {}",
-            image.cli_header().code_manager_table
+            cli_header.code_manager_table
         );
-        println!("  VTable Fixups: {}", image.cli_header().vtable_fixups);
+        println!("  VTable Fixups: {}", cli_header.vtable_fixups);
         println!(
             "  Export Address Table Jumps: {}",
-            image.cli_header().export_address_table_jumps
+            cli_header.export_address_table_jumps
         );
         println!(
             "  Managed/Native Header: {}",
-            image.cli_header().managed_native_header
+            cli_header.managed_native_header
         );
         println!();
 
@@ -3,6 +3,7 @@ extern crate ecma355metadata;
 use std::env;
 use std::fs::File;
 
+use ecma355metadata::pe::{CodeViewData, DebugType, PdbChecksum};
 use ecma355metadata::MetadataImage;
 use tracing::Level;
 
@@ -20,30 +21,31 @@ pub fn main() {
     } else {
         let file = File::open(&args[1]).unwrap();
         let image = MetadataImage::read(file).unwrap();
+        let pe = image.pe().unwrap();
 
         println!("COFF Header:");
-        println!("  Machine: 0x{:04X}", image.pe().coff_header().machine);
+        println!("  Machine: 0x{:04X}", pe.coff_header().machine);
         println!(
             "  Number of Sections: {}",
-            image.pe().coff_header().number_of_sections
+            pe.coff_header().number_of_sections
         );
-        println!("  Timestamp: {}", image.pe().coff_header().timestamp);
+        println!("  Timestamp: {}", pe.coff_header().timestamp);
         println!(
             "  Symbol Table Offset: 0x{:04X}",
-            image.pe().coff_header().symbol_table_addr
+            pe.coff_header().symbol_table_addr
         );
-        println!("  Symbol Count: {}", image.pe().coff_header().symbol_count);
+        println!("  Symbol Count: {}", pe.coff_header().symbol_count);
         println!(
             "  Optional Header Size: {}",
-            image.pe().coff_header().optional_header_size
+            pe.coff_header().optional_header_size
         );
         println!(
             "  Characteristics: {}",
-            image.pe().coff_header().characteristics
+            pe.coff_header().characteristics
         );
         println!();
 
-        if let Some(pe_header) = image.pe().pe_header() {
+        if let Some(pe_header) = pe.pe_header() {
             println!("PE Header:");
             println!("  Magic: {}", pe_header.magic);
             println!(
@@ -109,7 +111,7 @@ pub fn main() {
         }
 
         println!("Sections:");
-        for section in image.pe().sections() {
+        for section in pe.sections() {
             println!("  {}", section.name);
             println!("    Virtual Size: 0x{:08X}", section.virtual_size);
             println!("    Virtual Address: 0x{:08X}", section.virtual_address);
@@ -136,5 +138,76 @@ pub fn main() {
             );
             println!("    Characteristics: {}", section.characteristics);
         }
+        println!();
+
+        println!("Debug Directory:");
+        for entry in pe.debug_directory().unwrap() {
+            println!("  {}", entry.debug_type);
+            println!("    Version: {}.{}", entry.major_version, entry.minor_version);
+            println!("    Size of Data: 0x{:08X}", entry.size_of_data);
+            let data = pe.debug_data(&entry).unwrap();
+            match entry.debug_type {
+                DebugType::CodeView => {
+                    let code_view = CodeViewData::read(data).unwrap();
+                    println!("    Portable: {}", entry.is_portable_code_view());
+                    println!("    GUID: {}", code_view.guid);
+                    println!("    Age: {}", code_view.age);
+                    println!("    Path: {}", code_view.path);
+                }
+                DebugType::PdbChecksum => {
+                    let checksum = PdbChecksum::read(data).unwrap();
+                    println!("    Algorithm: {}", checksum.algorithm);
+                }
+                DebugType::EmbeddedPortablePdb => {
+                    let pdb = image.embedded_pdb().unwrap().unwrap();
+                    println!("    Metadata Version: {}", pdb.metadata_header().version);
+                }
+                _ => {}
+            }
+        }
+        println!();
+
+        println!("Resources:");
+        for resource in pe.resources().unwrap() {
+            println!("  {} {} (Language: {}): {}", resource.resource_type, resource.name, resource.language, resource.data);
+        }
+        if let Some(r2r) = image.ready_to_run().unwrap() {
+            println!();
+            println!("ReadyToRun:");
+            println!("  Version: {}.{}", r2r.header().major_version, r2r.header().minor_version);
+            if let Some(compiler) = r2r.compiler_identifier().unwrap() {
+                println!("  Compiler: {}", compiler);
+            }
+            for section in &r2r.header().sections {
+                println!("  {}: {}", section.section_type, section.range);
+            }
+            println!("  Precompiled Methods: {}", r2r.method_entry_points().unwrap().len());
+            println!("  Available Types: {}", r2r.available_types().unwrap().len());
+        }
+
+        println!();
+        println!("Certificates:");
+        for certificate in pe.certificates().unwrap() {
+            println!("  Type: 0x{:04X} (Revision 0x{:04X}, {} bytes)", certificate.certificate_type, certificate.revision, certificate.data.len());
+            if let Ok(signed) = certificate.signed_digest() {
+                let hash = pe.authenticode_hash(signed.algorithm).unwrap();
+                println!("    Algorithm: {}", signed.algorithm);
+                println!("    Hash Matches: {}", hash == signed.digest);
+            }
+        }
+        if let Some(version_info) = pe.version_info().unwrap() {
+            println!();
+            println!("Version Info:");
+            if let (Some(file), Some(product)) = (version_info.file_version(), version_info.product_version()) {
+                println!("  File Version: {}.{}.{}.{}", file[0], file[1], file[2], file[3]);
+                println!("  Product Version: {}.{}.{}.{}", product[0], product[1], product[2], product[3]);
+            }
+            for table in &version_info.string_tables {
+                println!("  {}", table.key);
+                for (key, value) in &table.strings {
+                    println!("    {}: {}", key, value);
+                }
+            }
+        }
     }
 }
@@ -17,7 +17,7 @@ pub fn main() {
 
     let args: Vec<_> = env::args().collect();
     if args.len() < 2 {
-        println!("Usage: dump_table <file> <table>");
+        println!("Usage: dump_table <file> [<table> | validate]");
     } else {
         let file_path = &args[1];
 
@@ -26,6 +26,8 @@ pub fn main() {
 
         if args.len() < 3 {
             dump_table_names(&image);
+        } else if args[2] == "validate" {
+            dump_violations(&image);
         } else {
             let table_name = &args[2];
             let table: TableIndex = table_name.parse().expect("Unknown metadata table");
@@ -60,6 +62,14 @@ pub fn dump_table_names(image: &MetadataImage) {
     }
 }
 
+pub fn dump_violations(image: &MetadataImage) {
+    let violations = image.validate();
+    println!("Validation: {} violations", violations.len());
+    for violation in violations {
+        println!("  {}", violation);
+    }
+}
+
 pub fn dump_assembly_ref_table(image: &MetadataImage) {
     let assembly_ref_table: Table<AssemblyRef> = image.table();
     println!("AssemblyRef Table: {} rows", assembly_ref_table.len());
@@ -0,0 +1,17 @@
+extern crate ecma355metadata;
+
+use std::env;
+use std::fs;
+
+pub fn main() {
+    let args: Vec<_> = env::args().collect();
+    if args.len() < 3 {
+        println!("Usage: ilasm <source.il> <output>");
+    } else {
+        let source = fs::read_to_string(&args[1]).unwrap();
+        match ecma355metadata::ilasm::assemble(&source) {
+            Ok(image) => fs::write(&args[2], image).unwrap(),
+            Err(e) => eprintln!("{}: {}", args[1], e),
+        }
+    }
+}
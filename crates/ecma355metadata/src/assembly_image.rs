@@ -0,0 +1,252 @@
+use std::fs::File;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::cli::tables::{self, TableHandle, TableIndex};
+use crate::cli::AssemblyHashAlgorithm;
+use crate::error::Error;
+use crate::pe::{HashAlgorithm, ImageData};
+use crate::MetadataImage;
+
+/// The File flag that marks a file as a resource file rather than a module.
+const CONTAINS_NO_METADATA: u32 = 0x0001;
+
+/// A module of a multi-module assembly, other than the manifest module.
+pub struct AssemblyModule<D: ImageData> {
+    /// The row in the manifest's File table that lists the module.
+    pub file: TableHandle,
+
+    /// The file name of the module, such as `Extra.netmodule`.
+    pub name: String,
+    pub image: MetadataImage<D>,
+}
+
+/// An assembly made up of its manifest module and the modules listed in the manifest's File table.
+///
+/// Most assemblies have a single module, in which case this is just the manifest module.
+pub struct AssemblyImage<D: ImageData = Vec<u8>> {
+    manifest: MetadataImage<D>,
+    modules: Vec<AssemblyModule<D>>,
+}
+
+impl<D: ImageData> AssemblyImage<D> {
+    /// Loads the modules of the assembly whose manifest is in `manifest`.
+    ///
+    /// `open_file` is called with the name of each module in the File table, and returns the data of that module. Each
+    /// module is checked against the hash that the File table records for it.
+    pub fn load<F: FnMut(&str) -> Result<D, Error>>(manifest: MetadataImage<D>, mut open_file: F) -> Result<AssemblyImage<D>, Error> {
+        if manifest.row_count(TableIndex::Assembly) == 0 {
+            return Err(Error::InvalidMetadata("image has no assembly manifest".into()));
+        }
+        let algorithm = match manifest.table::<tables::Assembly>().read(0)?.hash_alg_id {
+            AssemblyHashAlgorithm::None | AssemblyHashAlgorithm::SHA1 => HashAlgorithm::Sha1,
+            AssemblyHashAlgorithm::SHA256 => HashAlgorithm::Sha256,
+            AssemblyHashAlgorithm::SHA384 => HashAlgorithm::Sha384,
+            AssemblyHashAlgorithm::SHA512 => HashAlgorithm::Sha512,
+            AssemblyHashAlgorithm::MD5 => return Err(Error::InvalidMetadata("MD5 file hashes are not supported".into())),
+        };
+
+        let mut modules = Vec::new();
+        for row in manifest.table::<tables::File>().rows() {
+            let file = row.read()?;
+            if file.flags & CONTAINS_NO_METADATA != 0 {
+                continue;
+            }
+            let name = manifest.get_string(file.name).ok_or(Error::InvalidHeapReference)?.to_string_lossy().into_owned();
+            let image = MetadataImage::load_data(open_file(&name)?)?;
+
+            let expected = manifest.get_blob(file.hash_value).ok_or(Error::InvalidHeapReference)?;
+            if !expected.is_empty() {
+                let data = image.pe().ok_or(Error::NotAPortableExecutable)?.data();
+                let mut hasher = algorithm.hasher();
+                hasher.update(data.read(0..data.len())?);
+                if *hasher.finalize() != *expected {
+                    return Err(Error::ModuleHashMismatch(name));
+                }
+            }
+
+            modules.push(AssemblyModule {
+                file: TableHandle::new(row.row(), TableIndex::File),
+                name,
+                image,
+            });
+        }
+        Ok(AssemblyImage { manifest, modules })
+    }
+
+    /// Gets the module that holds the assembly manifest.
+    pub fn manifest(&self) -> &MetadataImage<D> {
+        &self.manifest
+    }
+
+    /// Gets the other modules of the assembly, in File table order.
+    pub fn modules(&self) -> &[AssemblyModule<D>] {
+        &self.modules
+    }
+
+    /// Finds a module by its file name, which is compared case-insensitively as on Windows.
+    pub fn module(&self, name: &str) -> Option<&AssemblyModule<D>> {
+        self.modules.iter().find(|m| m.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Finds a top-level type definition in any module of the assembly.
+    ///
+    /// Types that the manifest exports from another module are looked up in that module first. Types that aren't
+    /// exported, which are only visible inside the assembly, are found by searching every module.
+    pub fn find_type(&self, namespace: &str, name: &str) -> Option<(&MetadataImage<D>, TableHandle)> {
+        if let Some(type_def) = self.manifest.find_type_def(namespace, name) {
+            return Some((&self.manifest, type_def));
+        }
+
+        let text = |handle| self.manifest.get_string(handle).map(|s| s.to_string_lossy());
+        for exported in self.manifest.table::<tables::ExportedType>().iter().filter_map(|row| row.ok()) {
+            if exported.implementation.table() != TableIndex::File
+                || text(exported.type_name).as_deref() != Some(name)
+                || text(exported.type_namespace).as_deref() != Some(namespace)
+            {
+                continue;
+            }
+            let found = self
+                .modules
+                .iter()
+                .find(|m| m.file == exported.implementation)
+                .and_then(|m| m.image.find_type_def(namespace, name).map(|t| (&m.image, t)));
+            if found.is_some() {
+                return found;
+            }
+        }
+
+        self.modules.iter().find_map(|m| m.image.find_type_def(namespace, name).map(|t| (&m.image, t)))
+    }
+
+    /// Resolves a ModuleRef in `image`, which must be one of the modules of this assembly, to the module it names.
+    ///
+    /// Returns `None` if no module of the assembly has that name.
+    pub fn resolve_module_ref(&self, image: &MetadataImage<D>, module_ref: TableHandle) -> Result<Option<&MetadataImage<D>>, Error> {
+        if module_ref.table() != TableIndex::ModuleRef || module_ref.index() == 0 {
+            return Err(Error::InvalidCodedIndex);
+        }
+        let row = image.table::<tables::ModuleRef>().read(module_ref.index() - 1)?;
+        let name = image.get_string(row.name).ok_or(Error::InvalidHeapReference)?.to_string_lossy();
+        if let Some(module) = self.module(&name) {
+            return Ok(Some(&module.image));
+        }
+
+        let manifest = self.manifest.table::<tables::Module>().read(0)?;
+        let manifest_name = self.manifest.get_string(manifest.name).ok_or(Error::InvalidHeapReference)?.to_string_lossy();
+        Ok(if manifest_name.eq_ignore_ascii_case(&name) { Some(&self.manifest) } else { None })
+    }
+
+    /// Resolves a TypeRef in `image` whose resolution scope is a module of this assembly to its definition.
+    ///
+    /// Returns `None` for types that live in other assemblies, and for nested types.
+    pub fn resolve_type_ref<'a>(&'a self, image: &'a MetadataImage<D>, type_ref: TableHandle) -> Result<Option<(&'a MetadataImage<D>, TableHandle)>, Error> {
+        if type_ref.table() != TableIndex::TypeRef || type_ref.index() == 0 {
+            return Err(Error::InvalidCodedIndex);
+        }
+        let row = image.table::<tables::TypeRef>().read(type_ref.index() - 1)?;
+        let target = match row.resolution_scope.table() {
+            TableIndex::Module => image,
+            TableIndex::ModuleRef => match self.resolve_module_ref(image, row.resolution_scope)? {
+                Some(target) => target,
+                None => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+
+        let text = |handle| image.get_string(handle).map(|s| s.to_string_lossy()).ok_or(Error::InvalidHeapReference);
+        Ok(target.find_type_def(&text(row.namespace)?, &text(row.name)?).map(|t| (target, t)))
+    }
+}
+
+impl AssemblyImage<Mmap> {
+    /// Opens an assembly by memory-mapping its manifest module at `path` and the other modules next to it.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<AssemblyImage<Mmap>, Error> {
+        let path = path.as_ref();
+        let directory = path.parent().unwrap_or(Path::new(""));
+        AssemblyImage::load(MetadataImage::open(path)?, |name| {
+            let file = File::open(directory.join(name))?;
+            Ok(unsafe { Mmap::map(&file)? })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha1::{Digest, Sha1};
+
+    use super::*;
+    use crate::metadata_image::tests::{metadata_root, table_stream};
+    use crate::pe::test_image;
+
+    /// Builds `B.netmodule`, which defines `Ns.Widget`.
+    fn module() -> Vec<u8> {
+        test_image::cli_image(&metadata_root(&[
+            ("#~", table_stream(&[
+                (TableIndex::Module, 1, &[0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+                (TableIndex::TypeDef, 2, &[
+                    0x00, 0x00, 0x00, 0x00, 0x0D, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00,
+                    0x01, 0x00, 0x00, 0x00, 0x19, 0x00, 0x16, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00,
+                ]),
+            ])),
+            ("#Strings", b"\0B.netmodule\0<Module>\0Ns\0Widget\0".to_vec()),
+        ]))
+    }
+
+    /// Builds `A.dll`, the manifest module, which exports `Ns.Widget` from `B.netmodule` and references it.
+    fn manifest(module_hash: &[u8]) -> Vec<u8> {
+        let mut blobs = vec![0x00, module_hash.len() as u8];
+        blobs.extend_from_slice(module_hash);
+
+        test_image::cli_image(&metadata_root(&[
+            ("#~", table_stream(&[
+                (TableIndex::Module, 1, &[0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+                (TableIndex::TypeRef, 1, &[0x05, 0x00, 0x1F, 0x00, 0x1C, 0x00]),
+                (TableIndex::TypeDef, 1, &[0x00, 0x00, 0x00, 0x00, 0x13, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00]),
+                (TableIndex::ModuleRef, 1, &[0x07, 0x00]),
+                (TableIndex::Assembly, 1, &[
+                    0x04, 0x80, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x26, 0x00, 0x00, 0x00,
+                ]),
+                (TableIndex::File, 1, &[0x00, 0x00, 0x00, 0x00, 0x07, 0x00, 0x01, 0x00]),
+                (TableIndex::ExportedType, 1, &[0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x02, 0x1F, 0x00, 0x1C, 0x00, 0x04, 0x00]),
+            ])),
+            ("#Strings", b"\0A.dll\0B.netmodule\0<Module>\0Ns\0Widget\0A\0".to_vec()),
+            ("#Blob", blobs),
+        ]))
+    }
+
+    fn load(manifest: Vec<u8>) -> Result<AssemblyImage, Error> {
+        AssemblyImage::load(MetadataImage::load_data(manifest)?, |name| {
+            assert_eq!("B.netmodule", name);
+            Ok(module())
+        })
+    }
+
+    #[test]
+    pub fn load_multi_module_assembly() {
+        let assembly = load(manifest(&Sha1::digest(module()))).unwrap();
+        assert_eq!(1, assembly.modules().len());
+        assert_eq!(TableHandle::new(1, TableIndex::File), assembly.module("b.netmodule").unwrap().file);
+
+        let (image, widget) = assembly.find_type("Ns", "Widget").unwrap();
+        assert!(std::ptr::eq(image, &assembly.modules()[0].image));
+        assert_eq!(TableHandle::new(2, TableIndex::TypeDef), widget);
+        assert!(assembly.find_type("Ns", "Missing").is_none());
+
+        let module_ref = assembly.resolve_module_ref(assembly.manifest(), TableHandle::new(1, TableIndex::ModuleRef)).unwrap();
+        assert!(std::ptr::eq(module_ref.unwrap(), image));
+
+        let (image, resolved) = assembly.resolve_type_ref(assembly.manifest(), TableHandle::new(1, TableIndex::TypeRef)).unwrap().unwrap();
+        assert!(std::ptr::eq(image, &assembly.modules()[0].image));
+        assert_eq!(widget, resolved);
+    }
+
+    #[test]
+    pub fn reject_module_with_wrong_hash() {
+        match load(manifest(&[0xAB; 20])) {
+            Err(Error::ModuleHashMismatch(name)) => assert_eq!("B.netmodule", name),
+            _ => panic!("expected a hash mismatch"),
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use std::io::Read;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::error::Error;
+use crate::utils;
+
+/// The kinds of file embedded in a bundle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BundleFileType {
+    Unknown,
+    Assembly,
+    NativeBinary,
+    DepsJson,
+    RuntimeConfigJson,
+    Symbols,
+    Other(u8),
+}
+
+impl BundleFileType {
+    pub fn from_u8(value: u8) -> BundleFileType {
+        match value {
+            0 => BundleFileType::Unknown,
+            1 => BundleFileType::Assembly,
+            2 => BundleFileType::NativeBinary,
+            3 => BundleFileType::DepsJson,
+            4 => BundleFileType::RuntimeConfigJson,
+            5 => BundleFileType::Symbols,
+            x => BundleFileType::Other(x),
+        }
+    }
+}
+
+impl_display_via_debug!(BundleFileType);
+
+/// A file embedded in a bundle.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BundleEntry {
+    /// The offset of the file data from the start of the bundle.
+    pub offset: u64,
+
+    /// The size of the file once extracted.
+    pub size: u64,
+
+    /// The size of the deflate-compressed data, or 0 if the file is stored uncompressed.
+    pub compressed_size: u64,
+    pub file_type: BundleFileType,
+
+    /// The path of the file relative to the app directory, with `/` separators.
+    pub path: String,
+}
+
+impl BundleEntry {
+    pub fn is_compressed(&self) -> bool {
+        self.compressed_size != 0
+    }
+}
+
+/// The manifest at the end of a bundle, which lists the embedded files.
+#[derive(Debug)]
+pub struct BundleManifest {
+    pub major_version: u32,
+    pub minor_version: u32,
+    pub bundle_id: String,
+
+    /// The locations of the deps.json and runtimeconfig.json files, as offset and size. Only version 2 and later
+    /// record these.
+    pub deps_json: Option<(u64, u64)>,
+    pub runtime_config_json: Option<(u64, u64)>,
+    pub flags: u64,
+    pub entries: Vec<BundleEntry>,
+}
+
+impl BundleManifest {
+    pub fn read<A: Read>(mut buf: A) -> Result<BundleManifest, Error> {
+        let major_version = buf.read_u32::<LittleEndian>()?;
+        let minor_version = buf.read_u32::<LittleEndian>()?;
+        let count = buf.read_i32::<LittleEndian>()?;
+        if count < 0 {
+            return Err(Error::InvalidBundle(format!("invalid file count {}", count)));
+        }
+        let bundle_id = utils::read_prefixed_string(&mut buf)?;
+
+        let (deps_json, runtime_config_json, flags) = if major_version >= 2 {
+            let deps_json = (buf.read_u64::<LittleEndian>()?, buf.read_u64::<LittleEndian>()?);
+            let runtime_config_json = (buf.read_u64::<LittleEndian>()?, buf.read_u64::<LittleEndian>()?);
+            (Some(deps_json), Some(runtime_config_json), buf.read_u64::<LittleEndian>()?)
+        } else {
+            (None, None, 0)
+        };
+
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            entries.push(BundleEntry {
+                offset: buf.read_u64::<LittleEndian>()?,
+                size: buf.read_u64::<LittleEndian>()?,
+
+                // Compression was added in version 6
+                compressed_size: if major_version >= 6 { buf.read_u64::<LittleEndian>()? } else { 0 },
+                file_type: BundleFileType::from_u8(buf.read_u8()?),
+                path: utils::read_prefixed_string(&mut buf)?,
+            });
+        }
+
+        Ok(BundleManifest {
+            major_version,
+            minor_version,
+            bundle_id,
+            deps_json,
+            runtime_config_json,
+            flags,
+            entries,
+        })
+    }
+}
@@ -0,0 +1,5 @@
+mod reader;
+mod manifest;
+
+pub use self::reader::{Bundle, BUNDLE_SIGNATURE};
+pub use self::manifest::{BundleEntry, BundleFileType, BundleManifest};
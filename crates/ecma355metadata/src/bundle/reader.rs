@@ -0,0 +1,223 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use byteorder::{ByteOrder, LittleEndian};
+use flate2::read::DeflateDecoder;
+use memmap2::Mmap;
+
+use crate::bundle::{BundleEntry, BundleFileType, BundleManifest};
+use crate::error::Error;
+use crate::pe::ImageData;
+use crate::MetadataImage;
+
+/// The marker that the host builder places after the manifest offset in a bundled apphost.
+///
+/// This is the SHA-256 hash of ".net core bundle".
+pub const BUNDLE_SIGNATURE: [u8; 32] = [
+    0x8B, 0x12, 0x02, 0xB9, 0x6A, 0x61, 0x20, 0x38, 0x72, 0x7B, 0x93, 0x02, 0x14, 0xD7, 0xA0, 0x32, 0x13, 0xF5, 0xB9, 0xE6, 0xEF, 0xAE, 0x33, 0x18, 0xEE, 0x3B, 0x2D,
+    0xCE, 0x24, 0xB3, 0x6A, 0xAE,
+];
+
+/// A .NET single-file app: a native apphost (ELF, Mach-O or PE) with the app's files appended to it.
+pub struct Bundle<D: ImageData> {
+    data: D,
+    manifest: BundleManifest,
+}
+
+impl<D: ImageData> Bundle<D> {
+    /// Reads the bundle manifest from an apphost.
+    ///
+    /// Returns `None` if the file is not a bundle, including an apphost that has the marker but nothing appended.
+    pub fn load(data: D) -> Result<Option<Bundle<D>>, Error> {
+        let buf = data.read(0..data.len())?;
+        let marker = match find_signature(buf) {
+            Some(marker) if marker >= 8 => marker,
+            _ => return Ok(None),
+        };
+        let manifest_offset = LittleEndian::read_u64(&buf[(marker - 8)..marker]) as usize;
+        if manifest_offset == 0 {
+            return Ok(None);
+        }
+
+        let manifest = BundleManifest::read(Cursor::new(buf.get(manifest_offset..).ok_or(Error::DataOutOfRange)?))?;
+        Ok(Some(Bundle { data, manifest }))
+    }
+
+    pub fn manifest(&self) -> &BundleManifest {
+        &self.manifest
+    }
+
+    pub fn entries(&self) -> &[BundleEntry] {
+        &self.manifest.entries
+    }
+
+    /// Finds an entry by its path relative to the app directory.
+    pub fn find(&self, path: &str) -> Option<&BundleEntry> {
+        self.manifest.entries.iter().find(|e| e.path == path)
+    }
+
+    /// Gets the managed assemblies in the bundle.
+    pub fn assemblies(&self) -> impl Iterator<Item = &BundleEntry> {
+        self.manifest.entries.iter().filter(|e| e.file_type == BundleFileType::Assembly)
+    }
+
+    /// Gets the contents of a file, inflating it if it is compressed.
+    pub fn entry_data(&self, entry: &BundleEntry) -> Result<Cow<'_, [u8]>, Error> {
+        // The offset and sizes come from the manifest, so nothing about them can be trusted
+        let start = entry.offset as usize;
+        let end = |len: u64| start.checked_add(len as usize).ok_or(Error::DataOutOfRange);
+        if !entry.is_compressed() {
+            return Ok(Cow::Borrowed(self.data.read(start..end(entry.size)?)?));
+        }
+
+        let compressed = self.data.read(start..end(entry.compressed_size)?)?;
+        let mut data = Vec::new();
+        DeflateDecoder::new(compressed).take(entry.size.saturating_add(1)).read_to_end(&mut data)?;
+        if data.len() as u64 != entry.size {
+            return Err(Error::InvalidBundle(format!("{} inflated to {} bytes, expected {}", entry.path, data.len(), entry.size)));
+        }
+        Ok(Cow::Owned(data))
+    }
+
+    /// Opens an assembly in the bundle. Uncompressed assemblies are read in place.
+    pub fn open_assembly(&self, entry: &BundleEntry) -> Result<MetadataImage<Cow<'_, [u8]>>, Error> {
+        MetadataImage::load_data(self.entry_data(entry)?)
+    }
+}
+
+impl Bundle<Vec<u8>> {
+    pub fn read<R: Read>(mut reader: R) -> Result<Option<Bundle<Vec<u8>>>, Error> {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+        Bundle::load(buf)
+    }
+}
+
+impl Bundle<Mmap> {
+    /// Opens a bundle by memory-mapping the file at `path`.
+    ///
+    /// The file must not be modified while the bundle is open.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Option<Bundle<Mmap>>, Error> {
+        let file = File::open(path)?;
+        let map = unsafe { Mmap::map(&file)? };
+        Bundle::load(map)
+    }
+}
+
+fn find_signature(buf: &[u8]) -> Option<usize> {
+    buf.windows(BUNDLE_SIGNATURE.len())
+        .position(|window| window[0] == BUNDLE_SIGNATURE[0] && window == BUNDLE_SIGNATURE)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::DeflateEncoder;
+    use flate2::Compression;
+
+    use super::*;
+
+    fn string(s: &str) -> Vec<u8> {
+        let mut buf = vec![s.len() as u8];
+        buf.extend_from_slice(s.as_bytes());
+        buf
+    }
+
+    /// Builds a version 6 bundle from an apphost and a list of files, compressing the ones marked for compression.
+    fn bundle(files: &[(&str, BundleFileType, &[u8], bool)]) -> Vec<u8> {
+        let mut buf = b"\x7FELF apphost".to_vec();
+        let placeholder = buf.len();
+        buf.extend_from_slice(&[0u8; 8]);
+        buf.extend_from_slice(&BUNDLE_SIGNATURE);
+        buf.extend_from_slice(b" more apphost");
+
+        let mut entries = Vec::new();
+        for (path, file_type, contents, compress) in files {
+            let offset = buf.len() as u64;
+            let compressed_size = if *compress {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(contents).unwrap();
+                let compressed = encoder.finish().unwrap();
+                buf.extend_from_slice(&compressed);
+                compressed.len() as u64
+            } else {
+                buf.extend_from_slice(contents);
+                0
+            };
+            entries.push((offset, contents.len() as u64, compressed_size, *file_type, *path));
+        }
+
+        let manifest = buf.len() as u64;
+        buf[placeholder..(placeholder + 8)].copy_from_slice(&manifest.to_le_bytes());
+        buf.extend_from_slice(&6u32.to_le_bytes());
+        buf.extend_from_slice(&0u32.to_le_bytes());
+        buf.extend_from_slice(&(files.len() as i32).to_le_bytes());
+        buf.extend(string("Ab3dEf"));
+        buf.extend_from_slice(&[0u8; 40]);
+        for (offset, size, compressed_size, file_type, path) in entries {
+            buf.extend_from_slice(&offset.to_le_bytes());
+            buf.extend_from_slice(&size.to_le_bytes());
+            buf.extend_from_slice(&compressed_size.to_le_bytes());
+            buf.push(match file_type {
+                BundleFileType::Assembly => 1,
+                BundleFileType::DepsJson => 3,
+                _ => 0,
+            });
+            buf.extend(string(path));
+        }
+        buf
+    }
+
+    #[test]
+    pub fn read_bundle() {
+        let data = bundle(&[
+            ("app.dll", BundleFileType::Assembly, b"MZ not really an assembly", false),
+            ("app.deps.json", BundleFileType::DepsJson, b"{ \"runtimeTarget\": {} }", true),
+        ]);
+        let bundle = Bundle::load(data).unwrap().unwrap();
+        assert_eq!((6, 0), (bundle.manifest().major_version, bundle.manifest().minor_version));
+        assert_eq!("Ab3dEf", bundle.manifest().bundle_id);
+        assert_eq!(2, bundle.entries().len());
+
+        let assemblies: Vec<_> = bundle.assemblies().map(|e| e.path.as_str()).collect();
+        assert_eq!(vec!["app.dll"], assemblies);
+        assert_eq!(&b"MZ not really an assembly"[..], &bundle.entry_data(&bundle.entries()[0]).unwrap()[..]);
+
+        let deps = bundle.find("app.deps.json").unwrap();
+        assert!(deps.is_compressed());
+        assert_eq!(&b"{ \"runtimeTarget\": {} }"[..], &bundle.entry_data(deps).unwrap()[..]);
+    }
+
+    #[test]
+    pub fn apphost_without_bundle() {
+        assert!(Bundle::load(b"\x7FELF not a bundle".to_vec()).unwrap().is_none());
+
+        // An apphost that hasn't been bundled still has the marker, with a zero offset
+        let mut apphost = vec![0u8; 8];
+        apphost.extend_from_slice(&BUNDLE_SIGNATURE);
+        assert!(Bundle::load(apphost).unwrap().is_none());
+    }
+
+    #[test]
+    pub fn reject_bad_entries() {
+        let data = bundle(&[
+            ("app.dll", BundleFileType::Assembly, b"MZ not really an assembly", false),
+            ("app.deps.json", BundleFileType::DepsJson, b"{ \"runtimeTarget\": {} }", true),
+        ]);
+        let bundle = Bundle::load(data).unwrap().unwrap();
+
+        let mut entry = bundle.entries()[0].clone();
+        entry.size = u64::MAX;
+        assert!(matches!(bundle.entry_data(&entry), Err(Error::DataOutOfRange)));
+
+        // A compressed file has to inflate to exactly its size, in either direction
+        let mut entry = bundle.entries()[1].clone();
+        entry.size -= 1;
+        assert!(matches!(bundle.entry_data(&entry), Err(Error::InvalidBundle(_))));
+        entry.size += 2;
+        assert!(matches!(bundle.entry_data(&entry), Err(Error::InvalidBundle(_))));
+    }
+}
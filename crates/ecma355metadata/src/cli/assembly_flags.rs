@@ -3,11 +3,14 @@ use bitflags::bitflags;
 use crate::Error;
 
 #[repr(u32)]
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum AssemblyHashAlgorithm {
     None = 0x0,
     MD5 = 0x8003,
     SHA1 = 0x8004,
+    SHA256 = 0x800C,
+    SHA384 = 0x800D,
+    SHA512 = 0x800E,
 }
 
 impl TryFrom<u32> for AssemblyHashAlgorithm {
@@ -18,6 +21,9 @@ impl TryFrom<u32> for AssemblyHashAlgorithm {
             0x0 => Ok(AssemblyHashAlgorithm::None),
             0x8003 => Ok(AssemblyHashAlgorithm::MD5),
             0x8004 => Ok(AssemblyHashAlgorithm::SHA1),
+            0x800C => Ok(AssemblyHashAlgorithm::SHA256),
+            0x800D => Ok(AssemblyHashAlgorithm::SHA384),
+            0x800E => Ok(AssemblyHashAlgorithm::SHA512),
             _ => Err(Error::InvalidMetadata(format!("invalid AssemblyHashAlgorithm: 0x{:04X}", value))),
         }
     }
@@ -14,6 +14,11 @@ impl FieldAttributes {
         FieldAttributes(value)
     }
 
+    /// Gets the raw value of the attributes.
+    pub fn bits(self) -> u16 {
+        self.0
+    }
+
     pub fn access(self) -> Access {
         unsafe {
             mem::transmute((self.0 & Access::MASK) >> Access::SHIFT)
@@ -2,6 +2,7 @@ use std::ffi::CStr;
 use std::fmt::Display;
 use std::io::Read;
 use std::marker::PhantomData;
+use std::ops::Range;
 
 use byteorder::{LittleEndian, ReadBytesExt};
 
@@ -14,7 +15,7 @@ pub trait Handle {
     fn new(offset: usize) -> Self;
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct StringHandle(pub usize);
 
 impl Display for StringHandle {
@@ -23,7 +24,7 @@ impl Display for StringHandle {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct GuidHandle(pub usize);
 
 impl Display for GuidHandle {
@@ -32,7 +33,7 @@ impl Display for GuidHandle {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct BlobHandle(pub usize);
 
 impl Display for BlobHandle {
@@ -65,39 +66,52 @@ impl<T: Handle> HandleReader<T> {
     }
 }
 
+/// An offset into the `#US` heap, which holds the string literals used by `ldstr`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct UserStringHandle(pub usize);
+
+impl Display for UserStringHandle {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> Result<(), ::std::fmt::Error> {
+        write!(f, "(US)0x{:08X}", self.0)
+    }
+}
+
+impl UserStringHandle {
+    fn read(&self, buf: &[u8]) -> Option<String> {
+        // The same length prefix as a blob, followed by UTF-16 code units and a trailing flag byte
+        let bytes = BlobHandle(self.0).read(buf)?;
+        let units: Vec<u16> = bytes.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+        String::from_utf16(&units).ok()
+    }
+}
+
+/// The ranges of each heap stream, relative to the start of the metadata root.
 pub struct Heaps {
-    pub string_heap: Option<usize>,
-    pub userstring_heap: Option<usize>,
-    pub guid_heap: Option<usize>,
-    pub blob_heap: Option<usize>,
+    pub string_heap: Option<Range<usize>>,
+    pub userstring_heap: Option<Range<usize>>,
+    pub guid_heap: Option<Range<usize>>,
+    pub blob_heap: Option<Range<usize>>,
 }
 
 impl Heaps {
     pub fn get_string<'a>(&self, metadata_buf: &'a [u8], handle: StringHandle) -> Option<&'a CStr> {
-        match self.string_heap {
-            Some(offset) => {
-                handle.read(&metadata_buf[offset..])
-            },
-            None => None,
-        }
+        handle.read(Self::heap(metadata_buf, &self.string_heap)?)
+    }
+
+    pub fn get_user_string(&self, metadata_buf: &[u8], handle: UserStringHandle) -> Option<String> {
+        handle.read(Self::heap(metadata_buf, &self.userstring_heap)?)
     }
 
     pub fn get_guid(&self, metadata_buf: &[u8], handle: GuidHandle) -> Option<Guid> {
-        match self.guid_heap {
-            Some(offset) => {
-                handle.read(&metadata_buf[offset..])
-            },
-            None => None,
-        }
+        handle.read(Self::heap(metadata_buf, &self.guid_heap)?)
     }
 
     pub fn get_blob<'a>(&self, metadata_buf: &'a [u8], handle: BlobHandle) -> Option<&'a [u8]> {
-        match self.blob_heap {
-            Some(offset) => {
-                handle.read(&metadata_buf[offset..])
-            },
-            None => None,
-        }
+        handle.read(Self::heap(metadata_buf, &self.blob_heap)?)
+    }
+
+    fn heap<'a>(metadata_buf: &'a [u8], range: &Option<Range<usize>>) -> Option<&'a [u8]> {
+        range.as_ref().and_then(|r| metadata_buf.get(r.clone()))
     }
 }
 
@@ -125,9 +139,14 @@ impl Handle for GuidHandle {
 
 impl GuidHandle {
     fn read(&self, buf: &[u8]) -> Option<Guid> {
+        // GUID handles are 1-based indexes into the heap, with 0 meaning "null"
+        if self.0 == 0 {
+            return None;
+        }
+
         // Bounds check
-        let start = self.0;
-        let end = self.0 + 16;
+        let start = (self.0 - 1) * 16;
+        let end = start + 16;
         if end > buf.len() {
             return None;
         }
@@ -150,24 +169,22 @@ impl BlobHandle {
             None
         } else {
             // Read the header
-            if buf[self.0] & 0x80 == 0 {
+            let (start, len) = if buf[self.0] & 0x80 == 0 {
                 // 1-byte length
-                let start = self.0 + 1;
-                let len = (buf[self.0] as usize) & 0x7F;
-                Some(&buf[start..(start + len)])
-            } else if buf[self.0] & 0xC0 == 0 {
+                (self.0 + 1, (buf[self.0] as usize) & 0x7F)
+            } else if buf[self.0] & 0x40 == 0 {
                 // 2-byte length
-                let start = self.0 + 2;
-                let len = ((buf[self.0] as usize & 0x3F) << 8) + buf[self.0 + 1] as usize;
-                Some(&buf[start..(start + len)])
+                let header = buf.get(self.0..(self.0 + 2))?;
+                (self.0 + 2, ((header[0] as usize & 0x3F) << 8) + header[1] as usize)
             } else {
                 // 4-byte length
-                let start = self.0 + 4;
-                let len = ((buf[self.0] as usize) & 0x1F << 24) + ((buf[self.0 + 1] as usize) << 16)
-                    + ((buf[self.0 + 2] as usize) << 8)
-                    + buf[self.0 + 3] as usize;
-                Some(&buf[start..(start + len)])
-            }
+                let header = buf.get(self.0..(self.0 + 4))?;
+                let len = ((header[0] as usize & 0x1F) << 24) + ((header[1] as usize) << 16)
+                    + ((header[2] as usize) << 8)
+                    + header[3] as usize;
+                (self.0 + 4, len)
+            };
+            buf.get(start..(start + len))
         }
     }
 }
@@ -0,0 +1,326 @@
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::error::Error;
+use crate::utils;
+
+const RESOURCE_MANAGER_MAGIC: u32 = 0xBEEFCACE;
+
+/// A value stored in a `.resources` file.
+///
+/// Values of types that the format has no type code for are stored serialized, and are returned as raw bytes with
+/// their type name.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ResourceValue<'a> {
+    Null,
+    String(String),
+    Boolean(bool),
+    Char(u16),
+    Byte(u8),
+    SByte(i8),
+    Int16(i16),
+    UInt16(u16),
+    Int32(i32),
+    UInt32(u32),
+    Int64(i64),
+    UInt64(u64),
+    Single(f32),
+    Double(f64),
+    Decimal([u8; 16]),
+    DateTime(i64),
+    TimeSpan(i64),
+    ByteArray(&'a [u8]),
+    Stream(&'a [u8]),
+    Serialized { type_name: String, data: &'a [u8] },
+}
+
+/// A named entry in a `.resources` file.
+#[derive(Debug)]
+pub struct ManagedResource<'a> {
+    pub name: String,
+    pub value: ResourceValue<'a>,
+}
+
+/// A `.resources` file in the binary format written by `ResourceWriter`.
+pub struct ResourceSet<'a> {
+    buf: &'a [u8],
+    version: u32,
+    types: Vec<String>,
+
+    /// The name and the absolute data offset of each resource, in file order.
+    entries: Vec<(String, usize)>,
+}
+
+impl<'a> ResourceSet<'a> {
+    pub fn read(buf: &'a [u8]) -> Result<ResourceSet<'a>, Error> {
+        let mut reader = Reader { buf, pos: 0 };
+
+        // Resource manager header
+        if reader.u32()? != RESOURCE_MANAGER_MAGIC {
+            return Err(Error::InvalidSignature);
+        }
+        let header_version = reader.u32()?;
+        let header_size = reader.u32()? as usize;
+        if header_version > 1 {
+            // Later versions are skipped as a whole, using the size
+            reader.pos += header_size;
+        } else {
+            // The reader and resource set type names
+            reader.string()?;
+            reader.string()?;
+        }
+
+        // Resource reader header
+        let version = reader.u32()?;
+        if version != 1 && version != 2 {
+            return Err(Error::InvalidMetadata(format!("unsupported .resources version {}", version)));
+        }
+        let resource_count = reader.u32()? as usize;
+        let type_count = reader.u32()? as usize;
+        let mut types = Vec::with_capacity(type_count.min(buf.len()));
+        for _ in 0..type_count {
+            types.push(reader.string()?);
+        }
+
+        // The name hashes are aligned to 8 bytes with "PAD" bytes
+        while reader.pos & 7 != 0 {
+            reader.pos += 1;
+        }
+        reader.bytes(resource_count * 4)?;
+        let name_positions = reader.bytes(resource_count * 4)?;
+        let data_section = reader.u32()? as usize;
+        let name_section = reader.pos;
+
+        let mut entries = Vec::with_capacity(resource_count);
+        for position in name_positions.chunks_exact(4) {
+            let mut name_reader = Reader { buf, pos: name_section + LittleEndian::read_u32(position) as usize };
+            let len = name_reader.encoded_int()? as usize;
+            let units: Vec<u16> = name_reader.bytes(len)?.chunks_exact(2).map(LittleEndian::read_u16).collect();
+            let data = data_section + name_reader.u32()? as usize;
+            if data > buf.len() {
+                return Err(Error::DataOutOfRange);
+            }
+            entries.push((String::from_utf16_lossy(&units), data));
+        }
+
+        Ok(ResourceSet { buf, version, types, entries })
+    }
+
+    /// Gets the names of the types used by serialized values.
+    pub fn types(&self) -> &[String] {
+        &self.types
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Gets the names of every resource.
+    pub fn names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.entries.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Decodes the value of a resource by name.
+    pub fn get(&self, name: &str) -> Option<Result<ResourceValue<'a>, Error>> {
+        self.entries.iter().find(|(n, _)| n == name).map(|(_, offset)| self.read_value(*offset))
+    }
+
+    /// Decodes every resource.
+    pub fn entries(&self) -> Result<Vec<ManagedResource<'a>>, Error> {
+        self.entries
+            .iter()
+            .map(|(name, offset)| Ok(ManagedResource { name: name.clone(), value: self.read_value(*offset)? }))
+            .collect()
+    }
+
+    fn read_value(&self, offset: usize) -> Result<ResourceValue<'a>, Error> {
+        let mut reader = Reader { buf: self.buf, pos: offset };
+        let code = reader.encoded_int()? as usize;
+
+        // Version 1 files only store an index into the type table, with -1 meaning null
+        let code = if self.version == 1 {
+            if code as i32 == -1 { 0 } else { code + 0x40 }
+        } else {
+            code
+        };
+
+        Ok(match code {
+            0x00 => ResourceValue::Null,
+            0x01 => ResourceValue::String(reader.string()?),
+            0x02 => ResourceValue::Boolean(reader.bytes(1)?[0] != 0),
+            0x03 => ResourceValue::Char(LittleEndian::read_u16(reader.bytes(2)?)),
+            0x04 => ResourceValue::Byte(reader.bytes(1)?[0]),
+            0x05 => ResourceValue::SByte(reader.bytes(1)?[0] as i8),
+            0x06 => ResourceValue::Int16(LittleEndian::read_i16(reader.bytes(2)?)),
+            0x07 => ResourceValue::UInt16(LittleEndian::read_u16(reader.bytes(2)?)),
+            0x08 => ResourceValue::Int32(LittleEndian::read_i32(reader.bytes(4)?)),
+            0x09 => ResourceValue::UInt32(reader.u32()?),
+            0x0A => ResourceValue::Int64(LittleEndian::read_i64(reader.bytes(8)?)),
+            0x0B => ResourceValue::UInt64(LittleEndian::read_u64(reader.bytes(8)?)),
+            0x0C => ResourceValue::Single(LittleEndian::read_f32(reader.bytes(4)?)),
+            0x0D => ResourceValue::Double(LittleEndian::read_f64(reader.bytes(8)?)),
+            0x0E => {
+                let mut value = [0u8; 16];
+                value.copy_from_slice(reader.bytes(16)?);
+                ResourceValue::Decimal(value)
+            }
+            0x0F => ResourceValue::DateTime(LittleEndian::read_i64(reader.bytes(8)?)),
+            0x10 => ResourceValue::TimeSpan(LittleEndian::read_i64(reader.bytes(8)?)),
+            0x20 => {
+                let len = reader.u32()? as usize;
+                ResourceValue::ByteArray(reader.bytes(len)?)
+            }
+            0x21 => {
+                let len = reader.u32()? as usize;
+                ResourceValue::Stream(reader.bytes(len)?)
+            }
+            code if code >= 0x40 => {
+                let type_name = self
+                    .types
+                    .get(code - 0x40)
+                    .ok_or(Error::InvalidMetadata(format!("resource type index {} is out of range", code - 0x40)))?
+                    .clone();
+
+                // Serialized data has no length, so it runs up to the next resource's data
+                let end = self
+                    .entries
+                    .iter()
+                    .map(|(_, o)| *o)
+                    .filter(|o| *o > offset)
+                    .min()
+                    .unwrap_or(self.buf.len());
+                ResourceValue::Serialized { type_name, data: &self.buf[reader.pos.min(end)..end] }
+            }
+            code => return Err(Error::InvalidMetadata(format!("unknown resource type code 0x{:02X}", code))),
+        })
+    }
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let bytes = self.buf.get(self.pos..(self.pos + len)).ok_or(Error::DataOutOfRange)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(LittleEndian::read_u32(self.bytes(4)?))
+    }
+
+    /// Reads an integer in the 7-bit encoding used by `BinaryWriter`.
+    fn encoded_int(&mut self) -> Result<u32, Error> {
+        let mut rest = self.buf.get(self.pos..).ok_or(Error::DataOutOfRange)?;
+        let value = utils::read_7bit_encoded_int(&mut rest).map_err(|e| match e {
+            Error::IoError(_) => Error::DataOutOfRange,
+            e => e,
+        })?;
+        self.pos = self.buf.len() - rest.len();
+        Ok(value)
+    }
+
+    /// Reads a UTF-8 string prefixed with its 7-bit encoded length in bytes.
+    fn string(&mut self) -> Result<String, Error> {
+        let len = self.encoded_int()? as usize;
+        String::from_utf8(self.bytes(len)?.to_vec()).or(Err(Error::InvalidMetadata("invalid UTF-8 string".into())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> Vec<u8> {
+        let mut buf = vec![s.len() as u8];
+        buf.extend_from_slice(s.as_bytes());
+        buf
+    }
+
+    /// Builds a version 2 `.resources` file from names and encoded values.
+    fn resources(types: &[&str], values: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&RESOURCE_MANAGER_MAGIC.to_le_bytes());
+        buf.extend_from_slice(&1u32.to_le_bytes());
+        let reader_type = string("System.Resources.ResourceReader");
+        let set_type = string("System.Resources.RuntimeResourceSet");
+        buf.extend_from_slice(&((reader_type.len() + set_type.len()) as u32).to_le_bytes());
+        buf.extend(reader_type);
+        buf.extend(set_type);
+
+        buf.extend_from_slice(&2u32.to_le_bytes());
+        buf.extend_from_slice(&(values.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&(types.len() as u32).to_le_bytes());
+        for t in types {
+            buf.extend(string(t));
+        }
+        let pad = b"PAD";
+        let mut i = 0;
+        while buf.len() & 7 != 0 {
+            buf.push(pad[i % 3]);
+            i += 1;
+        }
+
+        let mut names = Vec::new();
+        let mut positions = Vec::new();
+        let mut data = Vec::new();
+        for (name, value) in values {
+            positions.push(names.len() as u32);
+            let utf16: Vec<u8> = name.encode_utf16().flat_map(|u| u.to_le_bytes()).collect();
+            names.push(utf16.len() as u8);
+            names.extend(utf16);
+            names.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            data.extend_from_slice(value);
+        }
+        for _ in values {
+            buf.extend_from_slice(&0u32.to_le_bytes());
+        }
+        for position in positions {
+            buf.extend_from_slice(&position.to_le_bytes());
+        }
+        let data_section = buf.len() + 4 + names.len();
+        buf.extend_from_slice(&(data_section as u32).to_le_bytes());
+        buf.extend(names);
+        buf.extend(data);
+        buf
+    }
+
+    #[test]
+    pub fn read_resource_values() {
+        let mut greeting = vec![0x01];
+        greeting.extend(string("Hello"));
+        let buf = resources(
+            &["System.Drawing.Point, System.Drawing"],
+            &[
+                ("Greeting", greeting),
+                ("Count", vec![0x08, 0x2A, 0x00, 0x00, 0x00]),
+                ("Data", vec![0x20, 0x03, 0x00, 0x00, 0x00, 0x01, 0x02, 0x03]),
+                ("Point", vec![0x40, 0xAA, 0xBB]),
+            ],
+        );
+
+        let set = ResourceSet::read(&buf).unwrap();
+        assert_eq!(4, set.len());
+        assert_eq!(vec!["Greeting", "Count", "Data", "Point"], set.names().collect::<Vec<_>>());
+        assert_eq!(ResourceValue::String("Hello".into()), set.get("Greeting").unwrap().unwrap());
+        assert_eq!(ResourceValue::Int32(42), set.get("Count").unwrap().unwrap());
+        assert_eq!(ResourceValue::ByteArray(&[1, 2, 3]), set.get("Data").unwrap().unwrap());
+        assert_eq!(
+            ResourceValue::Serialized { type_name: "System.Drawing.Point, System.Drawing".into(), data: &[0xAA, 0xBB] },
+            set.get("Point").unwrap().unwrap()
+        );
+        assert!(set.get("Missing").is_none());
+        assert_eq!(4, set.entries().unwrap().len());
+    }
+
+    #[test]
+    pub fn reject_bad_magic() {
+        assert!(ResourceSet::read(&[0u8; 16]).is_err());
+    }
+}
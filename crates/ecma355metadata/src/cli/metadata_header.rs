@@ -45,7 +45,7 @@ impl MetadataHeader {
             // Check if it's aligned
             if current_file_pos & 0x3 != 0 {
                 // Get the next 4-byte aligned value
-                let flags_start = (current_file_pos + 4) & !0x4u64;
+                let flags_start = (current_file_pos + 3) & !0x3u64;
                 if flags_start != current_file_pos {
                     buf.seek(SeekFrom::Start(flags_start))?;
                 }
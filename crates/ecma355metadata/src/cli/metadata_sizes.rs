@@ -15,7 +15,9 @@ bitflags! {
         const LARGE_STRINGS = 0x01;
         const LARGE_GUIDS = 0x02;
         const LARGE_BLOBS = 0x04;
+        const ENC_DELTAS = 0x20;
         const EXTRA_DATA = 0x40;
+        const DELETED_MARKS = 0x80;
     }
 }
 
@@ -31,9 +33,14 @@ impl std::fmt::Display for HeapSizes {
     }
 }
 
+#[derive(Clone)]
 pub struct MetadataSizes {
     heap_sizes: HeapSizes,
+    valid_mask: TableMask,
+    sorted_mask: TableMask,
     row_counts: [usize; TableIndex::MAX + 1],
+    external_row_counts: [usize; TableIndex::MAX + 1],
+    minimal_delta: bool,
 }
 
 impl MetadataSizes {
@@ -49,7 +56,7 @@ impl MetadataSizes {
 
         // Read valid and sorted vectors
         let valid_mask = TableMask::from_bits_truncate(buf.read_u64::<LittleEndian>()?);
-        let _sorted_mask = TableMask::from_bits_truncate(buf.read_u64::<LittleEndian>()?);
+        let sorted_mask = TableMask::from_bits_truncate(buf.read_u64::<LittleEndian>()?);
 
         // Load row counts
         let mut row_counts = [0; TableIndex::MAX + 1];
@@ -61,15 +68,58 @@ impl MetadataSizes {
         }
 
         Ok(MetadataSizes {
-            heap_sizes: heap_sizes,
-            row_counts: row_counts,
+            heap_sizes,
+            valid_mask,
+            sorted_mask,
+            row_counts,
+            external_row_counts: [0; TableIndex::MAX + 1],
+            minimal_delta: false,
         })
     }
 
+    /// Creates the sizes of tables that are being written, from their row counts.
+    pub fn new(heap_sizes: HeapSizes, sorted_mask: TableMask, row_counts: &[(TableIndex, usize)]) -> MetadataSizes {
+        let mut sizes = MetadataSizes {
+            heap_sizes,
+            valid_mask: TableMask::empty(),
+            sorted_mask,
+            row_counts: [0; TableIndex::MAX + 1],
+            external_row_counts: [0; TableIndex::MAX + 1],
+            minimal_delta: false,
+        };
+        for (idx, count) in row_counts.iter().filter(|(_, count)| *count > 0) {
+            sizes.row_counts[*idx as usize] = *count;
+            sizes.valid_mask |= TableMask::from_bits_truncate(1 << *idx as u64);
+        }
+        sizes
+    }
+
     pub fn heap_sizes(&self) -> HeapSizes {
         self.heap_sizes
     }
 
+    /// Checks if the tables are a minimal Edit-and-Continue delta, as marked by a `#JTD` stream in the metadata root.
+    ///
+    /// Every table index and coded index in a minimal delta is 4 bytes, whatever the row counts are.
+    pub fn is_minimal_delta(&self) -> bool {
+        self.minimal_delta
+    }
+
+    /// Marks the tables as a minimal delta. The `#JTD` stream that does so lives outside the tables stream.
+    pub fn set_minimal_delta(&mut self, minimal_delta: bool) {
+        self.minimal_delta = minimal_delta;
+    }
+
+    /// Gets the set of tables that are present in the image.
+    pub fn valid_tables(&self) -> TableMask {
+        self.valid_mask
+    }
+
+    /// Gets the set of tables that the image declares to be sorted.
+    pub fn sorted_tables(&self) -> TableMask {
+        self.sorted_mask
+    }
+
     pub fn row_count(&self, idx: TableIndex) -> usize {
         let idx = idx as usize;
         if idx > self.row_counts.len() {
@@ -80,8 +130,23 @@ impl MetadataSizes {
         }
     }
 
+    /// Sets the row counts of tables that live in another image, but are referenced by this one.
+    ///
+    /// Portable PDBs refer to the type system tables of their assembly, and the size of those references depends on
+    /// the row counts recorded in the `#Pdb` stream.
+    pub fn set_external_row_counts(&mut self, row_counts: &[(TableIndex, usize)]) {
+        for (idx, count) in row_counts {
+            self.external_row_counts[*idx as usize] = *count;
+        }
+    }
+
+    /// Gets the number of rows that an index into a table can refer to, including rows in an external image.
+    pub fn referenced_row_count(&self, idx: TableIndex) -> usize {
+        self.row_count(idx).max(self.external_row_counts.get(idx as usize).copied().unwrap_or(0))
+    }
+
     pub fn index_size(&self, idx: TableIndex) -> usize {
-        if self.row_count(idx) <= SMALL_TABLE_MAX_SIZE {
+        if self.referenced_row_count(idx) <= SMALL_TABLE_MAX_SIZE {
             SMALL_INDEX_SIZE
         } else {
             LARGE_INDEX_SIZE
@@ -12,6 +12,11 @@ impl MethodImplAttributes {
         MethodImplAttributes(value)
     }
 
+    /// Gets the raw value of the attributes.
+    pub fn bits(self) -> u16 {
+        self.0
+    }
+
     pub fn code_type(self) -> MethodCodeType {
         unsafe {
             mem::transmute((self.0 & MethodCodeType::MASK) >> MethodCodeType::SHIFT)
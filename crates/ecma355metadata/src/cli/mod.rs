@@ -10,12 +10,18 @@ mod method_attributes;
 mod method_impl_attributes;
 mod param_attributes;
 mod assembly_flags;
+mod managed_resources;
+mod strong_name;
+mod winmd;
 
 pub mod tables;
 pub mod signatures;
 pub mod heaps;
+pub mod pdb;
+pub mod ready_to_run;
+pub mod opcodes;
 
-pub use self::heaps::{BlobHandle, StringHandle, GuidHandle};
+pub use self::heaps::{BlobHandle, StringHandle, GuidHandle, UserStringHandle};
 pub use self::access::Access;
 pub use self::cli_header::CliHeader;
 pub use self::metadata_header::MetadataHeader;
@@ -29,4 +35,8 @@ pub use self::method_impl_attributes::{MethodCodeType, MethodImplAttributes, Met
 pub use self::param_attributes::ParamAttributes;
 pub use self::metadata_sizes::{HeapSizes, MetadataSizes, LARGE_INDEX_SIZE, SMALL_INDEX_SIZE,
                                SMALL_TABLE_MAX_SIZE};
-pub use self::assembly_flags::{AssemblyFlags, AssemblyHashAlgorithm};
\ No newline at end of file
+pub use self::assembly_flags::{AssemblyFlags, AssemblyHashAlgorithm};
+pub use self::managed_resources::{ManagedResource, ResourceSet, ResourceValue};
+pub use self::strong_name::{public_key_token, strong_name_hash, StrongNameKey, StrongNameStatus, ECMA_PUBLIC_KEY};
+pub use self::winmd::{find_projection, Projection, ProjectedName, TypeTreatment, WinMdKind, CLR_PREFIX, PROJECTIONS, WINRT_PREFIX};
+pub use self::opcodes::{FlowControl, OpCode, OperandType};
@@ -0,0 +1,400 @@
+/// The kind of operand that follows an opcode in the instruction stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OperandType {
+    /// No operand.
+    InlineNone,
+    /// A signed 8-bit integer.
+    ShortInlineI,
+    /// A signed 32-bit integer.
+    InlineI,
+    /// A signed 64-bit integer.
+    InlineI8,
+    /// A 32-bit floating point number.
+    ShortInlineR,
+    /// A 64-bit floating point number.
+    InlineR,
+    /// An 8-bit argument or local variable number.
+    ShortInlineVar,
+    /// A 16-bit argument or local variable number.
+    InlineVar,
+    /// An 8-bit branch offset, relative to the start of the next instruction.
+    ShortInlineBrTarget,
+    /// A 32-bit branch offset, relative to the start of the next instruction.
+    InlineBrTarget,
+    /// A 32-bit count followed by that many 32-bit branch offsets.
+    InlineSwitch,
+    /// A MethodDef, MemberRef or MethodSpec token.
+    InlineMethod,
+    /// A Field or MemberRef token.
+    InlineField,
+    /// A TypeDef, TypeRef or TypeSpec token.
+    InlineType,
+    /// A type, method or field token.
+    InlineTok,
+    /// A `#US` heap token.
+    InlineString,
+    /// A StandAloneSig token.
+    InlineSig,
+}
+
+impl OperandType {
+    /// Gets the size of the operand in bytes, which for `switch` is only the size of the count.
+    pub fn size(self) -> usize {
+        match self {
+            OperandType::InlineNone => 0,
+            OperandType::ShortInlineI | OperandType::ShortInlineVar | OperandType::ShortInlineBrTarget => 1,
+            OperandType::InlineVar => 2,
+            OperandType::InlineI8 | OperandType::InlineR => 8,
+            _ => 4,
+        }
+    }
+}
+
+/// How an instruction affects the flow of control.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlowControl {
+    /// Control continues with the next instruction.
+    Next,
+    /// Control always transfers to the branch target.
+    Branch,
+    /// Control transfers to a branch target or continues with the next instruction.
+    ConditionalBranch,
+    /// Control leaves the method, or the handler or filter block.
+    Return,
+    /// An exception is thrown.
+    Throw,
+    /// A prefix that modifies the instruction that follows it.
+    Meta,
+}
+
+/// Marks a stack transition that depends on the signature of the called method.
+pub const VARIABLE: u8 = u8::MAX;
+
+/// An opcode, with the operand and stack transition that go with it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OpCode {
+    /// The name of the instruction, as written in IL assembly.
+    pub name: &'static str,
+    /// The one-byte value, or the two-byte value starting with `0xFE`.
+    pub value: u16,
+    pub operand: OperandType,
+    /// The number of values popped from the evaluation stack, or `VARIABLE`.
+    pub pops: u8,
+    /// The number of values pushed to the evaluation stack, or `VARIABLE`.
+    pub pushes: u8,
+    pub flow: FlowControl,
+}
+
+impl OpCode {
+    /// Looks up an opcode by its IL assembly name, such as `ldarg.0`.
+    pub fn from_name(name: &str) -> Option<OpCode> {
+        ALL.iter().find(|op| op.name == name).copied()
+    }
+
+    /// Looks up an opcode by its value.
+    pub fn from_value(value: u16) -> Option<OpCode> {
+        ALL.iter().find(|op| op.value == value).copied()
+    }
+
+    /// Gets the size of the opcode in bytes, without the operand.
+    pub fn size(&self) -> usize {
+        if self.value > 0xFF { 2 } else { 1 }
+    }
+
+    /// Appends the opcode to an instruction stream.
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        if self.value > 0xFF {
+            buf.extend_from_slice(&self.value.to_be_bytes());
+        } else {
+            buf.push(self.value as u8);
+        }
+    }
+
+    /// Gets the one-byte form of a branch, or `None` if the opcode is not a long branch.
+    pub fn short_branch(&self) -> Option<OpCode> {
+        match self.value {
+            0x38..=0x44 => OpCode::from_value(self.value - 0x0D),
+            0xDD => Some(LEAVE_S),
+            _ => None,
+        }
+    }
+
+    /// Gets the four-byte form of a branch, or `None` if the opcode is not a short branch.
+    pub fn long_branch(&self) -> Option<OpCode> {
+        match self.value {
+            0x2B..=0x37 => OpCode::from_value(self.value + 0x0D),
+            0xDE => Some(LEAVE),
+            _ => None,
+        }
+    }
+}
+
+impl_display_via_debug!(OperandType);
+impl_display_via_debug!(FlowControl);
+
+impl ::std::fmt::Display for OpCode {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> Result<(), ::std::fmt::Error> {
+        f.write_str(self.name)
+    }
+}
+
+macro_rules! opcodes {
+    ($($ident:ident = $name:literal, $value:literal, $operand:ident, $pops:expr, $pushes:expr, $flow:ident;)*) => {
+        $(
+            pub const $ident: OpCode = OpCode {
+                name: $name,
+                value: $value,
+                operand: OperandType::$operand,
+                pops: $pops,
+                pushes: $pushes,
+                flow: FlowControl::$flow,
+            };
+        )*
+
+        /// Every opcode of the instruction set, in order of value.
+        pub const ALL: &[OpCode] = &[$($ident),*];
+    };
+}
+
+opcodes! {
+    NOP = "nop", 0x00, InlineNone, 0, 0, Next;
+    BREAK = "break", 0x01, InlineNone, 0, 0, Next;
+    LDARG_0 = "ldarg.0", 0x02, InlineNone, 0, 1, Next;
+    LDARG_1 = "ldarg.1", 0x03, InlineNone, 0, 1, Next;
+    LDARG_2 = "ldarg.2", 0x04, InlineNone, 0, 1, Next;
+    LDARG_3 = "ldarg.3", 0x05, InlineNone, 0, 1, Next;
+    LDLOC_0 = "ldloc.0", 0x06, InlineNone, 0, 1, Next;
+    LDLOC_1 = "ldloc.1", 0x07, InlineNone, 0, 1, Next;
+    LDLOC_2 = "ldloc.2", 0x08, InlineNone, 0, 1, Next;
+    LDLOC_3 = "ldloc.3", 0x09, InlineNone, 0, 1, Next;
+    STLOC_0 = "stloc.0", 0x0A, InlineNone, 1, 0, Next;
+    STLOC_1 = "stloc.1", 0x0B, InlineNone, 1, 0, Next;
+    STLOC_2 = "stloc.2", 0x0C, InlineNone, 1, 0, Next;
+    STLOC_3 = "stloc.3", 0x0D, InlineNone, 1, 0, Next;
+    LDARG_S = "ldarg.s", 0x0E, ShortInlineVar, 0, 1, Next;
+    LDARGA_S = "ldarga.s", 0x0F, ShortInlineVar, 0, 1, Next;
+    STARG_S = "starg.s", 0x10, ShortInlineVar, 1, 0, Next;
+    LDLOC_S = "ldloc.s", 0x11, ShortInlineVar, 0, 1, Next;
+    LDLOCA_S = "ldloca.s", 0x12, ShortInlineVar, 0, 1, Next;
+    STLOC_S = "stloc.s", 0x13, ShortInlineVar, 1, 0, Next;
+    LDNULL = "ldnull", 0x14, InlineNone, 0, 1, Next;
+    LDC_I4_M1 = "ldc.i4.m1", 0x15, InlineNone, 0, 1, Next;
+    LDC_I4_0 = "ldc.i4.0", 0x16, InlineNone, 0, 1, Next;
+    LDC_I4_1 = "ldc.i4.1", 0x17, InlineNone, 0, 1, Next;
+    LDC_I4_2 = "ldc.i4.2", 0x18, InlineNone, 0, 1, Next;
+    LDC_I4_3 = "ldc.i4.3", 0x19, InlineNone, 0, 1, Next;
+    LDC_I4_4 = "ldc.i4.4", 0x1A, InlineNone, 0, 1, Next;
+    LDC_I4_5 = "ldc.i4.5", 0x1B, InlineNone, 0, 1, Next;
+    LDC_I4_6 = "ldc.i4.6", 0x1C, InlineNone, 0, 1, Next;
+    LDC_I4_7 = "ldc.i4.7", 0x1D, InlineNone, 0, 1, Next;
+    LDC_I4_8 = "ldc.i4.8", 0x1E, InlineNone, 0, 1, Next;
+    LDC_I4_S = "ldc.i4.s", 0x1F, ShortInlineI, 0, 1, Next;
+    LDC_I4 = "ldc.i4", 0x20, InlineI, 0, 1, Next;
+    LDC_I8 = "ldc.i8", 0x21, InlineI8, 0, 1, Next;
+    LDC_R4 = "ldc.r4", 0x22, ShortInlineR, 0, 1, Next;
+    LDC_R8 = "ldc.r8", 0x23, InlineR, 0, 1, Next;
+    DUP = "dup", 0x25, InlineNone, 1, 2, Next;
+    POP = "pop", 0x26, InlineNone, 1, 0, Next;
+    JMP = "jmp", 0x27, InlineMethod, 0, 0, Return;
+    CALL = "call", 0x28, InlineMethod, VARIABLE, VARIABLE, Next;
+    CALLI = "calli", 0x29, InlineSig, VARIABLE, VARIABLE, Next;
+    RET = "ret", 0x2A, InlineNone, VARIABLE, 0, Return;
+    BR_S = "br.s", 0x2B, ShortInlineBrTarget, 0, 0, Branch;
+    BRFALSE_S = "brfalse.s", 0x2C, ShortInlineBrTarget, 1, 0, ConditionalBranch;
+    BRTRUE_S = "brtrue.s", 0x2D, ShortInlineBrTarget, 1, 0, ConditionalBranch;
+    BEQ_S = "beq.s", 0x2E, ShortInlineBrTarget, 2, 0, ConditionalBranch;
+    BGE_S = "bge.s", 0x2F, ShortInlineBrTarget, 2, 0, ConditionalBranch;
+    BGT_S = "bgt.s", 0x30, ShortInlineBrTarget, 2, 0, ConditionalBranch;
+    BLE_S = "ble.s", 0x31, ShortInlineBrTarget, 2, 0, ConditionalBranch;
+    BLT_S = "blt.s", 0x32, ShortInlineBrTarget, 2, 0, ConditionalBranch;
+    BNE_UN_S = "bne.un.s", 0x33, ShortInlineBrTarget, 2, 0, ConditionalBranch;
+    BGE_UN_S = "bge.un.s", 0x34, ShortInlineBrTarget, 2, 0, ConditionalBranch;
+    BGT_UN_S = "bgt.un.s", 0x35, ShortInlineBrTarget, 2, 0, ConditionalBranch;
+    BLE_UN_S = "ble.un.s", 0x36, ShortInlineBrTarget, 2, 0, ConditionalBranch;
+    BLT_UN_S = "blt.un.s", 0x37, ShortInlineBrTarget, 2, 0, ConditionalBranch;
+    BR = "br", 0x38, InlineBrTarget, 0, 0, Branch;
+    BRFALSE = "brfalse", 0x39, InlineBrTarget, 1, 0, ConditionalBranch;
+    BRTRUE = "brtrue", 0x3A, InlineBrTarget, 1, 0, ConditionalBranch;
+    BEQ = "beq", 0x3B, InlineBrTarget, 2, 0, ConditionalBranch;
+    BGE = "bge", 0x3C, InlineBrTarget, 2, 0, ConditionalBranch;
+    BGT = "bgt", 0x3D, InlineBrTarget, 2, 0, ConditionalBranch;
+    BLE = "ble", 0x3E, InlineBrTarget, 2, 0, ConditionalBranch;
+    BLT = "blt", 0x3F, InlineBrTarget, 2, 0, ConditionalBranch;
+    BNE_UN = "bne.un", 0x40, InlineBrTarget, 2, 0, ConditionalBranch;
+    BGE_UN = "bge.un", 0x41, InlineBrTarget, 2, 0, ConditionalBranch;
+    BGT_UN = "bgt.un", 0x42, InlineBrTarget, 2, 0, ConditionalBranch;
+    BLE_UN = "ble.un", 0x43, InlineBrTarget, 2, 0, ConditionalBranch;
+    BLT_UN = "blt.un", 0x44, InlineBrTarget, 2, 0, ConditionalBranch;
+    SWITCH = "switch", 0x45, InlineSwitch, 1, 0, ConditionalBranch;
+    LDIND_I1 = "ldind.i1", 0x46, InlineNone, 1, 1, Next;
+    LDIND_U1 = "ldind.u1", 0x47, InlineNone, 1, 1, Next;
+    LDIND_I2 = "ldind.i2", 0x48, InlineNone, 1, 1, Next;
+    LDIND_U2 = "ldind.u2", 0x49, InlineNone, 1, 1, Next;
+    LDIND_I4 = "ldind.i4", 0x4A, InlineNone, 1, 1, Next;
+    LDIND_U4 = "ldind.u4", 0x4B, InlineNone, 1, 1, Next;
+    LDIND_I8 = "ldind.i8", 0x4C, InlineNone, 1, 1, Next;
+    LDIND_I = "ldind.i", 0x4D, InlineNone, 1, 1, Next;
+    LDIND_R4 = "ldind.r4", 0x4E, InlineNone, 1, 1, Next;
+    LDIND_R8 = "ldind.r8", 0x4F, InlineNone, 1, 1, Next;
+    LDIND_REF = "ldind.ref", 0x50, InlineNone, 1, 1, Next;
+    STIND_REF = "stind.ref", 0x51, InlineNone, 2, 0, Next;
+    STIND_I1 = "stind.i1", 0x52, InlineNone, 2, 0, Next;
+    STIND_I2 = "stind.i2", 0x53, InlineNone, 2, 0, Next;
+    STIND_I4 = "stind.i4", 0x54, InlineNone, 2, 0, Next;
+    STIND_I8 = "stind.i8", 0x55, InlineNone, 2, 0, Next;
+    STIND_R4 = "stind.r4", 0x56, InlineNone, 2, 0, Next;
+    STIND_R8 = "stind.r8", 0x57, InlineNone, 2, 0, Next;
+    ADD = "add", 0x58, InlineNone, 2, 1, Next;
+    SUB = "sub", 0x59, InlineNone, 2, 1, Next;
+    MUL = "mul", 0x5A, InlineNone, 2, 1, Next;
+    DIV = "div", 0x5B, InlineNone, 2, 1, Next;
+    DIV_UN = "div.un", 0x5C, InlineNone, 2, 1, Next;
+    REM = "rem", 0x5D, InlineNone, 2, 1, Next;
+    REM_UN = "rem.un", 0x5E, InlineNone, 2, 1, Next;
+    AND = "and", 0x5F, InlineNone, 2, 1, Next;
+    OR = "or", 0x60, InlineNone, 2, 1, Next;
+    XOR = "xor", 0x61, InlineNone, 2, 1, Next;
+    SHL = "shl", 0x62, InlineNone, 2, 1, Next;
+    SHR = "shr", 0x63, InlineNone, 2, 1, Next;
+    SHR_UN = "shr.un", 0x64, InlineNone, 2, 1, Next;
+    NEG = "neg", 0x65, InlineNone, 1, 1, Next;
+    NOT = "not", 0x66, InlineNone, 1, 1, Next;
+    CONV_I1 = "conv.i1", 0x67, InlineNone, 1, 1, Next;
+    CONV_I2 = "conv.i2", 0x68, InlineNone, 1, 1, Next;
+    CONV_I4 = "conv.i4", 0x69, InlineNone, 1, 1, Next;
+    CONV_I8 = "conv.i8", 0x6A, InlineNone, 1, 1, Next;
+    CONV_R4 = "conv.r4", 0x6B, InlineNone, 1, 1, Next;
+    CONV_R8 = "conv.r8", 0x6C, InlineNone, 1, 1, Next;
+    CONV_U4 = "conv.u4", 0x6D, InlineNone, 1, 1, Next;
+    CONV_U8 = "conv.u8", 0x6E, InlineNone, 1, 1, Next;
+    CALLVIRT = "callvirt", 0x6F, InlineMethod, VARIABLE, VARIABLE, Next;
+    CPOBJ = "cpobj", 0x70, InlineType, 2, 0, Next;
+    LDOBJ = "ldobj", 0x71, InlineType, 1, 1, Next;
+    LDSTR = "ldstr", 0x72, InlineString, 0, 1, Next;
+    NEWOBJ = "newobj", 0x73, InlineMethod, VARIABLE, 1, Next;
+    CASTCLASS = "castclass", 0x74, InlineType, 1, 1, Next;
+    ISINST = "isinst", 0x75, InlineType, 1, 1, Next;
+    CONV_R_UN = "conv.r.un", 0x76, InlineNone, 1, 1, Next;
+    UNBOX = "unbox", 0x79, InlineType, 1, 1, Next;
+    THROW = "throw", 0x7A, InlineNone, 1, 0, Throw;
+    LDFLD = "ldfld", 0x7B, InlineField, 1, 1, Next;
+    LDFLDA = "ldflda", 0x7C, InlineField, 1, 1, Next;
+    STFLD = "stfld", 0x7D, InlineField, 2, 0, Next;
+    LDSFLD = "ldsfld", 0x7E, InlineField, 0, 1, Next;
+    LDSFLDA = "ldsflda", 0x7F, InlineField, 0, 1, Next;
+    STSFLD = "stsfld", 0x80, InlineField, 1, 0, Next;
+    STOBJ = "stobj", 0x81, InlineType, 2, 0, Next;
+    CONV_OVF_I1_UN = "conv.ovf.i1.un", 0x82, InlineNone, 1, 1, Next;
+    CONV_OVF_I2_UN = "conv.ovf.i2.un", 0x83, InlineNone, 1, 1, Next;
+    CONV_OVF_I4_UN = "conv.ovf.i4.un", 0x84, InlineNone, 1, 1, Next;
+    CONV_OVF_I8_UN = "conv.ovf.i8.un", 0x85, InlineNone, 1, 1, Next;
+    CONV_OVF_U1_UN = "conv.ovf.u1.un", 0x86, InlineNone, 1, 1, Next;
+    CONV_OVF_U2_UN = "conv.ovf.u2.un", 0x87, InlineNone, 1, 1, Next;
+    CONV_OVF_U4_UN = "conv.ovf.u4.un", 0x88, InlineNone, 1, 1, Next;
+    CONV_OVF_U8_UN = "conv.ovf.u8.un", 0x89, InlineNone, 1, 1, Next;
+    CONV_OVF_I_UN = "conv.ovf.i.un", 0x8A, InlineNone, 1, 1, Next;
+    CONV_OVF_U_UN = "conv.ovf.u.un", 0x8B, InlineNone, 1, 1, Next;
+    BOX = "box", 0x8C, InlineType, 1, 1, Next;
+    NEWARR = "newarr", 0x8D, InlineType, 1, 1, Next;
+    LDLEN = "ldlen", 0x8E, InlineNone, 1, 1, Next;
+    LDELEMA = "ldelema", 0x8F, InlineType, 2, 1, Next;
+    LDELEM_I1 = "ldelem.i1", 0x90, InlineNone, 2, 1, Next;
+    LDELEM_U1 = "ldelem.u1", 0x91, InlineNone, 2, 1, Next;
+    LDELEM_I2 = "ldelem.i2", 0x92, InlineNone, 2, 1, Next;
+    LDELEM_U2 = "ldelem.u2", 0x93, InlineNone, 2, 1, Next;
+    LDELEM_I4 = "ldelem.i4", 0x94, InlineNone, 2, 1, Next;
+    LDELEM_U4 = "ldelem.u4", 0x95, InlineNone, 2, 1, Next;
+    LDELEM_I8 = "ldelem.i8", 0x96, InlineNone, 2, 1, Next;
+    LDELEM_I = "ldelem.i", 0x97, InlineNone, 2, 1, Next;
+    LDELEM_R4 = "ldelem.r4", 0x98, InlineNone, 2, 1, Next;
+    LDELEM_R8 = "ldelem.r8", 0x99, InlineNone, 2, 1, Next;
+    LDELEM_REF = "ldelem.ref", 0x9A, InlineNone, 2, 1, Next;
+    STELEM_I = "stelem.i", 0x9B, InlineNone, 3, 0, Next;
+    STELEM_I1 = "stelem.i1", 0x9C, InlineNone, 3, 0, Next;
+    STELEM_I2 = "stelem.i2", 0x9D, InlineNone, 3, 0, Next;
+    STELEM_I4 = "stelem.i4", 0x9E, InlineNone, 3, 0, Next;
+    STELEM_I8 = "stelem.i8", 0x9F, InlineNone, 3, 0, Next;
+    STELEM_R4 = "stelem.r4", 0xA0, InlineNone, 3, 0, Next;
+    STELEM_R8 = "stelem.r8", 0xA1, InlineNone, 3, 0, Next;
+    STELEM_REF = "stelem.ref", 0xA2, InlineNone, 3, 0, Next;
+    LDELEM = "ldelem", 0xA3, InlineType, 2, 1, Next;
+    STELEM = "stelem", 0xA4, InlineType, 3, 0, Next;
+    UNBOX_ANY = "unbox.any", 0xA5, InlineType, 1, 1, Next;
+    CONV_OVF_I1 = "conv.ovf.i1", 0xB3, InlineNone, 1, 1, Next;
+    CONV_OVF_U1 = "conv.ovf.u1", 0xB4, InlineNone, 1, 1, Next;
+    CONV_OVF_I2 = "conv.ovf.i2", 0xB5, InlineNone, 1, 1, Next;
+    CONV_OVF_U2 = "conv.ovf.u2", 0xB6, InlineNone, 1, 1, Next;
+    CONV_OVF_I4 = "conv.ovf.i4", 0xB7, InlineNone, 1, 1, Next;
+    CONV_OVF_U4 = "conv.ovf.u4", 0xB8, InlineNone, 1, 1, Next;
+    CONV_OVF_I8 = "conv.ovf.i8", 0xB9, InlineNone, 1, 1, Next;
+    CONV_OVF_U8 = "conv.ovf.u8", 0xBA, InlineNone, 1, 1, Next;
+    REFANYVAL = "refanyval", 0xC2, InlineType, 1, 1, Next;
+    CKFINITE = "ckfinite", 0xC3, InlineNone, 1, 1, Next;
+    MKREFANY = "mkrefany", 0xC6, InlineType, 1, 1, Next;
+    LDTOKEN = "ldtoken", 0xD0, InlineTok, 0, 1, Next;
+    CONV_U2 = "conv.u2", 0xD1, InlineNone, 1, 1, Next;
+    CONV_U1 = "conv.u1", 0xD2, InlineNone, 1, 1, Next;
+    CONV_I = "conv.i", 0xD3, InlineNone, 1, 1, Next;
+    CONV_OVF_I = "conv.ovf.i", 0xD4, InlineNone, 1, 1, Next;
+    CONV_OVF_U = "conv.ovf.u", 0xD5, InlineNone, 1, 1, Next;
+    ADD_OVF = "add.ovf", 0xD6, InlineNone, 2, 1, Next;
+    ADD_OVF_UN = "add.ovf.un", 0xD7, InlineNone, 2, 1, Next;
+    MUL_OVF = "mul.ovf", 0xD8, InlineNone, 2, 1, Next;
+    MUL_OVF_UN = "mul.ovf.un", 0xD9, InlineNone, 2, 1, Next;
+    SUB_OVF = "sub.ovf", 0xDA, InlineNone, 2, 1, Next;
+    SUB_OVF_UN = "sub.ovf.un", 0xDB, InlineNone, 2, 1, Next;
+    ENDFINALLY = "endfinally", 0xDC, InlineNone, 0, 0, Return;
+    LEAVE = "leave", 0xDD, InlineBrTarget, 0, 0, Branch;
+    LEAVE_S = "leave.s", 0xDE, ShortInlineBrTarget, 0, 0, Branch;
+    STIND_I = "stind.i", 0xDF, InlineNone, 2, 0, Next;
+    CONV_U = "conv.u", 0xE0, InlineNone, 1, 1, Next;
+    ARGLIST = "arglist", 0xFE00, InlineNone, 0, 1, Next;
+    CEQ = "ceq", 0xFE01, InlineNone, 2, 1, Next;
+    CGT = "cgt", 0xFE02, InlineNone, 2, 1, Next;
+    CGT_UN = "cgt.un", 0xFE03, InlineNone, 2, 1, Next;
+    CLT = "clt", 0xFE04, InlineNone, 2, 1, Next;
+    CLT_UN = "clt.un", 0xFE05, InlineNone, 2, 1, Next;
+    LDFTN = "ldftn", 0xFE06, InlineMethod, 0, 1, Next;
+    LDVIRTFTN = "ldvirtftn", 0xFE07, InlineMethod, 1, 1, Next;
+    LDARG = "ldarg", 0xFE09, InlineVar, 0, 1, Next;
+    LDARGA = "ldarga", 0xFE0A, InlineVar, 0, 1, Next;
+    STARG = "starg", 0xFE0B, InlineVar, 1, 0, Next;
+    LDLOC = "ldloc", 0xFE0C, InlineVar, 0, 1, Next;
+    LDLOCA = "ldloca", 0xFE0D, InlineVar, 0, 1, Next;
+    STLOC = "stloc", 0xFE0E, InlineVar, 1, 0, Next;
+    LOCALLOC = "localloc", 0xFE0F, InlineNone, 1, 1, Next;
+    ENDFILTER = "endfilter", 0xFE11, InlineNone, 1, 0, Return;
+    UNALIGNED = "unaligned.", 0xFE12, ShortInlineI, 0, 0, Meta;
+    VOLATILE = "volatile.", 0xFE13, InlineNone, 0, 0, Meta;
+    TAIL = "tail.", 0xFE14, InlineNone, 0, 0, Meta;
+    INITOBJ = "initobj", 0xFE15, InlineType, 1, 0, Next;
+    CONSTRAINED = "constrained.", 0xFE16, InlineType, 0, 0, Meta;
+    CPBLK = "cpblk", 0xFE17, InlineNone, 3, 0, Next;
+    INITBLK = "initblk", 0xFE18, InlineNone, 3, 0, Next;
+    NO = "no.", 0xFE19, ShortInlineI, 0, 0, Meta;
+    RETHROW = "rethrow", 0xFE1A, InlineNone, 0, 0, Throw;
+    SIZEOF = "sizeof", 0xFE1C, InlineType, 0, 1, Next;
+    REFANYTYPE = "refanytype", 0xFE1D, InlineNone, 1, 1, Next;
+    READONLY = "readonly.", 0xFE1E, InlineNone, 0, 0, Meta;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn lookup_opcodes() {
+        assert_eq!(Some(LDARG_0), OpCode::from_name("ldarg.0"));
+        assert_eq!(Some(CONSTRAINED), OpCode::from_value(0xFE16));
+        assert_eq!(None, OpCode::from_name("ldarg.4"));
+        assert_eq!(Some(BLT_UN_S), BLT_UN.short_branch());
+        assert_eq!(Some(LEAVE), LEAVE_S.long_branch());
+        assert_eq!(None, SWITCH.short_branch());
+
+        let mut buf = Vec::new();
+        CEQ.encode(&mut buf);
+        RET.encode(&mut buf);
+        assert_eq!(vec![0xFE, 0x01, 0x2A], buf);
+        assert!(ALL.windows(2).all(|w| w[0].value < w[1].value));
+    }
+}
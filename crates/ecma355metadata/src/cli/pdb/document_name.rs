@@ -0,0 +1,55 @@
+use crate::cli::signatures::utils::read_compressed_u32;
+use crate::cli::BlobHandle;
+use crate::error::Error;
+
+/// Decodes the name blob of a Document row into a path.
+///
+/// The blob holds a separator character followed by the blob handles of each part of the path. `get_blob` looks up
+/// each part in the blob heap.
+pub fn read_document_name<'a>(mut blob: &[u8], get_blob: impl Fn(BlobHandle) -> Option<&'a [u8]>) -> Result<String, Error> {
+    let separator = match blob.split_first() {
+        Some((separator, rest)) => {
+            blob = rest;
+            *separator
+        }
+        None => return Err(Error::InvalidMetadata("document name blob is empty".into())),
+    };
+    if separator > 0x7F {
+        return Err(Error::InvalidMetadata("document name separator must be ASCII".into()));
+    }
+
+    let mut name = String::new();
+    let mut first = true;
+    while !blob.is_empty() {
+        if !first && separator != 0 {
+            name.push(separator as char);
+        }
+        first = false;
+
+        let part = read_compressed_u32(&mut blob)? as usize;
+        if part != 0 {
+            let bytes = get_blob(BlobHandle(part)).ok_or(Error::InvalidHeapReference)?;
+            name.push_str(std::str::from_utf8(bytes).or(Err(Error::InvalidMetadata("invalid UTF-8 string".into())))?);
+        }
+    }
+    Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn read_name_with_separator() {
+        let parts: &[&[u8]] = &[b"", b"src", b"Program.cs"];
+        let name = read_document_name(&[b'/', 0x00, 0x01, 0x02], |h| parts.get(h.0).copied()).unwrap();
+        assert_eq!("/src/Program.cs", name);
+    }
+
+    #[test]
+    pub fn read_name_without_separator() {
+        let parts: &[&[u8]] = &[b"", b"C:\\Program.cs"];
+        let name = read_document_name(&[0x00, 0x01], |h| parts.get(h.0).copied()).unwrap();
+        assert_eq!("C:\\Program.cs", name);
+    }
+}
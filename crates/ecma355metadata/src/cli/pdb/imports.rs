@@ -0,0 +1,70 @@
+use crate::cli::signatures::utils::{read_compressed_u32, read_type_def_or_ref_spec_encoded};
+use crate::cli::tables::{TableHandle, TableIndex};
+use crate::cli::BlobHandle;
+use crate::error::Error;
+
+/// An import in the imports blob of an ImportScope row.
+///
+/// Aliases and namespaces are blob handles that refer to UTF-8 strings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Import {
+    Namespace { namespace: BlobHandle },
+    AssemblyNamespace { assembly: TableHandle, namespace: BlobHandle },
+    Type { target: TableHandle },
+    XmlNamespace { alias: BlobHandle, namespace: BlobHandle },
+    AssemblyReferenceAlias { alias: BlobHandle },
+    AliasAssemblyReference { alias: BlobHandle, assembly: TableHandle },
+    AliasNamespace { alias: BlobHandle, namespace: BlobHandle },
+    AliasAssemblyNamespace { alias: BlobHandle, assembly: TableHandle, namespace: BlobHandle },
+    AliasType { alias: BlobHandle, target: TableHandle },
+}
+
+impl Import {
+    /// Decodes every import in an imports blob.
+    pub fn read_all(mut blob: &[u8]) -> Result<Vec<Import>, Error> {
+        let mut imports = Vec::new();
+        while !blob.is_empty() {
+            imports.push(Import::read(&mut blob)?);
+        }
+        Ok(imports)
+    }
+
+    fn read(blob: &mut &[u8]) -> Result<Import, Error> {
+        let handle = |blob: &mut &[u8]| -> Result<BlobHandle, Error> { Ok(BlobHandle(read_compressed_u32(blob)? as usize)) };
+        let assembly = |blob: &mut &[u8]| -> Result<TableHandle, Error> {
+            Ok(TableHandle::new(read_compressed_u32(blob)? as usize, TableIndex::AssemblyRef))
+        };
+
+        Ok(match read_compressed_u32(blob)? {
+            1 => Import::Namespace { namespace: handle(blob)? },
+            2 => Import::AssemblyNamespace { assembly: assembly(blob)?, namespace: handle(blob)? },
+            3 => Import::Type { target: read_type_def_or_ref_spec_encoded(blob)? },
+            4 => Import::XmlNamespace { alias: handle(blob)?, namespace: handle(blob)? },
+            5 => Import::AssemblyReferenceAlias { alias: handle(blob)? },
+            6 => Import::AliasAssemblyReference { alias: handle(blob)?, assembly: assembly(blob)? },
+            7 => Import::AliasNamespace { alias: handle(blob)?, namespace: handle(blob)? },
+            8 => Import::AliasAssemblyNamespace { alias: handle(blob)?, assembly: assembly(blob)?, namespace: handle(blob)? },
+            9 => Import::AliasType { alias: handle(blob)?, target: read_type_def_or_ref_spec_encoded(blob)? },
+            kind => return Err(Error::InvalidMetadata(format!("unknown import kind {}", kind))),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn read_imports() {
+        let imports = Import::read_all(&[0x01, 0x10, 0x07, 0x20, 0x21, 0x03, 0x09]).unwrap();
+        assert_eq!(
+            vec![
+                Import::Namespace { namespace: BlobHandle(0x10) },
+                Import::AliasNamespace { alias: BlobHandle(0x20), namespace: BlobHandle(0x21) },
+                Import::Type { target: TableHandle::new(2, TableIndex::TypeRef) },
+            ],
+            imports
+        );
+        assert!(Import::read_all(&[0x0A]).is_err());
+    }
+}
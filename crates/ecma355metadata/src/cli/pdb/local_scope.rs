@@ -0,0 +1,10 @@
+use crate::cli::tables::{LocalConstant, LocalVariable, TableHandle};
+
+/// A scope within a method body, with the local variables and constants declared in it.
+pub struct LocalScopeInfo {
+    pub start_offset: u32,
+    pub length: u32,
+    pub import_scope: TableHandle,
+    pub variables: Vec<LocalVariable>,
+    pub constants: Vec<LocalConstant>,
+}
@@ -0,0 +1,11 @@
+mod document_name;
+mod imports;
+mod local_scope;
+mod pdb_stream;
+mod sequence_points;
+
+pub use self::document_name::read_document_name;
+pub use self::imports::Import;
+pub use self::local_scope::LocalScopeInfo;
+pub use self::pdb_stream::PdbStream;
+pub use self::sequence_points::{SequencePoint, HIDDEN_LINE};
@@ -0,0 +1,57 @@
+use std::io::Read;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::cli::tables::{TableHandle, TableIndex, TableMask};
+use crate::error::Error;
+use crate::Guid;
+
+/// The `#Pdb` stream of a Portable PDB.
+pub struct PdbStream {
+    /// The PDB id, which matches the GUID and stamp in the CodeView entry of the image the PDB belongs to.
+    pub id: [u8; 20],
+
+    /// The entry point of the image, or `None` if it has no entry point.
+    pub entry_point: Option<TableHandle>,
+
+    /// The type system tables that the PDB refers to.
+    pub referenced_tables: TableMask,
+
+    /// The row counts of each referenced type system table, which live in the image rather than the PDB.
+    pub row_counts: Vec<(TableIndex, usize)>,
+}
+
+impl PdbStream {
+    pub fn read<A: Read>(mut buf: A) -> Result<PdbStream, Error> {
+        let mut id = [0u8; 20];
+        buf.read_exact(&mut id)?;
+
+        let entry_point = match buf.read_u32::<LittleEndian>()? {
+            0 => None,
+            token => Some(TableHandle::from_metadata_token(token)),
+        };
+
+        let referenced_tables = TableMask::from_bits_truncate(buf.read_u64::<LittleEndian>()?);
+        let mut row_counts = Vec::new();
+        for idx in referenced_tables.tables() {
+            row_counts.push((idx, buf.read_u32::<LittleEndian>()? as usize));
+        }
+
+        Ok(PdbStream {
+            id,
+            entry_point,
+            referenced_tables,
+            row_counts,
+        })
+    }
+
+    /// Gets the GUID part of the PDB id.
+    pub fn guid(&self) -> Guid {
+        Guid::from_bytes(&self.id[0..16])
+    }
+
+    /// Gets the stamp part of the PDB id, which matches the timestamp in the COFF header of a deterministic build.
+    pub fn stamp(&self) -> u32 {
+        u32::from_le_bytes([self.id[16], self.id[17], self.id[18], self.id[19]])
+    }
+}
@@ -0,0 +1,145 @@
+use crate::cli::signatures::utils::{read_compressed_i32, read_compressed_u32};
+use crate::cli::tables::{TableHandle, TableIndex};
+use crate::error::Error;
+
+/// The line number that marks a hidden sequence point.
+pub const HIDDEN_LINE: u32 = 0xFEEFEE;
+
+/// Maps an IL offset in a method body to a range of source text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SequencePoint {
+    pub document: TableHandle,
+    pub il_offset: u32,
+    pub start_line: u32,
+    pub start_column: u16,
+    pub end_line: u32,
+    pub end_column: u16,
+}
+
+impl SequencePoint {
+    /// Checks if the point is hidden, meaning the IL that follows it has no corresponding source.
+    pub fn is_hidden(&self) -> bool {
+        self.start_line == HIDDEN_LINE
+    }
+
+    /// Decodes a sequence points blob from the MethodDebugInformation table.
+    ///
+    /// `document` is the Document column of the row, which is null when the method spans several documents. In that
+    /// case the blob names the initial document itself.
+    pub fn read_all(mut blob: &[u8], document: TableHandle) -> Result<Vec<SequencePoint>, Error> {
+        // The local signature isn't needed to map offsets to lines
+        read_compressed_u32(&mut blob)?;
+
+        let mut document = if document.index() == 0 {
+            TableHandle::new(read_compressed_u32(&mut blob)? as usize, TableIndex::Document)
+        } else {
+            document
+        };
+
+        let mut points: Vec<SequencePoint> = Vec::new();
+        let mut il_offset: u32 = 0;
+        let mut previous_line: Option<(u32, u16)> = None;
+        while !blob.is_empty() {
+            let delta_il_offset = read_compressed_u32(&mut blob)?;
+            if delta_il_offset == 0 && !points.is_empty() {
+                // A document record switches the document for the points that follow
+                document = TableHandle::new(read_compressed_u32(&mut blob)? as usize, TableIndex::Document);
+                continue;
+            }
+            il_offset = il_offset
+                .checked_add(delta_il_offset)
+                .ok_or(Error::InvalidMetadata("sequence point IL offset is out of range".into()))?;
+
+            let delta_lines = read_compressed_u32(&mut blob)?;
+            let delta_columns = if delta_lines == 0 {
+                read_compressed_u32(&mut blob)? as i64
+            } else {
+                read_compressed_i32(&mut blob)? as i64
+            };
+
+            if delta_lines == 0 && delta_columns == 0 {
+                points.push(SequencePoint {
+                    document,
+                    il_offset,
+                    start_line: HIDDEN_LINE,
+                    start_column: 0,
+                    end_line: HIDDEN_LINE,
+                    end_column: 0,
+                });
+                continue;
+            }
+
+            // The first visible point has absolute start values, and later ones are relative to the previous visible point
+            let (start_line, start_column) = match previous_line {
+                None => (read_compressed_u32(&mut blob)? as i64, read_compressed_u32(&mut blob)? as i64),
+                Some((line, column)) => (
+                    line as i64 + read_compressed_i32(&mut blob)? as i64,
+                    column as i64 + read_compressed_i32(&mut blob)? as i64,
+                ),
+            };
+            let end_line = start_line + delta_lines as i64;
+            let end_column = start_column + delta_columns;
+            if start_line < 0 || start_column < 0 || end_column < 0 || end_line > u32::MAX as i64 || end_column > u16::MAX as i64 {
+                return Err(Error::InvalidMetadata("sequence point is out of range".into()));
+            }
+
+            let point = SequencePoint {
+                document,
+                il_offset,
+                start_line: start_line as u32,
+                start_column: start_column as u16,
+                end_line: end_line as u32,
+                end_column: end_column as u16,
+            };
+            previous_line = Some((point.start_line, point.start_column));
+            points.push(point);
+        }
+        Ok(points)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn read_sequence_points() {
+        let blob: &[u8] = &[
+            0x01, // Local signature
+            0x00, 0x00, 0x01, 0x05, 0x09, // IL_0000: (5,9)-(5,10)
+            0x02, 0x01, 0x04, 0x02, 0x00, // IL_0002: (6,9)-(7,11)
+            0x03, 0x00, 0x00, // IL_0005: hidden
+            0x00, 0x02, // Switch to document 2
+            0x01, 0x00, 0x04, 0x02, 0x02, // IL_0006: (7,10)-(7,14)
+        ];
+        let points = SequencePoint::read_all(blob, TableHandle::new(1, TableIndex::Document)).unwrap();
+        assert_eq!(4, points.len());
+
+        assert_eq!((0, 5, 9, 5, 10), (points[0].il_offset, points[0].start_line, points[0].start_column, points[0].end_line, points[0].end_column));
+        assert_eq!((2, 6, 9, 7, 11), (points[1].il_offset, points[1].start_line, points[1].start_column, points[1].end_line, points[1].end_column));
+        assert!(points[2].is_hidden());
+        assert_eq!(5, points[2].il_offset);
+        assert_eq!(1, points[2].document.index());
+        assert_eq!((6, 7, 10, 7, 14), (points[3].il_offset, points[3].start_line, points[3].start_column, points[3].end_line, points[3].end_column));
+        assert_eq!(2, points[3].document.index());
+    }
+
+    #[test]
+    pub fn read_initial_document() {
+        let blob: &[u8] = &[0x00, 0x03, 0x00, 0x00, 0x01, 0x01, 0x01];
+        let points = SequencePoint::read_all(blob, TableHandle::new(0, TableIndex::Document)).unwrap();
+        assert_eq!(3, points[0].document.index());
+        assert_eq!((1, 1, 1, 2), (points[0].start_line, points[0].start_column, points[0].end_line, points[0].end_column));
+    }
+
+    #[test]
+    pub fn reject_overflowing_offset() {
+        // Nine hidden points, each 0x1FFFFFFF bytes past the last, run past u32::MAX
+        let mut blob = vec![0x00];
+        for _ in 0..9 {
+            blob.extend_from_slice(&[0xDF, 0xFF, 0xFF, 0xFF, 0x00, 0x00]);
+        }
+        let result = SequencePoint::read_all(&blob, TableHandle::new(1, TableIndex::Document));
+        assert!(matches!(result, Err(Error::InvalidMetadata(_))));
+    }
+}
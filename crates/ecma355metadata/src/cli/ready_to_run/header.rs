@@ -0,0 +1,158 @@
+use std::io::Read;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::error::Error;
+use crate::pe::MemoryRange;
+
+/// The kinds of section listed in a ReadyToRun header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadyToRunSectionType {
+    CompilerIdentifier,
+    ImportSections,
+    RuntimeFunctions,
+    MethodDefEntryPoints,
+    ExceptionInfo,
+    DebugInfo,
+    DelayLoadMethodCallThunks,
+    AvailableTypes,
+    InstanceMethodEntryPoints,
+    InliningInfo,
+    ProfileDataInfo,
+    ManifestMetadata,
+    AttributePresence,
+    InliningInfo2,
+    ComponentAssemblies,
+    OwnerCompositeExecutable,
+    PgoInstrumentationData,
+    ManifestAssemblyMvids,
+    CrossModuleInlineInfo,
+    HotColdMap,
+    MethodIsGenericMap,
+    EnclosingTypeMap,
+    TypeGenericInfoMap,
+    Other(u32),
+}
+
+impl ReadyToRunSectionType {
+    pub fn from_u32(value: u32) -> ReadyToRunSectionType {
+        match value {
+            100 => ReadyToRunSectionType::CompilerIdentifier,
+            101 => ReadyToRunSectionType::ImportSections,
+            102 => ReadyToRunSectionType::RuntimeFunctions,
+            103 => ReadyToRunSectionType::MethodDefEntryPoints,
+            104 => ReadyToRunSectionType::ExceptionInfo,
+            105 => ReadyToRunSectionType::DebugInfo,
+            106 => ReadyToRunSectionType::DelayLoadMethodCallThunks,
+            108 => ReadyToRunSectionType::AvailableTypes,
+            109 => ReadyToRunSectionType::InstanceMethodEntryPoints,
+            110 => ReadyToRunSectionType::InliningInfo,
+            111 => ReadyToRunSectionType::ProfileDataInfo,
+            112 => ReadyToRunSectionType::ManifestMetadata,
+            113 => ReadyToRunSectionType::AttributePresence,
+            114 => ReadyToRunSectionType::InliningInfo2,
+            115 => ReadyToRunSectionType::ComponentAssemblies,
+            116 => ReadyToRunSectionType::OwnerCompositeExecutable,
+            117 => ReadyToRunSectionType::PgoInstrumentationData,
+            118 => ReadyToRunSectionType::ManifestAssemblyMvids,
+            119 => ReadyToRunSectionType::CrossModuleInlineInfo,
+            120 => ReadyToRunSectionType::HotColdMap,
+            121 => ReadyToRunSectionType::MethodIsGenericMap,
+            122 => ReadyToRunSectionType::EnclosingTypeMap,
+            123 => ReadyToRunSectionType::TypeGenericInfoMap,
+            x => ReadyToRunSectionType::Other(x),
+        }
+    }
+}
+
+impl_display_via_debug!(ReadyToRunSectionType);
+
+/// An entry in the section table of a ReadyToRun header.
+#[derive(Clone, Copy, Debug)]
+pub struct ReadyToRunSection {
+    pub section_type: ReadyToRunSectionType,
+    pub range: MemoryRange,
+}
+
+/// The header that the ManagedNativeHeader of the CLI header points to in a ReadyToRun image.
+#[derive(Debug)]
+pub struct ReadyToRunHeader {
+    pub major_version: u16,
+    pub minor_version: u16,
+    pub flags: u32,
+    pub sections: Vec<ReadyToRunSection>,
+}
+
+/// "RTR" in little-endian
+pub const READY_TO_RUN_SIGNATURE: u32 = 0x00525452;
+
+impl ReadyToRunHeader {
+    pub fn read<A: Read>(mut buf: A) -> Result<ReadyToRunHeader, Error> {
+        if buf.read_u32::<LittleEndian>()? != READY_TO_RUN_SIGNATURE {
+            return Err(Error::InvalidSignature);
+        }
+        let major_version = buf.read_u16::<LittleEndian>()?;
+        let minor_version = buf.read_u16::<LittleEndian>()?;
+        let flags = buf.read_u32::<LittleEndian>()?;
+        let count = buf.read_u32::<LittleEndian>()?;
+        let mut sections = Vec::new();
+        for _ in 0..count {
+            sections.push(ReadyToRunSection {
+                section_type: ReadyToRunSectionType::from_u32(buf.read_u32::<LittleEndian>()?),
+                range: MemoryRange::read(&mut buf)?,
+            });
+        }
+        Ok(ReadyToRunHeader {
+            major_version,
+            minor_version,
+            flags,
+            sections,
+        })
+    }
+
+    /// Gets the range of a section, if the image has one of that type.
+    pub fn section(&self, section_type: ReadyToRunSectionType) -> Option<MemoryRange> {
+        self.sections.iter().find(|s| s.section_type == section_type).map(|s| s.range)
+    }
+}
+
+/// A table of import cells that the runtime fills in lazily, such as method entry points and type handles.
+#[derive(Clone, Copy, Debug)]
+pub struct ImportSection {
+    /// The cells themselves.
+    pub range: MemoryRange,
+    pub flags: u16,
+    pub import_type: u8,
+
+    /// The size of each cell, or 0 if the cells are pointer-sized.
+    pub entry_size: u8,
+
+    /// The RVA of an array of RVAs, one per cell, each pointing at the signature that describes what fills the cell.
+    pub signatures: u32,
+    pub auxiliary_data: u32,
+}
+
+impl ImportSection {
+    pub const SIZE: usize = 20;
+
+    pub fn read<A: Read>(mut buf: A) -> Result<ImportSection, Error> {
+        Ok(ImportSection {
+            range: MemoryRange::read(&mut buf)?,
+            flags: buf.read_u16::<LittleEndian>()?,
+            import_type: buf.read_u8()?,
+            entry_size: buf.read_u8()?,
+            signatures: buf.read_u32::<LittleEndian>()?,
+            auxiliary_data: buf.read_u32::<LittleEndian>()?,
+        })
+    }
+}
+
+/// A block of precompiled code, with its unwind information.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RuntimeFunction {
+    pub begin: u32,
+
+    /// The end of the code, which is only recorded on x64. Other architectures find it in the unwind data.
+    pub end: Option<u32>,
+    pub unwind_data: u32,
+}
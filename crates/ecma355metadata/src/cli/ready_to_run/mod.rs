@@ -0,0 +1,214 @@
+mod header;
+mod native_format;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::cli::tables::{TableHandle, TableIndex};
+use crate::error::Error;
+use crate::pe::{ImageData, MemoryRange, PeImage};
+
+pub use self::header::{ImportSection, ReadyToRunHeader, ReadyToRunSection, ReadyToRunSectionType, RuntimeFunction, READY_TO_RUN_SIGNATURE};
+pub use self::native_format::{NativeArray, NativeHashtable, NativeReader};
+
+/// The precompiled code of a method.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MethodEntryPoint {
+    pub method: TableHandle,
+
+    /// The index of the method's code in the runtime functions table.
+    pub runtime_function: u32,
+
+    /// The RVA of the fixups the runtime must resolve before the code can run, if there are any.
+    pub fixups: Option<u32>,
+}
+
+/// The ReadyToRun (crossgen) data of an image.
+pub struct ReadyToRunImage<'a, D: ImageData> {
+    pe: &'a PeImage<D>,
+    header: ReadyToRunHeader,
+}
+
+/// The values that a ReadyToRun image XORs into the COFF machine type to record the target OS.
+const MACHINE_OS_OVERRIDES: [u16; 5] = [0x0000, 0x4644, 0x7B79, 0xADC4, 0x1993];
+const MACHINE_AMD64: u16 = 0x8664;
+
+impl<'a, D: ImageData> ReadyToRunImage<'a, D> {
+    /// Reads the ReadyToRun header at `header`, the ManagedNativeHeader range of the CLI header.
+    pub fn load(pe: &'a PeImage<D>, header: MemoryRange) -> Result<ReadyToRunImage<'a, D>, Error> {
+        let header = ReadyToRunHeader::read(pe.read_rva(header)?)?;
+        Ok(ReadyToRunImage { pe, header })
+    }
+
+    pub fn header(&self) -> &ReadyToRunHeader {
+        &self.header
+    }
+
+    /// Gets the data of a section, if the image has one of that type.
+    pub fn section_data(&self, section_type: ReadyToRunSectionType) -> Result<Option<&'a [u8]>, Error> {
+        match self.header.section(section_type) {
+            Some(range) => self.pe.read_rva(range).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Gets the name and version of the compiler that produced the image.
+    pub fn compiler_identifier(&self) -> Result<Option<String>, Error> {
+        Ok(self.section_data(ReadyToRunSectionType::CompilerIdentifier)?.map(|data| {
+            let len = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+            String::from_utf8_lossy(&data[..len]).into_owned()
+        }))
+    }
+
+    pub fn import_sections(&self) -> Result<Vec<ImportSection>, Error> {
+        let data = self.section_data(ReadyToRunSectionType::ImportSections)?.unwrap_or_default();
+        data.chunks_exact(ImportSection::SIZE).map(ImportSection::read).collect()
+    }
+
+    pub fn runtime_functions(&self) -> Result<Vec<RuntimeFunction>, Error> {
+        let data = self.section_data(ReadyToRunSectionType::RuntimeFunctions)?.unwrap_or_default();
+        let machine = self.pe.coff_header().machine;
+        let x64 = MACHINE_OS_OVERRIDES.iter().any(|os| machine ^ os == MACHINE_AMD64);
+        let u32_at = |entry: &[u8], index: usize| LittleEndian::read_u32(&entry[(index * 4)..(index * 4 + 4)]);
+        if x64 {
+            Ok(data
+                .chunks_exact(12)
+                .map(|entry| RuntimeFunction { begin: u32_at(entry, 0), end: Some(u32_at(entry, 1)), unwind_data: u32_at(entry, 2) })
+                .collect())
+        } else {
+            Ok(data
+                .chunks_exact(8)
+                .map(|entry| RuntimeFunction { begin: u32_at(entry, 0), end: None, unwind_data: u32_at(entry, 1) })
+                .collect())
+        }
+    }
+
+    /// Gets the entry points of the methods that were precompiled, in MethodDef order.
+    ///
+    /// Methods that are missing were not precompiled, and must be compiled from IL at runtime.
+    pub fn method_entry_points(&self) -> Result<Vec<MethodEntryPoint>, Error> {
+        let range = match self.header.section(ReadyToRunSectionType::MethodDefEntryPoints) {
+            Some(range) => range,
+            None => return Ok(Vec::new()),
+        };
+        let reader = NativeReader::new(self.pe.read_rva(range)?);
+        let array = NativeArray::read(reader, 0)?;
+
+        let mut entry_points = Vec::new();
+        for index in 0..array.len() {
+            let offset = match array.get(index)? {
+                Some(offset) => offset,
+                None => continue,
+            };
+
+            // The low bit says whether there are fixups, and the next whether they are before the entry or after it
+            let (id, next) = reader.decode_unsigned(offset)?;
+            let (runtime_function, fixups) = if id & 1 != 0 {
+                let fixups = if id & 2 != 0 {
+                    let (delta, _) = reader.decode_unsigned(next)?;
+                    next.checked_sub(delta as usize).ok_or(Error::DataOutOfRange)?
+                } else {
+                    next
+                };
+                (id >> 2, Some(range.start + fixups as u32))
+            } else {
+                (id >> 1, None)
+            };
+
+            entry_points.push(MethodEntryPoint {
+                method: TableHandle::new(index + 1, TableIndex::MethodDef),
+                runtime_function,
+                fixups,
+            });
+        }
+        Ok(entry_points)
+    }
+
+    /// Finds the precompiled code of a method, given its MethodDef handle.
+    pub fn method_entry_point(&self, method: TableHandle) -> Result<Option<MethodEntryPoint>, Error> {
+        Ok(self.method_entry_points()?.into_iter().find(|e| e.method == method))
+    }
+
+    /// Gets the types defined or forwarded by the image, as TypeDef and ExportedType handles.
+    pub fn available_types(&self) -> Result<Vec<TableHandle>, Error> {
+        let range = match self.header.section(ReadyToRunSectionType::AvailableTypes) {
+            Some(range) => range,
+            None => return Ok(Vec::new()),
+        };
+        let reader = NativeReader::new(self.pe.read_rva(range)?);
+        let table = NativeHashtable::read(reader, 0)?;
+
+        let mut types = Vec::new();
+        for offset in table.entries()? {
+            let (id, _) = reader.decode_unsigned(offset)?;
+            let table_index = if id & 1 != 0 { TableIndex::ExportedType } else { TableIndex::TypeDef };
+            types.push(TableHandle::new((id >> 1) as usize, table_index));
+        }
+        Ok(types)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pe::test_image::{self, SECTION_RVA};
+
+    fn put(buf: &mut [u8], offset: usize, values: &[u32]) {
+        for (i, value) in values.iter().enumerate() {
+            buf[(offset + i * 4)..(offset + i * 4 + 4)].copy_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    #[test]
+    pub fn read_ready_to_run_image() {
+        let rva = |offset: u32| SECTION_RVA + offset;
+        let mut section = vec![0u8; 0x200];
+
+        // Header with five sections
+        put(&mut section, 0x00, &[READY_TO_RUN_SIGNATURE, 0x000C_0009, 0, 5]);
+        put(&mut section, 0x10, &[100, rva(0x100), 9]);
+        put(&mut section, 0x1C, &[101, rva(0x110), 20]);
+        put(&mut section, 0x28, &[102, rva(0x130), 36]);
+        put(&mut section, 0x34, &[103, rva(0x160), 11]);
+        put(&mut section, 0x40, &[108, rva(0x180), 10]);
+
+        section[0x100..0x108].copy_from_slice(b"Crossgen");
+        put(&mut section, 0x110, &[rva(0x1A0), 16, 0x0800_0001, rva(0x1B0), 0]);
+        put(&mut section, 0x130, &[0x1000, 0x1010, 0x3000, 0x1010, 0x1020, 0x3008, 0x1020, 0x1040, 0x3010]);
+
+        // Three methods: the third has fixups, which follow its entry
+        section[0x160..0x16B].copy_from_slice(&[0x20, 0x01, 0x02, 0x02, 0x26, 0x16, 0x00, 0x04, 0x10, 0x12, 0x00]);
+
+        // A TypeDef and an ExportedType
+        section[0x180..0x18A].copy_from_slice(&[0x04, 0x03, 0x05, 0x07, 0x11, 0x06, 0x22, 0x04, 0x10, 0x06]);
+
+        let image = test_image::build(true, &section, &[]);
+        let pe = PeImage::load(image).unwrap();
+        let r2r = ReadyToRunImage::load(&pe, MemoryRange::new(SECTION_RVA, 0x4C)).unwrap();
+
+        assert_eq!((9, 12), (r2r.header().major_version, r2r.header().minor_version));
+        assert_eq!(Some("Crossgen".to_string()), r2r.compiler_identifier().unwrap());
+
+        let imports = r2r.import_sections().unwrap();
+        assert_eq!(1, imports.len());
+        assert_eq!((1, 0, 8), (imports[0].flags, imports[0].import_type, imports[0].entry_size));
+
+        let functions = r2r.runtime_functions().unwrap();
+        assert_eq!(3, functions.len());
+        assert_eq!(RuntimeFunction { begin: 0x1010, end: Some(0x1020), unwind_data: 0x3008 }, functions[1]);
+
+        let entry_points = r2r.method_entry_points().unwrap();
+        assert_eq!(
+            vec![
+                MethodEntryPoint { method: TableHandle::new(1, TableIndex::MethodDef), runtime_function: 0, fixups: None },
+                MethodEntryPoint { method: TableHandle::new(2, TableIndex::MethodDef), runtime_function: 1, fixups: None },
+                MethodEntryPoint { method: TableHandle::new(3, TableIndex::MethodDef), runtime_function: 2, fixups: Some(rva(0x16A)) },
+            ],
+            entry_points
+        );
+
+        assert_eq!(
+            vec![TableHandle::new(4, TableIndex::TypeDef), TableHandle::new(1, TableIndex::ExportedType)],
+            r2r.available_types().unwrap()
+        );
+    }
+}
@@ -0,0 +1,248 @@
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::error::Error;
+
+/// Reads the NativeFormat encoding that ReadyToRun images use for their lookup tables.
+///
+/// Offsets are relative to the start of the buffer, which is normally the data of a single ReadyToRun section.
+/// NativeFormat integers are not the same as the compressed integers of ECMA-335 signatures: the low bits of the first
+/// byte give the length, and the rest is little-endian.
+#[derive(Clone, Copy)]
+pub struct NativeReader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> NativeReader<'a> {
+    pub fn new(buf: &'a [u8]) -> NativeReader<'a> {
+        NativeReader { buf }
+    }
+
+    fn bytes(&self, offset: usize, len: usize) -> Result<&'a [u8], Error> {
+        self.buf.get(offset..(offset + len)).ok_or(Error::DataOutOfRange)
+    }
+
+    pub fn read_u8(&self, offset: usize) -> Result<u8, Error> {
+        Ok(self.bytes(offset, 1)?[0])
+    }
+
+    pub fn read_u16(&self, offset: usize) -> Result<u16, Error> {
+        Ok(LittleEndian::read_u16(self.bytes(offset, 2)?))
+    }
+
+    pub fn read_u32(&self, offset: usize) -> Result<u32, Error> {
+        Ok(LittleEndian::read_u32(self.bytes(offset, 4)?))
+    }
+
+    /// Decodes an unsigned integer, returning it and the offset that follows it.
+    pub fn decode_unsigned(&self, offset: usize) -> Result<(u32, usize), Error> {
+        let first = self.read_u8(offset)? as u32;
+        let byte = |n: usize| self.read_u8(offset + n).map(|b| b as u32);
+        if first & 0x01 == 0 {
+            Ok((first >> 1, offset + 1))
+        } else if first & 0x02 == 0 {
+            Ok(((first >> 2) | (byte(1)? << 6), offset + 2))
+        } else if first & 0x04 == 0 {
+            Ok(((first >> 3) | (byte(1)? << 5) | (byte(2)? << 13), offset + 3))
+        } else if first & 0x08 == 0 {
+            Ok(((first >> 4) | (byte(1)? << 4) | (byte(2)? << 12) | (byte(3)? << 20), offset + 4))
+        } else if first & 0x10 == 0 {
+            Ok((self.read_u32(offset + 1)?, offset + 5))
+        } else {
+            Err(Error::InvalidMetadata("invalid NativeFormat integer".into()))
+        }
+    }
+
+    /// Decodes a signed integer, returning it and the offset that follows it.
+    pub fn decode_signed(&self, offset: usize) -> Result<(i32, usize), Error> {
+        let first = self.read_u8(offset)?;
+        let byte = |n: usize| self.read_u8(offset + n).map(|b| b as i32);
+        let signed_byte = |n: usize| self.read_u8(offset + n).map(|b| b as i8 as i32);
+        let unsigned = first as i32;
+        if first & 0x01 == 0 {
+            Ok(((first as i8 as i32) >> 1, offset + 1))
+        } else if first & 0x02 == 0 {
+            Ok(((unsigned >> 2) | (signed_byte(1)? << 6), offset + 2))
+        } else if first & 0x04 == 0 {
+            Ok(((unsigned >> 3) | (byte(1)? << 5) | (signed_byte(2)? << 13), offset + 3))
+        } else if first & 0x08 == 0 {
+            Ok(((unsigned >> 4) | (byte(1)? << 4) | (byte(2)? << 12) | (signed_byte(3)? << 20), offset + 4))
+        } else if first & 0x10 == 0 {
+            Ok((self.read_u32(offset + 1)? as i32, offset + 5))
+        } else {
+            Err(Error::InvalidMetadata("invalid NativeFormat integer".into()))
+        }
+    }
+
+    /// Reads an entry of an index table, which is 1, 2 or 4 bytes depending on `entry_index_size`.
+    fn read_index(&self, offset: usize, index: usize, entry_index_size: u8) -> Result<usize, Error> {
+        match entry_index_size {
+            0 => self.read_u8(offset + index).map(|x| x as usize),
+            1 => self.read_u16(offset + 2 * index).map(|x| x as usize),
+            _ => self.read_u32(offset + 4 * index).map(|x| x as usize),
+        }
+    }
+}
+
+/// A sparse array, stored as a binary tree for each block of 16 elements.
+pub struct NativeArray<'a> {
+    reader: NativeReader<'a>,
+    base_offset: usize,
+    count: usize,
+    entry_index_size: u8,
+}
+
+const BLOCK_SIZE: usize = 16;
+
+impl<'a> NativeArray<'a> {
+    pub fn read(reader: NativeReader<'a>, offset: usize) -> Result<NativeArray<'a>, Error> {
+        let (header, base_offset) = reader.decode_unsigned(offset)?;
+        Ok(NativeArray {
+            reader,
+            base_offset,
+            count: (header >> 2) as usize,
+            entry_index_size: (header & 3) as u8,
+        })
+    }
+
+    /// Gets the number of slots in the array, including empty ones.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Finds the offset of the element at `index`, or `None` if that slot is empty.
+    pub fn get(&self, index: usize) -> Result<Option<usize>, Error> {
+        if index >= self.count {
+            return Ok(None);
+        }
+
+        let mut offset = self.base_offset + self.reader.read_index(self.base_offset, index / BLOCK_SIZE, self.entry_index_size)?;
+
+        // Walk down the tree for the block, one bit of the index at a time
+        let mut bit = BLOCK_SIZE >> 1;
+        while bit > 0 {
+            let (node, next) = self.reader.decode_unsigned(offset)?;
+            if index & bit != 0 {
+                if node & 2 != 0 {
+                    offset += (node >> 2) as usize;
+                    bit >>= 1;
+                    continue;
+                }
+            } else if node & 1 != 0 {
+                offset = next;
+                bit >>= 1;
+                continue;
+            }
+
+            // A leaf node can stand in for a subtree with a single element
+            if node & 3 == 0 && (node >> 2) as usize == index & (BLOCK_SIZE - 1) {
+                return Ok(Some(next));
+            }
+            return Ok(None);
+        }
+        Ok(Some(offset))
+    }
+}
+
+/// A hashtable keyed by 32-bit hash codes, whose entries point at variable-sized data.
+pub struct NativeHashtable<'a> {
+    reader: NativeReader<'a>,
+    base_offset: usize,
+    bucket_mask: usize,
+    entry_index_size: u8,
+}
+
+impl<'a> NativeHashtable<'a> {
+    pub fn read(reader: NativeReader<'a>, offset: usize) -> Result<NativeHashtable<'a>, Error> {
+        let header = reader.read_u8(offset)?;
+        Ok(NativeHashtable {
+            reader,
+            base_offset: offset + 1,
+            bucket_mask: (1usize << (header >> 2)) - 1,
+            entry_index_size: header & 3,
+        })
+    }
+
+    /// Gets the entries of a bucket, as the low byte of each hash code and the offset of the entry's data.
+    fn bucket(&self, bucket: usize) -> Result<Vec<(u8, usize)>, Error> {
+        let start = self.base_offset + self.reader.read_index(self.base_offset, bucket, self.entry_index_size)?;
+        let end = self.base_offset + self.reader.read_index(self.base_offset, bucket + 1, self.entry_index_size)?;
+
+        let mut entries = Vec::new();
+        let mut offset = start;
+        while offset < end {
+            let low_hashcode = self.reader.read_u8(offset)?;
+            let (delta, next) = self.reader.decode_signed(offset + 1)?;
+            let target = (offset as i64 + 1 + delta as i64).try_into().map_err(|_| Error::DataOutOfRange)?;
+            entries.push((low_hashcode, target));
+            offset = next;
+        }
+        Ok(entries)
+    }
+
+    /// Gets the offsets of the data of every entry in the table.
+    pub fn entries(&self) -> Result<Vec<usize>, Error> {
+        let mut entries = Vec::new();
+        for bucket in 0..=self.bucket_mask {
+            entries.extend(self.bucket(bucket)?.into_iter().map(|(_, offset)| offset));
+        }
+        Ok(entries)
+    }
+
+    /// Gets the offsets of the data of the entries that may match `hashcode`.
+    pub fn lookup(&self, hashcode: u32) -> Result<Vec<usize>, Error> {
+        let bucket = (hashcode >> 8) as usize & self.bucket_mask;
+        Ok(self
+            .bucket(bucket)?
+            .into_iter()
+            .filter(|(low, _)| *low == hashcode as u8)
+            .map(|(_, offset)| offset)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn decode_integers() {
+        let reader = NativeReader::new(&[0x54, 0xFD, 0x01, 0x0B, 0x00, 0x00, 0x01, 0xFF, 0xFF, 0x0F, 0x78, 0x56, 0x34, 0x12]);
+        assert_eq!((42, 1), reader.decode_unsigned(0).unwrap());
+        assert_eq!((127, 3), reader.decode_unsigned(1).unwrap());
+        assert_eq!((0x1234_5678, 14), reader.decode_unsigned(9).unwrap());
+
+        let reader = NativeReader::new(&[0xFE, 0xFD, 0xFF, 0x01, 0x02]);
+        assert_eq!((-1, 1), reader.decode_signed(0).unwrap());
+        assert_eq!((-1, 3), reader.decode_signed(1).unwrap());
+        assert_eq!((128, 5), reader.decode_signed(3).unwrap());
+    }
+
+    #[test]
+    pub fn read_sparse_array() {
+        // Four slots, where slot 3 is empty: header, block index, then the tree
+        let buf = [0x20, 0x01, 0x02, 0x02, 0x26, 0x16, 0x02, 0x04, 0x10, 0x06];
+        let array = NativeArray::read(NativeReader::new(&buf), 0).unwrap();
+        assert_eq!(4, array.len());
+        let reader = NativeReader::new(&buf);
+        let element = |index| array.get(index).unwrap().map(|offset| reader.decode_unsigned(offset).unwrap().0);
+        assert_eq!(Some(1), element(0));
+        assert_eq!(Some(2), element(1));
+        assert_eq!(Some(3), element(2));
+        assert_eq!(None, element(3));
+        assert_eq!(None, element(4));
+    }
+
+    #[test]
+    pub fn read_hashtable() {
+        // Two buckets with one entry each
+        let buf = [0x04, 0x03, 0x05, 0x07, 0x11, 0x06, 0x22, 0x04, 0x08, 0x06];
+        let table = NativeHashtable::read(NativeReader::new(&buf), 0).unwrap();
+        assert_eq!(vec![8, 9], table.entries().unwrap());
+        assert_eq!(vec![9], table.lookup(0x0122).unwrap());
+        assert!(table.lookup(0x0111).unwrap().is_empty());
+    }
+}
@@ -59,6 +59,9 @@ impl SignatureHeader {
 
     pub fn read(reader: &mut impl Read) -> Result<SignatureHeader, Error> {
         let value = reader.read_u8()?;
+        if value & CONV_OR_KIND_MASK > MAX_HEADER_VALUE || value & CONV_OR_KIND_MASK == 0x09 {
+            return Err(Error::InvalidMetadata(format!("invalid signature header: 0x{:02X}", value)));
+        }
         let generic_param_count = if value & 0x10 != 0 {
             utils::read_compressed_u32(reader)?
         } else {
@@ -0,0 +1,231 @@
+use byteorder::{ByteOrder, LittleEndian};
+use num_bigint::BigUint;
+use sha1::{Digest, Sha1};
+
+use crate::error::Error;
+use crate::pe::{HashAlgorithm, ImageData, MemoryRange, PeImage};
+
+/// The outcome of checking a strong-name signature.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StrongNameStatus {
+    /// The signature matches the image hash.
+    Valid,
+
+    /// The signature does not match the image hash, so the image was modified after signing or signed with another key.
+    Invalid,
+
+    /// Space is reserved for the signature, but it is all zeros.
+    DelaySigned,
+}
+
+impl_display_via_debug!(StrongNameStatus);
+
+/// The public key placeholder that ECMA-335 assemblies, such as mscorlib, carry instead of a real key.
+pub const ECMA_PUBLIC_KEY: [u8; 16] = [0, 0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0];
+
+const PUBLIC_KEY_HEADER_SIZE: usize = 12;
+const PUBLICKEYBLOB: u8 = 0x06;
+const RSA1_MAGIC: u32 = 0x31415352;
+
+/// Computes the public key token of a public key: the last 8 bytes of its SHA-1 hash, reversed.
+pub fn public_key_token(public_key: &[u8]) -> [u8; 8] {
+    let hash = Sha1::digest(public_key);
+    let mut token = [0u8; 8];
+    token.copy_from_slice(&hash[(hash.len() - 8)..]);
+    token.reverse();
+    token
+}
+
+/// An RSA public key, decoded from the `PublicKey` column of the Assembly table.
+///
+/// The blob is a `PublicKeyBlob`: the signature and hash algorithm IDs, followed by a CryptoAPI `PUBLICKEYBLOB`.
+#[derive(Debug)]
+pub struct StrongNameKey {
+    pub signature_algorithm: u32,
+    pub hash_algorithm: u32,
+
+    /// The modulus, in big-endian order.
+    pub modulus: Vec<u8>,
+    pub exponent: u32,
+}
+
+impl StrongNameKey {
+    pub fn read(public_key: &[u8]) -> Result<StrongNameKey, Error> {
+        if public_key == ECMA_PUBLIC_KEY {
+            return Err(Error::InvalidMetadata("the ECMA key is a placeholder, and cannot verify signatures".into()));
+        }
+        let header = public_key.get(0..PUBLIC_KEY_HEADER_SIZE).ok_or(Error::DataOutOfRange)?;
+        let key_size = LittleEndian::read_u32(&header[8..12]) as usize;
+        let key = public_key.get(PUBLIC_KEY_HEADER_SIZE..(PUBLIC_KEY_HEADER_SIZE + key_size)).ok_or(Error::DataOutOfRange)?;
+
+        // BLOBHEADER followed by RSAPUBKEY
+        if key.len() < 20 || key[0] != PUBLICKEYBLOB || LittleEndian::read_u32(&key[8..12]) != RSA1_MAGIC {
+            return Err(Error::InvalidMetadata("public key is not an RSA PUBLICKEYBLOB".into()));
+        }
+        let bit_length = LittleEndian::read_u32(&key[12..16]) as usize;
+        let mut modulus = key.get(20..(20 + bit_length / 8)).ok_or(Error::DataOutOfRange)?.to_vec();
+        modulus.reverse();
+
+        Ok(StrongNameKey {
+            signature_algorithm: LittleEndian::read_u32(&header[0..4]),
+            hash_algorithm: LittleEndian::read_u32(&header[4..8]),
+            modulus,
+            exponent: LittleEndian::read_u32(&key[16..20]),
+        })
+    }
+
+    /// Gets the algorithm used for the image hash, which is SHA-1 unless the key says otherwise.
+    pub fn hash(&self) -> Result<HashAlgorithm, Error> {
+        match self.hash_algorithm {
+            0 | 0x8004 => Ok(HashAlgorithm::Sha1),
+            0x800C => Ok(HashAlgorithm::Sha256),
+            0x800D => Ok(HashAlgorithm::Sha384),
+            0x800E => Ok(HashAlgorithm::Sha512),
+            x => Err(Error::InvalidMetadata(format!("unsupported strong-name hash algorithm: 0x{:04X}", x))),
+        }
+    }
+
+    /// Checks the strong-name signature at `signature`, the StrongNameSignature range of the CLI header.
+    pub fn verify<D: ImageData>(&self, pe: &PeImage<D>, signature: MemoryRange) -> Result<StrongNameStatus, Error> {
+        let stored = pe.read_rva(signature)?;
+        if stored.iter().all(|b| *b == 0) {
+            return Ok(StrongNameStatus::DelaySigned);
+        }
+
+        let algorithm = self.hash()?;
+        let hash = strong_name_hash(pe, signature, algorithm)?;
+        let mut expected = digest_info_prefix(algorithm).to_vec();
+        expected.extend_from_slice(&hash);
+        if self.modulus.len() < expected.len() + 11 {
+            return Err(Error::InvalidMetadata("public key is too small for the hash algorithm".into()));
+        }
+
+        // The signature is stored little-endian. Decrypting it must produce the PKCS#1 v1.5 padding around the DigestInfo.
+        let modulus = BigUint::from_bytes_be(&self.modulus);
+        let signature = BigUint::from_bytes_le(stored);
+        if signature >= modulus {
+            return Ok(StrongNameStatus::Invalid);
+        }
+        let decrypted = signature.modpow(&BigUint::from(self.exponent), &modulus).to_bytes_be();
+
+        let mut padded = vec![0x00, 0x01];
+        padded.resize(self.modulus.len() - expected.len() - 1, 0xFF);
+        padded.push(0x00);
+        padded.extend_from_slice(&expected);
+        Ok(if padded[1..] == decrypted[..] {
+            StrongNameStatus::Valid
+        } else {
+            StrongNameStatus::Invalid
+        })
+    }
+}
+
+/// Computes the hash that a strong-name signature covers.
+///
+/// This is the headers, with the checksum and certificate table entry zeroed, followed by the raw data of each section
+/// in file order, skipping the signature itself.
+pub fn strong_name_hash<D: ImageData>(pe: &PeImage<D>, signature: MemoryRange, algorithm: HashAlgorithm) -> Result<Vec<u8>, Error> {
+    let signature = pe.physical_range(signature).ok_or(Error::DataOutOfRange)?;
+    let headers_end = pe.optional_header_offset() + pe.coff_header().optional_header_size as usize + pe.sections().len() * 40;
+    let mut headers = pe.data().read(0..headers_end)?.to_vec();
+    for range in std::iter::once(pe.checksum_field()).chain(pe.certificate_directory_entry()) {
+        headers[range].fill(0);
+    }
+
+    let mut hasher = algorithm.hasher();
+    hasher.update(&headers);
+
+    let mut sections: Vec<_> = pe.sections().iter().collect();
+    sections.sort_by_key(|s| s.pointer_to_raw_data);
+    for section in sections {
+        let start = section.pointer_to_raw_data as usize;
+        let end = start + section.size_of_raw_data as usize;
+        if signature.start >= start && signature.end <= end {
+            hasher.update(pe.data().read(start..signature.start)?);
+            hasher.update(pe.data().read(signature.end..end)?);
+        } else {
+            hasher.update(pe.data().read(start..end)?);
+        }
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+/// Gets the DER encoding of a DigestInfo for `algorithm`, up to the digest itself.
+fn digest_info_prefix(algorithm: HashAlgorithm) -> &'static [u8] {
+    match algorithm {
+        HashAlgorithm::Sha1 => &[0x30, 0x21, 0x30, 0x09, 0x06, 0x05, 0x2B, 0x0E, 0x03, 0x02, 0x1A, 0x05, 0x00, 0x04, 0x14],
+        HashAlgorithm::Sha256 => &[0x30, 0x31, 0x30, 0x0D, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01, 0x05, 0x00, 0x04, 0x20],
+        HashAlgorithm::Sha384 => &[0x30, 0x41, 0x30, 0x0D, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02, 0x05, 0x00, 0x04, 0x30],
+        HashAlgorithm::Sha512 => &[0x30, 0x51, 0x30, 0x0D, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03, 0x05, 0x00, 0x04, 0x40],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pe::test_image::{self, SECTION_OFFSET, SECTION_RVA};
+
+    const MODULUS: &str = "abaa3cc034d9d5711750f771a6ff1ea7e8e691e9ca13413e0cd9e3fe2d973bad775947f71102309db2cff289822c6e2efe83c1b21702c3ce11eb7ffe7db914c7";
+    const PRIVATE_EXPONENT: &str = "4b51d773f1fc7e052fd8cba9273f9ae0cb7497230011f0b75c06225dfcfcf526900c18d07691fa0efddfea0c4c7451b4968da218d2b6fa133bd9752790b11d51";
+
+    fn public_key_blob() -> Vec<u8> {
+        let modulus = BigUint::parse_bytes(MODULUS.as_bytes(), 16).unwrap().to_bytes_le();
+        let mut blob = Vec::new();
+        blob.extend_from_slice(&0x2400u32.to_le_bytes());
+        blob.extend_from_slice(&0x8004u32.to_le_bytes());
+        blob.extend_from_slice(&(20 + modulus.len() as u32).to_le_bytes());
+        blob.extend_from_slice(&[PUBLICKEYBLOB, 2, 0, 0]);
+        blob.extend_from_slice(&0x2400u32.to_le_bytes());
+        blob.extend_from_slice(&RSA1_MAGIC.to_le_bytes());
+        blob.extend_from_slice(&(modulus.len() as u32 * 8).to_le_bytes());
+        blob.extend_from_slice(&65537u32.to_le_bytes());
+        blob.extend_from_slice(&modulus);
+        blob
+    }
+
+    fn signed_image(key: &StrongNameKey, signature: MemoryRange) -> Vec<u8> {
+        let mut section = vec![0x5Au8; 0x100];
+        section[0x10..0x50].fill(0);
+        let mut image = test_image::build(false, &section, &[]);
+
+        let pe = PeImage::load(image.as_slice()).unwrap();
+        let hash = strong_name_hash(&pe, signature, HashAlgorithm::Sha1).unwrap();
+        let mut padded = vec![0x00, 0x01];
+        padded.resize(key.modulus.len() - 15 - hash.len() - 1, 0xFF);
+        padded.push(0x00);
+        padded.extend_from_slice(digest_info_prefix(HashAlgorithm::Sha1));
+        padded.extend_from_slice(&hash);
+
+        let modulus = BigUint::from_bytes_be(&key.modulus);
+        let private_exponent = BigUint::parse_bytes(PRIVATE_EXPONENT.as_bytes(), 16).unwrap();
+        let mut signed = BigUint::from_bytes_be(&padded).modpow(&private_exponent, &modulus).to_bytes_le();
+        signed.resize(key.modulus.len(), 0);
+        image[(SECTION_OFFSET + 0x10)..(SECTION_OFFSET + 0x50)].copy_from_slice(&signed);
+        image
+    }
+
+    #[test]
+    pub fn compute_public_key_token() {
+        assert_eq!([0xB7, 0x7A, 0x5C, 0x56, 0x19, 0x34, 0xE0, 0x89], public_key_token(&ECMA_PUBLIC_KEY));
+    }
+
+    #[test]
+    pub fn verify_signature() {
+        let key = StrongNameKey::read(&public_key_blob()).unwrap();
+        assert_eq!(64, key.modulus.len());
+        assert_eq!(65537, key.exponent);
+
+        let signature = MemoryRange::new(SECTION_RVA + 0x10, 0x40);
+        let mut image = signed_image(&key, signature);
+        assert_eq!(StrongNameStatus::Valid, key.verify(&PeImage::load(image.as_slice()).unwrap(), signature).unwrap());
+
+        // The checksum isn't covered by the signature, but the section data is
+        image[0x98] ^= 0xFF;
+        assert_eq!(StrongNameStatus::Valid, key.verify(&PeImage::load(image.as_slice()).unwrap(), signature).unwrap());
+        image[SECTION_OFFSET + 0x80] ^= 0xFF;
+        assert_eq!(StrongNameStatus::Invalid, key.verify(&PeImage::load(image.as_slice()).unwrap(), signature).unwrap());
+
+        image[(SECTION_OFFSET + 0x10)..(SECTION_OFFSET + 0x50)].fill(0);
+        assert_eq!(StrongNameStatus::DelaySigned, key.verify(&PeImage::load(image.as_slice()).unwrap(), signature).unwrap());
+    }
+}
@@ -1,6 +1,7 @@
 #[macro_export]
 macro_rules! coded_index {
     ($name: ident, [$($table: ident $(,)?)+]) => {
+        #[allow(dead_code)]
         pub struct $name($crate::cli::tables::table_handle::TableHandle);
 
         impl $name {
@@ -23,21 +24,32 @@ macro_rules! coded_index {
         }
 
         impl $crate::cli::tables::table_row::CodedIndex for $name {
+            const KIND: $crate::cli::tables::table_row::ColumnKind =
+                $crate::cli::tables::table_row::ColumnKind::Coded($name::TABLE_MASK, $name::SHIFT_DISTANCE);
+
             fn read(decoder: &$crate::cli::tables::table_row::RowDecoder, buf: &mut &[u8]) -> Result<$crate::cli::tables::table_handle::TableHandle, $crate::error::Error> {
-                let index = if decoder.any_large($name::TABLE_MASK) {
-                    decoder.decode_u32(buf)? as usize
+                let value = if decoder.any_large($name::TABLE_MASK, $name::SHIFT_DISTANCE) {
+                    decoder.decode_u32(buf)?
                 } else {
-                    decoder.decode_u16(buf)? as usize
+                    decoder.decode_u16(buf)? as u32
                 };
+                $name::decode(value)
+            }
 
-                let tag = index & $name::TAG_MASK;
-                let index = index >> $name::SHIFT_DISTANCE;
-                let table = $name::TAG_MAP[tag];
+            fn decode(value: u32) -> Result<$crate::cli::tables::table_handle::TableHandle, $crate::error::Error> {
+                let tag = value as usize & $name::TAG_MASK;
+                let index = value as usize >> $name::SHIFT_DISTANCE;
+                let table = *$name::TAG_MAP.get(tag).ok_or($crate::error::Error::InvalidCodedIndex)?;
                 Ok($crate::cli::tables::table_handle::TableHandle::new(index, table))
             }
 
+            fn encode(handle: &$crate::cli::tables::table_handle::TableHandle) -> Option<u32> {
+                let tag = $name::TAG_MAP.iter().position(|t| *t == handle.table())?;
+                Some(((handle.index() << $name::SHIFT_DISTANCE) | tag) as u32)
+            }
+
             fn size(decoder: &$crate::cli::tables::table_row::RowDecoder) -> usize {
-                if decoder.any_large($name::TABLE_MASK) {
+                if decoder.any_large($name::TABLE_MASK, $name::SHIFT_DISTANCE) {
                     4
                 } else {
                     2
@@ -68,73 +80,146 @@ macro_rules! table_def {
     (@DECODE [$table: ident], $decoder: ident, $buf: ident) => {
         $decoder.decode_index($crate::cli::tables::table_index::TableIndex::$table, &mut $buf)?
     };
-    (@SIZE [$table: ident], $decoder: ident) => {
-        $decoder.size_of_index($crate::cli::tables::table_index::TableIndex::$table)
+    (@KIND [$table: ident]) => {
+        $crate::cli::tables::table_row::ColumnKind::Index($crate::cli::tables::table_index::TableIndex::$table)
+    };
+    (@FROM_RAW [$table: ident], $raw: expr) => {
+        $crate::cli::tables::table_handle::TableHandle::new($raw as usize, $crate::cli::tables::table_index::TableIndex::$table)
     };
 
     (@DECODE ($coded_index: ident), $decoder: ident, $buf: ident) => {
         <$coded_index as $crate::cli::tables::table_row::CodedIndex>::read($decoder, &mut $buf)?
     };
-    (@SIZE ($coded_index: ident), $decoder: ident) => {
-        <$coded_index as $crate::cli::tables::table_row::CodedIndex>::size($decoder)
+    (@KIND ($coded_index: ident)) => {
+        <$coded_index as $crate::cli::tables::table_row::CodedIndex>::KIND
+    };
+    (@FROM_RAW ($coded_index: ident), $raw: expr) => {
+        <$coded_index as $crate::cli::tables::table_row::CodedIndex>::decode($raw)?
     };
 
     (@DECODE $ty: ident as $from_ty: ident, $decoder: ident, $buf: ident) => {
         $ty::try_from(table_def!(@DECODE $from_ty, $decoder, $buf))?
     };
-    (@SIZE $ty: ident as $from_ty: ident, $decoder: ident) => {
-        table_def!(@SIZE $from_ty, $decoder)
+    (@KIND $ty: ident as $from_ty: ident) => {
+        table_def!(@KIND $from_ty)
+    };
+    (@FROM_RAW $ty: ident as $from_ty: ident, $raw: expr) => {
+        $ty::try_from(table_def!(@FROM_RAW $from_ty, $raw))?
     };
 
     (@DECODE u16, $decoder: ident, $buf: ident) => {
         $decoder.decode_u16(&mut $buf)?
     };
-    (@SIZE u16, $decoder: ident) => {
-        2
+    (@KIND u16) => {
+        $crate::cli::tables::table_row::ColumnKind::Fixed(2)
+    };
+    (@FROM_RAW u16, $raw: expr) => {
+        $raw as u16
     };
 
     (@DECODE u32, $decoder: ident, $buf: ident) => {
         $decoder.decode_u32(&mut $buf)?
     };
-    (@SIZE u32, $decoder: ident) => {
-        4
+    (@KIND u32) => {
+        $crate::cli::tables::table_row::ColumnKind::Fixed(4)
+    };
+    (@FROM_RAW u32, $raw: expr) => {
+        $raw
     };
 
     (@DECODE u8, $decoder: ident, $buf: ident) => {
         $decoder.decode_u8(&mut $buf)?
     };
-    (@SIZE u8, $decoder: ident) => {
-        1
+    (@KIND u8) => {
+        $crate::cli::tables::table_row::ColumnKind::Fixed(1)
+    };
+    (@FROM_RAW u8, $raw: expr) => {
+        $raw as u8
     };
 
     (@DECODE StringHandle, $decoder: ident, $buf: ident) => {
         $decoder.decode_string(&mut $buf)?
     };
-    (@SIZE StringHandle, $decoder: ident) => {
-        $decoder.size_of_string()
+    (@KIND StringHandle) => {
+        $crate::cli::tables::table_row::ColumnKind::String
+    };
+    (@FROM_RAW StringHandle, $raw: expr) => {
+        $crate::cli::StringHandle($raw as usize)
     };
 
     (@DECODE GuidHandle, $decoder: ident, $buf: ident) => {
         $decoder.decode_guid(&mut $buf)?
     };
-    (@SIZE GuidHandle, $decoder: ident) => {
-        $decoder.size_of_guid()
+    (@KIND GuidHandle) => {
+        $crate::cli::tables::table_row::ColumnKind::Guid
+    };
+    (@FROM_RAW GuidHandle, $raw: expr) => {
+        $crate::cli::GuidHandle($raw as usize)
     };
 
     (@DECODE BlobHandle, $decoder: ident, $buf: ident) => {
         $decoder.decode_blob(&mut $buf)?
     };
-    (@SIZE BlobHandle, $decoder: ident) => {
-        $decoder.size_of_blob()
+    (@KIND BlobHandle) => {
+        $crate::cli::tables::table_row::ColumnKind::Blob
+    };
+    (@FROM_RAW BlobHandle, $raw: expr) => {
+        $crate::cli::BlobHandle($raw as usize)
     };
 
     (@DECODE $ty: ident, $decoder: ident, $buf: ident) => {
         compile_error!("Unsupported column type");
     };
-    (@SIZE $ty: ident, $decoder: ident) => {
+    (@KIND $ty: ident) => {
+        compile_error!("Unsupported column type");
+    };
+    (@FROM_RAW $ty: ident, $raw: expr) => {
         compile_error!("Unsupported column type");
     };
 
+    (@TO_RAW [$table: ident], $value: expr) => {
+        $value.index() as u32
+    };
+    (@TO_RAW ($coded_index: ident), $value: expr) => {
+        <$coded_index as $crate::cli::tables::table_row::CodedIndex>::encode($value).ok_or($crate::error::Error::InvalidCodedIndex)?
+    };
+    (@TO_RAW $ty: ident $(as $from_ty: ident)?, $value: expr) => {
+        $crate::cli::tables::table_row::ColumnValue::raw($value)
+    };
+
+    (@REMAP [$table: ident], $value: expr, $target: ident, $map: ident) => {
+        $crate::cli::tables::table_row::remap_handle($value, $target, $map)
+    };
+    (@REMAP ($coded_index: ident), $value: expr, $target: ident, $map: ident) => {
+        $crate::cli::tables::table_row::remap_handle($value, $target, $map)
+    };
+    (@REMAP $ty: ident $(as $from_ty: ident)?, $value: expr, $target: ident, $map: ident) => {
+        ()
+    };
+
+    (@HEAP StringHandle, $value: expr, $f: ident) => {
+        $f($crate::cli::tables::table_row::HeapHandleMut::String($value))?
+    };
+    (@HEAP GuidHandle, $value: expr, $f: ident) => {
+        $f($crate::cli::tables::table_row::HeapHandleMut::Guid($value))?
+    };
+    (@HEAP BlobHandle, $value: expr, $f: ident) => {
+        $f($crate::cli::tables::table_row::HeapHandleMut::Blob($value))?
+    };
+    (@HEAP $ty: tt $(as $from_ty: ident)?, $value: expr, $f: ident) => {
+        ()
+    };
+
+    (@VISIT [$table: ident], $name: ident, $value: expr, $f: ident) => {
+        $f(stringify!($name), $value)
+    };
+    (@VISIT ($coded_index: ident), $name: ident, $value: expr, $f: ident) => {
+        $f(stringify!($name), $value)
+    };
+    (@VISIT $ty: ident $(as $from_ty: ident)?, $name: ident, $value: expr, $f: ident) => {
+        ()
+    };
+
     (
         $ty: ident, 
         [
@@ -151,6 +236,11 @@ macro_rules! table_def {
 
         impl $crate::cli::tables::table_row::TableRow for $ty {
             const INDEX: $crate::cli::tables::table_index::TableIndex = $crate::cli::tables::table_index::TableIndex::$ty;
+            const COLUMNS: &'static [$crate::cli::tables::table_row::ColumnKind] = &[
+                $(
+                    table_def!(@KIND $col_ty $(as $col_from_type)?),
+                )+
+            ];
 
             fn decode(decoder: &$crate::cli::tables::table_row::RowDecoder, mut buf: &[u8]) -> std::result::Result<Self, $crate::error::Error> {
                 $(
@@ -161,13 +251,55 @@ macro_rules! table_def {
                 })
             }
 
-            fn row_size(decoder: &$crate::cli::tables::table_row::RowDecoder) -> usize {
-                let mut size = 0;
+            fn for_each_handle(&self, mut f: impl FnMut(&'static str, &$crate::cli::tables::table_handle::TableHandle)) {
+                let _ = &mut f;
+                $(
+                    table_def!(@VISIT $col_ty $(as $col_from_type)?, $col_name, &self.$col_name, f);
+                )+
+            }
+
+            fn raw_columns(&self) -> std::result::Result<Vec<u32>, $crate::error::Error> {
+                Ok(vec![
+                    $(
+                        table_def!(@TO_RAW $col_ty $(as $col_from_type)?, &self.$col_name),
+                    )+
+                ])
+            }
+
+            fn remap(&mut self, table: $crate::cli::tables::table_index::TableIndex, map: &[usize]) {
+                let _ = (table, map);
                 $(
-                size += table_def!(@SIZE $col_ty $(as $col_from_type)?, decoder);
+                    table_def!(@REMAP $col_ty $(as $col_from_type)?, &mut self.$col_name, table, map);
                 )+
-                size
+            }
+
+            fn for_each_heap_handle(
+                &mut self,
+                mut f: impl FnMut($crate::cli::tables::table_row::HeapHandleMut<'_>) -> std::result::Result<(), $crate::error::Error>,
+            ) -> std::result::Result<(), $crate::error::Error> {
+                let _ = &mut f;
+                $(
+                    table_def!(@HEAP $col_ty $(as $col_from_type)?, &mut self.$col_name, f);
+                )+
+                Ok(())
             }
         }
+
+        const _: () = {
+            // Gives each column its position in the row, so accessors can find their precomputed offset.
+            #[allow(non_camel_case_types)]
+            enum Column {
+                $($col_name),+
+            }
+
+            impl<'a> $crate::cli::tables::RowView<'a, $ty> {
+                $(
+                    pub fn $col_name(&self) -> std::result::Result<table_def!(@FIELD $col_ty), $crate::error::Error> {
+                        let raw = self.read_column(Column::$col_name as usize);
+                        Ok(table_def!(@FROM_RAW $col_ty $(as $col_from_type)?, raw))
+                    }
+                )+
+            }
+        };
     };
-}
\ No newline at end of file
+}
@@ -6,7 +6,7 @@ mod table_index;
 mod table_handle;
 
 pub use self::table_index::{TableIndex, TableMask};
-pub use self::table_handle::TableHandle;
-pub use self::table_row::{TableRow, RowDecoder};
-pub use self::table::Table;
+pub use self::table_handle::{RowHandle, TableHandle};
+pub use self::table_row::{TableRow, RowDecoder, CodedIndex, ColumnKind, ColumnValue, HeapHandleMut, TableLayout};
+pub use self::table::{Table, RowView};
 pub use self::tables::*;
@@ -1,9 +1,11 @@
-use crate::cli::tables::table_row::{RowDecoder, TableRow};
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::cli::tables::table_row::{RowDecoder, TableLayout, TableRow};
 use crate::Error;
 
 pub struct Table<'buffer, 'decoder, T: TableRow> {
     buffer: &'buffer [u8],
-    decoder: RowDecoder<'decoder>,
+    decoder: &'decoder RowDecoder,
     row_count: usize,
     row_size: usize,
     _phantom: std::marker::PhantomData<T>,
@@ -14,10 +16,21 @@ struct TableIterator<'table, 'buffer, 'decoder, T: TableRow> {
     index: usize,
 }
 
+/// A borrowed view of a single row, which decodes columns only when they are requested.
+///
+/// Each table generates an accessor for every column on `RowView<T>`, which reads only that column's bytes.
+pub struct RowView<'a, T: TableRow> {
+    buffer: &'a [u8],
+    layout: &'a TableLayout,
+    decoder: &'a RowDecoder,
+    row: usize,
+    _phantom: std::marker::PhantomData<T>,
+}
+
 impl<'buffer, 'decoder, T: TableRow> Table<'buffer, 'decoder, T> {
-    pub fn new(buffer: &'buffer [u8], decoder: RowDecoder<'decoder>) -> Table<'buffer, 'decoder, T> {
+    pub fn new(buffer: &'buffer [u8], decoder: &'decoder RowDecoder) -> Table<'buffer, 'decoder, T> {
         let row_count = decoder.row_count(T::INDEX);
-        let row_size = T::row_size(&decoder);
+        let row_size = T::row_size(decoder);
         Table {
             buffer,
             decoder,
@@ -30,12 +43,22 @@ impl<'buffer, 'decoder, T: TableRow> Table<'buffer, 'decoder, T> {
     pub fn len(&self) -> usize { self.row_count }
 
     pub fn read(&self, index: usize) -> Result<T, Error> {
-        if index > self.row_count {
+        self.row(index).read()
+    }
+
+    /// Gets a view of the row at the 0-based `index`, without decoding any of its columns.
+    pub fn row(&self, index: usize) -> RowView<'_, T> {
+        if index >= self.row_count {
             panic!("row index {} exceeds table size {}", index, self.row_count);
         }
         let offset = index * self.row_size;
-        let row = &self.buffer.as_ref()[offset..(offset + self.row_size)];
-        T::decode(&self.decoder, row)
+        RowView {
+            buffer: &self.buffer[offset..(offset + self.row_size)],
+            layout: self.decoder.layout(T::INDEX),
+            decoder: self.decoder,
+            row: index + 1,
+            _phantom: std::marker::PhantomData,
+        }
     }
 
     pub fn iter<'b>(&'b self) -> impl Iterator<Item = Result<T, Error>> + 'b {
@@ -44,6 +67,34 @@ impl<'buffer, 'decoder, T: TableRow> Table<'buffer, 'decoder, T> {
             index: 0,
         }
     }
+
+    /// Iterates over views of every row in the table.
+    pub fn rows(&self) -> impl Iterator<Item = RowView<'_, T>> + '_ {
+        (0..self.row_count).map(move |index| self.row(index))
+    }
+}
+
+impl<'a, T: TableRow> RowView<'a, T> {
+    /// Gets the 1-based index of the row in its table.
+    pub fn row(&self) -> usize {
+        self.row
+    }
+
+    /// Decodes every column of the row.
+    pub fn read(&self) -> Result<T, Error> {
+        T::decode(self.decoder, self.buffer)
+    }
+
+    /// Reads the raw value of a column, using its precomputed offset and size.
+    pub fn read_column(&self, column: usize) -> u32 {
+        let (offset, size) = self.layout.column(column);
+        let bytes = &self.buffer[offset..(offset + size)];
+        match size {
+            1 => bytes[0] as u32,
+            2 => LittleEndian::read_u16(bytes) as u32,
+            _ => LittleEndian::read_u32(bytes),
+        }
+    }
 }
 
 impl<'table, 'buffer, 'decoder, T: TableRow> Iterator for TableIterator<'table, 'buffer, 'decoder, T> {
@@ -58,4 +109,51 @@ impl<'table, 'buffer, 'decoder, T: TableRow> Iterator for TableIterator<'table,
             Some(result)
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::cli::tables::{TableIndex, TypeRef};
+    use crate::cli::MetadataSizes;
+
+    fn type_ref_sizes() -> MetadataSizes {
+        let mut header = vec![
+            0x00, 0x00, 0x00, 0x00, // Reserved
+            0x02, 0x00, // Version
+            0x00, // Heap sizes
+            0x01, // Reserved
+        ];
+        header.extend_from_slice(&(1u64 << TableIndex::TypeRef as u64).to_le_bytes());
+        header.extend_from_slice(&0u64.to_le_bytes());
+        header.extend_from_slice(&2u32.to_le_bytes());
+        MetadataSizes::read(&mut Cursor::new(header)).unwrap()
+    }
+
+    #[test]
+    pub fn row_view_reads_single_columns() {
+        let sizes = type_ref_sizes();
+        let rows: &[u8] = &[
+            0x06, 0x00, 0x10, 0x00, 0x20, 0x00, // AssemblyRef 1, "name" at 0x10, "namespace" at 0x20
+            0x07, 0x00, 0x30, 0x00, 0x00, 0x00, // TypeRef 1, "name" at 0x30, no namespace
+        ];
+        let decoder = RowDecoder::new(&sizes);
+        let table = Table::<TypeRef>::new(rows, &decoder);
+        assert_eq!(2, table.len());
+
+        let first = table.row(0);
+        assert_eq!(1, first.row());
+        assert_eq!(0x10, first.name().unwrap().0);
+        assert_eq!(0x20, first.namespace().unwrap().0);
+        assert_eq!(TableIndex::AssemblyRef, first.resolution_scope().unwrap().table());
+
+        let second = table.row(1);
+        let decoded = second.read().unwrap();
+        assert_eq!(decoded.resolution_scope, second.resolution_scope().unwrap());
+        assert_eq!(TableIndex::TypeRef, decoded.resolution_scope.table());
+        assert_eq!(1, decoded.resolution_scope.index());
+        assert_eq!(0x30, second.name().unwrap().0);
+    }
+}
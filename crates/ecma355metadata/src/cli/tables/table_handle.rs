@@ -1,8 +1,10 @@
 use std::fmt;
+use std::marker::PhantomData;
 
 use crate::cli::tables::table_index::TableIndex;
+use crate::cli::tables::table_row::TableRow;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct TableHandle {
     index: usize,
     table: TableIndex,
@@ -22,6 +24,11 @@ impl TableHandle {
         TableHandle::new(index as usize, table.into())
     }
 
+    /// Gets the metadata token of the row, which holds the table index in the top byte and the row in the rest.
+    pub fn to_metadata_token(&self) -> u32 {
+        ((self.table as u32) << 24) | (self.index as u32 & 0x00FFFFFF)
+    }
+
     /// Gets the table index of the handle.
     pub fn table(&self) -> TableIndex {
         self.table
@@ -37,4 +44,53 @@ impl fmt::Display for TableHandle {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         write!(f, "{}[0x{:04X}]", self.table, self.index)
     }
-}
\ No newline at end of file
+}
+
+/// A handle to a row of a known table, such as one returned by the metadata builder.
+pub struct RowHandle<T: TableRow> {
+    row: usize,
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<T: TableRow> RowHandle<T> {
+    pub fn new(row: usize) -> RowHandle<T> {
+        RowHandle { row, _phantom: PhantomData }
+    }
+
+    /// Gets the 1-based index of the row in the table.
+    pub fn row(&self) -> usize {
+        self.row
+    }
+
+    pub fn handle(&self) -> TableHandle {
+        TableHandle::new(self.row, T::INDEX)
+    }
+}
+
+impl<T: TableRow> Clone for RowHandle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: TableRow> Copy for RowHandle<T> {}
+
+impl<T: TableRow> PartialEq for RowHandle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.row == other.row
+    }
+}
+
+impl<T: TableRow> Eq for RowHandle<T> {}
+
+impl<T: TableRow> fmt::Debug for RowHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{}", self.handle())
+    }
+}
+
+impl<T: TableRow> From<RowHandle<T>> for TableHandle {
+    fn from(handle: RowHandle<T>) -> TableHandle {
+        handle.handle()
+    }
+}
@@ -6,7 +6,7 @@ use std::str::FromStr;
 use crate::error::Error;
 
 #[repr(u8)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum TableIndex {
     Module = 0x00,
     TypeRef = 0x01,
@@ -184,6 +184,7 @@ impl Iterator for TableIndexIter {
 }
 
 bitflags! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
     pub struct TableMask : u64 {
         const NonExistent = 0;
         const Module = 1 << TableIndex::Module as u64;
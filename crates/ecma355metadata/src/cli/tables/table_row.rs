@@ -1,29 +1,210 @@
 use byteorder::{ByteOrder, LittleEndian};
 
-use crate::cli::tables::{TableHandle, TableIndex, TableMask};
-use crate::cli::{BlobHandle, GuidHandle, HeapSizes, MetadataSizes, StringHandle};
+use crate::cli::tables::{columns_of, TableHandle, TableIndex, TableMask};
+use crate::cli::{AssemblyFlags, AssemblyHashAlgorithm, BlobHandle, FieldAttributes, GuidHandle, HeapSizes, MetadataSizes, MethodAttributes, MethodImplAttributes, ParamAttributes, StringHandle, TypeAttributes};
 use crate::Error;
 
 pub trait TableRow: Sized {
     const INDEX: TableIndex;
 
+    /// Describes the storage of each column, in the order they appear in the row.
+    const COLUMNS: &'static [ColumnKind];
+
     fn decode(decoder: &RowDecoder, buf: &[u8]) -> Result<Self, Error>;
-    fn row_size(decoder: &RowDecoder) -> usize;
+
+    fn row_size(decoder: &RowDecoder) -> usize {
+        decoder.row_size(Self::INDEX)
+    }
+
+    /// Calls `f` with the column name and value of every simple or coded index column in the row.
+    fn for_each_handle(&self, f: impl FnMut(&'static str, &TableHandle));
+
+    /// Gets the value of every column as it is stored in the table, with coded indexes encoded.
+    ///
+    /// Fails if a coded index column holds a handle to a table that the coded index can't refer to.
+    fn raw_columns(&self) -> Result<Vec<u32>, Error>;
+
+    /// Rewrites the handles in the row that point into `table`, where `map[i]` is the new row of row `i + 1`.
+    fn remap(&mut self, table: TableIndex, map: &[usize]);
+
+    /// Calls `f` with every heap handle in the row, so it can be pointed into another set of heaps.
+    fn for_each_heap_handle(&mut self, f: impl FnMut(HeapHandleMut<'_>) -> Result<(), Error>) -> Result<(), Error>;
+}
+
+/// A heap handle column of a row, as visited by `TableRow::for_each_heap_handle`.
+pub enum HeapHandleMut<'a> {
+    String(&'a mut StringHandle),
+    Guid(&'a mut GuidHandle),
+    Blob(&'a mut BlobHandle),
+}
+
+/// A column value that is stored as a plain integer.
+pub trait ColumnValue {
+    fn raw(&self) -> u32;
+}
+
+impl ColumnValue for u8 {
+    fn raw(&self) -> u32 { *self as u32 }
+}
+
+impl ColumnValue for u16 {
+    fn raw(&self) -> u32 { *self as u32 }
+}
+
+impl ColumnValue for u32 {
+    fn raw(&self) -> u32 { *self }
+}
+
+impl ColumnValue for StringHandle {
+    fn raw(&self) -> u32 { self.0 as u32 }
+}
+
+impl ColumnValue for GuidHandle {
+    fn raw(&self) -> u32 { self.0 as u32 }
+}
+
+impl ColumnValue for BlobHandle {
+    fn raw(&self) -> u32 { self.0 as u32 }
+}
+
+impl ColumnValue for TypeAttributes {
+    fn raw(&self) -> u32 { self.bits() }
+}
+
+impl ColumnValue for FieldAttributes {
+    fn raw(&self) -> u32 { self.bits() as u32 }
+}
+
+impl ColumnValue for MethodAttributes {
+    fn raw(&self) -> u32 { self.bits() as u32 }
+}
+
+impl ColumnValue for MethodImplAttributes {
+    fn raw(&self) -> u32 { self.bits() as u32 }
+}
+
+impl ColumnValue for ParamAttributes {
+    fn raw(&self) -> u32 { self.bits() as u32 }
+}
+
+impl ColumnValue for AssemblyFlags {
+    fn raw(&self) -> u32 { self.bits() }
+}
+
+impl ColumnValue for AssemblyHashAlgorithm {
+    fn raw(&self) -> u32 { *self as u32 }
+}
+
+/// Points `handle` at its new row if it refers to `table`, leaving null handles alone.
+pub fn remap_handle(handle: &mut TableHandle, table: TableIndex, map: &[usize]) {
+    if handle.table() == table && handle.index() > 0 {
+        if let Some(row) = map.get(handle.index() - 1) {
+            *handle = TableHandle::new(*row, table);
+        }
+    }
 }
 
 pub trait CodedIndex: Sized {
+    const KIND: ColumnKind;
+
     fn read(decoder: &RowDecoder, buf: &mut &[u8]) -> Result<TableHandle, Error>;
+
+    /// Decodes a raw coded index value into a handle.
+    fn decode(value: u32) -> Result<TableHandle, Error>;
     fn size(decoder: &RowDecoder) -> usize;
+
+    /// Encodes a handle as a coded index value, or returns `None` if the handle's table is not one of the coded index's tables.
+    fn encode(handle: &TableHandle) -> Option<u32>;
+}
+
+/// Describes how a column is stored in a row.
+#[derive(Clone, Copy, Debug)]
+pub enum ColumnKind {
+    /// A constant of the given size, in bytes.
+    Fixed(usize),
+    String,
+    Guid,
+    Blob,
+    /// A simple index into a single table.
+    Index(TableIndex),
+    /// A coded index into one of a set of tables, using the given number of bits for the tag.
+    Coded(TableMask, usize),
+}
+
+/// The location of each column within the rows of a table.
+pub struct TableLayout {
+    row_size: usize,
+    columns: Vec<(usize, usize)>,
+}
+
+impl TableLayout {
+    pub fn row_size(&self) -> usize {
+        self.row_size
+    }
+
+    /// Gets the offset and size of a column, in bytes.
+    pub fn column(&self, column: usize) -> (usize, usize) {
+        self.columns[column]
+    }
 }
 
-pub struct RowDecoder<'a> {
-    metadata_sizes: &'a MetadataSizes,
+/// Decodes rows using the heap sizes and row counts of one metadata image.
+///
+/// The decoder owns a copy of the sizes, so it can be built once and cached alongside the image.
+pub struct RowDecoder {
+    metadata_sizes: MetadataSizes,
+    layouts: Vec<TableLayout>,
 }
 
-impl<'a> RowDecoder<'a> {
-    pub fn new(metadata_sizes: &'a MetadataSizes) -> RowDecoder<'a> {
-        RowDecoder {
-            metadata_sizes,
+impl RowDecoder {
+    pub fn new(metadata_sizes: &MetadataSizes) -> RowDecoder {
+        let mut decoder = RowDecoder {
+            metadata_sizes: metadata_sizes.clone(),
+            layouts: Vec::with_capacity(TableIndex::MAX + 1),
+        };
+
+        // Column sizes only depend on the heap sizes and row counts, so compute every layout up front
+        let mut layouts: Vec<_> = (0..=TableIndex::MAX).map(|_| TableLayout { row_size: 0, columns: Vec::new() }).collect();
+        for idx in TableIndex::each() {
+            let mut offset = 0;
+            let columns = columns_of(idx).iter().map(|kind| {
+                let size = decoder.size_of_column(*kind);
+                let column = (offset, size);
+                offset += size;
+                column
+            }).collect();
+            layouts[idx as usize] = TableLayout {
+                row_size: offset,
+                columns,
+            };
+        }
+        decoder.layouts = layouts;
+        decoder
+    }
+
+    /// Gets the precomputed layout of a table.
+    pub fn layout(&self, table_index: TableIndex) -> &TableLayout {
+        &self.layouts[table_index as usize]
+    }
+
+    pub fn row_size(&self, table_index: TableIndex) -> usize {
+        self.layout(table_index).row_size
+    }
+
+    pub fn size_of_column(&self, kind: ColumnKind) -> usize {
+        match kind {
+            ColumnKind::Fixed(size) => size,
+            ColumnKind::String => self.size_of_string(),
+            ColumnKind::Guid => self.size_of_guid(),
+            ColumnKind::Blob => self.size_of_blob(),
+            ColumnKind::Index(table) => self.size_of_index(table),
+            ColumnKind::Coded(mask, tag_bits) => {
+                if self.any_large(mask, tag_bits) {
+                    4
+                } else {
+                    2
+                }
+            }
         }
     }
 
@@ -66,7 +247,7 @@ impl<'a> RowDecoder<'a> {
     }
 
     pub fn decode_guid(&self, buf: &mut &[u8]) -> Result<GuidHandle, Error> {
-        if self.metadata_sizes.heap_sizes().contains(HeapSizes::LARGE_STRINGS) {
+        if self.metadata_sizes.heap_sizes().contains(HeapSizes::LARGE_GUIDS) {
             Ok(GuidHandle(self.decode_u32(buf)? as usize))
         } else {
             Ok(GuidHandle(self.decode_u16(buf)? as usize))
@@ -82,7 +263,7 @@ impl<'a> RowDecoder<'a> {
     }
 
     pub fn decode_blob(&self, buf: &mut &[u8]) -> Result<BlobHandle, Error> {
-        if self.metadata_sizes.heap_sizes().contains(HeapSizes::LARGE_STRINGS) {
+        if self.metadata_sizes.heap_sizes().contains(HeapSizes::LARGE_BLOBS) {
             Ok(BlobHandle(self.decode_u32(buf)? as usize))
         } else {
             Ok(BlobHandle(self.decode_u16(buf)? as usize))
@@ -113,16 +294,13 @@ impl<'a> RowDecoder<'a> {
         }
     }
 
-    pub fn any_large(&self, mask: TableMask) -> bool {
-        for table in mask.tables() {
-            if self.has_large_index(table) {
-                return true;
-            }
-        }
-        false
+    /// Returns true if a coded index over `mask`, using `tag_bits` bits for the tag, needs 4 bytes.
+    pub fn any_large(&self, mask: TableMask, tag_bits: usize) -> bool {
+        let max_small_rows = 1usize << (16 - tag_bits);
+        self.metadata_sizes.is_minimal_delta() || mask.tables().any(|table| self.metadata_sizes.referenced_row_count(table) >= max_small_rows)
     }
     
     fn has_large_index(&self, table: TableIndex) -> bool {
-        self.row_count(table) >= u16::MAX as usize
+        self.metadata_sizes.is_minimal_delta() || self.metadata_sizes.referenced_row_count(table) > u16::MAX as usize
     }
 }
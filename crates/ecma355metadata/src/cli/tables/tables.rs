@@ -1,4 +1,5 @@
 use crate::cli::{AssemblyFlags, AssemblyHashAlgorithm, BlobHandle, FieldAttributes, GuidHandle, MethodAttributes, MethodImplAttributes, ParamAttributes, StringHandle, TypeAttributes};
+use crate::cli::tables::{ColumnKind, TableIndex, TableRow};
 use crate::{table_def, coded_index};
 
 coded_index!(ResolutionScope, [
@@ -37,7 +38,7 @@ coded_index!(HasCustomAttribute, [
     InterfaceImpl,
     MemberRef,
     Module,
-    NonExistent, // Permission table, which is not used in the ECMA-335 spec
+    DeclSecurity, // Called Permission in ECMA-335 II.24.2.6
     Property,
     Event,
     StandAloneSig,
@@ -61,6 +62,73 @@ coded_index!(CustomAttributeType, [
     NonExistent,
 ]);
 
+coded_index!(HasFieldMarshal, [
+    Field,
+    Param,
+]);
+
+coded_index!(HasDeclSecurity, [
+    TypeDef,
+    MethodDef,
+    Assembly,
+]);
+
+coded_index!(HasSemantics, [
+    Event,
+    Property,
+]);
+
+coded_index!(MethodDefOrRef, [
+    MethodDef,
+    MemberRef,
+]);
+
+coded_index!(MemberForwarded, [
+    Field,
+    MethodDef,
+]);
+
+coded_index!(Implementation, [
+    File,
+    AssemblyRef,
+    ExportedType,
+]);
+
+coded_index!(TypeOrMethodDef, [
+    TypeDef,
+    MethodDef,
+]);
+
+coded_index!(HasCustomDebugInformation, [
+    MethodDef,
+    Field,
+    TypeRef,
+    TypeDef,
+    Param,
+    InterfaceImpl,
+    MemberRef,
+    Module,
+    DeclSecurity,
+    Property,
+    Event,
+    StandAloneSig,
+    ModuleRef,
+    TypeSpec,
+    Assembly,
+    AssemblyRef,
+    File,
+    ExportedType,
+    ManifestResource,
+    GenericParam,
+    GenericParamConstraint,
+    MethodSpec,
+    Document,
+    LocalScope,
+    LocalVariable,
+    LocalConstant,
+    ImportScope,
+]);
+
 table_def!(Module, [
     generation: u16, 
     name: StringHandle, 
@@ -151,4 +219,287 @@ table_def!(AssemblyRef, [
     name: StringHandle,
     culture: StringHandle,
     hash_value: BlobHandle,
-]);
\ No newline at end of file
+]);
+table_def!(FieldPtr, [
+    field: [Field],
+]);
+
+table_def!(MethodPtr, [
+    method: [MethodDef],
+]);
+
+table_def!(ParamPtr, [
+    param: [Param],
+]);
+
+table_def!(FieldMarshal, [
+    parent: (HasFieldMarshal),
+    native_type: BlobHandle,
+]);
+
+table_def!(DeclSecurity, [
+    action: u16,
+    parent: (HasDeclSecurity),
+    permission_set: BlobHandle,
+]);
+
+table_def!(ClassLayout, [
+    packing_size: u16,
+    class_size: u32,
+    parent: [TypeDef],
+]);
+
+table_def!(FieldLayout, [
+    offset: u32,
+    field: [Field],
+]);
+
+table_def!(StandAloneSig, [
+    signature: BlobHandle,
+]);
+
+table_def!(EventMap, [
+    parent: [TypeDef],
+    event_list: [Event],
+]);
+
+table_def!(EventPtr, [
+    event: [Event],
+]);
+
+table_def!(Event, [
+    flags: u16,
+    name: StringHandle,
+    event_type: (TypeDefOrRef),
+]);
+
+table_def!(PropertyMap, [
+    parent: [TypeDef],
+    property_list: [Property],
+]);
+
+table_def!(PropertyPtr, [
+    property: [Property],
+]);
+
+table_def!(Property, [
+    flags: u16,
+    name: StringHandle,
+    typ: BlobHandle,
+]);
+
+table_def!(MethodSemantics, [
+    semantics: u16,
+    method: [MethodDef],
+    association: (HasSemantics),
+]);
+
+table_def!(MethodImpl, [
+    class: [TypeDef],
+    method_body: (MethodDefOrRef),
+    method_declaration: (MethodDefOrRef),
+]);
+
+table_def!(ModuleRef, [
+    name: StringHandle,
+]);
+
+table_def!(TypeSpec, [
+    signature: BlobHandle,
+]);
+
+table_def!(ImplMap, [
+    mapping_flags: u16,
+    member_forwarded: (MemberForwarded),
+    import_name: StringHandle,
+    import_scope: [ModuleRef],
+]);
+
+table_def!(FieldRva, [
+    rva: u32,
+    field: [Field],
+]);
+
+table_def!(EncLog, [
+    token: u32,
+    func_code: u32,
+]);
+
+table_def!(EncMap, [
+    token: u32,
+]);
+
+table_def!(AssemblyProcessor, [
+    processor: u32,
+]);
+
+table_def!(AssemblyOS, [
+    os_platform_id: u32,
+    os_major_version: u32,
+    os_minor_version: u32,
+]);
+
+table_def!(AssemblyRefProcessor, [
+    processor: u32,
+    assembly_ref: [AssemblyRef],
+]);
+
+table_def!(AssemblyRefOS, [
+    os_platform_id: u32,
+    os_major_version: u32,
+    os_minor_version: u32,
+    assembly_ref: [AssemblyRef],
+]);
+
+table_def!(File, [
+    flags: u32,
+    name: StringHandle,
+    hash_value: BlobHandle,
+]);
+
+table_def!(ExportedType, [
+    flags: TypeAttributes as u32,
+    type_def_id: u32,
+    type_name: StringHandle,
+    type_namespace: StringHandle,
+    implementation: (Implementation),
+]);
+
+table_def!(ManifestResource, [
+    offset: u32,
+    flags: u32,
+    name: StringHandle,
+    implementation: (Implementation),
+]);
+
+table_def!(NestedClass, [
+    nested_class: [TypeDef],
+    enclosing_class: [TypeDef],
+]);
+
+table_def!(GenericParam, [
+    number: u16,
+    flags: u16,
+    owner: (TypeOrMethodDef),
+    name: StringHandle,
+]);
+
+table_def!(MethodSpec, [
+    method: (MethodDefOrRef),
+    instantiation: BlobHandle,
+]);
+
+table_def!(GenericParamConstraint, [
+    owner: [GenericParam],
+    constraint: (TypeDefOrRef),
+]);
+
+// Portable PDB tables
+
+table_def!(Document, [
+    name: BlobHandle,
+    hash_algorithm: GuidHandle,
+    hash: BlobHandle,
+    language: GuidHandle,
+]);
+
+table_def!(MethodDebugInformation, [
+    document: [Document],
+    sequence_points: BlobHandle,
+]);
+
+table_def!(LocalScope, [
+    method: [MethodDef],
+    import_scope: [ImportScope],
+    variable_list: [LocalVariable],
+    constant_list: [LocalConstant],
+    start_offset: u32,
+    length: u32,
+]);
+
+table_def!(LocalVariable, [
+    attributes: u16,
+    index: u16,
+    name: StringHandle,
+]);
+
+table_def!(LocalConstant, [
+    name: StringHandle,
+    signature: BlobHandle,
+]);
+
+table_def!(ImportScope, [
+    parent: [ImportScope],
+    imports: BlobHandle,
+]);
+
+table_def!(StateMachineMethod, [
+    move_next_method: [MethodDef],
+    kickoff_method: [MethodDef],
+]);
+
+table_def!(CustomDebugInformation, [
+    parent: (HasCustomDebugInformation),
+    kind: GuidHandle,
+    value: BlobHandle,
+]);
+
+/// Gets the column layout of a table, or an empty list for tables this crate can't decode.
+pub fn columns_of(table: TableIndex) -> &'static [ColumnKind] {
+    match table {
+        TableIndex::Module => Module::COLUMNS,
+        TableIndex::TypeRef => TypeRef::COLUMNS,
+        TableIndex::TypeDef => TypeDef::COLUMNS,
+        TableIndex::FieldPtr => FieldPtr::COLUMNS,
+        TableIndex::Field => Field::COLUMNS,
+        TableIndex::MethodPtr => MethodPtr::COLUMNS,
+        TableIndex::MethodDef => MethodDef::COLUMNS,
+        TableIndex::ParamPtr => ParamPtr::COLUMNS,
+        TableIndex::Param => Param::COLUMNS,
+        TableIndex::InterfaceImpl => InterfaceImpl::COLUMNS,
+        TableIndex::MemberRef => MemberRef::COLUMNS,
+        TableIndex::Constant => Constant::COLUMNS,
+        TableIndex::CustomAttribute => CustomAttribute::COLUMNS,
+        TableIndex::FieldMarshal => FieldMarshal::COLUMNS,
+        TableIndex::DeclSecurity => DeclSecurity::COLUMNS,
+        TableIndex::ClassLayout => ClassLayout::COLUMNS,
+        TableIndex::FieldLayout => FieldLayout::COLUMNS,
+        TableIndex::StandAloneSig => StandAloneSig::COLUMNS,
+        TableIndex::EventMap => EventMap::COLUMNS,
+        TableIndex::EventPtr => EventPtr::COLUMNS,
+        TableIndex::Event => Event::COLUMNS,
+        TableIndex::PropertyMap => PropertyMap::COLUMNS,
+        TableIndex::PropertyPtr => PropertyPtr::COLUMNS,
+        TableIndex::Property => Property::COLUMNS,
+        TableIndex::MethodSemantics => MethodSemantics::COLUMNS,
+        TableIndex::MethodImpl => MethodImpl::COLUMNS,
+        TableIndex::ModuleRef => ModuleRef::COLUMNS,
+        TableIndex::TypeSpec => TypeSpec::COLUMNS,
+        TableIndex::ImplMap => ImplMap::COLUMNS,
+        TableIndex::FieldRva => FieldRva::COLUMNS,
+        TableIndex::EncLog => EncLog::COLUMNS,
+        TableIndex::EncMap => EncMap::COLUMNS,
+        TableIndex::Assembly => Assembly::COLUMNS,
+        TableIndex::AssemblyProcessor => AssemblyProcessor::COLUMNS,
+        TableIndex::AssemblyOS => AssemblyOS::COLUMNS,
+        TableIndex::AssemblyRef => AssemblyRef::COLUMNS,
+        TableIndex::AssemblyRefProcessor => AssemblyRefProcessor::COLUMNS,
+        TableIndex::AssemblyRefOS => AssemblyRefOS::COLUMNS,
+        TableIndex::File => File::COLUMNS,
+        TableIndex::ExportedType => ExportedType::COLUMNS,
+        TableIndex::ManifestResource => ManifestResource::COLUMNS,
+        TableIndex::NestedClass => NestedClass::COLUMNS,
+        TableIndex::GenericParam => GenericParam::COLUMNS,
+        TableIndex::MethodSpec => MethodSpec::COLUMNS,
+        TableIndex::GenericParamConstraint => GenericParamConstraint::COLUMNS,
+        TableIndex::Document => Document::COLUMNS,
+        TableIndex::MethodDebugInformation => MethodDebugInformation::COLUMNS,
+        TableIndex::LocalScope => LocalScope::COLUMNS,
+        TableIndex::LocalVariable => LocalVariable::COLUMNS,
+        TableIndex::LocalConstant => LocalConstant::COLUMNS,
+        TableIndex::ImportScope => ImportScope::COLUMNS,
+        TableIndex::StateMachineMethod => StateMachineMethod::COLUMNS,
+        TableIndex::CustomDebugInformation => CustomDebugInformation::COLUMNS,
+        _ => &[],
+    }
+}
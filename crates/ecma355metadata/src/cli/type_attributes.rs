@@ -12,6 +12,11 @@ impl TypeAttributes {
         TypeAttributes(value)
     }
 
+    /// Gets the raw value of the attributes.
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
     pub fn visibility(self) -> TypeVisibility {
         unsafe {
             mem::transmute((self.0 & TypeVisibility::MASK) >> TypeVisibility::SHIFT)
@@ -143,6 +148,7 @@ bitflags! {
         const SpecialName = 0x400;
         const Import = 0x1000;
         const Serializable = 0x2000;
+        const WindowsRuntime = 0x4000;
         const BeforeFieldInit = 0x00100000;
         const RTSpecialName = 0x00000800;
         const HasSecurity = 0x00040000;
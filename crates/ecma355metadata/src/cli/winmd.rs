@@ -0,0 +1,210 @@
+/// The kinds of Windows Metadata file, which are told apart by the version string in the metadata header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WinMdKind {
+    /// A file produced by MIDL, such as those in the Windows SDK. Its version string is `WindowsRuntime 1.4`.
+    Platform,
+
+    /// A file produced by a managed compiler, whose version string also names the CLR, as in
+    /// `WindowsRuntime 1.4;CLR v4.0.30319`. Each public class has a `<CLR>`-prefixed twin holding its implementation.
+    Managed,
+}
+
+impl WinMdKind {
+    /// Detects the kind of Windows Metadata file from the version string of its metadata header.
+    ///
+    /// Returns `None` for ordinary ECMA-335 metadata.
+    pub fn from_version(version: &str) -> Option<WinMdKind> {
+        let version = version.trim_end_matches('\0');
+        if !version.starts_with("WindowsRuntime") {
+            None
+        } else if version.contains("CLR") {
+            Some(WinMdKind::Managed)
+        } else {
+            Some(WinMdKind::Platform)
+        }
+    }
+}
+
+impl_display_via_debug!(WinMdKind);
+
+/// A Windows Runtime type that the CLR replaces with a .NET type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Projection {
+    pub winrt_namespace: &'static str,
+    pub winrt_name: &'static str,
+    pub clr_namespace: &'static str,
+    pub clr_name: &'static str,
+
+    /// The reference assembly that defines the .NET type.
+    pub assembly: &'static str,
+}
+
+macro_rules! projections {
+    ($($winrt_namespace:literal $winrt_name:literal => $clr_namespace:literal $clr_name:literal in $assembly:literal,)*) => {
+        &[$(Projection {
+            winrt_namespace: $winrt_namespace,
+            winrt_name: $winrt_name,
+            clr_namespace: $clr_namespace,
+            clr_name: $clr_name,
+            assembly: $assembly,
+        },)*]
+    };
+}
+
+/// The types the CLR projects, in the order the runtime lists them.
+pub const PROJECTIONS: &[Projection] = projections! {
+    "Windows.Foundation.Metadata" "AttributeUsageAttribute" => "System" "AttributeUsageAttribute" in "System.Runtime",
+    "Windows.Foundation.Metadata" "AttributeTargets" => "System" "AttributeTargets" in "System.Runtime",
+    "Windows.UI" "Color" => "Windows.UI" "Color" in "System.Runtime.WindowsRuntime",
+    "Windows.Foundation" "DateTime" => "System" "DateTimeOffset" in "System.Runtime",
+    "Windows.Foundation" "EventHandler`1" => "System" "EventHandler`1" in "System.Runtime",
+    "Windows.Foundation" "EventRegistrationToken" => "System.Runtime.InteropServices.WindowsRuntime" "EventRegistrationToken" in "System.Runtime.InteropServices.WindowsRuntime",
+    "Windows.Foundation" "HResult" => "System" "Exception" in "System.Runtime",
+    "Windows.Foundation" "IReference`1" => "System" "Nullable`1" in "System.Runtime",
+    "Windows.Foundation" "Point" => "Windows.Foundation" "Point" in "System.Runtime.WindowsRuntime",
+    "Windows.Foundation" "Rect" => "Windows.Foundation" "Rect" in "System.Runtime.WindowsRuntime",
+    "Windows.Foundation" "Size" => "Windows.Foundation" "Size" in "System.Runtime.WindowsRuntime",
+    "Windows.Foundation" "TimeSpan" => "System" "TimeSpan" in "System.Runtime",
+    "Windows.Foundation" "Uri" => "System" "Uri" in "System.Runtime",
+    "Windows.Foundation" "IClosable" => "System" "IDisposable" in "System.Runtime",
+    "Windows.Foundation.Collections" "IIterable`1" => "System.Collections.Generic" "IEnumerable`1" in "System.Runtime",
+    "Windows.Foundation.Collections" "IVector`1" => "System.Collections.Generic" "IList`1" in "System.Runtime",
+    "Windows.Foundation.Collections" "IVectorView`1" => "System.Collections.Generic" "IReadOnlyList`1" in "System.Runtime",
+    "Windows.Foundation.Collections" "IMap`2" => "System.Collections.Generic" "IDictionary`2" in "System.Runtime",
+    "Windows.Foundation.Collections" "IMapView`2" => "System.Collections.Generic" "IReadOnlyDictionary`2" in "System.Runtime",
+    "Windows.Foundation.Collections" "IKeyValuePair`2" => "System.Collections.Generic" "KeyValuePair`2" in "System.Runtime",
+    "Windows.UI.Xaml.Input" "ICommand" => "System.Windows.Input" "ICommand" in "System.ObjectModel",
+    "Windows.UI.Xaml.Interop" "IBindableIterable" => "System.Collections" "IEnumerable" in "System.Runtime",
+    "Windows.UI.Xaml.Interop" "IBindableVector" => "System.Collections" "IList" in "System.Runtime",
+    "Windows.UI.Xaml.Interop" "INotifyCollectionChanged" => "System.Collections.Specialized" "INotifyCollectionChanged" in "System.ObjectModel",
+    "Windows.UI.Xaml.Interop" "NotifyCollectionChangedEventHandler" => "System.Collections.Specialized" "NotifyCollectionChangedEventHandler" in "System.ObjectModel",
+    "Windows.UI.Xaml.Interop" "NotifyCollectionChangedEventArgs" => "System.Collections.Specialized" "NotifyCollectionChangedEventArgs" in "System.ObjectModel",
+    "Windows.UI.Xaml.Interop" "NotifyCollectionChangedAction" => "System.Collections.Specialized" "NotifyCollectionChangedAction" in "System.ObjectModel",
+    "Windows.UI.Xaml.Interop" "TypeName" => "System" "Type" in "System.Runtime",
+    "Windows.UI.Xaml.Data" "INotifyPropertyChanged" => "System.ComponentModel" "INotifyPropertyChanged" in "System.ObjectModel",
+    "Windows.UI.Xaml.Data" "PropertyChangedEventHandler" => "System.ComponentModel" "PropertyChangedEventHandler" in "System.ObjectModel",
+    "Windows.UI.Xaml.Data" "PropertyChangedEventArgs" => "System.ComponentModel" "PropertyChangedEventArgs" in "System.ObjectModel",
+    "Windows.UI.Xaml" "CornerRadius" => "Windows.UI.Xaml" "CornerRadius" in "System.Runtime.WindowsRuntime.UI.Xaml",
+    "Windows.UI.Xaml" "Duration" => "Windows.UI.Xaml" "Duration" in "System.Runtime.WindowsRuntime.UI.Xaml",
+    "Windows.UI.Xaml" "DurationType" => "Windows.UI.Xaml" "DurationType" in "System.Runtime.WindowsRuntime.UI.Xaml",
+    "Windows.UI.Xaml" "GridLength" => "Windows.UI.Xaml" "GridLength" in "System.Runtime.WindowsRuntime.UI.Xaml",
+    "Windows.UI.Xaml" "GridUnitType" => "Windows.UI.Xaml" "GridUnitType" in "System.Runtime.WindowsRuntime.UI.Xaml",
+    "Windows.UI.Xaml" "Thickness" => "Windows.UI.Xaml" "Thickness" in "System.Runtime.WindowsRuntime.UI.Xaml",
+    "Windows.UI.Xaml.Controls.Primitives" "GeneratorPosition" => "Windows.UI.Xaml.Controls.Primitives" "GeneratorPosition" in "System.Runtime.WindowsRuntime.UI.Xaml",
+    "Windows.UI.Xaml.Media" "Matrix" => "Windows.UI.Xaml.Media" "Matrix" in "System.Runtime.WindowsRuntime.UI.Xaml",
+    "Windows.UI.Xaml.Media.Animation" "KeyTime" => "Windows.UI.Xaml.Media.Animation" "KeyTime" in "System.Runtime.WindowsRuntime.UI.Xaml",
+    "Windows.UI.Xaml.Media.Animation" "RepeatBehavior" => "Windows.UI.Xaml.Media.Animation" "RepeatBehavior" in "System.Runtime.WindowsRuntime.UI.Xaml",
+    "Windows.UI.Xaml.Media.Animation" "RepeatBehaviorType" => "Windows.UI.Xaml.Media.Animation" "RepeatBehaviorType" in "System.Runtime.WindowsRuntime.UI.Xaml",
+    "Windows.UI.Xaml.Media.Media3D" "Matrix3D" => "Windows.UI.Xaml.Media.Media3D" "Matrix3D" in "System.Runtime.WindowsRuntime.UI.Xaml",
+    "Windows.Foundation.Numerics" "Vector2" => "System.Numerics" "Vector2" in "System.Numerics.Vectors",
+    "Windows.Foundation.Numerics" "Vector3" => "System.Numerics" "Vector3" in "System.Numerics.Vectors",
+    "Windows.Foundation.Numerics" "Vector4" => "System.Numerics" "Vector4" in "System.Numerics.Vectors",
+    "Windows.Foundation.Numerics" "Matrix3x2" => "System.Numerics" "Matrix3x2" in "System.Numerics.Vectors",
+    "Windows.Foundation.Numerics" "Matrix4x4" => "System.Numerics" "Matrix4x4" in "System.Numerics.Vectors",
+    "Windows.Foundation.Numerics" "Plane" => "System.Numerics" "Plane" in "System.Numerics.Vectors",
+    "Windows.Foundation.Numerics" "Quaternion" => "System.Numerics" "Quaternion" in "System.Numerics.Vectors",
+};
+
+/// Finds the projection of a Windows Runtime type, if the CLR replaces it.
+pub fn find_projection(namespace: &str, name: &str) -> Option<&'static Projection> {
+    PROJECTIONS.iter().find(|p| p.winrt_namespace == namespace && p.winrt_name == name)
+}
+
+/// The prefix of the implementation classes in a managed winmd.
+pub const CLR_PREFIX: &str = "<CLR>";
+
+/// The prefix the CLR gives to the Windows Runtime classes of a managed winmd, which their `<CLR>` twins replace.
+pub const WINRT_PREFIX: &str = "<WinRT>";
+
+/// How the CLR changes a type when it loads a Windows Metadata file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TypeTreatment {
+    /// The type is seen as it is written.
+    None,
+
+    /// The type is replaced by a .NET type.
+    Redirected(&'static Projection),
+
+    /// A `<CLR>` implementation class of a managed winmd, which is seen under its name without the prefix.
+    UnmangledClrName,
+
+    /// A Windows Runtime class of a managed winmd, which is hidden behind a `<WinRT>` prefix so that its `<CLR>` twin
+    /// can take its name.
+    PrefixedWinRtName,
+}
+
+impl_display_via_debug!(TypeTreatment);
+
+/// The name of a type as the CLR sees it, alongside the name written in the metadata.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProjectedName {
+    pub namespace: String,
+    pub name: String,
+    pub unprojected_namespace: String,
+    pub unprojected_name: String,
+    pub treatment: TypeTreatment,
+}
+
+impl ProjectedName {
+    /// Creates a name with no projection applied.
+    pub fn unprojected(namespace: String, name: String) -> ProjectedName {
+        ProjectedName {
+            namespace: namespace.clone(),
+            name: name.clone(),
+            unprojected_namespace: namespace,
+            unprojected_name: name,
+            treatment: TypeTreatment::None,
+        }
+    }
+
+    /// Applies a treatment to an unprojected name.
+    pub fn with_treatment(mut self, treatment: TypeTreatment) -> ProjectedName {
+        match treatment {
+            TypeTreatment::None => {}
+            TypeTreatment::Redirected(projection) => {
+                self.namespace = projection.clr_namespace.to_string();
+                self.name = projection.clr_name.to_string();
+            }
+            TypeTreatment::UnmangledClrName => self.name = self.unprojected_name[CLR_PREFIX.len()..].to_string(),
+            TypeTreatment::PrefixedWinRtName => self.name = format!("{}{}", WINRT_PREFIX, self.unprojected_name),
+        }
+        self.treatment = treatment;
+        self
+    }
+}
+
+impl ::std::fmt::Display for ProjectedName {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        if self.namespace.is_empty() {
+            write!(f, "{}", self.name)
+        } else {
+            write!(f, "{}.{}", self.namespace, self.name)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn detect_winmd_kind() {
+        assert_eq!(None, WinMdKind::from_version("v4.0.30319\0\0"));
+        assert_eq!(Some(WinMdKind::Platform), WinMdKind::from_version("WindowsRuntime 1.4\0\0"));
+        assert_eq!(Some(WinMdKind::Managed), WinMdKind::from_version("WindowsRuntime 1.4;CLR v4.0.30319\0\0"));
+    }
+
+    #[test]
+    pub fn project_names() {
+        let projection = find_projection("Windows.Foundation.Collections", "IVector`1").unwrap();
+        let name = ProjectedName::unprojected("Windows.Foundation.Collections".into(), "IVector`1".into()).with_treatment(TypeTreatment::Redirected(projection));
+        assert_eq!("System.Collections.Generic.IList`1", name.to_string());
+        assert_eq!("IVector`1", name.unprojected_name);
+        assert!(find_projection("Windows.Foundation", "IAsyncAction").is_none());
+
+        let name = ProjectedName::unprojected("Ns".into(), "<CLR>Widget".into()).with_treatment(TypeTreatment::UnmangledClrName);
+        assert_eq!("Ns.Widget", name.to_string());
+        let name = ProjectedName::unprojected("Ns".into(), "Widget".into()).with_treatment(TypeTreatment::PrefixedWinRtName);
+        assert_eq!("Ns.<WinRT>Widget", name.to_string());
+    }
+}
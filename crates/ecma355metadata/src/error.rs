@@ -21,6 +21,10 @@ pub enum Error {
     #[error("data directory not found")]
     DirectoryNotFound,
 
+    /// A read went past the end of the image data.
+    #[error("read past the end of the image data")]
+    DataOutOfRange,
+
     /// The requested section was not found.
     #[error("section not found")]
     SectionNotFound,
@@ -49,6 +53,34 @@ pub enum Error {
     #[error("invalid coded index")]
     InvalidCodedIndex,
 
+    /// A certificate in the certificate table could not be decoded.
+    #[error("invalid certificate: {0}")]
+    InvalidCertificate(String),
+
+    /// A single-file bundle could not be read.
+    #[error("invalid bundle: {0}")]
+    InvalidBundle(String),
+
+    /// A NuGet package could not be read.
+    #[error("invalid package: {0}")]
+    InvalidPackage(String),
+
+    /// A module of a multi-module assembly does not match the hash recorded in the assembly's File table.
+    #[error("module {0} does not match its hash in the assembly manifest")]
+    ModuleHashMismatch(String),
+
+    /// A method body could not be encoded.
+    #[error("invalid method body: {0}")]
+    InvalidMethodBody(String),
+
+    /// The image can't be written back with changes, because of how its headers are laid out.
+    #[error("image can't be rewritten: {0}")]
+    CannotRewrite(String),
+
+    /// IL assembly source could not be assembled.
+    #[error("invalid IL at line {line}: {message}")]
+    InvalidIl { line: usize, message: String },
+
     /// The type code is not recognized
     #[error("unknown type code: {0}")]
     UnknownTypeCode(u32),
@@ -1,6 +1,6 @@
 use std::fmt::{Display, Error, Formatter};
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Guid([u8; 16]);
 
 impl Guid {
@@ -15,6 +15,10 @@ impl Guid {
         guid.0.copy_from_slice(bytes);
         guid
     }
+
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
 }
 
 impl Default for Guid {
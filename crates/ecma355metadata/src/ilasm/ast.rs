@@ -0,0 +1,277 @@
+use crate::cli::OpCode;
+
+/// The declarations of an ILAsm source file.
+#[derive(Debug, Default)]
+pub struct Source {
+    pub assembly: Option<AssemblyDecl>,
+    pub assembly_refs: Vec<AssemblyDecl>,
+    pub module: Option<String>,
+    pub module_attributes: Vec<CustomDecl>,
+    pub subsystem: Option<u16>,
+    pub corflags: Option<u32>,
+    pub classes: Vec<ClassDecl>,
+    pub fields: Vec<FieldDecl>,
+    pub methods: Vec<MethodDecl>,
+}
+
+/// An `.assembly` or `.assembly extern` declaration.
+#[derive(Debug, Default)]
+pub struct AssemblyDecl {
+    pub name: String,
+    pub version: [u16; 4],
+    /// The public key of a definition, or the public key or public key token of a reference.
+    pub public_key: Vec<u8>,
+    /// Whether `public_key` is a token, from `.publickeytoken`.
+    pub public_key_token: bool,
+    pub culture: String,
+    pub hash_algorithm: Option<u32>,
+    pub attributes: Vec<CustomDecl>,
+}
+
+/// A name of a type, which is in the module being assembled unless it has an assembly scope.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TypeName {
+    pub scope: Option<String>,
+    /// The full name of the outermost type, followed by the names of each nested type.
+    pub names: Vec<String>,
+}
+
+/// A generic parameter, by number or by name.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GenericRef {
+    Index(u32),
+    Name(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Type {
+    Void,
+    Bool,
+    Char,
+    I1,
+    U1,
+    I2,
+    U2,
+    I4,
+    U4,
+    I8,
+    U8,
+    R4,
+    R8,
+    I,
+    U,
+    String,
+    Object,
+    TypedRef,
+    /// A type name without `class` or `valuetype`, as used in token operands.
+    Named(TypeName),
+    Class(TypeName),
+    /// A type name with `valuetype`.
+    Value(TypeName),
+    GenericParam(GenericRef),
+    MethodGenericParam(GenericRef),
+    GenericInst(Box<Type>, Vec<Type>),
+    SzArray(Box<Type>),
+    Array(Box<Type>, u32),
+    Ptr(Box<Type>),
+    ByRef(Box<Type>),
+    Pinned(Box<Type>),
+}
+
+/// A method signature, as given in a declaration or a reference.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MethodSig {
+    pub instance: bool,
+    pub explicit: bool,
+    pub vararg: bool,
+    pub generic_count: u32,
+    pub ret: Type,
+    pub params: Vec<Type>,
+    /// Where `...` comes in the parameters of a vararg call site: the parameters after it are extra arguments.
+    pub sentinel: Option<usize>,
+}
+
+/// A reference to a method, such as the operand of `call`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MethodRef {
+    pub sig: MethodSig,
+    /// The type declaring the method, or `None` for a global method.
+    pub owner: Option<Type>,
+    pub name: String,
+    pub generic_args: Vec<Type>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldRef {
+    pub field_type: Type,
+    pub owner: Option<Type>,
+    pub name: String,
+}
+
+/// A `.custom` attribute, with its value as raw bytes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CustomDecl {
+    pub constructor: MethodRef,
+    pub value: Vec<u8>,
+    pub line: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct GenericParamDecl {
+    pub name: String,
+    pub flags: u16,
+    pub constraints: Vec<Type>,
+}
+
+/// Whether a class was declared with `value` or `enum`, which decides its base type when it has no `extends`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ClassKind {
+    #[default]
+    Class,
+    Value,
+    Enum,
+}
+
+#[derive(Debug, Default)]
+pub struct ClassDecl {
+    pub flags: u32,
+    pub kind: ClassKind,
+    pub namespace: String,
+    pub name: String,
+    pub generic_params: Vec<GenericParamDecl>,
+    pub extends: Option<Type>,
+    pub implements: Vec<Type>,
+    pub pack: Option<u16>,
+    pub size: Option<u32>,
+    pub fields: Vec<FieldDecl>,
+    pub methods: Vec<MethodDecl>,
+    pub properties: Vec<PropertyDecl>,
+    pub events: Vec<EventDecl>,
+    pub nested: Vec<ClassDecl>,
+    pub attributes: Vec<CustomDecl>,
+    pub line: usize,
+}
+
+/// The element type and value blob of a `Constant` row.
+#[derive(Debug, PartialEq)]
+pub struct ConstantDecl {
+    pub element_type: u8,
+    pub value: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub struct FieldDecl {
+    pub flags: u16,
+    pub field_type: Type,
+    pub name: String,
+    pub offset: Option<u32>,
+    pub constant: Option<ConstantDecl>,
+    pub line: usize,
+}
+
+#[derive(Debug)]
+pub struct ParamDecl {
+    pub flags: u16,
+    pub param_type: Type,
+    pub name: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct LocalDecl {
+    pub local_type: Type,
+    pub name: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct MethodDecl {
+    pub flags: u16,
+    pub impl_flags: u16,
+    pub instance: bool,
+    pub explicit: bool,
+    pub vararg: bool,
+    pub ret: Type,
+    pub name: String,
+    pub generic_params: Vec<GenericParamDecl>,
+    pub params: Vec<ParamDecl>,
+    pub entry_point: bool,
+    pub locals: Vec<LocalDecl>,
+    pub init_locals: bool,
+    pub body: Vec<BodyItem>,
+    /// The methods of base types or interfaces that this method implements, from `.override`. The signature is only
+    /// given by the `.override method` form, and is otherwise the same as the method's own.
+    pub overrides: Vec<(Type, String, Option<MethodSig>)>,
+    pub attributes: Vec<CustomDecl>,
+    pub line: usize,
+}
+
+/// A `.property` declaration, with the methods that get, set or otherwise access it.
+#[derive(Debug)]
+pub struct PropertyDecl {
+    pub flags: u16,
+    pub instance: bool,
+    pub property_type: Type,
+    pub name: String,
+    pub params: Vec<Type>,
+    pub constant: Option<ConstantDecl>,
+    pub accessors: Vec<(u16, MethodRef)>,
+    pub attributes: Vec<CustomDecl>,
+    pub line: usize,
+}
+
+/// An `.event` declaration, with the methods that add, remove or raise handlers.
+#[derive(Debug)]
+pub struct EventDecl {
+    pub flags: u16,
+    pub event_type: Type,
+    pub name: String,
+    pub accessors: Vec<(u16, MethodRef)>,
+    pub attributes: Vec<CustomDecl>,
+    pub line: usize,
+}
+
+/// An argument or local variable, by number or by name.
+#[derive(Debug, PartialEq)]
+pub enum VarRef {
+    Index(u16),
+    Name(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Operand {
+    None,
+    Int(i64),
+    Float(f64),
+    Var(VarRef),
+    Label(String),
+    Switch(Vec<String>),
+    Method(MethodRef),
+    Field(FieldRef),
+    Type(Type),
+    String(String),
+    Sig(MethodSig),
+}
+
+#[derive(Debug)]
+pub enum Handler {
+    Catch(Type),
+    Filter(FilterStart),
+    Finally,
+    Fault,
+}
+
+/// Where a filter starts: a block of its own, or a label.
+#[derive(Debug)]
+pub enum FilterStart {
+    Block(Vec<BodyItem>),
+    Label(String),
+}
+
+#[derive(Debug)]
+pub enum BodyItem {
+    Label(String),
+    Instruction { opcode: OpCode, operand: Operand, line: usize },
+    /// A `.try` block, followed by its handler blocks.
+    TryBlock { body: Vec<BodyItem>, handlers: Vec<(Handler, Vec<BodyItem>)> },
+    /// A `.try` that gives its blocks as label ranges: the try range, and each handler with its range.
+    TryRange { start: String, end: String, handlers: Vec<(Handler, String, String)> },
+}
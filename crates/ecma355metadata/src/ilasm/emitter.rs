@@ -0,0 +1,959 @@
+use std::collections::HashMap;
+
+use crate::cli::opcodes::{self, OperandType};
+use crate::cli::tables::{self, RowHandle, TableHandle, TableIndex};
+use crate::cli::{
+    AssemblyFlags, AssemblyHashAlgorithm, CliFlags, FieldAttributes, GuidHandle, MethodAttributes, MethodImplAttributes,
+    ParamAttributes, TypeAttributes,
+};
+use crate::error::Error;
+use crate::ilasm::ast::*;
+use crate::pe::Subsystem;
+use crate::writer::{write_compressed_u32, ExceptionHandler, InstructionEncoder, Label, MetadataBuilder, PeBuilder};
+use crate::Guid;
+
+/// Assemblies that a class without `extends` takes its base type from, in the order they are looked for.
+const CORE_LIBRARIES: &[&str] = &["System.Private.CoreLib", "System.Runtime", "netstandard", "mscorlib"];
+
+/// The signatures of the methods of a type with the same name, with their rows.
+type Overloads = Vec<(Vec<u8>, usize)>;
+
+/// A type that holds fields and methods: `<Module>` or one of the classes of the source.
+struct TypeEntry<'a> {
+    class: Option<&'a ClassDecl>,
+    fields: &'a [FieldDecl],
+    methods: &'a [MethodDecl],
+    enclosing: Option<usize>,
+    first_field: usize,
+    first_method: usize,
+}
+
+/// The state of the method body being emitted.
+struct Body {
+    encoder: InstructionEncoder,
+    /// The labels by name, with whether they have been marked and the line they were first used on.
+    labels: HashMap<String, (Label, bool, usize)>,
+    arguments: HashMap<String, u16>,
+    locals: HashMap<String, u16>,
+}
+
+impl Body {
+    fn label(&mut self, name: &str, line: usize) -> Label {
+        let encoder = &mut self.encoder;
+        self.labels.entry(name.to_string()).or_insert_with(|| (encoder.define_label(), false, line)).0
+    }
+
+    fn new_label(&mut self) -> Label {
+        let label = self.encoder.define_label();
+        self.encoder.mark_label(label);
+        label
+    }
+}
+
+/// Builds the metadata and method bodies of a parsed source, and writes them as a PE image.
+///
+/// Types are numbered before anything is written, with `<Module>` first and nested classes right after the class
+/// enclosing them, so that every field and method has a known row and references to members of the module resolve to
+/// their definitions.
+pub struct Emitter<'a> {
+    source: &'a Source,
+    metadata: MetadataBuilder,
+    types: Vec<TypeEntry<'a>>,
+    type_defs: HashMap<Vec<String>, usize>,
+    value_types: Vec<bool>,
+    methods: HashMap<(usize, String), Overloads>,
+    fields: HashMap<(usize, String), usize>,
+    assembly_refs: HashMap<String, TableHandle>,
+    type_refs: HashMap<TypeName, TableHandle>,
+    type_specs: HashMap<Vec<u8>, TableHandle>,
+    member_refs: HashMap<(TableHandle, String, Vec<u8>), TableHandle>,
+    method_specs: HashMap<(TableHandle, Vec<u8>), TableHandle>,
+    class_generics: Vec<String>,
+    method_generics: Vec<String>,
+    line: usize,
+}
+
+impl<'a> Emitter<'a> {
+    pub fn new(source: &'a Source) -> Emitter<'a> {
+        Emitter {
+            source,
+            metadata: MetadataBuilder::new(),
+            types: Vec::new(),
+            type_defs: HashMap::new(),
+            value_types: Vec::new(),
+            methods: HashMap::new(),
+            fields: HashMap::new(),
+            assembly_refs: HashMap::new(),
+            type_refs: HashMap::new(),
+            type_specs: HashMap::new(),
+            member_refs: HashMap::new(),
+            method_specs: HashMap::new(),
+            class_generics: Vec::new(),
+            method_generics: Vec::new(),
+            line: 1,
+        }
+    }
+
+    fn error(&self, message: String) -> Error {
+        Error::InvalidIl { line: self.line, message }
+    }
+
+    /// Writes the image, with the given MVID.
+    pub fn emit(mut self, mvid: Guid) -> Result<Vec<u8>, Error> {
+        let source = self.source;
+        let entry_point = all_methods(source).any(|m| m.entry_point);
+        let module_name = match (&source.module, &source.assembly) {
+            (Some(module), _) => module.clone(),
+            (None, Some(assembly)) => format!("{}.{}", assembly.name, if entry_point { "exe" } else { "dll" }),
+            (None, None) => return Err(self.error("the source declares neither .assembly nor .module".to_string())),
+        };
+        let module = tables::Module {
+            generation: 0,
+            name: self.metadata.add_string(&module_name),
+            mvid: self.metadata.add_guid(mvid),
+            enc_id: GuidHandle(0),
+            enc_base_id: GuidHandle(0),
+        };
+        self.metadata.add_row(module);
+        self.assemblies()?;
+
+        self.types.push(TypeEntry {
+            class: None,
+            fields: &source.fields,
+            methods: &source.methods,
+            enclosing: None,
+            first_field: 1,
+            first_method: 1,
+        });
+        self.value_types.push(false);
+        self.flatten(&source.classes, None, &[])?;
+        self.number_members()?;
+
+        let mut bodies = Vec::new();
+        let mut entry = None;
+        self.type_defs()?;
+        for index in 0..self.types.len() {
+            self.members(index, &mut bodies, &mut entry)?;
+        }
+        for index in 1..self.types.len() {
+            self.class_rows(index)?;
+        }
+        self.class_generics.clear();
+        self.method_generics.clear();
+        if let Some(assembly) = &source.assembly {
+            self.custom_attributes(TableHandle::new(1, TableIndex::Assembly), &assembly.attributes)?;
+        }
+        self.custom_attributes(TableHandle::new(1, TableIndex::Module), &source.module_attributes)?;
+
+        let mut builder = PeBuilder::new(self.metadata);
+        builder.set_dll(entry.is_none());
+        if let Some(entry) = entry {
+            builder.set_entry_point(entry);
+        }
+        if let Some(subsystem) = source.subsystem {
+            builder.set_subsystem(Subsystem::new(subsystem));
+        }
+        if let Some(flags) = source.corflags {
+            builder.set_cli_flags(CliFlags::from_bits_truncate(flags));
+        }
+        for (method, body) in bodies {
+            builder.add_method_body(method, &body);
+        }
+        builder.build()
+    }
+
+    fn assemblies(&mut self) -> Result<(), Error> {
+        if let Some(assembly) = &self.source.assembly {
+            let hash_alg_id = match assembly.hash_algorithm {
+                Some(id) => AssemblyHashAlgorithm::try_from(id)?,
+                None => AssemblyHashAlgorithm::SHA1,
+            };
+            let flags = if assembly.public_key.is_empty() { AssemblyFlags::empty() } else { AssemblyFlags::PublicKey };
+            let row = tables::Assembly {
+                hash_alg_id,
+                major_version: assembly.version[0],
+                minor_version: assembly.version[1],
+                build_number: assembly.version[2],
+                revision_number: assembly.version[3],
+                flags,
+                public_key: self.metadata.add_blob(&assembly.public_key),
+                name: self.metadata.add_string(&assembly.name),
+                culture: self.metadata.add_string(&assembly.culture),
+            };
+            self.metadata.add_row(row);
+        }
+        for assembly in &self.source.assembly_refs {
+            let full_key = !assembly.public_key.is_empty() && !assembly.public_key_token;
+            let row = tables::AssemblyRef {
+                major_version: assembly.version[0],
+                minor_version: assembly.version[1],
+                build_number: assembly.version[2],
+                revision_number: assembly.version[3],
+                flags: if full_key { AssemblyFlags::PublicKey } else { AssemblyFlags::empty() },
+                public_key_or_token: self.metadata.add_blob(&assembly.public_key),
+                name: self.metadata.add_string(&assembly.name),
+                culture: self.metadata.add_string(&assembly.culture),
+                hash_value: self.metadata.add_blob(&[]),
+            };
+            let handle = self.metadata.add_row(row).handle();
+            self.assembly_refs.insert(assembly.name.clone(), handle);
+        }
+        Ok(())
+    }
+
+    /// Numbers the classes, each followed by the classes nested in it.
+    fn flatten(&mut self, classes: &'a [ClassDecl], enclosing: Option<usize>, path: &[String]) -> Result<(), Error> {
+        for class in classes {
+            self.line = class.line;
+            let mut names = path.to_vec();
+            names.push(if class.namespace.is_empty() { class.name.clone() } else { format!("{}.{}", class.namespace, class.name) });
+            let row = self.types.len() + 1;
+            if self.type_defs.insert(names.clone(), row).is_some() {
+                return Err(self.error(format!("class {} is declared twice", names.join("/"))));
+            }
+            let value_type = match &class.extends {
+                Some(Type::Named(base)) | Some(Type::Class(base)) => {
+                    matches!(base.names[..], [ref n] if n == "System.ValueType" || n == "System.Enum")
+                }
+                Some(_) => false,
+                None => class.kind != ClassKind::Class,
+            };
+            self.value_types.push(value_type);
+            self.types.push(TypeEntry {
+                class: Some(class),
+                fields: &class.fields,
+                methods: &class.methods,
+                enclosing,
+                first_field: 0,
+                first_method: 0,
+            });
+            self.flatten(&class.nested, Some(row), &names)?;
+        }
+        Ok(())
+    }
+
+    /// Gives every field and method its row, and records the signatures of methods so calls can find them.
+    fn number_members(&mut self) -> Result<(), Error> {
+        let (mut field_row, mut method_row) = (1, 1);
+        for index in 0..self.types.len() {
+            let entry = &self.types[index];
+            let (class, fields, methods) = (entry.class, entry.fields, entry.methods);
+            self.types[index].first_field = field_row;
+            self.types[index].first_method = method_row;
+            self.set_class_generics(class);
+            for field in fields {
+                self.fields.insert((index + 1, field.name.clone()), field_row);
+                field_row += 1;
+            }
+            for method in methods {
+                self.line = method.line;
+                self.set_method_generics(method);
+                let signature = self.method_signature(&declared_signature(method))?;
+                self.methods.entry((index + 1, method.name.clone())).or_default().push((signature, method_row));
+                method_row += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn set_class_generics(&mut self, class: Option<&ClassDecl>) {
+        self.class_generics = class.map_or_else(Vec::new, |c| c.generic_params.iter().map(|p| p.name.clone()).collect());
+    }
+
+    fn set_method_generics(&mut self, method: &MethodDecl) {
+        self.method_generics = method.generic_params.iter().map(|p| p.name.clone()).collect();
+    }
+
+    fn type_defs(&mut self) -> Result<(), Error> {
+        let core_library = self
+            .source
+            .assembly_refs
+            .iter()
+            .filter_map(|a| CORE_LIBRARIES.iter().position(|n| *n == a.name).map(|i| (i, a.name.clone())))
+            .min()
+            .map(|(_, name)| name);
+        for index in 0..self.types.len() {
+            let entry = &self.types[index];
+            let (class, first_field, first_method) = (entry.class, entry.first_field, entry.first_method);
+            self.set_class_generics(class);
+            let (flags, name, namespace, extends) = match class {
+                None => (0, "<Module>", "", TableHandle::new(0, TableIndex::TypeRef)),
+                Some(class) => {
+                    self.line = class.line;
+                    let extends = match &class.extends {
+                        Some(base) => self.type_token(base)?,
+                        // Interfaces have no base type, and neither does System.Object when assembling a core library
+                        None if class.flags & 0x20 != 0 || (class.namespace == "System" && class.name == "Object") => {
+                            TableHandle::new(0, TableIndex::TypeRef)
+                        }
+                        None => {
+                            let scope = core_library.clone().ok_or_else(|| {
+                                self.error(format!("class {} has no base type, and no core library is referenced", class.name))
+                            })?;
+                            let base = match class.kind {
+                                ClassKind::Class => "System.Object",
+                                ClassKind::Value => "System.ValueType",
+                                ClassKind::Enum => "System.Enum",
+                            };
+                            self.type_token(&Type::Class(TypeName { scope: Some(scope), names: vec![base.to_string()] }))?
+                        }
+                    };
+                    (class.flags, class.name.as_str(), class.namespace.as_str(), extends)
+                }
+            };
+            let row = tables::TypeDef {
+                flags: TypeAttributes::new(flags),
+                type_name: self.metadata.add_string(name),
+                type_namespace: self.metadata.add_string(namespace),
+                extends,
+                field_list: TableHandle::new(first_field, TableIndex::Field),
+                method_list: TableHandle::new(first_method, TableIndex::MethodDef),
+            };
+            self.metadata.add_row(row);
+        }
+        Ok(())
+    }
+
+    fn members(
+        &mut self,
+        index: usize,
+        bodies: &mut Vec<(RowHandle<tables::MethodDef>, Vec<u8>)>,
+        entry_point: &mut Option<TableHandle>,
+    ) -> Result<(), Error> {
+        let entry = &self.types[index];
+        let (class, fields, methods) = (entry.class, entry.fields, entry.methods);
+        let owner = TableHandle::new(index + 1, TableIndex::TypeDef);
+        self.set_class_generics(class);
+        self.method_generics.clear();
+        for field in fields {
+            self.line = field.line;
+            let mut signature = vec![0x06];
+            self.encode_type(&field.field_type, &mut signature)?;
+            let row = tables::Field {
+                flags: FieldAttributes::new(field.flags),
+                name: self.metadata.add_string(&field.name),
+                signature: self.metadata.add_blob(&signature),
+            };
+            let handle = self.metadata.add_row(row).handle();
+            if let Some(offset) = field.offset {
+                self.metadata.add_row(tables::FieldLayout { offset, field: handle });
+            }
+            if let Some(constant) = &field.constant {
+                let row = tables::Constant {
+                    typ: constant.element_type,
+                    reserved: 0,
+                    parent: handle,
+                    value: self.metadata.add_blob(&constant.value),
+                };
+                self.metadata.add_row(row);
+            }
+        }
+        for method in methods {
+            self.line = method.line;
+            self.set_method_generics(method);
+            if class.is_none() && method.flags & 0x10 == 0 {
+                return Err(self.error(format!("global method {} must be static", method.name)));
+            }
+            let declared = declared_signature(method);
+            let signature = self.method_signature(&declared)?;
+            let row = tables::MethodDef {
+                rva: 0,
+                impl_flags: MethodImplAttributes::new(method.impl_flags),
+                flags: MethodAttributes::new(method.flags),
+                name: self.metadata.add_string(&method.name),
+                signature: self.metadata.add_blob(&signature),
+                params: self.metadata.next_row::<tables::Param>().handle(),
+            };
+            let row = self.metadata.add_row(row);
+            for (sequence, param) in method.params.iter().enumerate() {
+                let row = tables::Param {
+                    flags: ParamAttributes::from_bits_truncate(param.flags),
+                    sequence: sequence as u16 + 1,
+                    name: self.metadata.add_string(param.name.as_deref().unwrap_or("")),
+                };
+                self.metadata.add_row(row);
+            }
+            self.generic_params(row.handle(), &method.generic_params)?;
+            self.custom_attributes(row.handle(), &method.attributes)?;
+            for (base, name, sig) in &method.overrides {
+                let sig = sig.clone().unwrap_or_else(|| declared.clone());
+                let declaration = MethodRef { sig, owner: Some(base.clone()), name: name.clone(), generic_args: Vec::new() };
+                let declaration = self.method_token(&declaration)?;
+                self.metadata.add_row(tables::MethodImpl { class: owner, method_body: row.handle(), method_declaration: declaration });
+            }
+
+            // Abstract, runtime, internal call and P/Invoke methods have no body.
+            let has_body = method.flags & 0x2400 == 0 && method.impl_flags & 0x1003 == 0;
+            if has_body {
+                let body = self.method_body(method, declared.instance)?;
+                bodies.push((row, body));
+            } else if !method.body.is_empty() || !method.locals.is_empty() {
+                return Err(self.error(format!("method {} can't have a body", method.name)));
+            }
+            if method.entry_point {
+                if entry_point.is_some() {
+                    return Err(self.error("more than one method is the entry point".to_string()));
+                }
+                *entry_point = Some(row.handle());
+            }
+        }
+        Ok(())
+    }
+
+    fn class_rows(&mut self, index: usize) -> Result<(), Error> {
+        let entry = &self.types[index];
+        let (class, enclosing) = (entry.class.expect("classes follow <Module>"), entry.enclosing);
+        let handle = TableHandle::new(index + 1, TableIndex::TypeDef);
+        self.line = class.line;
+        self.set_class_generics(Some(class));
+        self.method_generics.clear();
+        if let Some(enclosing) = enclosing {
+            let enclosing_class = TableHandle::new(enclosing, TableIndex::TypeDef);
+            self.metadata.add_row(tables::NestedClass { nested_class: handle, enclosing_class });
+        }
+        for interface in &class.implements {
+            let interface = self.type_token(interface)?;
+            self.metadata.add_row(tables::InterfaceImpl { class: handle, interface });
+        }
+        if class.pack.is_some() || class.size.is_some() {
+            let row = tables::ClassLayout {
+                packing_size: class.pack.unwrap_or(0),
+                class_size: class.size.unwrap_or(0),
+                parent: handle,
+            };
+            self.metadata.add_row(row);
+        }
+        self.generic_params(handle, &class.generic_params)?;
+        self.custom_attributes(handle, &class.attributes)?;
+
+        if !class.properties.is_empty() {
+            let property_list = self.metadata.next_row::<tables::Property>().handle();
+            self.metadata.add_row(tables::PropertyMap { parent: handle, property_list });
+            for property in &class.properties {
+                self.line = property.line;
+                let mut signature = vec![if property.instance { 0x28 } else { 0x08 }];
+                write_compressed_u32(&mut signature, property.params.len() as u32);
+                self.encode_type(&property.property_type, &mut signature)?;
+                for param in &property.params {
+                    self.encode_type(param, &mut signature)?;
+                }
+                let row = tables::Property {
+                    flags: property.flags,
+                    name: self.metadata.add_string(&property.name),
+                    typ: self.metadata.add_blob(&signature),
+                };
+                let association = self.metadata.add_row(row).handle();
+                if let Some(constant) = &property.constant {
+                    let row = tables::Constant {
+                        typ: constant.element_type,
+                        reserved: 0,
+                        parent: association,
+                        value: self.metadata.add_blob(&constant.value),
+                    };
+                    self.metadata.add_row(row);
+                }
+                self.semantics(association, &property.accessors)?;
+                self.custom_attributes(association, &property.attributes)?;
+            }
+        }
+        if !class.events.is_empty() {
+            let event_list = self.metadata.next_row::<tables::Event>().handle();
+            self.metadata.add_row(tables::EventMap { parent: handle, event_list });
+            for event in &class.events {
+                self.line = event.line;
+                let row = tables::Event {
+                    flags: event.flags,
+                    name: self.metadata.add_string(&event.name),
+                    event_type: self.type_token(&event.event_type)?,
+                };
+                let association = self.metadata.add_row(row).handle();
+                self.semantics(association, &event.accessors)?;
+                self.custom_attributes(association, &event.attributes)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn semantics(&mut self, association: TableHandle, accessors: &[(u16, MethodRef)]) -> Result<(), Error> {
+        for (semantics, accessor) in accessors {
+            let method = self.method_token(accessor)?;
+            if method.table() != TableIndex::MethodDef {
+                return Err(self.error(format!("accessor {} is not a method of this module", accessor.name)));
+            }
+            self.metadata.add_row(tables::MethodSemantics { semantics: *semantics, method, association });
+        }
+        Ok(())
+    }
+
+    fn generic_params(&mut self, owner: TableHandle, params: &[GenericParamDecl]) -> Result<(), Error> {
+        for (number, param) in params.iter().enumerate() {
+            let row = tables::GenericParam {
+                number: number as u16,
+                flags: param.flags,
+                owner,
+                name: self.metadata.add_string(&param.name),
+            };
+            let param_handle = self.metadata.add_row(row).handle();
+            for constraint in &param.constraints {
+                let constraint = self.type_token(constraint)?;
+                self.metadata.add_row(tables::GenericParamConstraint { owner: param_handle, constraint });
+            }
+        }
+        Ok(())
+    }
+
+    fn custom_attributes(&mut self, parent: TableHandle, attributes: &[CustomDecl]) -> Result<(), Error> {
+        for attribute in attributes {
+            self.line = attribute.line;
+            let constructor = self.method_token(&attribute.constructor)?;
+            if constructor.table() == TableIndex::MethodSpec {
+                return Err(self.error("an attribute constructor can't be generic".to_string()));
+            }
+            let row = tables::CustomAttribute { parent, typ: constructor, value: self.metadata.add_blob(&attribute.value) };
+            self.metadata.add_row(row);
+        }
+        Ok(())
+    }
+
+    fn method_body(&mut self, method: &MethodDecl, instance: bool) -> Result<Vec<u8>, Error> {
+        let mut body = Body {
+            encoder: InstructionEncoder::new(),
+            labels: HashMap::new(),
+            arguments: HashMap::new(),
+            locals: HashMap::new(),
+        };
+        body.encoder.set_init_locals(method.init_locals);
+        for (index, param) in method.params.iter().enumerate() {
+            if let Some(name) = &param.name {
+                body.arguments.insert(name.clone(), index as u16 + instance as u16);
+            }
+        }
+        if !method.locals.is_empty() {
+            let mut signature = vec![0x07];
+            write_compressed_u32(&mut signature, method.locals.len() as u32);
+            for (index, local) in method.locals.iter().enumerate() {
+                self.encode_type(&local.local_type, &mut signature)?;
+                if let Some(name) = &local.name {
+                    body.locals.insert(name.clone(), index as u16);
+                }
+            }
+            let signature = self.metadata.add_blob(&signature);
+            let signature = self.metadata.add_row(tables::StandAloneSig { signature });
+            body.encoder.set_local_signature(signature.handle());
+        }
+
+        self.items(&mut body, &method.body)?;
+        if let Some((name, (_, _, line))) = body.labels.iter().filter(|(_, (_, marked, _))| !marked).min_by_key(|(_, l)| l.2) {
+            self.line = *line;
+            return Err(self.error(format!("label {} is not defined in method {}", name, method.name)));
+        }
+        self.line = method.line;
+        body.encoder.encode().map_err(|e| self.error(format!("{} in method {}", e, method.name)))
+    }
+
+    fn items(&mut self, body: &mut Body, items: &[BodyItem]) -> Result<(), Error> {
+        for item in items {
+            match item {
+                BodyItem::Label(name) => {
+                    let label = body.label(name, self.line);
+                    let marked = &mut body.labels.get_mut(name).expect("label was just defined").1;
+                    if *marked {
+                        return Err(self.error(format!("label {} is defined twice", name)));
+                    }
+                    *marked = true;
+                    body.encoder.mark_label(label);
+                }
+                BodyItem::Instruction { opcode, operand, line } => {
+                    self.line = *line;
+                    self.instruction(body, *opcode, operand)?;
+                }
+                BodyItem::TryBlock { body: try_items, handlers } => {
+                    let try_start = body.new_label();
+                    self.items(body, try_items)?;
+                    let try_end = body.new_label();
+                    for (handler, handler_items) in handlers {
+                        let handler = match handler {
+                            Handler::Filter(FilterStart::Block(filter_items)) => {
+                                let filter_start = body.new_label();
+                                self.items(body, filter_items)?;
+                                ExceptionHandler::Filter(filter_start)
+                            }
+                            handler => self.handler(body, handler)?,
+                        };
+                        let handler_start = body.new_label();
+                        self.items(body, handler_items)?;
+                        let handler_end = body.new_label();
+                        body.encoder.add_exception_region(handler, try_start, try_end, handler_start, handler_end);
+                    }
+                }
+                BodyItem::TryRange { start, end, handlers } => {
+                    let (try_start, try_end) = (body.label(start, self.line), body.label(end, self.line));
+                    for (handler, handler_start, handler_end) in handlers {
+                        let handler = self.handler(body, handler)?;
+                        let (handler_start, handler_end) = (body.label(handler_start, self.line), body.label(handler_end, self.line));
+                        body.encoder.add_exception_region(handler, try_start, try_end, handler_start, handler_end);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn handler(&mut self, body: &mut Body, handler: &Handler) -> Result<ExceptionHandler, Error> {
+        Ok(match handler {
+            Handler::Catch(typ) => ExceptionHandler::Catch(self.type_token(typ)?),
+            Handler::Filter(FilterStart::Label(name)) => ExceptionHandler::Filter(body.label(name, self.line)),
+            Handler::Filter(FilterStart::Block(_)) => return Err(self.error("a filter block needs a .try block".to_string())),
+            Handler::Finally => ExceptionHandler::Finally,
+            Handler::Fault => ExceptionHandler::Fault,
+        })
+    }
+
+    fn instruction(&mut self, body: &mut Body, opcode: opcodes::OpCode, operand: &Operand) -> Result<(), Error> {
+        let out_of_range = |emitter: &Self| emitter.error(format!("operand of {} is out of range", opcode));
+        match (opcode.operand, operand) {
+            (OperandType::InlineNone, Operand::None) => body.encoder.op_code(opcode),
+            (OperandType::ShortInlineI, Operand::Int(value)) => {
+                if !(-128..=255).contains(value) {
+                    return Err(out_of_range(self));
+                }
+                body.encoder.raw(opcode, &[*value as u8]);
+            }
+            (OperandType::InlineI, Operand::Int(value)) => {
+                if !(i32::MIN as i64..=u32::MAX as i64).contains(value) {
+                    return Err(out_of_range(self));
+                }
+                body.encoder.raw(opcode, &(*value as i32).to_le_bytes());
+            }
+            (OperandType::InlineI8, Operand::Int(value)) => body.encoder.raw(opcode, &value.to_le_bytes()),
+            (OperandType::ShortInlineR, Operand::Float(value)) => body.encoder.raw(opcode, &(*value as f32).to_le_bytes()),
+            (OperandType::InlineR, Operand::Float(value)) => body.encoder.raw(opcode, &value.to_le_bytes()),
+            (OperandType::ShortInlineVar, Operand::Var(var)) | (OperandType::InlineVar, Operand::Var(var)) => {
+                let names = if opcode.name.contains("arg") { &body.arguments } else { &body.locals };
+                let index = match var {
+                    VarRef::Index(index) => *index,
+                    VarRef::Name(name) => *names.get(name).ok_or_else(|| self.error(format!("unknown variable {}", name)))?,
+                };
+                if opcode.operand == OperandType::ShortInlineVar {
+                    let index = u8::try_from(index).map_err(|_| out_of_range(self))?;
+                    body.encoder.raw(opcode, &[index]);
+                } else {
+                    body.encoder.raw(opcode, &index.to_le_bytes());
+                }
+            }
+            (OperandType::ShortInlineBrTarget, Operand::Label(name)) | (OperandType::InlineBrTarget, Operand::Label(name)) => {
+                let label = body.label(name, self.line);
+                body.encoder.branch(opcode, label);
+            }
+            (OperandType::InlineSwitch, Operand::Switch(names)) => {
+                let labels: Vec<Label> = names.iter().map(|name| body.label(name, self.line)).collect();
+                body.encoder.switch(&labels);
+            }
+            (OperandType::InlineMethod, Operand::Method(method)) => {
+                let token = self.method_token(method)?;
+                if matches!(opcode, opcodes::CALL | opcodes::CALLVIRT | opcodes::NEWOBJ) {
+                    let this = method.sig.instance && opcode != opcodes::NEWOBJ;
+                    let arguments = method.sig.params.len() as u16 + this as u16;
+                    body.encoder.call(opcode, token, arguments, method.sig.ret != Type::Void);
+                } else {
+                    body.encoder.token(opcode, token);
+                }
+            }
+            (OperandType::InlineSig, Operand::Sig(sig)) => {
+                let signature = self.method_signature(sig)?;
+                let signature = self.metadata.add_blob(&signature);
+                let signature = self.metadata.add_row(tables::StandAloneSig { signature });
+                let arguments = sig.params.len() as u16 + sig.instance as u16;
+                body.encoder.call(opcode, signature.handle(), arguments, sig.ret != Type::Void);
+            }
+            (OperandType::InlineField, Operand::Field(field)) | (OperandType::InlineTok, Operand::Field(field)) => {
+                let token = self.field_token(field)?;
+                body.encoder.token(opcode, token);
+            }
+            (OperandType::InlineTok, Operand::Method(method)) => {
+                let token = self.method_token(method)?;
+                body.encoder.token(opcode, token);
+            }
+            (OperandType::InlineType, Operand::Type(typ)) | (OperandType::InlineTok, Operand::Type(typ)) => {
+                let token = self.type_token(typ)?;
+                body.encoder.token(opcode, token);
+            }
+            (OperandType::InlineString, Operand::String(value)) => {
+                let value = self.metadata.add_user_string(value);
+                body.encoder.load_string(value);
+            }
+            _ => return Err(self.error(format!("invalid operand for {}", opcode))),
+        }
+        Ok(())
+    }
+
+    /// Gets the TypeDef or TypeRef of a class name.
+    fn type_handle(&mut self, name: &TypeName) -> Result<TableHandle, Error> {
+        let own_assembly = self.source.assembly.as_ref().map(|a| a.name.as_str());
+        match name.scope.as_deref() {
+            None => {}
+            Some(scope) if Some(scope) == own_assembly => {}
+            Some(scope) => return self.type_ref(scope, &name.names),
+        }
+        match self.type_defs.get(&name.names) {
+            Some(row) => Ok(TableHandle::new(*row, TableIndex::TypeDef)),
+            None => Err(self.error(format!("unknown type {}", name.names.join("/")))),
+        }
+    }
+
+    fn type_ref(&mut self, scope: &str, names: &[String]) -> Result<TableHandle, Error> {
+        let key = TypeName { scope: Some(scope.to_string()), names: names.to_vec() };
+        if let Some(handle) = self.type_refs.get(&key) {
+            return Ok(*handle);
+        }
+        let (resolution_scope, namespace, name) = match names {
+            [full_name] => {
+                let assembly = self.assembly_refs.get(scope).copied();
+                let assembly = assembly.ok_or_else(|| self.error(format!("unknown assembly {}", scope)))?;
+                match full_name.rfind('.') {
+                    Some(dot) => (assembly, &full_name[..dot], &full_name[dot + 1..]),
+                    None => (assembly, "", full_name.as_str()),
+                }
+            }
+            [outer @ .., nested] => (self.type_ref(scope, outer)?, "", nested.as_str()),
+            [] => unreachable!("type names have at least one part"),
+        };
+        let row = tables::TypeRef {
+            resolution_scope,
+            name: self.metadata.add_string(name),
+            namespace: self.metadata.add_string(namespace),
+        };
+        let handle = self.metadata.add_row(row).handle();
+        self.type_refs.insert(key, handle);
+        Ok(handle)
+    }
+
+    /// Gets a TypeDef or TypeRef for a class name, and a TypeSpec for any other type.
+    fn type_token(&mut self, typ: &Type) -> Result<TableHandle, Error> {
+        match typ {
+            Type::Named(name) | Type::Class(name) | Type::Value(name) => self.type_handle(name),
+            _ => {
+                let mut signature = Vec::new();
+                self.encode_type(typ, &mut signature)?;
+                if let Some(handle) = self.type_specs.get(&signature) {
+                    return Ok(*handle);
+                }
+                let blob = self.metadata.add_blob(&signature);
+                let handle = self.metadata.add_row(tables::TypeSpec { signature: blob }).handle();
+                self.type_specs.insert(signature, handle);
+                Ok(handle)
+            }
+        }
+    }
+
+    /// Gets the MethodDef of a method of the module, or a MemberRef, wrapped in a MethodSpec if it has type arguments.
+    fn method_token(&mut self, method: &MethodRef) -> Result<TableHandle, Error> {
+        let signature = self.method_signature(&method.sig)?;
+        let parent = match &method.owner {
+            Some(owner) => self.type_token(owner)?,
+            None => TableHandle::new(1, TableIndex::TypeDef),
+        };
+        let target = if parent.table() == TableIndex::TypeDef {
+            // A vararg method is found by its fixed parameters, and a call passing extra arguments gets a MemberRef
+            let fixed = method.sig.sentinel.unwrap_or(method.sig.params.len());
+            let declared = MethodSig { params: method.sig.params[..fixed].to_vec(), sentinel: None, ..method.sig.clone() };
+            let declared = self.method_signature(&declared)?;
+            let overloads = self.methods.get(&(parent.index(), method.name.clone()));
+            let definition = match overloads.and_then(|o| o.iter().find(|(s, _)| *s == declared)) {
+                Some((_, row)) => TableHandle::new(*row, TableIndex::MethodDef),
+                None => return Err(self.error(format!("no method {} with this signature is declared", method.name))),
+            };
+            if fixed < method.sig.params.len() {
+                self.member_ref(definition, &method.name, signature)
+            } else {
+                definition
+            }
+        } else {
+            self.member_ref(parent, &method.name, signature)
+        };
+        if method.generic_args.is_empty() {
+            return Ok(target);
+        }
+        let mut instantiation = vec![0x0A];
+        write_compressed_u32(&mut instantiation, method.generic_args.len() as u32);
+        for arg in &method.generic_args {
+            self.encode_type(arg, &mut instantiation)?;
+        }
+        let key = (target, instantiation);
+        if let Some(handle) = self.method_specs.get(&key) {
+            return Ok(*handle);
+        }
+        let row = tables::MethodSpec { method: target, instantiation: self.metadata.add_blob(&key.1) };
+        let handle = self.metadata.add_row(row).handle();
+        self.method_specs.insert(key, handle);
+        Ok(handle)
+    }
+
+    fn field_token(&mut self, field: &FieldRef) -> Result<TableHandle, Error> {
+        let parent = match &field.owner {
+            Some(owner) => self.type_token(owner)?,
+            None => TableHandle::new(1, TableIndex::TypeDef),
+        };
+        if parent.table() == TableIndex::TypeDef {
+            return match self.fields.get(&(parent.index(), field.name.clone())) {
+                Some(row) => Ok(TableHandle::new(*row, TableIndex::Field)),
+                None => Err(self.error(format!("no field {} is declared", field.name))),
+            };
+        }
+        let mut signature = vec![0x06];
+        self.encode_type(&field.field_type, &mut signature)?;
+        Ok(self.member_ref(parent, &field.name, signature))
+    }
+
+    fn member_ref(&mut self, class: TableHandle, name: &str, signature: Vec<u8>) -> TableHandle {
+        let key = (class, name.to_string(), signature);
+        if let Some(handle) = self.member_refs.get(&key) {
+            return *handle;
+        }
+        let row = tables::MemberRef { class, name: self.metadata.add_string(name), signature: self.metadata.add_blob(&key.2) };
+        let handle = self.metadata.add_row(row).handle();
+        self.member_refs.insert(key, handle);
+        handle
+    }
+
+    fn method_signature(&mut self, sig: &MethodSig) -> Result<Vec<u8>, Error> {
+        let mut convention = if sig.vararg { 0x05 } else { 0x00 };
+        if sig.generic_count > 0 {
+            convention |= 0x10;
+        }
+        if sig.instance {
+            convention |= 0x20;
+        }
+        if sig.explicit {
+            convention |= 0x40;
+        }
+        let mut signature = vec![convention];
+        if sig.generic_count > 0 {
+            write_compressed_u32(&mut signature, sig.generic_count);
+        }
+        write_compressed_u32(&mut signature, sig.params.len() as u32);
+        self.encode_type(&sig.ret, &mut signature)?;
+        for (index, param) in sig.params.iter().enumerate() {
+            if sig.sentinel == Some(index) {
+                signature.push(0x41);
+            }
+            self.encode_type(param, &mut signature)?;
+        }
+        Ok(signature)
+    }
+
+    fn encode_type(&mut self, typ: &Type, buf: &mut Vec<u8>) -> Result<(), Error> {
+        let element = match typ {
+            Type::Void => 0x01,
+            Type::Bool => 0x02,
+            Type::Char => 0x03,
+            Type::I1 => 0x04,
+            Type::U1 => 0x05,
+            Type::I2 => 0x06,
+            Type::U2 => 0x07,
+            Type::I4 => 0x08,
+            Type::U4 => 0x09,
+            Type::I8 => 0x0A,
+            Type::U8 => 0x0B,
+            Type::R4 => 0x0C,
+            Type::R8 => 0x0D,
+            Type::String => 0x0E,
+            Type::TypedRef => 0x16,
+            Type::I => 0x18,
+            Type::U => 0x19,
+            Type::Object => 0x1C,
+            Type::Named(_) | Type::Class(_) | Type::Value(_) => {
+                let handle = self.type_token(typ)?;
+                buf.push(self.class_element(typ, handle));
+                write_compressed_u32(buf, type_def_or_ref(handle));
+                return Ok(());
+            }
+            Type::GenericParam(param) | Type::MethodGenericParam(param) => {
+                let (element, names) = match typ {
+                    Type::GenericParam(_) => (0x13, &self.class_generics),
+                    _ => (0x1E, &self.method_generics),
+                };
+                let index = match param {
+                    GenericRef::Index(index) => *index,
+                    GenericRef::Name(name) => names
+                        .iter()
+                        .position(|n| n == name)
+                        .ok_or_else(|| self.error(format!("unknown generic parameter {}", name)))? as u32,
+                };
+                buf.push(element);
+                write_compressed_u32(buf, index);
+                return Ok(());
+            }
+            Type::GenericInst(base, args) => {
+                let handle = self.type_token(base)?;
+                buf.push(0x15);
+                buf.push(self.class_element(base, handle));
+                write_compressed_u32(buf, type_def_or_ref(handle));
+                write_compressed_u32(buf, args.len() as u32);
+                for arg in args {
+                    self.encode_type(arg, buf)?;
+                }
+                return Ok(());
+            }
+            Type::Array(element, rank) => {
+                buf.push(0x14);
+                self.encode_type(element, buf)?;
+                write_compressed_u32(buf, *rank);
+                // No sizes and no lower bounds.
+                buf.extend_from_slice(&[0, 0]);
+                return Ok(());
+            }
+            Type::SzArray(element) | Type::Ptr(element) | Type::ByRef(element) | Type::Pinned(element) => {
+                buf.push(match typ {
+                    Type::SzArray(_) => 0x1D,
+                    Type::Ptr(_) => 0x0F,
+                    Type::ByRef(_) => 0x10,
+                    _ => 0x45,
+                });
+                return self.encode_type(element, buf);
+            }
+        };
+        buf.push(element);
+        Ok(())
+    }
+
+    /// Gets `ELEMENT_TYPE_VALUETYPE` or `ELEMENT_TYPE_CLASS` for a class name, which for a bare name depends on
+    /// whether a class of the module derives from `System.ValueType` or `System.Enum`.
+    fn class_element(&self, typ: &Type, handle: TableHandle) -> u8 {
+        let value_type = match typ {
+            Type::Value(_) => true,
+            Type::Named(_) => handle.table() == TableIndex::TypeDef && self.value_types[handle.index() - 1],
+            _ => false,
+        };
+        if value_type { 0x11 } else { 0x12 }
+    }
+}
+
+fn type_def_or_ref(handle: TableHandle) -> u32 {
+    let tag = match handle.table() {
+        TableIndex::TypeDef => 0,
+        TableIndex::TypeRef => 1,
+        _ => 2,
+    };
+    ((handle.index() as u32) << 2) | tag
+}
+
+/// Gets the signature of a method declaration, which is an instance method unless it is static.
+fn declared_signature(method: &MethodDecl) -> MethodSig {
+    MethodSig {
+        instance: method.instance || method.flags & 0x10 == 0,
+        explicit: method.explicit,
+        vararg: method.vararg,
+        generic_count: method.generic_params.len() as u32,
+        ret: method.ret.clone(),
+        params: method.params.iter().map(|p| p.param_type.clone()).collect(),
+        sentinel: None,
+    }
+}
+
+fn all_methods(source: &Source) -> impl Iterator<Item = &MethodDecl> {
+    fn class_methods(class: &ClassDecl) -> Box<dyn Iterator<Item = &MethodDecl> + '_> {
+        Box::new(class.methods.iter().chain(class.nested.iter().flat_map(class_methods)))
+    }
+    source.methods.iter().chain(source.classes.iter().flat_map(class_methods))
+}
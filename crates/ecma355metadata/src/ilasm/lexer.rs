@@ -0,0 +1,164 @@
+use crate::error::Error;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    /// A keyword, directive, opcode or dotted name, such as `.class`, `ldc.i4.s` or `System.Console`.
+    Word(String),
+    /// A name in single quotes, which may contain characters a word can't.
+    Quoted(String),
+    /// A number, as written. Hex bytes such as `0B` are numbers too, so the parser decides how to read them.
+    Number(String),
+    /// A string literal in double quotes, with its escapes resolved.
+    String(String),
+    Symbol(&'static str),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lexeme {
+    pub token: Token,
+    pub line: usize,
+}
+
+/// Symbols, longest first so that `::` isn't read as two `:`.
+const SYMBOLS: &[&str] = &["...", "::", "!!", "{", "}", "(", ")", "[", "]", "<", ">", ",", ":", "=", "/", "!", "&", "*", "+", "-"];
+
+/// Splits ILAsm source into tokens, dropping whitespace and comments.
+pub fn tokenize(source: &str) -> Result<Vec<Lexeme>, Error> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut lexemes = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+    let error = |line: usize, message: &str| Error::InvalidIl { line, message: message.to_string() };
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if c == '\n' {
+            line += 1;
+            i += 1;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c == '/' && next == Some('/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && next == Some('*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                if chars[i] == '\n' {
+                    line += 1;
+                }
+                i += 1;
+            }
+            if i >= chars.len() {
+                return Err(error(line, "unterminated comment"));
+            }
+            i += 2;
+        } else if c == '"' || c == '\'' {
+            let start_line = line;
+            let mut value = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(error(start_line, "unterminated string")),
+                    Some(q) if *q == c => break,
+                    Some('\\') => {
+                        i += 1;
+                        value.push(match chars.get(i) {
+                            Some('n') => '\n',
+                            Some('t') => '\t',
+                            Some('r') => '\r',
+                            Some('0') => '\0',
+                            Some(other) => *other,
+                            None => return Err(error(start_line, "unterminated string")),
+                        });
+                    }
+                    Some(other) => {
+                        if *other == '\n' {
+                            line += 1;
+                        }
+                        value.push(*other);
+                    }
+                }
+                i += 1;
+            }
+            i += 1;
+            let token = if c == '"' { Token::String(value) } else { Token::Quoted(value) };
+            lexemes.push(Lexeme { token, line: start_line });
+        } else if c.is_ascii_digit() || (c == '-' && next.is_some_and(|n| n.is_ascii_digit())) {
+            let start = i;
+            i += 1;
+            while i < chars.len() {
+                let d = chars[i];
+                let exponent_sign = (d == '-' || d == '+')
+                    && matches!(chars[i - 1], 'e' | 'E')
+                    && !chars[start..i].iter().any(|x| *x == 'x' || *x == 'X');
+                if d.is_ascii_alphanumeric() || d == '.' || d == '_' || exponent_sign {
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+            lexemes.push(Lexeme { token: Token::Number(chars[start..i].iter().collect()), line });
+        } else if is_word_start(c) && !(c == '.' && next == Some('.')) {
+            let start = i;
+            i += 1;
+            while i < chars.len() && is_word_char(chars[i]) {
+                i += 1;
+            }
+            lexemes.push(Lexeme { token: Token::Word(chars[start..i].iter().collect()), line });
+        } else {
+            let rest: String = chars[i..(i + 3).min(chars.len())].iter().collect();
+            let symbol = SYMBOLS.iter().find(|s| rest.starts_with(**s)).ok_or_else(|| error(line, &format!("unexpected character '{}'", c)))?;
+            lexemes.push(Lexeme { token: Token::Symbol(symbol), line });
+            i += symbol.len();
+        }
+    }
+    Ok(lexemes)
+}
+
+fn is_word_start(c: char) -> bool {
+    c.is_alphabetic() || matches!(c, '_' | '$' | '@' | '?' | '.' | '`')
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '$' | '@' | '?' | '.' | '`')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn tokenize_instructions() {
+        let source = "IL_0000: ldc.i4.s -1 // comment\n  call void [System.Console]System.Console::WriteLine(string) /* a\nb */ ldstr \"a\\\"b\"";
+        let tokens: Vec<_> = tokenize(source).unwrap();
+        let words = |s: &str| Token::Word(s.to_string());
+        assert_eq!(
+            vec![
+                words("IL_0000"),
+                Token::Symbol(":"),
+                words("ldc.i4.s"),
+                Token::Number("-1".into()),
+                words("call"),
+                words("void"),
+                Token::Symbol("["),
+                words("System.Console"),
+                Token::Symbol("]"),
+                words("System.Console"),
+                Token::Symbol("::"),
+                words("WriteLine"),
+                Token::Symbol("("),
+                words("string"),
+                Token::Symbol(")"),
+                words("ldstr"),
+                Token::String("a\"b".into()),
+            ],
+            tokens.iter().map(|l| l.token.clone()).collect::<Vec<_>>()
+        );
+        assert_eq!(3, tokens.last().unwrap().line);
+        assert_eq!(Token::Number("1.5e-3".into()), tokenize("1.5e-3").unwrap()[0].token);
+        assert_eq!(Token::Quoted("<Module>".into()), tokenize("'<Module>'").unwrap()[0].token);
+        assert!(tokenize("ldstr \"open").is_err());
+    }
+}
@@ -0,0 +1,229 @@
+mod ast;
+mod emitter;
+mod lexer;
+mod parser;
+
+use sha1::{Digest, Sha1};
+
+use crate::error::Error;
+use crate::Guid;
+
+use self::emitter::Emitter;
+
+/// Assembles ILAsm source into a PE image.
+///
+/// The source declares the assembly, its references and its classes as `ilasm` takes them, and the image is a DLL
+/// unless a method is marked `.entrypoint`. The MVID is taken from a hash of the source, so the same source always
+/// assembles to the same image. Errors give the line of the source they occurred on.
+pub fn assemble(source: &str) -> Result<Vec<u8>, Error> {
+    let parsed = parser::parse(lexer::tokenize(source)?)?;
+    let hash = Sha1::digest(source.as_bytes());
+    Emitter::new(&parsed).emit(Guid::from_bytes(&hash[..16]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::tables::{self, TableHandle, TableIndex};
+    use crate::cli::AssemblyFlags;
+    use crate::pe::{FileCharacteristics, MemoryRange};
+    use crate::MetadataImage;
+
+    const PROGRAM: &str = r#"
+        .assembly extern System.Runtime { .publickeytoken = (B0 3F 5F 7F 11 D5 0A 3A) .ver 8:0:0:0 }
+        .assembly extern System.Console { .publickeytoken = (B0 3F 5F 7F 11 D5 0A 3A) .ver 8:0:0:0 }
+        .assembly Program { .ver 1:0:0:0 }
+
+        .class public auto ansi beforefieldinit Demo.Box`1<T> {
+            .field private !T 'value'
+            .method public hidebysig specialname rtspecialname instance void .ctor(!T 'value') cil managed {
+                ldarg.0
+                call instance void [System.Runtime]System.Object::.ctor()
+                ldarg.0
+                ldarg.1
+                stfld !0 class Demo.Box`1<!T>::'value'
+                ret
+            }
+            .method public hidebysig specialname instance !T get_Value() cil managed {
+                ldarg.0
+                ldfld !0 class Demo.Box`1<!T>::'value'
+                ret
+            }
+            .property instance !T Value() {
+                .get instance !0 Demo.Box`1::get_Value()
+            }
+        }
+
+        .class public abstract auto ansi sealed beforefieldinit Demo.Program {
+            .method public hidebysig static int32 Main(string[] args) cil managed {
+                .entrypoint
+                .locals init (int32 result, class Demo.Box`1<int32> box)
+                ldc.i4.s 42
+                newobj instance void class Demo.Box`1<int32>::.ctor(!0)
+                stloc box
+                .try {
+                    ldloc.1
+                    callvirt instance !0 class Demo.Box`1<int32>::get_Value()
+                    stloc.0
+                    ldloc.0
+                    switch (One, Two)
+                    leave.s Done
+                One:
+                    ldstr "one"
+                    call void [System.Console]System.Console::WriteLine(string)
+                    leave.s Done
+                Two:
+                    leave.s Done
+                } catch [System.Runtime]System.Exception {
+                    pop
+                    leave.s Done
+                }
+            Done:
+                ldloc result
+                ret
+            }
+        }
+    "#;
+
+    #[test]
+    pub fn assemble_program() {
+        let image = MetadataImage::load_data(assemble(PROGRAM).unwrap()).unwrap();
+        assert!(image.validate().is_empty(), "{:?}", image.validate());
+        assert_eq!(Some(TableHandle::new(2, TableIndex::TypeDef)), image.find_type_def("Demo", "Box`1"));
+        assert_eq!(TableHandle::new(3, TableIndex::MethodDef), image.cli_header().unwrap().entry_point_token);
+        assert!(!image.pe().unwrap().coff_header().characteristics.contains(FileCharacteristics::DLL));
+
+        let module = image.table::<tables::Module>().read(0).unwrap();
+        assert_eq!("Program.exe", image.get_string(module.name).unwrap().to_str().unwrap());
+        let type_refs: Vec<_> = image.table::<tables::TypeRef>().iter()
+            .map(|row| image.get_string(row.unwrap().name).unwrap().to_str().unwrap().to_string())
+            .collect();
+        assert_eq!(vec!["Object", "Console", "Exception"], type_refs);
+        assert_eq!(1, image.row_count(TableIndex::Property));
+        assert_eq!(1, image.row_count(TableIndex::GenericParam));
+        assert_eq!(2, image.row_count(TableIndex::TypeSpec));
+
+        // Main has locals and an exception region, so it has a fat header with a small exception section.
+        let main = image.table::<tables::MethodDef>().read(2).unwrap();
+        let pe = image.pe().unwrap();
+        let header = pe.read_rva(MemoryRange::new(main.rva, 12)).unwrap();
+        assert_eq!(0x301B, u16::from_le_bytes([header[0], header[1]]));
+        let code_size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        let sections = (main.rva + 12 + code_size + 3) & !3;
+        let section = pe.read_rva(MemoryRange::new(sections, 16)).unwrap();
+        assert_eq!([0x01, 16], section[..2]);
+
+        assert_eq!(assemble(PROGRAM).unwrap(), assemble(PROGRAM).unwrap());
+    }
+
+    const SHAPES: &str = r#"
+        .assembly extern System.Runtime { .publickeytoken = (B0 3F 5F 7F 11 D5 0A 3A) .ver 8:0:0:0 }
+        .assembly extern Keyed { .publickey = (00 24 00 00 04 80 00 00) .ver 1:0:0:0 }
+        .assembly Shapes { .ver 1:0:0:0 }
+
+        .class public sequential ansi sealed value Demo.Point {
+            .field public int32 X
+        }
+        .class public ansi sealed enum Demo.Color {
+            .field public specialname rtspecialname int32 value__
+        }
+        .class interface public abstract Demo.IShape {
+            .method public hidebysig newslot abstract virtual instance int32 Area() cil managed {}
+        }
+        .class public auto ansi Demo.Square implements Demo.IShape {
+            .field public Demo.Point Corner
+            .method private hidebysig newslot virtual final instance int32 Measure() cil managed {
+                .override method instance int32 Demo.IShape::Area()
+                ldc.i4.M1
+                brzero.s Done
+            Done:
+                ldc.i4.0
+                ret
+            }
+            .method public hidebysig static vararg int32 Sum(int32 first) cil managed {
+                ldarg.0
+                ret
+            }
+            .method public hidebysig static void Caller() cil managed {
+                ldc.i4.1
+                ldc.i4.2
+                call vararg int32 Demo.Square::Sum(int32, ..., int32)
+                pop
+                ldc.i4.1
+                call vararg int32 Demo.Square::Sum(int32)
+                pop
+                ret
+            }
+            .property instance int32 Sides() = int32(4) {
+                .get instance int32 Demo.Square::Measure()
+            }
+        }
+    "#;
+
+    #[test]
+    pub fn assemble_value_types_and_varargs() {
+        let image = MetadataImage::load_data(assemble(SHAPES).unwrap()).unwrap();
+        let type_refs = image.table::<tables::TypeRef>();
+        let base_name = |row: usize| {
+            let extends = image.table::<tables::TypeDef>().read(row).unwrap().extends;
+            match extends.index() {
+                0 => String::new(),
+                index => image.get_string(type_refs.read(index - 1).unwrap().name).unwrap().to_str().unwrap().to_string(),
+            }
+        };
+        assert_eq!(vec!["ValueType", "Enum", "", "Object"], (1..5).map(base_name).collect::<Vec<_>>());
+
+        // A bare reference to a value type of the module is still a value type
+        let corner = image.table::<tables::Field>().read(2).unwrap();
+        assert_eq!([0x06, 0x11, 0x08], image.get_blob(corner.signature).unwrap());
+
+        // The override names the interface method, whatever the overriding method is called
+        let method_impl = image.table::<tables::MethodImpl>().read(0).unwrap();
+        assert_eq!(TableHandle::new(1, TableIndex::MethodDef), method_impl.method_declaration);
+        assert_eq!(TableHandle::new(2, TableIndex::MethodDef), method_impl.method_body);
+
+        // Only the call with extra arguments needs a MemberRef, which points at the vararg method
+        assert_eq!(1, image.row_count(TableIndex::MemberRef));
+        let vararg_call = image.table::<tables::MemberRef>().read(0).unwrap();
+        assert_eq!(TableHandle::new(3, TableIndex::MethodDef), vararg_call.class);
+        assert_eq!([0x05, 0x02, 0x08, 0x08, 0x41, 0x08], image.get_blob(vararg_call.signature).unwrap());
+
+        // The property's default value hangs off the property, not a field
+        let sides = image.table::<tables::Property>().read(0).unwrap();
+        assert_eq!(0x1000, sides.flags);
+        let constant = image.table::<tables::Constant>().read(0).unwrap();
+        assert_eq!((0x08, TableHandle::new(1, TableIndex::Property)), (constant.typ, constant.parent));
+        assert_eq!([4, 0, 0, 0], image.get_blob(constant.value).unwrap());
+
+        let assembly_refs = image.table::<tables::AssemblyRef>();
+        assert!(!assembly_refs.read(0).unwrap().flags.contains(AssemblyFlags::PublicKey));
+        assert!(assembly_refs.read(1).unwrap().flags.contains(AssemblyFlags::PublicKey));
+        assert!(image.validate().is_empty(), "{:?}", image.validate());
+    }
+
+    #[test]
+    pub fn report_errors_by_line() {
+        let error = |source: &str| match assemble(source) {
+            Err(Error::InvalidIl { line, message }) => (line, message),
+            other => panic!("{:?}", other.map(|_| ())),
+        };
+        assert_eq!(3, error(".assembly A {}\n.method static void M() {\n  br Missing\n  ret }").0);
+        assert_eq!(2, error(".assembly A {}\n.method static void M() { call void N() ret }").0);
+        assert_eq!(
+            (1, "unknown assembly mscorlib".to_string()),
+            error(".assembly A {} .class C extends [mscorlib]System.Object {}")
+        );
+        assert_eq!(
+            (2, "class C has no base type, and no core library is referenced".to_string()),
+            error(".assembly A {}\n.class C {}")
+        );
+        assert_eq!(
+            (2, "types in other modules are not supported".to_string()),
+            error(".assembly A {}\n.class C extends [.module Other.dll]Base {}")
+        );
+        assert_eq!(
+            (2, "pinvokeimpl is not supported".to_string()),
+            error(".assembly A {}\n.method pinvokeimpl(\"lib\") static void M() {}")
+        );
+    }
+}
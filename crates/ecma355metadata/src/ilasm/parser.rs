@@ -0,0 +1,1119 @@
+use crate::cli::{OpCode, OperandType};
+use crate::error::Error;
+use crate::ilasm::ast::*;
+use crate::ilasm::lexer::{Lexeme, Token};
+
+const TYPE_FLAGS: &[(&str, u32)] = &[
+    ("private", 0x0),
+    ("public", 0x1),
+    ("auto", 0x0),
+    ("sequential", 0x8),
+    ("explicit", 0x10),
+    ("ansi", 0x0),
+    ("unicode", 0x10000),
+    ("autochar", 0x20000),
+    ("interface", 0x20),
+    ("abstract", 0x80),
+    ("sealed", 0x100),
+    ("specialname", 0x400),
+    ("rtspecialname", 0x800),
+    ("import", 0x1000),
+    ("serializable", 0x2000),
+    ("beforefieldinit", 0x100000),
+];
+
+const NESTED_VISIBILITY: &[(&str, u32)] = &[
+    ("public", 0x2),
+    ("private", 0x3),
+    ("family", 0x4),
+    ("assembly", 0x5),
+    ("famandassem", 0x6),
+    ("famorassem", 0x7),
+];
+
+const MEMBER_ACCESS: &[(&str, u16)] = &[
+    ("privatescope", 0x0),
+    ("compilercontrolled", 0x0),
+    ("private", 0x1),
+    ("famandassem", 0x2),
+    ("assembly", 0x3),
+    ("family", 0x4),
+    ("famorassem", 0x5),
+    ("public", 0x6),
+];
+
+const FIELD_FLAGS: &[(&str, u16)] = &[
+    ("static", 0x10),
+    ("initonly", 0x20),
+    ("literal", 0x40),
+    ("notserialized", 0x80),
+    ("specialname", 0x200),
+    ("rtspecialname", 0x400),
+];
+
+const METHOD_FLAGS: &[(&str, u16)] = &[
+    ("static", 0x10),
+    ("final", 0x20),
+    ("virtual", 0x40),
+    ("hidebysig", 0x80),
+    ("newslot", 0x100),
+    ("strict", 0x200),
+    ("abstract", 0x400),
+    ("specialname", 0x800),
+    ("rtspecialname", 0x1000),
+    ("pinvokeimpl", 0x2000),
+    ("reqsecobj", 0x8000),
+];
+
+const METHOD_IMPL_FLAGS: &[(&str, u16)] = &[
+    ("cil", 0x0),
+    ("native", 0x1),
+    ("runtime", 0x3),
+    ("managed", 0x0),
+    ("unmanaged", 0x4),
+    ("noinlining", 0x8),
+    ("forwardref", 0x10),
+    ("synchronized", 0x20),
+    ("nooptimization", 0x40),
+    ("preservesig", 0x80),
+    ("aggressiveinlining", 0x100),
+    ("internalcall", 0x1000),
+];
+
+/// Other names that `ilasm` accepts for instructions.
+const OPCODE_ALIASES: &[(&str, &str)] = &[
+    ("brnull", "brfalse"),
+    ("brnull.s", "brfalse.s"),
+    ("brzero", "brfalse"),
+    ("brzero.s", "brfalse.s"),
+    ("brinst", "brtrue"),
+    ("brinst.s", "brtrue.s"),
+    ("ldc.i4.M1", "ldc.i4.m1"),
+    ("endfault", "endfinally"),
+    ("ldelem.u8", "ldelem.i8"),
+    ("ldind.u8", "ldind.i8"),
+];
+
+const PRIMITIVE_TYPES: &[(&str, Type)] = &[
+    ("void", Type::Void),
+    ("bool", Type::Bool),
+    ("char", Type::Char),
+    ("int8", Type::I1),
+    ("uint8", Type::U1),
+    ("int16", Type::I2),
+    ("uint16", Type::U2),
+    ("int32", Type::I4),
+    ("uint32", Type::U4),
+    ("int64", Type::I8),
+    ("uint64", Type::U8),
+    ("float32", Type::R4),
+    ("float64", Type::R8),
+    ("string", Type::String),
+    ("object", Type::Object),
+    ("typedref", Type::TypedRef),
+];
+
+/// Parses the tokens of an ILAsm source file into its declarations.
+pub fn parse(tokens: Vec<Lexeme>) -> Result<Source, Error> {
+    let mut parser = Parser { tokens, pos: 0 };
+    let mut source = Source::default();
+    parser.declarations(&mut source, "")?;
+    if parser.pos < parser.tokens.len() {
+        return Err(parser.error("unexpected '}'"));
+    }
+    Ok(source)
+}
+
+struct Parser {
+    tokens: Vec<Lexeme>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + offset).map(|l| &l.token)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens.get(self.pos).or_else(|| self.tokens.last()).map_or(1, |l| l.line)
+    }
+
+    fn error(&self, message: &str) -> Error {
+        Error::InvalidIl { line: self.line(), message: message.to_string() }
+    }
+
+    fn next(&mut self) -> Result<Token, Error> {
+        let token = self.peek().cloned().ok_or_else(|| self.error("unexpected end of file"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn is_word(&self, word: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w == word)
+    }
+
+    fn is_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol)
+    }
+
+    fn eat_word(&mut self, word: &str) -> bool {
+        let found = self.is_word(word);
+        self.pos += found as usize;
+        found
+    }
+
+    fn eat_symbol(&mut self, symbol: &str) -> bool {
+        let found = self.is_symbol(symbol);
+        self.pos += found as usize;
+        found
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), Error> {
+        if self.eat_symbol(symbol) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", symbol)))
+        }
+    }
+
+    fn expect_word(&mut self, word: &str) -> Result<(), Error> {
+        if self.eat_word(word) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", word)))
+        }
+    }
+
+    /// Reads a word or a quoted name, which may be a dotted name.
+    fn name(&mut self) -> Result<String, Error> {
+        match self.peek() {
+            Some(Token::Word(_)) | Some(Token::Quoted(_)) => {}
+            _ => return Err(self.error("expected a name")),
+        }
+        let mut name = String::new();
+        loop {
+            match self.next()? {
+                Token::Word(w) | Token::Quoted(w) => name.push_str(&w),
+                _ => unreachable!(),
+            }
+            // A word ending with a dot is joined to a quoted part after it, as in System.'My Type'.
+            match self.peek() {
+                Some(Token::Quoted(_)) if name.ends_with('.') => {}
+                _ => return Ok(name),
+            }
+        }
+    }
+
+    fn integer(&mut self) -> Result<i64, Error> {
+        match self.next()? {
+            Token::Number(text) => parse_integer(&text).ok_or_else(|| self.previous_error(&format!("invalid number '{}'", text))),
+            _ => Err(self.previous_error("expected a number")),
+        }
+    }
+
+    fn float(&mut self) -> Result<f64, Error> {
+        match self.next()? {
+            Token::Number(text) => text
+                .parse::<f64>()
+                .ok()
+                .or_else(|| parse_integer(&text).map(|i| i as f64))
+                .ok_or_else(|| self.previous_error(&format!("invalid number '{}'", text))),
+            _ => Err(self.previous_error("expected a number")),
+        }
+    }
+
+    fn previous_error(&self, message: &str) -> Error {
+        let line = self.tokens[self.pos - 1].line;
+        Error::InvalidIl { line, message: message.to_string() }
+    }
+
+    /// Reads a string, joining literals that are concatenated with `+`.
+    fn string(&mut self) -> Result<String, Error> {
+        let mut value = String::new();
+        loop {
+            match self.next()? {
+                Token::String(s) => value.push_str(&s),
+                _ => return Err(self.previous_error("expected a string")),
+            }
+            if !self.eat_symbol("+") {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// Reads bytes in hex, such as `( 01 00 00 00 )`.
+    fn bytes(&mut self) -> Result<Vec<u8>, Error> {
+        self.expect_symbol("(")?;
+        let mut bytes = Vec::new();
+        while !self.eat_symbol(")") {
+            match self.next()? {
+                Token::Number(text) | Token::Word(text) if text.len() == 2 => {
+                    let byte = u8::from_str_radix(&text, 16).map_err(|_| self.previous_error(&format!("invalid byte '{}'", text)))?;
+                    bytes.push(byte);
+                }
+                _ => return Err(self.previous_error("expected a byte in hex")),
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// Reads a set of flags from a table, stopping at the first word that isn't one.
+    fn flags<T: Copy + std::ops::BitOr<Output = T>>(&mut self, table: &[(&str, T)], mut flags: T) -> T {
+        while let Some(Token::Word(w)) = self.peek() {
+            match table.iter().find(|(name, _)| name == w) {
+                Some((_, value)) => flags = flags | *value,
+                None => break,
+            }
+            self.pos += 1;
+        }
+        flags
+    }
+
+    /// Skips a parenthesized group, such as the details of `marshal` or `modreq`.
+    fn skip_group(&mut self) -> Result<(), Error> {
+        self.expect_symbol("(")?;
+        let mut depth = 1;
+        while depth > 0 {
+            match self.next()? {
+                Token::Symbol("(") => depth += 1,
+                Token::Symbol(")") => depth -= 1,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn declarations(&mut self, source: &mut Source, namespace: &str) -> Result<(), Error> {
+        while let Some(token) = self.peek() {
+            let directive = match token {
+                Token::Symbol("}") => return Ok(()),
+                Token::Word(w) => w.clone(),
+                _ => return Err(self.error("expected a declaration")),
+            };
+            self.pos += 1;
+            match directive.as_str() {
+                ".assembly" => {
+                    if self.eat_word("extern") {
+                        let assembly = self.assembly()?;
+                        source.assembly_refs.push(assembly);
+                    } else if source.assembly.is_some() {
+                        return Err(self.error("only one .assembly can be declared"));
+                    } else {
+                        source.assembly = Some(self.assembly()?);
+                    }
+                }
+                ".module" => {
+                    if self.eat_word("extern") {
+                        self.name()?;
+                    } else {
+                        source.module = Some(self.name()?);
+                    }
+                }
+                ".namespace" => {
+                    let name = self.name()?;
+                    let nested = if namespace.is_empty() { name } else { format!("{}.{}", namespace, name) };
+                    self.expect_symbol("{")?;
+                    self.declarations(source, &nested)?;
+                    self.expect_symbol("}")?;
+                }
+                ".class" => {
+                    let class = self.class(namespace, false)?;
+                    source.classes.push(class);
+                }
+                ".field" => source.fields.push(self.field()?),
+                ".method" => source.methods.push(self.method()?),
+                ".custom" => source.module_attributes.push(self.custom()?),
+                ".subsystem" => source.subsystem = Some(self.integer()? as u16),
+                ".corflags" => source.corflags = Some(self.integer()? as u32),
+                ".imagebase" | ".stackreserve" => {
+                    self.integer()?;
+                }
+                ".file" => {
+                    self.expect_word("alignment")?;
+                    self.integer()?;
+                }
+                ".mresource" | ".data" | ".vtfixup" | ".permission" | ".permissionset" => {
+                    return Err(self.previous_error(&format!("{} is not supported", directive)));
+                }
+                _ => return Err(self.previous_error(&format!("unexpected '{}'", directive))),
+            }
+        }
+        Ok(())
+    }
+
+    fn assembly(&mut self) -> Result<AssemblyDecl, Error> {
+        while self.eat_word("retargetable") || self.eat_word("legacy") || self.eat_word("library") {}
+        let mut assembly = AssemblyDecl { name: self.name()?, ..AssemblyDecl::default() };
+        if self.eat_word("as") {
+            self.name()?;
+        }
+        self.expect_symbol("{")?;
+        while !self.eat_symbol("}") {
+            match self.next()? {
+                Token::Word(w) if w == ".ver" => {
+                    for (i, part) in assembly.version.iter_mut().enumerate() {
+                        if i > 0 {
+                            self.expect_symbol(":")?;
+                        }
+                        *part = self.integer()? as u16;
+                    }
+                }
+                Token::Word(w) if w == ".publickey" || w == ".publickeytoken" => {
+                    self.expect_symbol("=")?;
+                    assembly.public_key = self.bytes()?;
+                    assembly.public_key_token = w == ".publickeytoken";
+                }
+                Token::Word(w) if w == ".hash" => {
+                    if self.eat_word("algorithm") {
+                        assembly.hash_algorithm = Some(self.integer()? as u32);
+                    } else {
+                        self.expect_symbol("=")?;
+                        self.bytes()?;
+                    }
+                }
+                Token::Word(w) if w == ".culture" || w == ".locale" => assembly.culture = self.string()?,
+                Token::Word(w) if w == ".custom" => {
+                    let custom = self.custom()?;
+                    assembly.attributes.push(custom);
+                }
+                _ => return Err(self.previous_error("unexpected token in .assembly")),
+            }
+        }
+        Ok(assembly)
+    }
+
+    fn class(&mut self, namespace: &str, nested: bool) -> Result<ClassDecl, Error> {
+        let line = self.line();
+        let mut flags = 0;
+        let mut kind = ClassKind::Class;
+        while let Some(Token::Word(w)) = self.peek() {
+            if w == "nested" {
+                self.pos += 1;
+                match NESTED_VISIBILITY.iter().find(|(name, _)| self.is_word(name)) {
+                    Some((_, value)) => flags |= value,
+                    None => return Err(self.error("expected the visibility of the nested class")),
+                }
+                self.pos += 1;
+            } else if w == "value" || w == "enum" {
+                kind = if w == "value" { ClassKind::Value } else { ClassKind::Enum };
+                self.pos += 1;
+            } else if let Some((_, value)) = TYPE_FLAGS.iter().find(|(name, _)| name == w) {
+                flags |= value;
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        let full_name = self.name()?;
+        let full_name = if namespace.is_empty() || nested { full_name } else { format!("{}.{}", namespace, full_name) };
+        let (namespace, name) = match full_name.rfind('.') {
+            Some(dot) if !nested && dot > 0 => (full_name[..dot].to_string(), full_name[dot + 1..].to_string()),
+            _ => (String::new(), full_name),
+        };
+        let mut class = ClassDecl { flags, kind, namespace, name, line, ..ClassDecl::default() };
+        class.generic_params = self.generic_params()?;
+        if self.eat_word("extends") {
+            class.extends = Some(self.parse_type()?);
+        }
+        if self.eat_word("implements") {
+            loop {
+                class.implements.push(self.parse_type()?);
+                if !self.eat_symbol(",") {
+                    break;
+                }
+            }
+        }
+        self.expect_symbol("{")?;
+        while !self.eat_symbol("}") {
+            match self.next()? {
+                Token::Word(w) => match w.as_str() {
+                    ".field" => class.fields.push(self.field()?),
+                    ".method" => class.methods.push(self.method()?),
+                    ".class" => class.nested.push(self.class("", true)?),
+                    ".property" => class.properties.push(self.property()?),
+                    ".event" => class.events.push(self.event()?),
+                    ".custom" => class.attributes.push(self.custom()?),
+                    ".pack" => class.pack = Some(self.integer()? as u16),
+                    ".size" => class.size = Some(self.integer()? as u32),
+                    _ => return Err(self.previous_error(&format!("unexpected '{}' in .class", w))),
+                },
+                _ => return Err(self.previous_error("expected a member declaration")),
+            }
+        }
+        Ok(class)
+    }
+
+    /// Reads generic parameters such as `<+ T, class (IComparable) U>`, if there are any.
+    fn generic_params(&mut self) -> Result<Vec<GenericParamDecl>, Error> {
+        let mut params = Vec::new();
+        if !self.eat_symbol("<") {
+            return Ok(params);
+        }
+        loop {
+            let mut param = GenericParamDecl::default();
+            loop {
+                if self.eat_symbol("+") {
+                    param.flags |= 0x1;
+                } else if self.eat_symbol("-") {
+                    param.flags |= 0x2;
+                } else if self.eat_word("class") {
+                    param.flags |= 0x4;
+                } else if self.eat_word("valuetype") {
+                    param.flags |= 0x8;
+                } else if self.eat_word(".ctor") {
+                    param.flags |= 0x10;
+                } else {
+                    break;
+                }
+            }
+            if self.eat_symbol("(") {
+                loop {
+                    param.constraints.push(self.parse_type()?);
+                    if !self.eat_symbol(",") {
+                        break;
+                    }
+                }
+                self.expect_symbol(")")?;
+            }
+            param.name = self.name()?;
+            params.push(param);
+            if !self.eat_symbol(",") {
+                break;
+            }
+        }
+        self.expect_symbol(">")?;
+        Ok(params)
+    }
+
+    fn field(&mut self) -> Result<FieldDecl, Error> {
+        let line = self.line();
+        let offset = if self.eat_symbol("[") {
+            let offset = self.integer()? as u32;
+            self.expect_symbol("]")?;
+            Some(offset)
+        } else {
+            None
+        };
+        let mut flags = 0;
+        while let Some(Token::Word(w)) = self.peek() {
+            if let Some((_, value)) = MEMBER_ACCESS.iter().chain(FIELD_FLAGS).find(|(name, _)| name == w) {
+                flags |= value;
+                self.pos += 1;
+            } else if w == "marshal" {
+                self.pos += 1;
+                self.skip_group()?;
+            } else {
+                break;
+            }
+        }
+        let field_type = self.parse_type()?;
+        let name = self.name()?;
+        let constant = if self.eat_symbol("=") { Some(self.constant()?) } else { None };
+        if constant.is_some() {
+            flags |= 0x8000;
+        }
+        Ok(FieldDecl { flags, field_type, name, offset, constant, line })
+    }
+
+    /// Reads the value of a literal, such as `int32(42)`, `"text"` or `nullref`.
+    fn constant(&mut self) -> Result<ConstantDecl, Error> {
+        if let Some(Token::String(_)) = self.peek() {
+            let value = self.string()?;
+            let value = value.encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+            return Ok(ConstantDecl { element_type: 0x0E, value });
+        }
+        if self.eat_word("nullref") {
+            return Ok(ConstantDecl { element_type: 0x12, value: vec![0; 4] });
+        }
+        let kind = self.name()?;
+        self.expect_symbol("(")?;
+        let (element_type, value) = match kind.as_str() {
+            "bool" => {
+                let value = if self.eat_word("true") {
+                    1
+                } else if self.eat_word("false") {
+                    0
+                } else {
+                    self.integer()? as u8
+                };
+                (0x02, vec![value])
+            }
+            "char" => (0x03, (self.integer()? as u16).to_le_bytes().to_vec()),
+            "int8" => (0x04, vec![self.integer()? as u8]),
+            "uint8" => (0x05, vec![self.integer()? as u8]),
+            "int16" => (0x06, (self.integer()? as u16).to_le_bytes().to_vec()),
+            "uint16" => (0x07, (self.integer()? as u16).to_le_bytes().to_vec()),
+            "int32" => (0x08, (self.integer()? as u32).to_le_bytes().to_vec()),
+            "uint32" => (0x09, (self.integer()? as u32).to_le_bytes().to_vec()),
+            "int64" => (0x0A, self.integer()?.to_le_bytes().to_vec()),
+            "uint64" => (0x0B, self.integer()?.to_le_bytes().to_vec()),
+            "float32" => (0x0C, (self.float()? as f32).to_le_bytes().to_vec()),
+            "float64" => (0x0D, self.float()?.to_le_bytes().to_vec()),
+            _ => return Err(self.previous_error(&format!("unknown constant type '{}'", kind))),
+        };
+        self.expect_symbol(")")?;
+        Ok(ConstantDecl { element_type, value })
+    }
+
+    fn method(&mut self) -> Result<MethodDecl, Error> {
+        let line = self.line();
+        let mut flags = 0;
+        while let Some(Token::Word(w)) = self.peek() {
+            if let Some((_, value)) = MEMBER_ACCESS.iter().chain(METHOD_FLAGS).find(|(name, _)| name == w) {
+                flags |= value;
+                self.pos += 1;
+                if *value == 0x2000 {
+                    return Err(self.previous_error("pinvokeimpl is not supported"));
+                }
+            } else {
+                break;
+            }
+        }
+        let (instance, explicit, vararg) = self.calling_convention();
+        let ret = self.parse_type()?;
+        if self.eat_word("marshal") {
+            self.skip_group()?;
+        }
+        let name = self.name()?;
+        let generic_params = self.generic_params()?;
+        self.expect_symbol("(")?;
+        let mut params = Vec::new();
+        while !self.eat_symbol(")") {
+            if !params.is_empty() {
+                self.expect_symbol(",")?;
+            }
+            let mut flags = 0;
+            while self.eat_symbol("[") {
+                flags |= match self.name()?.as_str() {
+                    "in" => 0x1,
+                    "out" => 0x2,
+                    "opt" => 0x10,
+                    other => return Err(self.previous_error(&format!("unknown parameter attribute '{}'", other))),
+                };
+                self.expect_symbol("]")?;
+            }
+            let param_type = self.parse_type()?;
+            if self.eat_word("marshal") {
+                self.skip_group()?;
+            }
+            let name = match self.peek() {
+                Some(Token::Word(_)) | Some(Token::Quoted(_)) => Some(self.name()?),
+                _ => None,
+            };
+            params.push(ParamDecl { flags, param_type, name });
+        }
+        let impl_flags = self.flags(METHOD_IMPL_FLAGS, 0);
+        let mut method = MethodDecl {
+            flags,
+            impl_flags,
+            instance,
+            explicit,
+            vararg,
+            ret,
+            name,
+            generic_params,
+            params,
+            entry_point: false,
+            locals: Vec::new(),
+            init_locals: false,
+            body: Vec::new(),
+            overrides: Vec::new(),
+            attributes: Vec::new(),
+            line,
+        };
+        self.expect_symbol("{")?;
+        method.body = self.body(&mut method)?;
+        Ok(method)
+    }
+
+    fn calling_convention(&mut self) -> (bool, bool, bool) {
+        let instance = self.eat_word("instance");
+        let explicit = self.eat_word("explicit");
+        let vararg = self.eat_word("vararg");
+        self.eat_word("default");
+        (instance, explicit, vararg)
+    }
+
+    /// Reads the items of a method body or block up to its closing brace, with the method directives it may hold.
+    fn body(&mut self, method: &mut MethodDecl) -> Result<Vec<BodyItem>, Error> {
+        let mut items = Vec::new();
+        while !self.eat_symbol("}") {
+            let line = self.line();
+            let word = match self.next()? {
+                Token::Word(w) | Token::Quoted(w) if self.is_symbol(":") => {
+                    self.pos += 1;
+                    items.push(BodyItem::Label(w));
+                    continue;
+                }
+                Token::Word(w) => w,
+                _ => return Err(self.previous_error("expected an instruction")),
+            };
+            match word.as_str() {
+                ".entrypoint" => method.entry_point = true,
+                ".maxstack" => {
+                    self.integer()?;
+                }
+                ".locals" => {
+                    if self.eat_word("init") {
+                        method.init_locals = true;
+                    }
+                    self.expect_symbol("(")?;
+                    while !self.eat_symbol(")") {
+                        if self.eat_symbol(",") {
+                            continue;
+                        }
+                        if self.eat_symbol("[") {
+                            self.integer()?;
+                            self.expect_symbol("]")?;
+                        }
+                        let local_type = self.parse_type()?;
+                        let name = match self.peek() {
+                            Some(Token::Word(_)) | Some(Token::Quoted(_)) => Some(self.name()?),
+                            _ => None,
+                        };
+                        method.locals.push(LocalDecl { local_type, name });
+                    }
+                }
+                ".custom" => method.attributes.push(self.custom()?),
+                ".override" => {
+                    if self.eat_word("method") {
+                        let declaration = self.method_ref()?;
+                        let owner = declaration.owner.ok_or_else(|| self.previous_error("expected the type of the overridden method"))?;
+                        method.overrides.push((owner, declaration.name, Some(declaration.sig)));
+                    } else {
+                        let owner = self.parse_type()?;
+                        self.expect_symbol("::")?;
+                        let name = self.name()?;
+                        method.overrides.push((owner, name, None));
+                    }
+                }
+                ".try" => items.push(self.try_block(method)?),
+                _ => {
+                    let name = OPCODE_ALIASES.iter().find(|(alias, _)| *alias == word).map_or(word.as_str(), |(_, name)| name);
+                    let opcode = OpCode::from_name(name).ok_or_else(|| self.previous_error(&format!("unknown instruction '{}'", word)))?;
+                    let operand = self.operand(opcode)?;
+                    items.push(BodyItem::Instruction { opcode, operand, line });
+                }
+            }
+        }
+        Ok(items)
+    }
+
+    fn try_block(&mut self, method: &mut MethodDecl) -> Result<BodyItem, Error> {
+        if self.eat_symbol("{") {
+            let body = self.body(method)?;
+            let mut handlers = Vec::new();
+            while let Some(handler) = self.handler(method)? {
+                self.expect_symbol("{")?;
+                handlers.push((handler, self.body(method)?));
+            }
+            if handlers.is_empty() {
+                return Err(self.error("expected a handler for the .try block"));
+            }
+            Ok(BodyItem::TryBlock { body, handlers })
+        } else {
+            let start = self.name()?;
+            self.expect_word("to")?;
+            let end = self.name()?;
+            let mut handlers = Vec::new();
+            while let Some(handler) = self.handler(method)? {
+                self.expect_word("handler")?;
+                let handler_start = self.name()?;
+                self.expect_word("to")?;
+                handlers.push((handler, handler_start, self.name()?));
+            }
+            if handlers.is_empty() {
+                return Err(self.error("expected a handler for the .try block"));
+            }
+            Ok(BodyItem::TryRange { start, end, handlers })
+        }
+    }
+
+    fn handler(&mut self, method: &mut MethodDecl) -> Result<Option<Handler>, Error> {
+        Ok(Some(if self.eat_word("catch") {
+            Handler::Catch(self.parse_type()?)
+        } else if self.eat_word("finally") {
+            Handler::Finally
+        } else if self.eat_word("fault") {
+            Handler::Fault
+        } else if self.eat_word("filter") {
+            if self.eat_symbol("{") {
+                Handler::Filter(FilterStart::Block(self.body(method)?))
+            } else {
+                Handler::Filter(FilterStart::Label(self.name()?))
+            }
+        } else {
+            return Ok(None);
+        }))
+    }
+
+    fn operand(&mut self, opcode: OpCode) -> Result<Operand, Error> {
+        Ok(match opcode.operand {
+            OperandType::InlineNone => Operand::None,
+            OperandType::ShortInlineI | OperandType::InlineI | OperandType::InlineI8 => Operand::Int(self.integer()?),
+            OperandType::ShortInlineR | OperandType::InlineR => Operand::Float(self.float()?),
+            OperandType::ShortInlineVar | OperandType::InlineVar => match self.peek() {
+                Some(Token::Number(_)) => Operand::Var(VarRef::Index(self.integer()? as u16)),
+                _ => Operand::Var(VarRef::Name(self.name()?)),
+            },
+            OperandType::ShortInlineBrTarget | OperandType::InlineBrTarget => Operand::Label(self.name()?),
+            OperandType::InlineSwitch => {
+                self.expect_symbol("(")?;
+                let mut labels = Vec::new();
+                while !self.eat_symbol(")") {
+                    if !labels.is_empty() {
+                        self.expect_symbol(",")?;
+                    }
+                    labels.push(self.name()?);
+                }
+                Operand::Switch(labels)
+            }
+            OperandType::InlineMethod => Operand::Method(self.method_ref()?),
+            OperandType::InlineField => Operand::Field(self.field_ref()?),
+            OperandType::InlineType => Operand::Type(self.parse_type()?),
+            OperandType::InlineTok => {
+                if self.eat_word("method") {
+                    Operand::Method(self.method_ref()?)
+                } else if self.eat_word("field") {
+                    Operand::Field(self.field_ref()?)
+                } else {
+                    Operand::Type(self.parse_type()?)
+                }
+            }
+            OperandType::InlineString => Operand::String(self.string()?),
+            OperandType::InlineSig => {
+                let (instance, explicit, vararg) = self.calling_convention();
+                let ret = self.parse_type()?;
+                let (params, sentinel) = self.param_list()?;
+                Operand::Sig(MethodSig { instance, explicit, vararg, generic_count: 0, ret, params, sentinel })
+            }
+        })
+    }
+
+    /// Reads a method reference, such as `instance void [mscorlib]System.Object::.ctor()`.
+    fn method_ref(&mut self) -> Result<MethodRef, Error> {
+        let (instance, explicit, vararg) = self.calling_convention();
+        let ret = self.parse_type()?;
+        let owner = self.member_owner();
+        let name = self.name()?;
+        let generic_args = if self.is_symbol("<") { self.type_list("<", ">")? } else { Vec::new() };
+        let (params, sentinel) = self.param_list()?;
+        let generic_count = generic_args.len() as u32;
+        let sig = MethodSig { instance, explicit, vararg, generic_count, ret, params, sentinel };
+        Ok(MethodRef { sig, owner, name, generic_args })
+    }
+
+    fn field_ref(&mut self) -> Result<FieldRef, Error> {
+        let field_type = self.parse_type()?;
+        let owner = self.member_owner();
+        let name = self.name()?;
+        Ok(FieldRef { field_type, owner, name })
+    }
+
+    /// Reads the type that declares a member and the `::` after it, or nothing if the member is global.
+    fn member_owner(&mut self) -> Option<Type> {
+        let start = self.pos;
+        match self.parse_type() {
+            Ok(owner) if self.eat_symbol("::") => Some(owner),
+            _ => {
+                self.pos = start;
+                None
+            }
+        }
+    }
+
+    /// Reads a list of types between the given brackets.
+    fn type_list(&mut self, open: &str, close: &str) -> Result<Vec<Type>, Error> {
+        self.expect_symbol(open)?;
+        let mut types = Vec::new();
+        while !self.eat_symbol(close) {
+            if !types.is_empty() {
+                self.expect_symbol(",")?;
+            }
+            types.push(self.parse_type()?);
+        }
+        Ok(types)
+    }
+
+    /// Reads the parameter types of a method reference, with where the vararg sentinel `...` comes among them.
+    fn param_list(&mut self) -> Result<(Vec<Type>, Option<usize>), Error> {
+        self.expect_symbol("(")?;
+        let (mut types, mut sentinel) = (Vec::new(), None);
+        while !self.eat_symbol(")") {
+            if !types.is_empty() || sentinel.is_some() {
+                self.expect_symbol(",")?;
+            }
+            if sentinel.is_none() && self.eat_symbol("...") {
+                sentinel = Some(types.len());
+            } else {
+                types.push(self.parse_type()?);
+            }
+        }
+        Ok((types, sentinel))
+    }
+
+    fn custom(&mut self) -> Result<CustomDecl, Error> {
+        let line = self.line();
+        let constructor = self.method_ref()?;
+        let value = if self.eat_symbol("=") { self.bytes()? } else { Vec::new() };
+        Ok(CustomDecl { constructor, value, line })
+    }
+
+    fn property(&mut self) -> Result<PropertyDecl, Error> {
+        let line = self.line();
+        let mut flags = self.flags(&[("specialname", 0x200), ("rtspecialname", 0x400)], 0);
+        let instance = self.eat_word("instance");
+        let property_type = self.parse_type()?;
+        let name = self.name()?;
+        let params = self.type_list("(", ")")?;
+        let constant = if self.eat_symbol("=") { Some(self.constant()?) } else { None };
+        if constant.is_some() {
+            flags |= 0x1000;
+        }
+        let mut property = PropertyDecl { flags, instance, property_type, name, params, constant, accessors: Vec::new(), attributes: Vec::new(), line };
+        self.expect_symbol("{")?;
+        while !self.eat_symbol("}") {
+            let semantics = match self.next()? {
+                Token::Word(w) if w == ".set" => 0x1,
+                Token::Word(w) if w == ".get" => 0x2,
+                Token::Word(w) if w == ".other" => 0x4,
+                Token::Word(w) if w == ".custom" => {
+                    property.attributes.push(self.custom()?);
+                    continue;
+                }
+                _ => return Err(self.previous_error("unexpected token in .property")),
+            };
+            property.accessors.push((semantics, self.method_ref()?));
+        }
+        Ok(property)
+    }
+
+    fn event(&mut self) -> Result<EventDecl, Error> {
+        let line = self.line();
+        let flags = self.flags(&[("specialname", 0x200), ("rtspecialname", 0x400)], 0);
+        let event_type = self.parse_type()?;
+        let name = self.name()?;
+        let mut event = EventDecl { flags, event_type, name, accessors: Vec::new(), attributes: Vec::new(), line };
+        self.expect_symbol("{")?;
+        while !self.eat_symbol("}") {
+            let semantics = match self.next()? {
+                Token::Word(w) if w == ".other" => 0x4,
+                Token::Word(w) if w == ".addon" => 0x8,
+                Token::Word(w) if w == ".removeon" => 0x10,
+                Token::Word(w) if w == ".fire" => 0x20,
+                Token::Word(w) if w == ".custom" => {
+                    event.attributes.push(self.custom()?);
+                    continue;
+                }
+                _ => return Err(self.previous_error("unexpected token in .event")),
+            };
+            event.accessors.push((semantics, self.method_ref()?));
+        }
+        Ok(event)
+    }
+
+    fn parse_type(&mut self) -> Result<Type, Error> {
+        let mut typ = self.element_type()?;
+        loop {
+            // A bracket followed by a name starts the scope of the next type, as in `void [mscorlib]System.Object::`.
+            if self.is_symbol("[") && !matches!(self.peek_at(1), Some(Token::Word(_)) | Some(Token::Quoted(_))) {
+                self.pos += 1;
+                let mut rank = 1;
+                while !self.eat_symbol("]") {
+                    match self.next()? {
+                        Token::Symbol(",") => rank += 1,
+                        Token::Number(_) | Token::Symbol("...") => {}
+                        _ => return Err(self.previous_error("invalid array shape")),
+                    }
+                }
+                typ = if rank == 1 && !matches!(self.tokens[self.pos - 2].token, Token::Number(_) | Token::Symbol("...")) {
+                    Type::SzArray(Box::new(typ))
+                } else {
+                    Type::Array(Box::new(typ), rank)
+                };
+            } else if self.eat_symbol("*") {
+                typ = Type::Ptr(Box::new(typ));
+            } else if self.eat_symbol("&") {
+                typ = Type::ByRef(Box::new(typ));
+            } else if self.eat_word("pinned") {
+                typ = Type::Pinned(Box::new(typ));
+            } else if self.is_word("modreq") || self.is_word("modopt") {
+                self.pos += 1;
+                self.skip_group()?;
+            } else if self.is_symbol("<") && matches!(typ, Type::Named(_) | Type::Class(_) | Type::Value(_)) {
+                let args = self.type_list("<", ">")?;
+                typ = Type::GenericInst(Box::new(typ), args);
+            } else {
+                return Ok(typ);
+            }
+        }
+    }
+
+    fn element_type(&mut self) -> Result<Type, Error> {
+        if self.eat_symbol("!!") {
+            return Ok(Type::MethodGenericParam(self.generic_ref()?));
+        }
+        if self.eat_symbol("!") {
+            return Ok(Type::GenericParam(self.generic_ref()?));
+        }
+        if self.is_symbol("[") {
+            return Ok(Type::Named(self.type_name()?));
+        }
+        let word = match self.peek() {
+            Some(Token::Word(w)) => w.clone(),
+            Some(Token::Quoted(_)) => return Ok(Type::Named(self.type_name()?)),
+            _ => return Err(self.error("expected a type")),
+        };
+        if let Some((_, typ)) = PRIMITIVE_TYPES.iter().find(|(name, _)| *name == word) {
+            self.pos += 1;
+            return Ok(typ.clone());
+        }
+        self.pos += 1;
+        Ok(match word.as_str() {
+            "class" => Type::Class(self.type_name()?),
+            "valuetype" => Type::Value(self.type_name()?),
+            "unsigned" => match self.next()? {
+                Token::Word(w) if w == "int8" => Type::U1,
+                Token::Word(w) if w == "int16" => Type::U2,
+                Token::Word(w) if w == "int32" => Type::U4,
+                Token::Word(w) if w == "int64" => Type::U8,
+                _ => return Err(self.previous_error("expected an integer type")),
+            },
+            "native" => {
+                let unsigned = self.eat_word("unsigned");
+                match self.next()? {
+                    Token::Word(w) if w == "int" => {
+                        if unsigned { Type::U } else { Type::I }
+                    }
+                    Token::Word(w) if w == "uint" && !unsigned => Type::U,
+                    _ => return Err(self.previous_error("expected 'int'")),
+                }
+            }
+            _ => {
+                self.pos -= 1;
+                Type::Named(self.type_name()?)
+            }
+        })
+    }
+
+    fn generic_ref(&mut self) -> Result<GenericRef, Error> {
+        match self.peek() {
+            Some(Token::Number(_)) => Ok(GenericRef::Index(self.integer()? as u32)),
+            _ => Ok(GenericRef::Name(self.name()?)),
+        }
+    }
+
+    /// Reads a class name, such as `[mscorlib]System.Collections.Generic.List`1` or `Outer/Inner`.
+    fn type_name(&mut self) -> Result<TypeName, Error> {
+        let mut scope = None;
+        if self.eat_symbol("[") {
+            if self.eat_word(".module") {
+                return Err(self.previous_error("types in other modules are not supported"));
+            } else {
+                scope = Some(self.name()?);
+            }
+            self.expect_symbol("]")?;
+        }
+        let mut names = vec![self.name()?];
+        while self.eat_symbol("/") {
+            names.push(self.name()?);
+        }
+        Ok(TypeName { scope, names })
+    }
+}
+
+fn parse_integer(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let value = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()? as i64,
+        None => digits.parse::<u64>().ok()? as i64,
+    };
+    Some(if negative { value.wrapping_neg() } else { value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ilasm::lexer::tokenize;
+
+    #[test]
+    pub fn parse_declarations() {
+        let source = r#"
+            .assembly extern mscorlib { .publickeytoken = (B7 7A 5C 56 19 34 E0 89) .ver 4:0:0:0 }
+            .assembly Test { .ver 1:2:3:4 }
+            .namespace Demo {
+                .class public auto ansi sealed Box`1<class T> extends [mscorlib]System.Object {
+                    .field private !T 'value'
+                    .field public static literal int32 Answer = int32(42)
+                    .method public hidebysig static int32 Add(int32 a, [opt] int32 b) cil managed {
+                        .maxstack 2
+                        .locals init (int32 sum)
+                        ldarg.0
+                        ldarg b
+                        add
+                        stloc.0
+                    L: ldloc sum
+                        brtrue.s L
+                        ldloc.0
+                        ret
+                    }
+                }
+            }
+        "#;
+        let source = parse(tokenize(source).unwrap()).unwrap();
+        assert_eq!([0xB7, 0x7A, 0x5C, 0x56, 0x19, 0x34, 0xE0, 0x89], source.assembly_refs[0].public_key[..]);
+        assert_eq!([1, 2, 3, 4], source.assembly.unwrap().version);
+
+        let class = &source.classes[0];
+        assert_eq!(("Demo", "Box`1"), (class.namespace.as_str(), class.name.as_str()));
+        assert_eq!(0x101, class.flags);
+        assert_eq!(("T", 0x4), (class.generic_params[0].name.as_str(), class.generic_params[0].flags));
+        let object = TypeName { scope: Some("mscorlib".into()), names: vec!["System.Object".into()] };
+        assert_eq!(Some(Type::Named(object)), class.extends);
+        assert_eq!(Type::GenericParam(GenericRef::Name("T".into())), class.fields[0].field_type);
+        assert_eq!(Some(ConstantDecl { element_type: 0x08, value: vec![42, 0, 0, 0] }), class.fields[1].constant);
+
+        let add = &class.methods[0];
+        assert_eq!((0x96, 0), (add.flags, add.impl_flags));
+        assert_eq!(0x10, add.params[1].flags);
+        assert!(add.init_locals);
+        assert_eq!(9, add.body.len());
+        assert!(matches!(&add.body[4], BodyItem::Label(l) if l == "L"));
+        assert!(matches!(&add.body[1], BodyItem::Instruction { operand: Operand::Var(VarRef::Name(n)), .. } if n == "b"));
+    }
+
+    #[test]
+    pub fn parse_member_references() {
+        let tokens = tokenize("call instance void class [mscorlib]System.Collections.Generic.List`1<int32[]>::Add(!0) ret").unwrap();
+        let mut parser = Parser { tokens, pos: 1 };
+        let method = parser.method_ref().unwrap();
+        assert!(method.sig.instance);
+        assert_eq!("Add", method.name);
+        let list = TypeName { scope: Some("mscorlib".into()), names: vec!["System.Collections.Generic.List`1".into()] };
+        let args = vec![Type::SzArray(Box::new(Type::I4))];
+        assert_eq!(Some(Type::GenericInst(Box::new(Type::Class(list)), args)), method.owner);
+        assert_eq!(vec![Type::GenericParam(GenericRef::Index(0))], method.sig.params);
+
+        let tokens = tokenize("call void Main<string>()").unwrap();
+        let mut parser = Parser { tokens, pos: 1 };
+        let method = parser.method_ref().unwrap();
+        assert_eq!((None, "Main"), (method.owner, method.name.as_str()));
+        assert_eq!(vec![Type::String], method.generic_args);
+
+        let tokens = tokenize("call vararg void M(int32, ..., string)").unwrap();
+        let mut parser = Parser { tokens, pos: 1 };
+        let method = parser.method_ref().unwrap();
+        assert_eq!((vec![Type::I4, Type::String], Some(1)), (method.sig.params, method.sig.sentinel));
+
+        assert!(OPCODE_ALIASES.iter().all(|(_, name)| OpCode::from_name(name).is_some()));
+        let error = parse(tokenize(".class Foo {\n .method void M() { frob } }").unwrap()).unwrap_err();
+        assert!(matches!(error, Error::InvalidIl { line: 2, .. }));
+    }
+}
@@ -18,6 +18,8 @@ mod error;
 mod utils;
 mod guid;
 mod metadata_image;
+mod assembly_image;
+mod merged_image;
 
 /// Contains CLI metadata structures
 pub mod cli;
@@ -25,9 +27,26 @@ pub mod cli;
 /// Contains PE structures
 pub mod pe;
 
+/// Contains readers for .NET single-file app bundles
+pub mod bundle;
+
+/// Contains readers for NuGet packages
+pub mod nuget;
+
+/// Contains the ECMA-335 Partition II metadata validator
+pub mod validation;
+
+/// Contains builders that write metadata
+pub mod writer;
+
+/// Contains an assembler for ILAsm source
+pub mod ilasm;
+
 pub use error::Error;
 
 pub use pe::PeImage;
 pub use cli::CliHeader;
 pub use guid::Guid;
 pub use metadata_image::MetadataImage;
+pub use assembly_image::{AssemblyImage, AssemblyModule};
+pub use merged_image::{EncFuncCode, EncLogEntry, MergedImage};
@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+use std::ffi::CStr;
+
+use crate::cli::tables::{self, TableHandle, TableIndex, TableRow};
+use crate::cli::{BlobHandle, GuidHandle, StringHandle};
+use crate::error::Error;
+use crate::pe::ImageData;
+use crate::{Guid, MetadataImage};
+
+/// The operation an EncLog entry records.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EncFuncCode {
+    /// The row was added or updated in place.
+    Default,
+    AddMethod,
+    AddField,
+    AddParameter,
+    AddProperty,
+    AddEvent,
+    Other(u32),
+}
+
+impl EncFuncCode {
+    pub fn from_u32(value: u32) -> EncFuncCode {
+        match value {
+            0 => EncFuncCode::Default,
+            1 => EncFuncCode::AddMethod,
+            2 => EncFuncCode::AddField,
+            3 => EncFuncCode::AddParameter,
+            4 => EncFuncCode::AddProperty,
+            5 => EncFuncCode::AddEvent,
+            x => EncFuncCode::Other(x),
+        }
+    }
+}
+
+impl_display_via_debug!(EncFuncCode);
+
+/// An entry in the EncLog table of a delta.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EncLogEntry {
+    pub token: TableHandle,
+    pub func_code: EncFuncCode,
+}
+
+struct Generation<D: ImageData> {
+    image: MetadataImage<D>,
+    number: u16,
+
+    /// The rows that a delta defines in each table, in the order they are stored, from its EncMap table.
+    rows: HashMap<TableIndex, Vec<usize>>,
+
+    /// Where the heaps of the generation start in the combined heaps of every generation. The GUID heap isn't
+    /// included, as each delta's heap starts with zeroed space for the GUIDs of the generations before it.
+    string_start: usize,
+    blob_start: usize,
+}
+
+/// A read-only view of a base image with Edit-and-Continue deltas applied to it, as produced by hot reload.
+///
+/// Tokens resolve to the row from the newest generation that defines them, and heap handles resolve to whichever
+/// generation's heap they point into: the string and blob heaps of each delta continue on from those of the generation
+/// before it.
+pub struct MergedImage<D: ImageData = Vec<u8>> {
+    generations: Vec<Generation<D>>,
+}
+
+impl<D: ImageData> MergedImage<D> {
+    /// Applies `deltas`, in the order they were produced, to `base`.
+    ///
+    /// Each delta must have been made from the generation before it, as recorded by the EncBaseId column of its Module
+    /// table.
+    pub fn new(base: MetadataImage<D>, deltas: Vec<MetadataImage<D>>) -> Result<MergedImage<D>, Error> {
+        let mut merged = MergedImage {
+            generations: vec![Generation {
+                number: module_generation(&base)?,
+                image: base,
+                rows: HashMap::new(),
+                string_start: 0,
+                blob_start: 0,
+            }],
+        };
+
+        for image in deltas {
+            if image.row_count(TableIndex::EncMap) == 0 {
+                return Err(Error::InvalidMetadata("delta has no EncMap table".into()));
+            }
+            let previous = merged.generations.last().expect("the base is always present");
+            let number = module_generation(&image)?;
+            if number <= previous.number {
+                return Err(Error::InvalidMetadata(format!("delta generation {} follows generation {}", number, previous.number)));
+            }
+
+            let heaps = previous.image.heaps();
+            let heap_len = |range: &Option<std::ops::Range<usize>>| range.as_ref().map_or(0, |r| r.len());
+            let string_start = previous.string_start + heap_len(&heaps.string_heap);
+            let blob_start = previous.blob_start + heap_len(&heaps.blob_heap);
+
+            let mut rows: HashMap<TableIndex, Vec<usize>> = HashMap::new();
+            for row in image.table::<tables::EncMap>().iter() {
+                let token = TableHandle::from_metadata_token(row?.token);
+                rows.entry(token.table()).or_default().push(token.index());
+            }
+            for table_rows in rows.values_mut() {
+                table_rows.sort_unstable();
+            }
+
+            merged.generations.push(Generation { image, number, rows, string_start, blob_start });
+
+            let index = merged.generations.len() - 1;
+            let module = merged.generations[index].image.table::<tables::Module>().read(0)?;
+            let base = merged.generations[index - 1].image.table::<tables::Module>().read(0)?;
+            if merged.get_guid(module.enc_base_id) != merged.get_guid_in(index - 1, base.enc_id) {
+                return Err(Error::InvalidMetadata(format!("delta generation {} was not made from the generation before it", number)));
+            }
+        }
+        Ok(merged)
+    }
+
+    /// Gets the number of generations, including the base.
+    pub fn generation_count(&self) -> usize {
+        self.generations.len()
+    }
+
+    /// Gets the image of a generation, where generation 0 is the base.
+    pub fn generation(&self, index: usize) -> &MetadataImage<D> {
+        &self.generations[index].image
+    }
+
+    /// Gets the generation number that the Module table of a generation records.
+    pub fn generation_number(&self, index: usize) -> u16 {
+        self.generations[index].number
+    }
+
+    /// Gets the number of rows in a table, including the rows that deltas add.
+    pub fn row_count(&self, table: TableIndex) -> usize {
+        self.generations
+            .iter()
+            .map(|g| match g.rows.get(&table) {
+                Some(rows) => rows.last().copied().unwrap_or(0),
+                None => 0,
+            })
+            .fold(self.generations[0].image.row_count(table), usize::max)
+    }
+
+    /// Finds the newest definition of a row, as the index of its generation and its 1-based row in that generation.
+    pub fn resolve(&self, handle: TableHandle) -> Option<(usize, usize)> {
+        for (index, generation) in self.generations.iter().enumerate().skip(1).rev() {
+            if let Some(position) = generation.rows.get(&handle.table()).and_then(|rows| rows.binary_search(&handle.index()).ok()) {
+                return Some((index, position + 1));
+            }
+        }
+        if handle.index() > 0 && handle.index() <= self.generations[0].image.row_count(handle.table()) {
+            Some((0, handle.index()))
+        } else {
+            None
+        }
+    }
+
+    /// Reads the newest version of a row, given its 1-based index.
+    ///
+    /// Heap handles in the row can be resolved with the heap methods of the merged image.
+    pub fn read<T: TableRow>(&self, row: usize) -> Result<T, Error> {
+        let handle = TableHandle::new(row, T::INDEX);
+        let (generation, local) = self.resolve(handle).ok_or_else(|| Error::InvalidMetadata(format!("{} is not defined by any generation", handle)))?;
+        self.generations[generation].image.table::<T>().read(local - 1)
+    }
+
+    /// Gets the EncLog entries of a generation, which list the changes it made.
+    pub fn enc_log(&self, index: usize) -> Result<Vec<EncLogEntry>, Error> {
+        self.generations[index]
+            .image
+            .table::<tables::EncLog>()
+            .iter()
+            .map(|row| {
+                let row = row?;
+                Ok(EncLogEntry {
+                    token: TableHandle::from_metadata_token(row.token),
+                    func_code: EncFuncCode::from_u32(row.func_code),
+                })
+            })
+            .collect()
+    }
+
+    pub fn get_string(&self, handle: StringHandle) -> Option<&CStr> {
+        let generation = self.generations.iter().rev().find(|g| handle.0 >= g.string_start)?;
+        generation.image.get_string(StringHandle(handle.0 - generation.string_start))
+    }
+
+    pub fn get_blob(&self, handle: BlobHandle) -> Option<&[u8]> {
+        let generation = self.generations.iter().rev().find(|g| handle.0 >= g.blob_start)?;
+        generation.image.get_blob(BlobHandle(handle.0 - generation.blob_start))
+    }
+
+    pub fn get_guid(&self, handle: GuidHandle) -> Option<Guid> {
+        self.get_guid_in(self.generations.len() - 1, handle)
+    }
+
+    /// Resolves a GUID handle as it was seen by a generation, which can't see the GUIDs of later ones.
+    fn get_guid_in(&self, index: usize, handle: GuidHandle) -> Option<Guid> {
+        // GUID handles are the same in every generation, so the newest heap that is long enough holds the GUID
+        let guid_count = |g: &Generation<D>| g.image.heaps().guid_heap.as_ref().map_or(0, |r| r.len() / 16);
+        let generation = self.generations[..=index].iter().rev().find(|g| handle.0 <= guid_count(g))?;
+        generation.image.get_guid(handle)
+    }
+}
+
+fn module_generation<D: ImageData>(image: &MetadataImage<D>) -> Result<u16, Error> {
+    if image.row_count(TableIndex::Module) == 0 {
+        return Err(Error::InvalidMetadata("image has no Module table".into()));
+    }
+    Ok(image.table::<tables::Module>().read(0)?.generation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata_image::tests::{metadata_root, table_stream};
+
+    /// Builds a base with `<Module>` and `Widget` types.
+    fn base() -> Vec<u8> {
+        base_with_guids(0, &[])
+    }
+
+    /// Builds a base with `<Module>` and `Widget` types, and a module MVID pointing into `guids`.
+    fn base_with_guids(mvid: u8, guids: &[[u8; 16]]) -> Vec<u8> {
+        metadata_root(&[
+            ("#~", table_stream(&[
+                (TableIndex::Module, 1, &[0x00, 0x00, 0x01, 0x00, mvid, 0x00, 0x00, 0x00, 0x00, 0x00]),
+                (TableIndex::TypeDef, 2, &[
+                    0x00, 0x00, 0x00, 0x00, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00,
+                    0x01, 0x00, 0x00, 0x00, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00,
+                ]),
+            ])),
+            ("#Strings", b"\0Test\0<Module>\0Widget\0".to_vec()),
+            ("#GUID", guids.concat()),
+        ])
+    }
+
+    /// Builds a minimal delta that renames `Widget` to `Gadget` and adds `Extra`, with 4-byte indexes throughout.
+    fn delta() -> Vec<u8> {
+        delta_with_guids(0, 1, &[[0x11; 16]])
+    }
+
+    /// Builds the same delta as `delta`, with module MVID and EncId columns pointing into `guids`.
+    fn delta_with_guids(mvid: u8, enc_id: u8, guids: &[[u8; 16]]) -> Vec<u8> {
+        let type_def = |name: u8| [0x01, 0x00, 0x00, 0x00, name, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01, 0, 0, 0, 0x01, 0, 0, 0];
+        let mut type_defs = type_def(0x19).to_vec();
+        type_defs.extend_from_slice(&type_def(0x20));
+
+        let mut tables = table_stream(&[
+            (TableIndex::Module, 1, &[0x01, 0x00, 0x01, 0x00, 0x00, 0x00, mvid, 0x00, 0x00, 0x00, enc_id, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+            (TableIndex::TypeDef, 2, &type_defs),
+            (TableIndex::EncLog, 3, &[
+                0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x02, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00,
+                0x03, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00,
+            ]),
+            (TableIndex::EncMap, 3, &[0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x02, 0x03, 0x00, 0x00, 0x02]),
+        ]);
+        tables[6] = 0x27;
+        metadata_root(&[
+            ("#-", tables),
+            ("#Strings", b"\0Gadget\0Extra\0".to_vec()),
+            ("#GUID", guids.concat()),
+            ("#JTD", Vec::new()),
+        ])
+    }
+
+    fn type_name(merged: &MergedImage, row: usize) -> String {
+        let type_def = merged.read::<tables::TypeDef>(row).unwrap();
+        merged.get_string(type_def.type_name).unwrap().to_string_lossy().into_owned()
+    }
+
+    #[test]
+    pub fn apply_delta() {
+        let base = MetadataImage::load_metadata(base()).unwrap();
+        let delta = MetadataImage::load_metadata(delta()).unwrap();
+        let merged = MergedImage::new(base, vec![delta]).unwrap();
+
+        assert_eq!(2, merged.generation_count());
+        assert_eq!((0, 1), (merged.generation_number(0), merged.generation_number(1)));
+        assert_eq!(3, merged.row_count(TableIndex::TypeDef));
+        assert_eq!(Some((1, 1)), merged.resolve(TableHandle::new(2, TableIndex::TypeDef)));
+        assert_eq!(vec!["<Module>", "Gadget", "Extra"], (1..=3).map(|row| type_name(&merged, row)).collect::<Vec<_>>());
+
+        // The module name still comes from the base heap
+        let module = merged.read::<tables::Module>(1).unwrap();
+        assert_eq!(c"Test", merged.get_string(module.name).unwrap());
+        assert_eq!(Some(Guid::from_bytes(&[0x11; 16])), merged.get_guid(module.enc_id));
+
+        let log = merged.enc_log(1).unwrap();
+        assert_eq!(EncLogEntry { token: TableHandle::new(3, TableIndex::TypeDef), func_code: EncFuncCode::Default }, log[2]);
+    }
+
+    #[test]
+    pub fn resolve_guids_in_padded_heaps() {
+        // Each delta's GUID heap has zeroes in place of the GUIDs of earlier generations
+        let base = MetadataImage::load_metadata(base_with_guids(1, &[[0xAA; 16]])).unwrap();
+        let delta = MetadataImage::load_metadata(delta_with_guids(2, 3, &[[0; 16], [0xAA; 16], [0x11; 16]])).unwrap();
+        let merged = MergedImage::new(base, vec![delta]).unwrap();
+
+        let module = merged.read::<tables::Module>(1).unwrap();
+        assert_eq!(Some(Guid::from_bytes(&[0xAA; 16])), merged.get_guid(module.mvid));
+        assert_eq!(Some(Guid::from_bytes(&[0x11; 16])), merged.get_guid(module.enc_id));
+        assert_eq!(Some(Guid::from_bytes(&[0xAA; 16])), merged.get_guid_in(0, GuidHandle(1)));
+        assert_eq!(None, merged.get_guid(GuidHandle(4)));
+    }
+
+    #[test]
+    pub fn reject_out_of_order_deltas() {
+        let base = MetadataImage::load_metadata(base()).unwrap();
+        let deltas = vec![MetadataImage::load_metadata(delta()).unwrap(), MetadataImage::load_metadata(delta()).unwrap()];
+        assert!(MergedImage::new(base, deltas).is_err());
+    }
+}
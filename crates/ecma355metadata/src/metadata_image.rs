@@ -1,58 +1,118 @@
+use std::collections::HashMap;
 use std::ffi::CStr;
-use std::io::{Cursor, Read};
-use std::ops::Deref;
+use std::io::{Cursor, Read, Seek};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use memmap2::Mmap;
 
 use tracing::trace;
 
-use crate::cli::tables::{self, TableIndex};
-use crate::pe::{DirectoryType, PeImage};
-use crate::cli::{BlobHandle, CliHeader, GuidHandle, MetadataHeader, MetadataSizes, StringHandle};
+use crate::cli::tables::{self, TableHandle, TableIndex};
+use crate::pe::{self, DebugType, DirectoryType, ImageData, MemoryRange, PeImage, StreamingData};
+use crate::cli::{self as cli, BlobHandle, CliHeader, GuidHandle, MetadataHeader, MetadataSizes, ProjectedName, StringHandle, UserStringHandle, StrongNameKey, StrongNameStatus, TypeFlags, TypeSemantics, TypeTreatment, TypeVisibility, WinMdKind};
 use crate::cli::tables::{Table, TableRow, RowDecoder};
 use crate::error::Error;
 use crate::Guid;
+use crate::validation::{self, Violation};
 use crate::cli::heaps::Heaps;
+use crate::cli::pdb::{self, Import, LocalScopeInfo, PdbStream, SequencePoint};
+use crate::cli::ready_to_run::{ReadyToRunImage, READY_TO_RUN_SIGNATURE};
+
+/// Where the metadata root of an image was found.
+enum MetadataSource<D: ImageData> {
+    /// The metadata is embedded in a PE image, and was located through its CLI header.
+    Pe(Box<PeSource<D>>),
+
+    /// The data is the metadata root itself, as in a Portable PDB file or a raw metadata blob.
+    Raw(D),
+}
 
-pub struct MetadataImage<D: Deref<Target = [u8]> = Vec<u8>> {
+/// A PE image, with the CLI header that locates its metadata.
+struct PeSource<D: ImageData> {
     pe: PeImage<D>,
     cli_header: CliHeader,
+}
+
+/// A parsed metadata image.
+///
+/// Everything derived from the headers is computed once at load time, and indexes are built the first time they are
+/// needed. Images are `Send + Sync` whenever their data is, so a single image can be shared between threads with `Arc`.
+pub struct MetadataImage<D: ImageData = Vec<u8>> {
+    source: MetadataSource<D>,
     metadata_header: MetadataHeader,
-    metadata_sizes: MetadataSizes,
+    metadata_range: Range<usize>,
+    pdb_stream: Option<PdbStream>,
+    row_decoder: RowDecoder,
     table_offsets: Vec<(TableIndex, usize)>,
     heaps: Heaps,
+    type_index: OnceLock<HashMap<(String, String), TableHandle>>,
+    enclosing_types: OnceLock<HashMap<usize, TableHandle>>,
 }
 
-impl<D: Deref<Target = [u8]>> MetadataImage<D> {
+impl<D: ImageData> MetadataImage<D> {
     pub fn load_data(data: D) -> Result<MetadataImage<D>, Error> {
         MetadataImage::load(PeImage::load(data)?)
     }
 
     pub fn load(pe: PeImage<D>) -> Result<MetadataImage<D>, Error> {
         // Find the CLI header
-        let cli_header_dir = pe.pe_header()
-            .ok_or(Error::CliHeaderNotFound)?
-            .directories()
-            .iter()
-            .find(|x| x.directory_type == DirectoryType::CliHeader)
-            .ok_or(Error::CliHeaderNotFound)?;
-        let cli_header_buf = &pe[cli_header_dir.range];
-        let cli_header = CliHeader::read(cli_header_buf)?;
-
-        let metadata_buf = &pe[cli_header.metadata];
+        let cli_header_range = pe.directory(DirectoryType::CliHeader).ok_or(Error::CliHeaderNotFound)?;
+        let cli_header = CliHeader::read(pe.read_rva(cli_header_range)?)?;
+
+        let metadata_range = pe.physical_range(cli_header.metadata).ok_or(Error::InvalidMetadata(
+            "metadata root is not contained in a single section".into(),
+        ))?;
         trace!(%cli_header.metadata, "cil metadata located");
+
+        MetadataImage::load_root(MetadataSource::Pe(Box::new(PeSource { pe, cli_header })), metadata_range)
+    }
+
+    /// Loads an image from data that starts directly with the metadata root (the `BSJB` signature), such as a
+    /// Portable PDB file or a metadata blob extracted from a PE image.
+    pub fn load_metadata(data: D) -> Result<MetadataImage<D>, Error> {
+        let metadata_range = 0..data.len();
+        MetadataImage::load_root(MetadataSource::Raw(data), metadata_range)
+    }
+
+    fn load_root(source: MetadataSource<D>, metadata_range: Range<usize>) -> Result<MetadataImage<D>, Error> {
+        let metadata_buf = source.data().read(metadata_range.clone())?;
         let metadata_header = MetadataHeader::read(Cursor::new(metadata_buf))?;
 
+        // Edit-and-Continue deltas use the uncompressed '#-' stream, which has the same layout
         let stream = metadata_header
             .get_stream("#~")
+            .or_else(|| metadata_header.get_stream("#-"))
             .ok_or(Error::InvalidMetadata(
                 "image does not contain a '#~' metadata stream".into(),
             ))?;
-        let stream_buf = &metadata_buf[stream.offset as usize..(stream.offset + stream.size) as usize];
+        let stream_end = stream.offset.checked_add(stream.size).ok_or(Error::InvalidMetadata("'#~' stream is out of range".into()))?;
+        let stream_buf = metadata_buf
+            .get(stream.offset as usize..stream_end as usize)
+            .ok_or(Error::InvalidMetadata("'#~' stream is out of range".into()))?;
         let mut cursor = Cursor::new(stream_buf);
-        let metadata_sizes = MetadataSizes::read(&mut cursor)?;
+        let mut metadata_sizes = MetadataSizes::read(&mut cursor)?;
+        metadata_sizes.set_minimal_delta(metadata_header.get_stream("#JTD").is_some());
 
-        // Scan the image to find the offsets of each table
+        // Portable PDBs record the row counts of the type system tables they refer to in the #Pdb stream
+        let pdb_stream = match metadata_header.get_stream("#Pdb") {
+            Some(stream) => {
+                let pdb_end = stream.offset.checked_add(stream.size).ok_or(Error::InvalidMetadata("'#Pdb' stream is out of range".into()))?;
+                let pdb_buf = metadata_buf
+                    .get(stream.offset as usize..pdb_end as usize)
+                    .ok_or(Error::InvalidMetadata("'#Pdb' stream is out of range".into()))?;
+                let pdb_stream = PdbStream::read(pdb_buf)?;
+                metadata_sizes.set_external_row_counts(&pdb_stream.row_counts);
+                Some(pdb_stream)
+            }
+            None => None,
+        };
+
+        // Scan the image to find the offsets of each table, relative to the metadata root
         let mut table_offsets = Vec::new();
-        let mut table_base_rva = cli_header.metadata.start as usize + stream.offset as usize + cursor.position() as usize;
+        let mut table_base_rva = stream.offset as usize + cursor.position() as usize;
+        let row_decoder = RowDecoder::new(&metadata_sizes);
         {
             fn load_table<T: TableRow>(table_offsets: &mut Vec<(TableIndex, usize)>, offset: &mut usize, row_decoder: &RowDecoder) {
                 let idx = T::INDEX;
@@ -64,82 +124,78 @@ impl<D: Deref<Target = [u8]>> MetadataImage<D> {
                     *offset = *offset + table_size;
                 }
             }
-            fn skip_table(row_decoder: &RowDecoder, idx: TableIndex) {
-                let row_count = row_decoder.row_count(idx);
-                if row_count > 0 {
-                    // TODO: See if we can skip this safely?
-                    // We haven't seen it in the wild yet, but we can't safely skip it without knowing it's sizes.
-                    panic!("table {} is not supported", idx)
-                }
-            }
 
-            let row_decoder = RowDecoder::new(&metadata_sizes);
             load_table::<tables::Module>(&mut table_offsets, &mut table_base_rva, &row_decoder);
             load_table::<tables::TypeRef>(&mut table_offsets, &mut table_base_rva, &row_decoder);
             load_table::<tables::TypeDef>(&mut table_offsets, &mut table_base_rva, &row_decoder);
-            skip_table(&row_decoder, TableIndex::FieldPtr);
+            load_table::<tables::FieldPtr>(&mut table_offsets, &mut table_base_rva, &row_decoder);
             load_table::<tables::Field>(&mut table_offsets, &mut table_base_rva, &row_decoder);
-            skip_table(&row_decoder, TableIndex::MethodPtr);
+            load_table::<tables::MethodPtr>(&mut table_offsets, &mut table_base_rva, &row_decoder);
             load_table::<tables::MethodDef>(&mut table_offsets, &mut table_base_rva, &row_decoder);
-            skip_table(&row_decoder, TableIndex::ParamPtr);
+            load_table::<tables::ParamPtr>(&mut table_offsets, &mut table_base_rva, &row_decoder);
             load_table::<tables::Param>(&mut table_offsets, &mut table_base_rva, &row_decoder);
             load_table::<tables::InterfaceImpl>(&mut table_offsets, &mut table_base_rva, &row_decoder);
             load_table::<tables::MemberRef>(&mut table_offsets, &mut table_base_rva, &row_decoder);
             load_table::<tables::Constant>(&mut table_offsets, &mut table_base_rva, &row_decoder);
             load_table::<tables::CustomAttribute>(&mut table_offsets, &mut table_base_rva, &row_decoder);
-            skip_table(&row_decoder, TableIndex::FieldMarshal);
-            skip_table(&row_decoder, TableIndex::DeclSecurity);
-            skip_table(&row_decoder, TableIndex::ClassLayout);
-            skip_table(&row_decoder, TableIndex::FieldLayout);
-            skip_table(&row_decoder, TableIndex::StandAloneSig);
-            skip_table(&row_decoder, TableIndex::EventMap);
-            skip_table(&row_decoder, TableIndex::EventPtr);
-            skip_table(&row_decoder, TableIndex::Event);
-            skip_table(&row_decoder, TableIndex::PropertyMap);
-            skip_table(&row_decoder, TableIndex::PropertyPtr);
-            skip_table(&row_decoder, TableIndex::Property);
-            skip_table(&row_decoder, TableIndex::MethodSemantics);
-            skip_table(&row_decoder, TableIndex::MethodImpl);
-            skip_table(&row_decoder, TableIndex::ModuleRef);
-            skip_table(&row_decoder, TableIndex::TypeSpec);
-            skip_table(&row_decoder, TableIndex::ImplMap);
-            skip_table(&row_decoder, TableIndex::FieldRva);
-            skip_table(&row_decoder, TableIndex::EncLog);
-            skip_table(&row_decoder, TableIndex::EncMap);
+            load_table::<tables::FieldMarshal>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::DeclSecurity>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::ClassLayout>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::FieldLayout>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::StandAloneSig>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::EventMap>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::EventPtr>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::Event>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::PropertyMap>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::PropertyPtr>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::Property>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::MethodSemantics>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::MethodImpl>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::ModuleRef>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::TypeSpec>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::ImplMap>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::FieldRva>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::EncLog>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::EncMap>(&mut table_offsets, &mut table_base_rva, &row_decoder);
             load_table::<tables::Assembly>(&mut table_offsets, &mut table_base_rva, &row_decoder);
-            skip_table(&row_decoder, TableIndex::AssemblyProcessor);
-            skip_table(&row_decoder, TableIndex::AssemblyOS);
+            load_table::<tables::AssemblyProcessor>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::AssemblyOS>(&mut table_offsets, &mut table_base_rva, &row_decoder);
             load_table::<tables::AssemblyRef>(&mut table_offsets, &mut table_base_rva, &row_decoder);
-            skip_table(&row_decoder, TableIndex::AssemblyRefProcessor);
-            skip_table(&row_decoder, TableIndex::AssemblyRefOS);
-            skip_table(&row_decoder, TableIndex::File);
-            skip_table(&row_decoder, TableIndex::ExportedType);
-            skip_table(&row_decoder, TableIndex::ManifestResource);
-            skip_table(&row_decoder, TableIndex::NestedClass);
-            skip_table(&row_decoder, TableIndex::GenericParam);
-            skip_table(&row_decoder, TableIndex::MethodSpec);
-            skip_table(&row_decoder, TableIndex::GenericParamConstraint);
-            skip_table(&row_decoder, TableIndex::Document);
-            skip_table(&row_decoder, TableIndex::MethodDebugInformation);
-            skip_table(&row_decoder, TableIndex::LocalScope);
-            skip_table(&row_decoder, TableIndex::LocalVariable);
-            skip_table(&row_decoder, TableIndex::LocalConstant);
-            skip_table(&row_decoder, TableIndex::ImportScope);
-            skip_table(&row_decoder, TableIndex::StateMachineMethod);
-            skip_table(&row_decoder, TableIndex::CustomDebugInformation);
-        }
-
-        // Find heap offsets
-        let string_heap = metadata_header.get_stream("#Strings").map(|x| x.offset as usize);
-        let userstring_heap = metadata_header.get_stream("#US").map(|x| x.offset as usize);
-        let guid_heap = metadata_header.get_stream("#GUID").map(|x| x.offset as usize);
-        let blob_heap = metadata_header.get_stream("#Blob").map(|x| x.offset as usize);
+            load_table::<tables::AssemblyRefProcessor>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::AssemblyRefOS>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::File>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::ExportedType>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::ManifestResource>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::NestedClass>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::GenericParam>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::MethodSpec>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::GenericParamConstraint>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::Document>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::MethodDebugInformation>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::LocalScope>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::LocalVariable>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::LocalConstant>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::ImportScope>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::StateMachineMethod>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+            load_table::<tables::CustomDebugInformation>(&mut table_offsets, &mut table_base_rva, &row_decoder);
+        }
+        if table_base_rva > stream_end as usize {
+            return Err(Error::InvalidMetadata("tables run past the end of the '#~' stream".into()));
+        }
+
+        // Find heap ranges
+        let heap_range = |name: &str| metadata_header.get_stream(name).map(|x| x.offset as usize..x.offset as usize + x.size as usize);
+        let string_heap = heap_range("#Strings");
+        let userstring_heap = heap_range("#US");
+        let guid_heap = heap_range("#GUID");
+        let blob_heap = heap_range("#Blob");
 
         Ok(MetadataImage {
-            pe,
-            cli_header,
+            source,
             metadata_header,
-            metadata_sizes,
+            metadata_range,
+            pdb_stream,
+            row_decoder,
             table_offsets,
             heaps: Heaps {
                 string_heap,
@@ -147,15 +203,25 @@ impl<D: Deref<Target = [u8]>> MetadataImage<D> {
                 guid_heap,
                 blob_heap,
             },
+            type_index: OnceLock::new(),
+            enclosing_types: OnceLock::new(),
         })
     }
 
-    pub fn pe(&self) -> &PeImage<D> {
-        &self.pe
+    /// Gets the PE image that contains the metadata, if it was loaded from one.
+    pub fn pe(&self) -> Option<&PeImage<D>> {
+        match &self.source {
+            MetadataSource::Pe(source) => Some(&source.pe),
+            MetadataSource::Raw(_) => None,
+        }
     }
 
-    pub fn cli_header(&self) -> &CliHeader {
-        &self.cli_header
+    /// Gets the CLI header of the PE image that contains the metadata, if it was loaded from one.
+    pub fn cli_header(&self) -> Option<&CliHeader> {
+        match &self.source {
+            MetadataSource::Pe(source) => Some(&source.cli_header),
+            MetadataSource::Raw(_) => None,
+        }
     }
 
     pub fn metadata_header(&self) -> &MetadataHeader {
@@ -163,35 +229,375 @@ impl<D: Deref<Target = [u8]>> MetadataImage<D> {
     }
 
     pub fn row_count(&self, table_index: TableIndex) -> usize {
-        self.metadata_sizes.row_count(table_index)
+        self.row_decoder.row_count(table_index)
     }
 
-    pub fn table<T: TableRow>(&self) -> Table<T> {
-        let decoder = RowDecoder::new(&self.metadata_sizes);
+    /// Gets the bytes of the metadata root, which holds all of the metadata streams.
+    pub fn metadata(&self) -> &[u8] {
+        self.source.data().read(self.metadata_range.clone()).expect("metadata root was validated at load time")
+    }
+
+    pub fn row_decoder(&self) -> &RowDecoder {
+        &self.row_decoder
+    }
+
+    pub fn table<T: TableRow>(&self) -> Table<'_, '_, T> {
         let buffer = match self.table_offsets.binary_search_by_key(&T::INDEX, |(index, _)| *index) {
             Ok(index) => {
                 let start = self.table_offsets[index].1;
-                let size = self.row_count(T::INDEX) * T::row_size(&decoder);
-                &self.pe[start..(start + size)]
+                let size = self.row_count(T::INDEX) * T::row_size(&self.row_decoder);
+                // load_root checked that every table ends inside the tables stream
+                &self.metadata()[start..(start + size)]
             },
             Err(_) => &[]
         };
-        Table::new(buffer, decoder)
+        Table::new(buffer, &self.row_decoder)
+    }
+
+    pub(crate) fn heaps(&self) -> &Heaps {
+        &self.heaps
     }
 
     pub fn get_string(&self, handle: StringHandle) -> Option<&CStr> {
-        let metadata_buf = &self.pe[self.cli_header.metadata];
-        self.heaps.get_string(metadata_buf, handle)
+        self.heaps.get_string(self.metadata(), handle)
     }
 
     pub fn get_guid(&self, handle: GuidHandle) -> Option<Guid> {
-        let metadata_buf = &self.pe[self.cli_header.metadata];
-        self.heaps.get_guid(metadata_buf, handle)
+        self.heaps.get_guid(self.metadata(), handle)
     }
 
     pub fn get_blob(&self, handle: BlobHandle) -> Option<&[u8]> {
-        let metadata_buf = &self.pe[self.cli_header.metadata];
-        self.heaps.get_blob(metadata_buf, handle)
+        self.heaps.get_blob(self.metadata(), handle)
+    }
+
+    /// Gets a string literal from the `#US` heap.
+    pub fn get_user_string(&self, handle: UserStringHandle) -> Option<String> {
+        self.heaps.get_user_string(self.metadata(), handle)
+    }
+
+    /// Finds a top-level type definition by namespace and name.
+    ///
+    /// The index is built from the TypeDef table on first use.
+    pub fn find_type_def(&self, namespace: &str, name: &str) -> Option<TableHandle> {
+        let index = self.type_index.get_or_init(|| {
+            let enclosing_types = self.enclosing_types();
+            let mut index = HashMap::new();
+            for row in self.table::<tables::TypeDef>().rows() {
+                if enclosing_types.contains_key(&row.row()) {
+                    continue;
+                }
+                let text = |handle: Result<StringHandle, Error>| handle.ok()
+                    .and_then(|h| self.get_string(h))
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                index.entry((text(row.type_namespace()), text(row.type_name())))
+                    .or_insert_with(|| TableHandle::new(row.row(), TableIndex::TypeDef));
+            }
+            index
+        });
+        index.get(&(namespace.to_string(), name.to_string())).copied()
+    }
+
+    /// Gets the type that encloses a nested type definition, if it is nested.
+    pub fn enclosing_type(&self, type_def: TableHandle) -> Option<TableHandle> {
+        if type_def.table() != TableIndex::TypeDef {
+            return None;
+        }
+        self.enclosing_types().get(&type_def.index()).copied()
+    }
+
+    fn enclosing_types(&self) -> &HashMap<usize, TableHandle> {
+        self.enclosing_types.get_or_init(|| {
+            self.table::<tables::NestedClass>()
+                .iter()
+                .filter_map(|row| row.ok())
+                .map(|row| (row.nested_class.index(), row.enclosing_class))
+                .collect()
+        })
+    }
+
+    /// Gets the kind of Windows Metadata file the image is, if it is one.
+    pub fn winmd_kind(&self) -> Option<WinMdKind> {
+        WinMdKind::from_version(&self.metadata_header.version)
+    }
+
+    /// Gets the name of a type definition as the CLR sees it, along with the name written in the metadata.
+    ///
+    /// Names are only projected in Windows Metadata files. Types that the CLR replaces with .NET types are given the
+    /// name of the .NET type, and the two names of each class in a managed winmd are swapped over.
+    pub fn type_def_name(&self, type_def: TableHandle) -> Result<ProjectedName, Error> {
+        if type_def.table() != TableIndex::TypeDef || type_def.index() == 0 {
+            return Err(Error::InvalidCodedIndex);
+        }
+        let row = self.table::<tables::TypeDef>().read(type_def.index() - 1)?;
+        let name = ProjectedName::unprojected(self.string(row.type_namespace)?, self.string(row.type_name)?);
+
+        let treatment = match self.winmd_kind() {
+            None => TypeTreatment::None,
+            Some(kind) if row.flags.flags().contains(TypeFlags::WindowsRuntime) => match kind {
+                WinMdKind::Platform => cli::find_projection(&name.namespace, &name.name).map_or(TypeTreatment::None, TypeTreatment::Redirected),
+                WinMdKind::Managed if self.needs_winrt_prefix(&row)? => TypeTreatment::PrefixedWinRtName,
+                WinMdKind::Managed => TypeTreatment::None,
+            },
+            Some(WinMdKind::Managed) if Self::is_clr_implementation(&row, &name) => TypeTreatment::UnmangledClrName,
+            Some(_) => TypeTreatment::None,
+        };
+        Ok(name.with_treatment(treatment))
+    }
+
+    /// Gets the name of a type reference as the CLR sees it, along with the name written in the metadata.
+    pub fn type_ref_name(&self, type_ref: TableHandle) -> Result<ProjectedName, Error> {
+        if type_ref.table() != TableIndex::TypeRef || type_ref.index() == 0 {
+            return Err(Error::InvalidCodedIndex);
+        }
+        let row = self.table::<tables::TypeRef>().read(type_ref.index() - 1)?;
+        let name = ProjectedName::unprojected(self.string(row.namespace)?, self.string(row.name)?);
+        let treatment = match self.winmd_kind() {
+            Some(_) => cli::find_projection(&name.namespace, &name.name).map_or(TypeTreatment::None, TypeTreatment::Redirected),
+            None => TypeTreatment::None,
+        };
+        Ok(name.with_treatment(treatment))
+    }
+
+    /// Checks if a type in a managed winmd is the `<CLR>` twin of a Windows Runtime class. Twins are always NotPublic
+    /// and SpecialName, so a public type that happens to have the prefix keeps its name.
+    fn is_clr_implementation(row: &tables::TypeDef, name: &ProjectedName) -> bool {
+        row.flags.visibility() == TypeVisibility::NotPublic
+            && row.flags.flags().contains(TypeFlags::SpecialName)
+            && name.name.starts_with(cli::CLR_PREFIX)
+    }
+
+    /// Checks if a Windows Runtime type in a managed winmd is a public class, which has a `<CLR>` twin. Interfaces,
+    /// structs, enums, delegates and attributes don't.
+    fn needs_winrt_prefix(&self, row: &tables::TypeDef) -> Result<bool, Error> {
+        if row.flags.visibility() != TypeVisibility::Public || row.flags.semantics() == TypeSemantics::Interface {
+            return Ok(false);
+        }
+        if row.extends.table() != TableIndex::TypeRef || row.extends.index() == 0 {
+            return Ok(true);
+        }
+        let base = self.table::<tables::TypeRef>().read(row.extends.index() - 1)?;
+        let special = ["ValueType", "Enum", "MulticastDelegate", "Attribute"];
+        Ok(self.string(base.namespace)? != "System" || !special.contains(&self.string(base.name)?.as_str()))
+    }
+
+    fn string(&self, handle: StringHandle) -> Result<String, Error> {
+        Ok(self.get_string(handle).ok_or(Error::InvalidHeapReference)?.to_string_lossy().into_owned())
+    }
+
+    /// Gets the data of a resource embedded in the image, given its 0-based row in the ManifestResource table.
+    ///
+    /// Returns `None` if the resource lives in another file or assembly.
+    pub fn manifest_resource(&self, index: usize) -> Result<Option<&[u8]>, Error> {
+        let row = self.table::<tables::ManifestResource>().read(index)?;
+        if row.implementation.index() != 0 {
+            return Ok(None);
+        }
+        let (pe, cli_header) = match &self.source {
+            MetadataSource::Pe(source) => (&source.pe, &source.cli_header),
+            MetadataSource::Raw(_) => return Ok(None),
+        };
+
+        // Each resource is a 4-byte length followed by the data, at an offset into the CLI resources directory
+        let resources = pe.read_rva(cli_header.resources)?;
+        let start = row.offset as usize;
+        let len = resources.get(start..(start + 4)).ok_or(Error::DataOutOfRange)?;
+        let len = u32::from_le_bytes([len[0], len[1], len[2], len[3]]) as usize;
+        resources.get((start + 4)..(start + 4 + len)).ok_or(Error::DataOutOfRange).map(Some)
+    }
+
+    /// Reads the ReadyToRun data of the image, if it was precompiled with crossgen.
+    pub fn ready_to_run(&self) -> Result<Option<ReadyToRunImage<'_, D>>, Error> {
+        let (pe, cli_header) = match &self.source {
+            MetadataSource::Pe(source) => (&source.pe, &source.cli_header),
+            MetadataSource::Raw(_) => return Ok(None),
+        };
+        let header = cli_header.managed_native_header;
+        if header.start == 0 || header.len < 4 {
+            return Ok(None);
+        }
+
+        // The same field points to the NGen header in images precompiled by NGen, which isn't supported
+        let signature = pe.read_rva(MemoryRange::new(header.start, 4))?;
+        if u32::from_le_bytes([signature[0], signature[1], signature[2], signature[3]]) != READY_TO_RUN_SIGNATURE {
+            return Ok(None);
+        }
+        ReadyToRunImage::load(pe, header).map(Some)
+    }
+
+    /// Computes the public key token of the assembly, if it has a public key.
+    pub fn public_key_token(&self) -> Result<Option<[u8; 8]>, Error> {
+        Ok(self.assembly_public_key()?.map(cli::public_key_token))
+    }
+
+    /// Gets the public key token of an assembly reference, given its 0-based row in the AssemblyRef table.
+    ///
+    /// References may hold either the full public key or just the token, so this is the way to compare them with the
+    /// token of a referenced assembly.
+    pub fn assembly_ref_token(&self, index: usize) -> Result<Option<[u8; 8]>, Error> {
+        let row = self.table::<tables::AssemblyRef>().read(index)?;
+        let blob = self.get_blob(row.public_key_or_token).ok_or(Error::InvalidHeapReference)?;
+        if blob.is_empty() {
+            Ok(None)
+        } else if row.flags.contains(cli::AssemblyFlags::PublicKey) {
+            Ok(Some(cli::public_key_token(blob)))
+        } else {
+            let token = blob.try_into().map_err(|_| Error::InvalidMetadata("public key token is not 8 bytes".into()))?;
+            Ok(Some(token))
+        }
+    }
+
+    /// Checks the strong-name signature of the assembly against its public key.
+    ///
+    /// Returns `None` if the image is not strong-named.
+    pub fn verify_strong_name(&self) -> Result<Option<StrongNameStatus>, Error> {
+        let (pe, cli_header) = match &self.source {
+            MetadataSource::Pe(source) => (&source.pe, &source.cli_header),
+            MetadataSource::Raw(_) => return Ok(None),
+        };
+        let public_key = match self.assembly_public_key()? {
+            Some(public_key) => public_key,
+            None => return Ok(None),
+        };
+        if cli_header.strong_name.start == 0 || cli_header.strong_name.len == 0 {
+            return Ok(None);
+        }
+        StrongNameKey::read(public_key)?.verify(pe, cli_header.strong_name).map(Some)
+    }
+
+    fn assembly_public_key(&self) -> Result<Option<&[u8]>, Error> {
+        if self.row_count(TableIndex::Assembly) == 0 {
+            return Ok(None);
+        }
+        let row = self.table::<tables::Assembly>().read(0)?;
+        let public_key = self.get_blob(row.public_key).ok_or(Error::InvalidHeapReference)?;
+        Ok(if public_key.is_empty() { None } else { Some(public_key) })
+    }
+
+    /// Gets the `#Pdb` stream, if this image is a Portable PDB.
+    pub fn pdb_stream(&self) -> Option<&PdbStream> {
+        self.pdb_stream.as_ref()
+    }
+
+    /// Gets the path of a document in a Portable PDB.
+    pub fn document_name(&self, document: TableHandle) -> Result<String, Error> {
+        let row = self.pdb_row::<tables::Document>(document)?;
+        let blob = self.get_blob(row.name).ok_or(Error::InvalidHeapReference)?;
+        pdb::read_document_name(blob, |handle| self.get_blob(handle))
+    }
+
+    /// Gets the sequence points of a method in a Portable PDB, in IL offset order.
+    pub fn sequence_points(&self, method: TableHandle) -> Result<Vec<SequencePoint>, Error> {
+        if method.table() != TableIndex::MethodDef {
+            return Err(Error::InvalidMetadata(format!("{} is not a method definition", method)));
+        }
+        // MethodDebugInformation rows correspond one to one with MethodDef rows
+        let handle = TableHandle::new(method.index(), TableIndex::MethodDebugInformation);
+        let row = self.pdb_row::<tables::MethodDebugInformation>(handle)?;
+        if row.sequence_points.0 == 0 {
+            return Ok(Vec::new());
+        }
+        let blob = self.get_blob(row.sequence_points).ok_or(Error::InvalidHeapReference)?;
+        SequencePoint::read_all(blob, row.document)
+    }
+
+    /// Finds the sequence point that covers an IL offset in a method, which gives the source line for that offset.
+    ///
+    /// Returns `None` if no point covers the offset, or if the covering point is hidden.
+    pub fn find_sequence_point(&self, method: TableHandle, il_offset: u32) -> Result<Option<SequencePoint>, Error> {
+        let points = self.sequence_points(method)?;
+        let covering = points.iter().rev().find(|p| p.il_offset <= il_offset);
+        Ok(covering.filter(|p| !p.is_hidden()).copied())
+    }
+
+    /// Gets the local scopes of a method in a Portable PDB, with the variables and constants declared in each.
+    pub fn local_scopes(&self, method: TableHandle) -> Result<Vec<LocalScopeInfo>, Error> {
+        let scopes = self.table::<tables::LocalScope>();
+        let variables = self.table::<tables::LocalVariable>();
+        let constants = self.table::<tables::LocalConstant>();
+
+        // Each scope owns the run of variables and constants up to where the next scope's run starts
+        let mut result = Vec::new();
+        for index in 0..scopes.len() {
+            let scope = scopes.read(index)?;
+            if scope.method.index() != method.index() {
+                continue;
+            }
+            let next = if index + 1 < scopes.len() { Some(scopes.read(index + 1)?) } else { None };
+            let variable_end = next.as_ref().map_or(variables.len() + 1, |n| n.variable_list.index());
+            let constant_end = next.as_ref().map_or(constants.len() + 1, |n| n.constant_list.index());
+
+            let read_run = |start: usize, end: usize, len: usize| (start.max(1)..end.min(len + 1)).map(|i| i - 1);
+            result.push(LocalScopeInfo {
+                start_offset: scope.start_offset,
+                length: scope.length,
+                import_scope: scope.import_scope,
+                variables: read_run(scope.variable_list.index(), variable_end, variables.len())
+                    .map(|i| variables.read(i))
+                    .collect::<Result<_, _>>()?,
+                constants: read_run(scope.constant_list.index(), constant_end, constants.len())
+                    .map(|i| constants.read(i))
+                    .collect::<Result<_, _>>()?,
+            });
+        }
+        Ok(result)
+    }
+
+    /// Gets the imports declared by an import scope in a Portable PDB.
+    pub fn imports(&self, import_scope: TableHandle) -> Result<Vec<Import>, Error> {
+        let row = self.pdb_row::<tables::ImportScope>(import_scope)?;
+        if row.imports.0 == 0 {
+            return Ok(Vec::new());
+        }
+        Import::read_all(self.get_blob(row.imports).ok_or(Error::InvalidHeapReference)?)
+    }
+
+    /// Gets the async or iterator method that a compiler-generated `MoveNext` method was generated from.
+    pub fn kickoff_method(&self, move_next: TableHandle) -> Result<Option<TableHandle>, Error> {
+        for row in self.table::<tables::StateMachineMethod>().iter() {
+            let row = row?;
+            if row.move_next_method.index() == move_next.index() {
+                return Ok(Some(row.kickoff_method));
+            }
+        }
+        Ok(None)
+    }
+
+    fn pdb_row<T: TableRow>(&self, handle: TableHandle) -> Result<T, Error> {
+        let table = self.table::<T>();
+        if handle.index() == 0 || handle.index() > table.len() {
+            return Err(Error::InvalidMetadata(format!("{} does not exist", handle)));
+        }
+        table.read(handle.index() - 1)
+    }
+
+    /// Loads the Portable PDB embedded in the debug directory of the PE image, if there is one.
+    pub fn embedded_pdb(&self) -> Result<Option<MetadataImage<Vec<u8>>>, Error> {
+        let pe = match self.pe() {
+            Some(pe) => pe,
+            None => return Ok(None),
+        };
+        match pe.debug_directory()?.iter().find(|x| x.debug_type == DebugType::EmbeddedPortablePdb) {
+            Some(entry) => {
+                let pdb = pe::read_embedded_pdb(pe.debug_data(entry)?)?;
+                MetadataImage::load_metadata(pdb).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Checks the metadata tables against the validation rules in ECMA-335 Partition II, chapter 22.
+    pub fn validate(&self) -> Vec<Violation> {
+        validation::validate(self)
+    }
+}
+
+impl<D: ImageData> MetadataSource<D> {
+    fn data(&self) -> &D {
+        match self {
+            MetadataSource::Pe(source) => source.pe.data(),
+            MetadataSource::Raw(data) => data,
+        }
     }
 }
 
@@ -199,4 +605,287 @@ impl MetadataImage<Vec<u8>> {
     pub fn read<R: Read>(reader: R) -> Result<MetadataImage<Vec<u8>>, Error> {
         MetadataImage::load(PeImage::read(reader)?)
     }
-}
\ No newline at end of file
+
+    /// Reads an image that starts directly with the metadata root, such as a Portable PDB file.
+    pub fn read_metadata<R: Read>(mut reader: R) -> Result<MetadataImage<Vec<u8>>, Error> {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+        MetadataImage::load_metadata(buf)
+    }
+}
+
+impl MetadataImage<Mmap> {
+    /// Opens an image by memory-mapping the file at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<MetadataImage<Mmap>, Error> {
+        MetadataImage::load(PeImage::open(path)?)
+    }
+}
+
+impl<R: Read + Seek> MetadataImage<StreamingData<R>> {
+    /// Opens an image that only reads the sections it touches from `reader`.
+    pub fn stream(reader: R) -> Result<MetadataImage<StreamingData<R>>, Error> {
+        MetadataImage::load(PeImage::stream(reader)?)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::fs::File;
+    use std::sync::Arc;
+
+    use super::*;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    /// Builds a metadata root holding the given streams.
+    pub(crate) fn metadata_root(streams: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        versioned_metadata_root("v4.0.30319", streams)
+    }
+
+    /// Builds a metadata root with the given version string, holding the given streams.
+    pub(crate) fn versioned_metadata_root(version: &str, streams: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let version_size = (version.len() + 4) / 4 * 4;
+        let header_size = 20 + version_size + streams.iter().map(|(name, _)| 8 + (name.len() + 4) / 4 * 4).sum::<usize>();
+
+        let mut buf = Vec::new();
+        buf.extend_from_slice(b"BSJB");
+        buf.extend_from_slice(&[0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        buf.extend_from_slice(&(version_size as u32).to_le_bytes());
+        buf.extend_from_slice(version.as_bytes());
+        buf.resize(buf.len() + version_size - version.len(), 0);
+        buf.extend_from_slice(&[0x00, 0x00]);
+        buf.extend_from_slice(&(streams.len() as u16).to_le_bytes());
+        let mut offset = header_size;
+        for (name, data) in streams {
+            let size = (data.len() + 3) / 4 * 4;
+            buf.extend_from_slice(&(offset as u32).to_le_bytes());
+            buf.extend_from_slice(&(size as u32).to_le_bytes());
+            buf.extend_from_slice(name.as_bytes());
+            buf.resize((buf.len() + 4) / 4 * 4, 0);
+            offset += size;
+        }
+        assert_eq!(header_size, buf.len());
+
+        for (_, data) in streams {
+            buf.extend_from_slice(data);
+            buf.resize((buf.len() + 3) / 4 * 4, 0);
+        }
+        buf
+    }
+
+    /// Builds a `#~` stream with small heaps, from the row count and row bytes of each table.
+    pub(crate) fn table_stream(tables: &[(TableIndex, u32, &[u8])]) -> Vec<u8> {
+        let mut buf = vec![0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x01];
+        let valid = tables.iter().fold(0u64, |mask, (table, _, _)| mask | (1 << *table as u64));
+        buf.extend_from_slice(&valid.to_le_bytes());
+        buf.extend_from_slice(&0u64.to_le_bytes());
+        for (_, rows, _) in tables {
+            buf.extend_from_slice(&rows.to_le_bytes());
+        }
+        for (_, _, data) in tables {
+            buf.extend_from_slice(data);
+        }
+        buf
+    }
+
+    /// Builds a metadata root with a single Module row named "Test".
+    fn raw_metadata() -> Vec<u8> {
+        metadata_root(&[
+            ("#~", table_stream(&[(TableIndex::Module, 1, &[0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00])])),
+            ("#Strings", b"\0Test\0".to_vec()),
+        ])
+    }
+
+    /// Builds a Portable PDB with one document, "src/a.cs", and sequence points for MethodDef 1.
+    fn portable_pdb() -> Vec<u8> {
+        let mut pdb = vec![0x11; 20];
+        pdb.extend_from_slice(&0u32.to_le_bytes());
+        pdb.extend_from_slice(&(1u64 << TableIndex::MethodDef as u64).to_le_bytes());
+        pdb.extend_from_slice(&1u32.to_le_bytes());
+
+        let blobs = vec![
+            0x00,
+            0x03, b'/', 0x05, 0x09, // Document name
+            0x03, b's', b'r', b'c',
+            0x04, b'a', b'.', b'c', b's',
+            0x0B, 0x00, 0x00, 0x00, 0x01, 0x05, 0x09, 0x04, 0x01, 0x00, 0x02, 0x00, // Sequence points
+        ];
+
+        metadata_root(&[
+            ("#Pdb", pdb),
+            ("#~", table_stream(&[
+                (TableIndex::Document, 1, &[0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+                (TableIndex::MethodDebugInformation, 1, &[0x01, 0x00, 0x0E, 0x00]),
+            ])),
+            ("#Blob", blobs),
+        ])
+    }
+
+    #[test]
+    pub fn load_raw_metadata() {
+        let image = MetadataImage::load_metadata(raw_metadata()).unwrap();
+        assert!(image.pe().is_none());
+        assert!(image.cli_header().is_none());
+        assert_eq!(1, image.row_count(TableIndex::Module));
+
+        let module = image.table::<tables::Module>().read(0).unwrap();
+        assert_eq!(c"Test", image.get_string(module.name).unwrap());
+    }
+
+    #[test]
+    pub fn reject_truncated_metadata() {
+        // Cutting the root anywhere inside its streams is an error, not a panic
+        let metadata = raw_metadata();
+        let tables_end = metadata.len() - 8;
+        for len in 0..tables_end {
+            assert!(MetadataImage::load_metadata(metadata[..len].to_vec()).is_err(), "{}", len);
+        }
+
+        // More rows than the stream holds
+        let overrun = metadata_root(&[
+            ("#~", table_stream(&[(TableIndex::Module, 5, &[0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00])])),
+            ("#Strings", b"\0Test\0".to_vec()),
+        ]);
+        assert!(matches!(MetadataImage::load_metadata(overrun), Err(Error::InvalidMetadata(_))));
+    }
+
+    #[test]
+    pub fn map_il_offsets_to_lines() {
+        let image = MetadataImage::load_metadata(portable_pdb()).unwrap();
+        let pdb_stream = image.pdb_stream().unwrap();
+        assert_eq!(vec![(TableIndex::MethodDef, 1)], pdb_stream.row_counts);
+        assert_eq!(0, image.row_count(TableIndex::MethodDef));
+
+        assert_eq!("src/a.cs", image.document_name(TableHandle::new(1, TableIndex::Document)).unwrap());
+
+        let method = TableHandle::new(1, TableIndex::MethodDef);
+        let point = image.find_sequence_point(method, 3).unwrap().unwrap();
+        assert_eq!((5, 9, 5, 10), (point.start_line, point.start_column, point.end_line, point.end_column));
+        let point = image.find_sequence_point(method, 4).unwrap().unwrap();
+        assert_eq!((6, 9, 7, 9), (point.start_line, point.start_column, point.end_line, point.end_column));
+        assert_eq!(1, point.document.index());
+
+        let not_a_method = TableHandle::new(1, TableIndex::Document);
+        assert!(matches!(image.sequence_points(not_a_method), Err(Error::InvalidMetadata(_))));
+    }
+
+    /// Builds a managed winmd with a class `Ns.Widget`, its `<CLR>` twin, a `<CLR>Widget` that isn't SpecialName, and
+    /// references to `IVector`1` and `Object`.
+    fn managed_winmd() -> Vec<u8> {
+        versioned_metadata_root("WindowsRuntime 1.4;CLR v4.0.30319", &[
+            ("#~", table_stream(&[
+                (TableIndex::Module, 1, &[0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+                (TableIndex::TypeRef, 2, &[
+                    0x06, 0x00, 0x3F, 0x00, 0x20, 0x00,
+                    0x06, 0x00, 0x50, 0x00, 0x49, 0x00,
+                ]),
+                (TableIndex::TypeDef, 4, &[
+                    0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00,
+                    0x00, 0x04, 0x00, 0x00, 0x0D, 0x00, 0x0A, 0x00, 0x09, 0x00, 0x01, 0x00, 0x01, 0x00,
+                    0x01, 0x40, 0x00, 0x00, 0x19, 0x00, 0x0A, 0x00, 0x09, 0x00, 0x01, 0x00, 0x01, 0x00,
+                    0x00, 0x00, 0x00, 0x00, 0x0D, 0x00, 0x0A, 0x00, 0x09, 0x00, 0x01, 0x00, 0x01, 0x00,
+                ]),
+            ])),
+            ("#Strings", b"\0<Module>\0Ns\0<CLR>Widget\0Widget\0Windows.Foundation.Collections\0IVector`1\0System\0Object\0".to_vec()),
+        ])
+    }
+
+    #[test]
+    pub fn project_winmd_names() {
+        let image = MetadataImage::load_metadata(managed_winmd()).unwrap();
+        assert_eq!(Some(WinMdKind::Managed), image.winmd_kind());
+
+        let clr = image.type_def_name(TableHandle::new(2, TableIndex::TypeDef)).unwrap();
+        assert_eq!(("Ns.Widget", "<CLR>Widget", TypeTreatment::UnmangledClrName), (clr.to_string().as_str(), clr.unprojected_name.as_str(), clr.treatment));
+        let winrt = image.type_def_name(TableHandle::new(3, TableIndex::TypeDef)).unwrap();
+        assert_eq!(("Ns.<WinRT>Widget", TypeTreatment::PrefixedWinRtName), (winrt.to_string().as_str(), winrt.treatment));
+        let plain = image.type_def_name(TableHandle::new(4, TableIndex::TypeDef)).unwrap();
+        assert_eq!(("Ns.<CLR>Widget", TypeTreatment::None), (plain.to_string().as_str(), plain.treatment));
+
+        let vector = image.type_ref_name(TableHandle::new(1, TableIndex::TypeRef)).unwrap();
+        assert_eq!("System.Collections.Generic.IList`1", vector.to_string());
+        assert_eq!("Windows.Foundation.Collections", vector.unprojected_namespace);
+        let object = image.type_ref_name(TableHandle::new(2, TableIndex::TypeRef)).unwrap();
+        assert_eq!(TypeTreatment::None, object.treatment);
+
+        // Ordinary metadata is never projected
+        let image = MetadataImage::load_metadata(raw_metadata()).unwrap();
+        assert_eq!(None, image.winmd_kind());
+    }
+
+    /// Builds a Webcil image with one section holding a CLI header and `raw_metadata`.
+    fn webcil() -> Vec<u8> {
+        let metadata = raw_metadata();
+        let mut section = Vec::new();
+        section.extend_from_slice(&72u32.to_le_bytes());
+        section.extend_from_slice(&[0x02, 0x00, 0x05, 0x00]);
+        section.extend_from_slice(&0x2048u32.to_le_bytes());
+        section.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+        section.resize(72, 0);
+        section.extend_from_slice(&metadata);
+
+        let mut buf = b"WbIL".to_vec();
+        buf.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00]);
+        buf.extend_from_slice(&0x2000u32.to_le_bytes());
+        buf.extend_from_slice(&72u32.to_le_bytes());
+        buf.extend_from_slice(&[0u8; 8]);
+        for value in [section.len() as u32, 0x2000, section.len() as u32, 0x40] {
+            buf.extend_from_slice(&value.to_le_bytes());
+        }
+        buf.resize(0x40, 0);
+        buf.extend_from_slice(&section);
+        buf
+    }
+
+    #[test]
+    pub fn load_webcil() {
+        let image = MetadataImage::load_data(webcil()).unwrap();
+        assert!(image.pe().unwrap().webcil_header().is_some());
+        assert_eq!(5, image.cli_header().unwrap().minor_runtime_version);
+        let module = image.table::<tables::Module>().read(0).unwrap();
+        assert_eq!(c"Test", image.get_string(module.name).unwrap());
+
+        // Wrap the payload in a wasm module, after a passive segment holding its size
+        let payload = webcil();
+        let leb128 = |mut value: usize| {
+            let mut bytes = Vec::new();
+            loop {
+                let byte = (value & 0x7F) as u8;
+                value >>= 7;
+                if value == 0 {
+                    bytes.push(byte);
+                    return bytes;
+                }
+                bytes.push(byte | 0x80);
+            }
+        };
+        let mut data_section = vec![0x02, 0x01, 0x04];
+        data_section.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        data_section.push(0x01);
+        data_section.extend(leb128(payload.len()));
+        data_section.extend_from_slice(&payload);
+
+        let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+        wasm.extend_from_slice(&[0x01, 0x04, 0x01, 0x60, 0x00, 0x00]);
+        wasm.push(0x0B);
+        wasm.extend(leb128(data_section.len()));
+        wasm.extend(data_section);
+
+        let image = MetadataImage::load_data(wasm).unwrap();
+        let module = image.table::<tables::Module>().read(0).unwrap();
+        assert_eq!(c"Test", image.get_string(module.name).unwrap());
+    }
+
+    #[test]
+    pub fn load_rejects_raw_metadata() {
+        assert!(MetadataImage::load_data(raw_metadata()).is_err());
+    }
+
+    #[test]
+    pub fn images_can_be_shared_between_threads() {
+        assert_send_sync::<MetadataImage<Vec<u8>>>();
+        assert_send_sync::<MetadataImage<Mmap>>();
+        assert_send_sync::<MetadataImage<StreamingData<File>>>();
+        assert_send_sync::<Arc<MetadataImage>>();
+    }
+}
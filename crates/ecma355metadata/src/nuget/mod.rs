@@ -0,0 +1,5 @@
+mod package;
+mod target_framework;
+
+pub use self::package::{AssetKind, NuGetPackage, PackageAssembly};
+pub use self::target_framework::{FrameworkFamily, TargetFramework};
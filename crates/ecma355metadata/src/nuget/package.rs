@@ -0,0 +1,204 @@
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::Path;
+
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+use crate::error::Error;
+use crate::nuget::TargetFramework;
+use crate::MetadataImage;
+
+/// The folders of a package that hold assemblies.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AssetKind {
+    /// `lib/`, the assemblies used at runtime, and at compile time unless the package has reference assemblies.
+    Lib,
+
+    /// `ref/`, the reference assemblies used at compile time.
+    Ref,
+}
+
+impl AssetKind {
+    fn folder(self) -> &'static str {
+        match self {
+            AssetKind::Lib => "lib",
+            AssetKind::Ref => "ref",
+        }
+    }
+}
+
+impl_display_via_debug!(AssetKind);
+
+/// An assembly in a package.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PackageAssembly {
+    /// The path of the entry in the package archive.
+    pub path: String,
+    pub kind: AssetKind,
+
+    /// The framework the assembly targets, from the name of the folder it is in.
+    pub framework: TargetFramework,
+}
+
+impl PackageAssembly {
+    /// Gets the file name of the assembly.
+    pub fn file_name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+}
+
+/// A NuGet package (`.nupkg`), which is a zip archive.
+pub struct NuGetPackage<R: Read + Seek> {
+    archive: ZipArchive<R>,
+}
+
+impl NuGetPackage<File> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<NuGetPackage<File>, Error> {
+        NuGetPackage::new(File::open(path)?)
+    }
+}
+
+impl<R: Read + Seek> NuGetPackage<R> {
+    pub fn new(reader: R) -> Result<NuGetPackage<R>, Error> {
+        Ok(NuGetPackage {
+            archive: ZipArchive::new(reader).map_err(zip_error)?,
+        })
+    }
+
+    /// Lists the assemblies in the `lib/` and `ref/` folders of the package.
+    ///
+    /// Assemblies must be directly inside a framework folder, like `lib/net8.0/A.dll`. Assemblies directly inside `lib/`,
+    /// which older packages use, apply to any framework.
+    pub fn assemblies(&self) -> Vec<PackageAssembly> {
+        let mut assemblies = Vec::new();
+        for path in self.archive.file_names() {
+            let parts: Vec<&str> = path.split('/').collect();
+            let kind = match [AssetKind::Lib, AssetKind::Ref].into_iter().find(|k| parts[0].eq_ignore_ascii_case(k.folder())) {
+                Some(kind) => kind,
+                None => continue,
+            };
+            let (framework, file_name) = match parts[..] {
+                [_, file_name] => ("", file_name),
+                [_, framework, file_name] => (framework, file_name),
+                _ => continue,
+            };
+            if !file_name.to_ascii_lowercase().ends_with(".dll") {
+                continue;
+            }
+            assemblies.push(PackageAssembly {
+                path: path.to_string(),
+                kind,
+                framework: TargetFramework::parse(framework),
+            });
+        }
+        assemblies.sort_by(|a, b| a.path.cmp(&b.path));
+        assemblies
+    }
+
+    /// Lists the frameworks the package has assemblies of a kind for.
+    pub fn frameworks(&self, kind: AssetKind) -> Vec<TargetFramework> {
+        let mut frameworks: Vec<TargetFramework> = Vec::new();
+        for assembly in self.assemblies().into_iter().filter(|a| a.kind == kind) {
+            if !frameworks.contains(&assembly.framework) {
+                frameworks.push(assembly.framework);
+            }
+        }
+        frameworks
+    }
+
+    /// Picks the framework folder that a project targeting `target` would use assemblies of a kind from.
+    pub fn best_match(&self, kind: AssetKind, target: &TargetFramework) -> Option<TargetFramework> {
+        target.best_match(&self.frameworks(kind)).cloned()
+    }
+
+    /// Gets the assemblies that a project targeting `target` would use from the package.
+    ///
+    /// Returns an empty list if no framework folder is compatible.
+    pub fn assemblies_for(&self, kind: AssetKind, target: &TargetFramework) -> Vec<PackageAssembly> {
+        match self.best_match(kind, target) {
+            Some(framework) => self.assemblies().into_iter().filter(|a| a.kind == kind && a.framework == framework).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Reads an entry of the package into memory, inflating it.
+    pub fn read_entry(&mut self, path: &str) -> Result<Vec<u8>, Error> {
+        let file = self.archive.by_name(path).map_err(zip_error)?;
+        // The size is from the central directory, so read one byte past it to catch an entry that's bigger
+        let size = file.size();
+        let mut data = Vec::new();
+        file.take(size.saturating_add(1)).read_to_end(&mut data)?;
+        if data.len() as u64 != size {
+            return Err(Error::InvalidPackage(format!("{} inflated to {} bytes, expected {}", path, data.len(), size)));
+        }
+        Ok(data)
+    }
+
+    /// Opens an assembly in the package, without extracting it to disk.
+    pub fn open_assembly(&mut self, path: &str) -> Result<MetadataImage<Vec<u8>>, Error> {
+        MetadataImage::load_data(self.read_entry(path)?)
+    }
+}
+
+fn zip_error(error: ZipError) -> Error {
+    match error {
+        ZipError::Io(e) => Error::IoError(e),
+        e => Error::InvalidPackage(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    use super::*;
+
+    fn package(files: &[&str]) -> NuGetPackage<Cursor<Vec<u8>>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for file in files {
+            writer.start_file(*file, FileOptions::default()).unwrap();
+            writer.write_all(file.as_bytes()).unwrap();
+        }
+        let buf = writer.finish().unwrap().into_inner();
+        NuGetPackage::new(Cursor::new(buf)).unwrap()
+    }
+
+    #[test]
+    pub fn list_package_assemblies() {
+        let mut package = package(&[
+            "Example.nuspec",
+            "lib/net462/Example.dll",
+            "lib/net462/Example.xml",
+            "lib/netstandard2.0/Example.dll",
+            "lib/net8.0/Example.dll",
+            "lib/net8.0/Example.Extra.dll",
+            "ref/net8.0/Example.dll",
+            "runtimes/win-x64/lib/net8.0/Example.dll",
+        ]);
+
+        let assemblies = package.assemblies();
+        assert_eq!(5, assemblies.len());
+        assert_eq!(AssetKind::Ref, assemblies[4].kind);
+        assert_eq!("Example.dll", assemblies[4].file_name());
+
+        let frameworks: Vec<_> = package.frameworks(AssetKind::Lib).iter().map(|f| f.to_string()).collect();
+        assert_eq!(vec!["net462", "net8.0", "netstandard2.0"], frameworks);
+
+        let net9 = TargetFramework::parse("net9.0");
+        let paths: Vec<_> = package.assemblies_for(AssetKind::Lib, &net9).into_iter().map(|a| a.path).collect();
+        assert_eq!(vec!["lib/net8.0/Example.Extra.dll", "lib/net8.0/Example.dll"], paths);
+
+        let netcore = TargetFramework::parse("netcoreapp3.1");
+        assert_eq!(Some(TargetFramework::parse("netstandard2.0")), package.best_match(AssetKind::Lib, &netcore));
+        assert_eq!(None, package.best_match(AssetKind::Ref, &netcore));
+
+        assert_eq!(b"lib/net462/Example.dll".to_vec(), package.read_entry("lib/net462/Example.dll").unwrap());
+        // The entry holds its own path rather than an image, so it doesn't start with "MZ"
+        assert!(matches!(package.open_assembly("lib/net462/Example.dll"), Err(Error::InvalidSignature)));
+        assert!(matches!(package.read_entry("lib/missing.dll"), Err(Error::InvalidPackage(_))));
+    }
+}
@@ -0,0 +1,213 @@
+/// The family of a target framework.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FrameworkFamily {
+    /// .NET Framework, such as `net472`.
+    NetFramework,
+
+    /// .NET Core and .NET 5 and later, such as `netcoreapp3.1` and `net8.0`.
+    NetCoreApp,
+
+    /// .NET Standard, such as `netstandard2.0`.
+    NetStandard,
+
+    /// Assets placed directly in `lib/` or `ref/`, which apply to every framework.
+    Any,
+
+    /// A framework this crate has no compatibility rules for, such as a portable profile. These only match themselves.
+    Other(String),
+}
+
+/// A target framework moniker (TFM), as used in package folder names.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TargetFramework {
+    pub family: FrameworkFamily,
+    pub version: [u16; 4],
+
+    /// The OS of a platform-specific framework, such as `windows` in `net6.0-windows`.
+    pub platform: Option<String>,
+}
+
+impl TargetFramework {
+    /// Parses a short framework name, such as `net8.0`, `netstandard2.0` or `net472`.
+    pub fn parse(name: &str) -> TargetFramework {
+        let name = name.to_ascii_lowercase();
+        let (framework, platform) = match name.split_once('-') {
+            Some((framework, platform)) => (framework.to_string(), Some(platform.to_string())),
+            None => (name.clone(), None),
+        };
+
+        let parsed = if framework.is_empty() || framework == "any" {
+            Some((FrameworkFamily::Any, [0; 4]))
+        } else if let Some(version) = framework.strip_prefix("netcoreapp") {
+            dotted_version(version).map(|v| (FrameworkFamily::NetCoreApp, v))
+        } else if let Some(version) = framework.strip_prefix("netstandard") {
+            dotted_version(version).map(|v| (FrameworkFamily::NetStandard, v))
+        } else if let Some(version) = framework.strip_prefix("net") {
+            if version.contains('.') {
+                // net5.0 and later are .NET Core, and always have a dot
+                dotted_version(version).filter(|v| v[0] >= 5).map(|v| (FrameworkFamily::NetCoreApp, v))
+            } else {
+                digit_version(version).map(|v| (FrameworkFamily::NetFramework, v))
+            }
+        } else {
+            None
+        };
+
+        match parsed {
+            Some((family, version)) => TargetFramework { family, version, platform },
+            None => TargetFramework {
+                family: FrameworkFamily::Other(name),
+                version: [0; 4],
+                platform: None,
+            },
+        }
+    }
+
+    /// Checks if a project targeting this framework can use assets built for `candidate`.
+    pub fn can_use(&self, candidate: &TargetFramework) -> bool {
+        match &candidate.family {
+            FrameworkFamily::Any => true,
+            FrameworkFamily::Other(_) => candidate == self,
+            family if *family == self.family => candidate.version <= self.version && self.platform_allows(candidate),
+            FrameworkFamily::NetStandard => match self.supported_net_standard() {
+                Some(version) => candidate.version <= version,
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Picks the candidate that NuGet would pick for a project targeting this framework, if any are compatible.
+    ///
+    /// The same framework family wins over .NET Standard, which wins over assets for any framework. Within a family the
+    /// highest version wins, and then platform-specific assets win over portable ones.
+    pub fn best_match<'a, I: IntoIterator<Item = &'a TargetFramework>>(&self, candidates: I) -> Option<&'a TargetFramework> {
+        candidates
+            .into_iter()
+            .filter(|c| self.can_use(c))
+            .max_by_key(|c| {
+                let family_rank = match &c.family {
+                    FrameworkFamily::Any => 0,
+                    FrameworkFamily::NetStandard if self.family != FrameworkFamily::NetStandard => 1,
+                    _ => 2,
+                };
+                (family_rank, c.version, c.platform.is_some())
+            })
+    }
+
+    fn platform_allows(&self, candidate: &TargetFramework) -> bool {
+        match (&self.platform, &candidate.platform) {
+            (_, None) => true,
+            (Some(platform), Some(candidate)) => platform_name(platform) == platform_name(candidate),
+            (None, Some(_)) => false,
+        }
+    }
+
+    /// Gets the highest version of .NET Standard that this framework implements.
+    fn supported_net_standard(&self) -> Option<[u16; 4]> {
+        let v = self.version;
+        match self.family {
+            FrameworkFamily::NetCoreApp if v >= [3, 0, 0, 0] => Some([2, 1, 0, 0]),
+            FrameworkFamily::NetCoreApp if v >= [2, 0, 0, 0] => Some([2, 0, 0, 0]),
+            FrameworkFamily::NetCoreApp => Some([1, 6, 0, 0]),
+            FrameworkFamily::NetFramework if v >= [4, 6, 1, 0] => Some([2, 0, 0, 0]),
+            FrameworkFamily::NetFramework if v >= [4, 6, 0, 0] => Some([1, 3, 0, 0]),
+            FrameworkFamily::NetFramework if v >= [4, 5, 1, 0] => Some([1, 2, 0, 0]),
+            FrameworkFamily::NetFramework if v >= [4, 5, 0, 0] => Some([1, 1, 0, 0]),
+            _ => None,
+        }
+    }
+}
+
+impl ::std::fmt::Display for TargetFramework {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        let v = self.version;
+        match &self.family {
+            FrameworkFamily::NetFramework => {
+                write!(f, "net{}{}", v[0], v[1])?;
+                if v[2] != 0 {
+                    write!(f, "{}", v[2])?;
+                }
+            }
+            FrameworkFamily::NetCoreApp if v[0] >= 5 => write!(f, "net{}.{}", v[0], v[1])?,
+            FrameworkFamily::NetCoreApp => write!(f, "netcoreapp{}.{}", v[0], v[1])?,
+            FrameworkFamily::NetStandard => write!(f, "netstandard{}.{}", v[0], v[1])?,
+            FrameworkFamily::Any => write!(f, "any")?,
+            FrameworkFamily::Other(name) => return write!(f, "{}", name),
+        }
+        if let Some(platform) = &self.platform {
+            write!(f, "-{}", platform)?;
+        }
+        Ok(())
+    }
+}
+
+/// Strips the version from a platform, such as `windows7.0`.
+fn platform_name(platform: &str) -> &str {
+    platform.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.')
+}
+
+/// Parses a version like `3.1` or `2.0.3`.
+fn dotted_version(version: &str) -> Option<[u16; 4]> {
+    let mut result = [0u16; 4];
+    let mut parts = 0;
+    for (i, part) in version.split('.').enumerate() {
+        if i >= 4 {
+            return None;
+        }
+        result[i] = part.parse().ok()?;
+        parts += 1;
+    }
+    if parts == 0 {
+        None
+    } else {
+        Some(result)
+    }
+}
+
+/// Parses a .NET Framework version like `472`, where each digit is a component.
+fn digit_version(version: &str) -> Option<[u16; 4]> {
+    if version.is_empty() || version.len() > 4 || !version.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let mut result = [0u16; 4];
+    for (i, digit) in version.bytes().enumerate() {
+        result[i] = (digit - b'0') as u16;
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn parse_framework_names() {
+        for name in ["net472", "net48", "netcoreapp3.1", "net8.0", "net6.0-windows", "netstandard2.0", "any"] {
+            assert_eq!(name, TargetFramework::parse(name).to_string());
+        }
+        assert_eq!(FrameworkFamily::NetFramework, TargetFramework::parse("net472").family);
+        assert_eq!([4, 7, 2, 0], TargetFramework::parse("net472").version);
+        assert_eq!(FrameworkFamily::NetCoreApp, TargetFramework::parse("NET5.0").family);
+        assert_eq!(FrameworkFamily::Other("portable-net45+win8".into()), TargetFramework::parse("portable-net45+win8").family);
+    }
+
+    #[test]
+    pub fn pick_best_match() {
+        let candidates: Vec<_> = ["net462", "netstandard1.3", "netstandard2.0", "net6.0", "net6.0-windows", "net8.0", "any"]
+            .iter()
+            .map(|n| TargetFramework::parse(n))
+            .collect();
+        let best = |target: &str| TargetFramework::parse(target).best_match(&candidates).map(|f| f.to_string());
+
+        assert_eq!(Some("net8.0".into()), best("net9.0"));
+        assert_eq!(Some("net6.0".into()), best("net7.0"));
+        assert_eq!(Some("net6.0-windows".into()), best("net7.0-windows"));
+        assert_eq!(Some("netstandard2.0".into()), best("netcoreapp3.1"));
+        assert_eq!(Some("net462".into()), best("net48"));
+        assert_eq!(Some("netstandard1.3".into()), best("net46"));
+        assert_eq!(Some("any".into()), best("net45"));
+        assert_eq!(Some("netstandard2.0".into()), TargetFramework::parse("net8.0").best_match(&candidates[0..3]).map(|f| f.to_string()));
+        assert_eq!(None, TargetFramework::parse("net40").best_match(&candidates[0..3]));
+    }
+}
@@ -0,0 +1,352 @@
+use std::ops::Range;
+
+use byteorder::{ByteOrder, LittleEndian};
+use sha1::Sha1;
+use sha2::digest::DynDigest;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+use crate::error::Error;
+use crate::pe::{DirectoryType, ImageData, PeImage};
+
+/// The values of the wCertificateType field of a certificate table entry.
+pub mod certificate_types {
+    pub const X509: u16 = 0x0001;
+    pub const PKCS_SIGNED_DATA: u16 = 0x0002;
+    pub const TS_STACK_SIGNED: u16 = 0x0004;
+}
+
+/// The hash algorithms that an Authenticode signature can use for the image hash.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    fn from_oid(oid: &[u8]) -> Option<HashAlgorithm> {
+        match oid {
+            OID_SHA1 => Some(HashAlgorithm::Sha1),
+            OID_SHA256 => Some(HashAlgorithm::Sha256),
+            OID_SHA384 => Some(HashAlgorithm::Sha384),
+            OID_SHA512 => Some(HashAlgorithm::Sha512),
+            _ => None,
+        }
+    }
+
+    /// Creates a hasher that computes digests with this algorithm.
+    pub fn hasher(self) -> Box<dyn DynDigest> {
+        match self {
+            HashAlgorithm::Sha1 => Box::new(Sha1::new()),
+            HashAlgorithm::Sha256 => Box::new(Sha256::new()),
+            HashAlgorithm::Sha384 => Box::new(Sha384::new()),
+            HashAlgorithm::Sha512 => Box::new(Sha512::new()),
+        }
+    }
+}
+
+impl ::std::fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match self {
+            HashAlgorithm::Sha1 => write!(f, "SHA1"),
+            HashAlgorithm::Sha256 => write!(f, "SHA256"),
+            HashAlgorithm::Sha384 => write!(f, "SHA384"),
+            HashAlgorithm::Sha512 => write!(f, "SHA512"),
+        }
+    }
+}
+
+/// An entry in the certificate table (WIN_CERTIFICATE).
+#[derive(Debug)]
+pub struct Certificate<'a> {
+    pub revision: u16,
+    pub certificate_type: u16,
+    pub data: &'a [u8],
+}
+
+/// The image hash recorded in an Authenticode signature.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SignedDigest {
+    pub algorithm: HashAlgorithm,
+    pub digest: Vec<u8>,
+}
+
+const CERTIFICATE_HEADER_SIZE: usize = 8;
+
+impl<'a> Certificate<'a> {
+    /// Gets the PKCS#7 SignedData blob, if this is an Authenticode signature.
+    pub fn pkcs7(&self) -> Option<&'a [u8]> {
+        if self.certificate_type == certificate_types::PKCS_SIGNED_DATA {
+            Some(self.data)
+        } else {
+            None
+        }
+    }
+
+    /// Extracts the image hash that the signature covers.
+    ///
+    /// This only decodes the SpcIndirectDataContent of the signature. It does not check the signature itself, or the
+    /// certificate chain.
+    pub fn signed_digest(&self) -> Result<SignedDigest, Error> {
+        let pkcs7 = self.pkcs7().ok_or_else(|| Error::InvalidCertificate("not a PKCS#7 signature".into()))?;
+
+        // ContentInfo ::= SEQUENCE { contentType, [0] EXPLICIT SignedData }
+        let mut content_info = Der::new(pkcs7).expect(SEQUENCE)?;
+        if content_info.expect(OBJECT_IDENTIFIER)?.buf != OID_SIGNED_DATA {
+            return Err(Error::InvalidCertificate("content is not SignedData".into()));
+        }
+
+        // SignedData ::= SEQUENCE { version, digestAlgorithms, encapContentInfo, ... }
+        let mut signed_data = content_info.expect(CONTEXT_0)?.expect(SEQUENCE)?;
+        signed_data.expect(INTEGER)?;
+        signed_data.expect(SET)?;
+        let mut encapsulated = signed_data.expect(SEQUENCE)?;
+        if encapsulated.expect(OBJECT_IDENTIFIER)?.buf != OID_SPC_INDIRECT_DATA {
+            return Err(Error::InvalidCertificate("content is not SpcIndirectDataContent".into()));
+        }
+
+        // SpcIndirectDataContent ::= SEQUENCE { data, messageDigest DigestInfo }
+        let mut indirect_data = encapsulated.expect(CONTEXT_0)?.expect(SEQUENCE)?;
+        indirect_data.expect(SEQUENCE)?;
+        let mut digest_info = indirect_data.expect(SEQUENCE)?;
+        let algorithm = digest_info.expect(SEQUENCE)?.expect(OBJECT_IDENTIFIER)?;
+        let digest = digest_info.expect(OCTET_STRING)?;
+
+        Ok(SignedDigest {
+            algorithm: HashAlgorithm::from_oid(algorithm.buf).ok_or_else(|| Error::InvalidCertificate("unsupported digest algorithm".into()))?,
+            digest: digest.buf.to_vec(),
+        })
+    }
+}
+
+/// Reads the entries of a certificate table.
+pub fn read_certificates(mut buf: &[u8]) -> Result<Vec<Certificate<'_>>, Error> {
+    let mut certificates = Vec::new();
+    while buf.len() >= CERTIFICATE_HEADER_SIZE {
+        let length = LittleEndian::read_u32(&buf[0..4]) as usize;
+        if length < CERTIFICATE_HEADER_SIZE || length > buf.len() {
+            return Err(Error::DataOutOfRange);
+        }
+        certificates.push(Certificate {
+            revision: LittleEndian::read_u16(&buf[4..6]),
+            certificate_type: LittleEndian::read_u16(&buf[6..8]),
+            data: &buf[CERTIFICATE_HEADER_SIZE..length],
+        });
+
+        // Entries are aligned to 8 bytes
+        buf = &buf[((length + 7) & !7).min(buf.len())..];
+    }
+    Ok(certificates)
+}
+
+impl<D: ImageData> PeImage<D> {
+    /// Reads the entries of the certificate table.
+    ///
+    /// Unlike the other data directories, the certificate table is located by file offset, and is not mapped into memory.
+    pub fn certificates(&self) -> Result<Vec<Certificate<'_>>, Error> {
+        match self.certificate_table() {
+            Some(range) => read_certificates(self.data().read(range)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Computes the Authenticode hash of the image.
+    ///
+    /// The hash covers the whole file except the checksum, the certificate table directory entry and the certificate
+    /// table itself, so it is the same before and after the image is signed.
+    pub fn authenticode_hash(&self, algorithm: HashAlgorithm) -> Result<Vec<u8>, Error> {
+        self.pe_header().ok_or(Error::NotAPortableExecutable)?;
+        let mut excluded = Vec::with_capacity(3);
+        excluded.push(self.checksum_field());
+        if let Some(entry) = self.certificate_directory_entry() {
+            excluded.push(entry);
+        }
+        if let Some(range) = self.certificate_table() {
+            excluded.push(range);
+        }
+        excluded.sort_by_key(|r| r.start);
+
+        let mut hasher = algorithm.hasher();
+        let mut position = 0;
+        for range in excluded.iter().chain(std::iter::once(&(self.data().len()..self.data().len()))) {
+            if range.start > position {
+                hasher.update(self.data().read(position..range.start)?);
+            }
+            position = position.max(range.end);
+        }
+        Ok(hasher.finalize().to_vec())
+    }
+
+    /// Checks if the image hash matches the digest recorded in the first Authenticode signature.
+    ///
+    /// Returns `None` if the image is not signed.
+    pub fn verify_authenticode_hash(&self) -> Result<Option<bool>, Error> {
+        let certificates = self.certificates()?;
+        let signature = match certificates.iter().find(|c| c.pkcs7().is_some()) {
+            Some(signature) => signature,
+            None => return Ok(None),
+        };
+        let signed = signature.signed_digest()?;
+        Ok(Some(self.authenticode_hash(signed.algorithm)? == signed.digest))
+    }
+
+    fn certificate_table(&self) -> Option<Range<usize>> {
+        self.directory(DirectoryType::CertificateTable)
+            .map(|range| range.start as usize..range.end() as usize)
+    }
+
+    /// Gets the file range of the checksum field in the optional header.
+    pub(crate) fn checksum_field(&self) -> Range<usize> {
+        let checksum = self.optional_header_offset() + CHECKSUM_OFFSET;
+        checksum..(checksum + 4)
+    }
+
+    /// Gets the file range of the certificate table entry in the data directories, if the header has one.
+    pub(crate) fn certificate_directory_entry(&self) -> Option<Range<usize>> {
+        let pe_header = self.pe_header()?;
+        if pe_header.number_of_data_directories <= CERTIFICATE_DIRECTORY_INDEX {
+            return None;
+        }
+        let directories = self.optional_header_offset() + if pe_header.magic.is_pe32plus() { 112 } else { 96 };
+        let entry = directories + CERTIFICATE_DIRECTORY_INDEX as usize * 8;
+        Some(entry..(entry + 8))
+    }
+}
+
+const CHECKSUM_OFFSET: usize = 64;
+const CERTIFICATE_DIRECTORY_INDEX: u32 = 4;
+
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+const INTEGER: u8 = 0x02;
+const OCTET_STRING: u8 = 0x04;
+const OBJECT_IDENTIFIER: u8 = 0x06;
+const CONTEXT_0: u8 = 0xA0;
+
+const OID_SIGNED_DATA: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x07, 0x02];
+const OID_SPC_INDIRECT_DATA: &[u8] = &[0x2B, 0x06, 0x01, 0x04, 0x01, 0x82, 0x37, 0x02, 0x01, 0x04];
+const OID_SHA1: &[u8] = &[0x2B, 0x0E, 0x03, 0x02, 0x1A];
+const OID_SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+const OID_SHA384: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02];
+const OID_SHA512: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03];
+
+/// Walks a DER encoded value one element at a time.
+///
+/// Only definite lengths are supported, which is all DER allows.
+struct Der<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Der<'a> {
+    fn new(buf: &'a [u8]) -> Der<'a> {
+        Der { buf }
+    }
+
+    /// Reads the next element, returning its tag and contents.
+    fn read(&mut self) -> Result<(u8, &'a [u8]), Error> {
+        if self.buf.len() < 2 {
+            return Err(Error::InvalidCertificate("unexpected end of data".into()));
+        }
+        let tag = self.buf[0];
+        let (len, header_size) = if self.buf[1] < 0x80 {
+            (self.buf[1] as usize, 2)
+        } else {
+            let count = (self.buf[1] & 0x7F) as usize;
+            if count == 0 || count > 4 || self.buf.len() < 2 + count {
+                return Err(Error::InvalidCertificate("unsupported length encoding".into()));
+            }
+            (self.buf[2..(2 + count)].iter().fold(0usize, |len, b| (len << 8) | *b as usize), 2 + count)
+        };
+        if self.buf.len() - header_size < len {
+            return Err(Error::InvalidCertificate("element is longer than its container".into()));
+        }
+        let contents = &self.buf[header_size..(header_size + len)];
+        self.buf = &self.buf[(header_size + len)..];
+        Ok((tag, contents))
+    }
+
+    /// Reads the next element, which must have the tag `tag`, and returns a walker over its contents.
+    fn expect(&mut self, tag: u8) -> Result<Der<'a>, Error> {
+        let (actual, contents) = self.read()?;
+        if actual != tag {
+            return Err(Error::InvalidCertificate(format!("expected tag 0x{:02X}, found 0x{:02X}", tag, actual)));
+        }
+        Ok(Der::new(contents))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pe::test_image::{self, SECTION_OFFSET};
+    use crate::pe::MemoryRange;
+
+    fn tlv(tag: u8, contents: &[u8]) -> Vec<u8> {
+        let mut buf = vec![tag];
+        if contents.len() < 0x80 {
+            buf.push(contents.len() as u8);
+        } else {
+            buf.extend_from_slice(&[0x82, (contents.len() >> 8) as u8, contents.len() as u8]);
+        }
+        buf.extend_from_slice(contents);
+        buf
+    }
+
+    fn authenticode_signature(digest: &[u8]) -> Vec<u8> {
+        let digest_info = [tlv(SEQUENCE, &[tlv(OBJECT_IDENTIFIER, OID_SHA256), vec![0x05, 0x00]].concat()), tlv(OCTET_STRING, digest)].concat();
+        let indirect_data = tlv(SEQUENCE, &[tlv(SEQUENCE, &[0x06, 0x01, 0x00]), tlv(SEQUENCE, &digest_info)].concat());
+        let encapsulated = tlv(SEQUENCE, &[tlv(OBJECT_IDENTIFIER, OID_SPC_INDIRECT_DATA), tlv(CONTEXT_0, &indirect_data)].concat());
+        let signed_data = tlv(SEQUENCE, &[tlv(INTEGER, &[1]), tlv(SET, &[]), encapsulated, tlv(SET, &[])].concat());
+        tlv(SEQUENCE, &[tlv(OBJECT_IDENTIFIER, OID_SIGNED_DATA), tlv(CONTEXT_0, &signed_data)].concat())
+    }
+
+    fn sign(section: &[u8], digest: &[u8]) -> Vec<u8> {
+        let signature = authenticode_signature(digest);
+        let table_offset = (SECTION_OFFSET + 0x200) as u32;
+        let table_size = (CERTIFICATE_HEADER_SIZE + signature.len() + 7) & !7;
+        let mut image = test_image::build(false, section, &[(DirectoryType::CertificateTable, MemoryRange::new(table_offset, table_size as u32))]);
+        assert_eq!(table_offset as usize, image.len());
+
+        image.extend_from_slice(&((CERTIFICATE_HEADER_SIZE + signature.len()) as u32).to_le_bytes());
+        image.extend_from_slice(&0x0200u16.to_le_bytes());
+        image.extend_from_slice(&certificate_types::PKCS_SIGNED_DATA.to_le_bytes());
+        image.extend_from_slice(&signature);
+        image.resize(table_offset as usize + table_size, 0);
+        image
+    }
+
+    #[test]
+    pub fn hash_skips_signature_fields() {
+        let unsigned = test_image::build(false, &[0xAB; 0x10], &[]);
+        let expected = Sha256::new()
+            .chain_update(&unsigned[..0x98])
+            .chain_update(&unsigned[0x9C..0x118])
+            .chain_update(&unsigned[0x120..])
+            .finalize()
+            .to_vec();
+        let hash = PeImage::load(unsigned.as_slice()).unwrap().authenticode_hash(HashAlgorithm::Sha256).unwrap();
+        assert_eq!(expected, hash);
+
+        // Signing the image doesn't change the hash, but changing the section data does
+        let signed = sign(&[0xAB; 0x10], &hash);
+        assert_eq!(hash, PeImage::load(signed.as_slice()).unwrap().authenticode_hash(HashAlgorithm::Sha256).unwrap());
+        let tampered = sign(&[0xAC; 0x10], &hash);
+        assert_ne!(hash, PeImage::load(tampered.as_slice()).unwrap().authenticode_hash(HashAlgorithm::Sha256).unwrap());
+    }
+
+    #[test]
+    pub fn verify_signed_digest() {
+        let hash = PeImage::load(test_image::build(false, &[1, 2, 3], &[])).unwrap().authenticode_hash(HashAlgorithm::Sha256).unwrap();
+        let image = PeImage::load(sign(&[1, 2, 3], &hash)).unwrap();
+
+        let certificates = image.certificates().unwrap();
+        assert_eq!(1, certificates.len());
+        assert_eq!(0x0200, certificates[0].revision);
+        assert_eq!(SignedDigest { algorithm: HashAlgorithm::Sha256, digest: hash }, certificates[0].signed_digest().unwrap());
+        assert_eq!(Some(true), image.verify_authenticode_hash().unwrap());
+
+        let tampered = PeImage::load(sign(&[1, 2, 4], &certificates[0].signed_digest().unwrap().digest)).unwrap();
+        assert_eq!(Some(false), tampered.verify_authenticode_hash().unwrap());
+    }
+}
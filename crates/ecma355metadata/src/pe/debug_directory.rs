@@ -0,0 +1,237 @@
+use std::io::Read;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use flate2::read::DeflateDecoder;
+
+use crate::error::Error;
+use crate::Guid;
+
+/// The type of an entry in the debug directory.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DebugType {
+    Unknown,
+    Coff,
+    CodeView,
+    Fpo,
+    Misc,
+    Exception,
+    Fixup,
+    OmapToSrc,
+    OmapFromSrc,
+    Borland,
+    Clsid,
+    Repro,
+    EmbeddedPortablePdb,
+    PdbChecksum,
+    Other(u32),
+}
+
+impl DebugType {
+    pub fn from_u32(value: u32) -> DebugType {
+        match value {
+            0 => DebugType::Unknown,
+            1 => DebugType::Coff,
+            2 => DebugType::CodeView,
+            3 => DebugType::Fpo,
+            4 => DebugType::Misc,
+            5 => DebugType::Exception,
+            6 => DebugType::Fixup,
+            7 => DebugType::OmapToSrc,
+            8 => DebugType::OmapFromSrc,
+            9 => DebugType::Borland,
+            11 => DebugType::Clsid,
+            16 => DebugType::Repro,
+            17 => DebugType::EmbeddedPortablePdb,
+            19 => DebugType::PdbChecksum,
+            x => DebugType::Other(x),
+        }
+    }
+}
+
+impl ::std::fmt::Display for DebugType {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> Result<(), ::std::fmt::Error> {
+        match *self {
+            DebugType::Unknown => f.write_str("Unknown"),
+            DebugType::Coff => f.write_str("COFF"),
+            DebugType::CodeView => f.write_str("CodeView"),
+            DebugType::Fpo => f.write_str("FPO"),
+            DebugType::Misc => f.write_str("Misc"),
+            DebugType::Exception => f.write_str("Exception"),
+            DebugType::Fixup => f.write_str("Fixup"),
+            DebugType::OmapToSrc => f.write_str("OMAP to Source"),
+            DebugType::OmapFromSrc => f.write_str("OMAP from Source"),
+            DebugType::Borland => f.write_str("Borland"),
+            DebugType::Clsid => f.write_str("CLSID"),
+            DebugType::Repro => f.write_str("Reproducible"),
+            DebugType::EmbeddedPortablePdb => f.write_str("Embedded Portable PDB"),
+            DebugType::PdbChecksum => f.write_str("PDB Checksum"),
+            DebugType::Other(x) => write!(f, "0x{:08X}", x),
+        }
+    }
+}
+
+/// An IMAGE_DEBUG_DIRECTORY entry.
+#[derive(Debug)]
+pub struct DebugDirectoryEntry {
+    pub characteristics: u32,
+    pub timestamp: u32,
+    pub major_version: u16,
+    pub minor_version: u16,
+    pub debug_type: DebugType,
+    pub size_of_data: u32,
+    pub address_of_raw_data: u32,
+    pub pointer_to_raw_data: u32,
+}
+
+impl DebugDirectoryEntry {
+    pub const SIZE: usize = 28;
+
+    pub fn read<A: Read>(buf: &mut A) -> Result<DebugDirectoryEntry, Error> {
+        Ok(DebugDirectoryEntry {
+            characteristics: buf.read_u32::<LittleEndian>()?,
+            timestamp: buf.read_u32::<LittleEndian>()?,
+            major_version: buf.read_u16::<LittleEndian>()?,
+            minor_version: buf.read_u16::<LittleEndian>()?,
+            debug_type: DebugType::from_u32(buf.read_u32::<LittleEndian>()?),
+            size_of_data: buf.read_u32::<LittleEndian>()?,
+            address_of_raw_data: buf.read_u32::<LittleEndian>()?,
+            pointer_to_raw_data: buf.read_u32::<LittleEndian>()?,
+        })
+    }
+
+    /// Checks if a CodeView entry refers to a Portable PDB rather than a Windows PDB.
+    ///
+    /// Compilers mark this by setting the minor version to 0x504D ("PM").
+    pub fn is_portable_code_view(&self) -> bool {
+        self.debug_type == DebugType::CodeView && self.minor_version == 0x504D
+    }
+}
+
+/// The data of a CodeView entry in RSDS format, which identifies the PDB that matches the image.
+#[derive(Debug)]
+pub struct CodeViewData {
+    pub guid: Guid,
+    pub age: u32,
+    pub path: String,
+}
+
+const RSDS_SIGNATURE: u32 = 0x53445352;
+
+impl CodeViewData {
+    pub fn read(data: &[u8]) -> Result<CodeViewData, Error> {
+        let mut buf = data;
+        if buf.read_u32::<LittleEndian>()? != RSDS_SIGNATURE {
+            return Err(Error::InvalidSignature);
+        }
+
+        let mut guid = [0u8; 16];
+        buf.read_exact(&mut guid)?;
+        let age = buf.read_u32::<LittleEndian>()?;
+
+        // The path is a null-terminated UTF-8 string, which may be followed by padding
+        let len = buf.iter().position(|b| *b == 0).unwrap_or(buf.len());
+        let path = String::from_utf8(buf[..len].to_vec())
+            .or(Err(Error::InvalidMetadata("invalid UTF-8 string".into())))?;
+
+        Ok(CodeViewData {
+            guid: Guid::from_bytes(&guid),
+            age,
+            path,
+        })
+    }
+}
+
+/// The data of a PdbChecksum entry, which holds a hash of the PDB that matches the image.
+#[derive(Debug)]
+pub struct PdbChecksum {
+    /// The name of the hash algorithm, such as "SHA256".
+    pub algorithm: String,
+    pub checksum: Vec<u8>,
+}
+
+impl PdbChecksum {
+    pub fn read(data: &[u8]) -> Result<PdbChecksum, Error> {
+        let len = data.iter().position(|b| *b == 0).ok_or(Error::InvalidMetadata(
+            "unterminated PDB checksum algorithm name".into(),
+        ))?;
+        let algorithm = String::from_utf8(data[..len].to_vec())
+            .or(Err(Error::InvalidMetadata("invalid UTF-8 string".into())))?;
+        Ok(PdbChecksum {
+            algorithm,
+            checksum: data[(len + 1)..].to_vec(),
+        })
+    }
+}
+
+const MPDB_SIGNATURE: u32 = 0x4244504D;
+
+/// Inflates the data of an EmbeddedPortablePdb entry into the bytes of the Portable PDB.
+///
+/// The data is the "MPDB" signature, the size of the PDB and then the PDB compressed with deflate.
+pub fn read_embedded_pdb(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut buf = data;
+    if buf.read_u32::<LittleEndian>()? != MPDB_SIGNATURE {
+        return Err(Error::InvalidSignature);
+    }
+    let size = buf.read_u32::<LittleEndian>()?;
+
+    // The size comes from the file, so only inflate one byte past it, which is enough to tell that it's wrong
+    let mut pdb = Vec::new();
+    DeflateDecoder::new(buf).take(size as u64 + 1).read_to_end(&mut pdb)?;
+    if pdb.len() != size as usize {
+        return Err(Error::InvalidMetadata(format!(
+            "embedded PDB inflated to {} bytes, expected {}",
+            pdb.len(),
+            size
+        )));
+    }
+    Ok(pdb)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::DeflateEncoder;
+    use flate2::Compression;
+
+    use super::*;
+
+    #[test]
+    pub fn read_code_view() {
+        let mut data = b"RSDS".to_vec();
+        data.extend_from_slice(&[0x01; 16]);
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(b"C:\\src\\Test.pdb\0\0\0");
+
+        let code_view = CodeViewData::read(&data).unwrap();
+        assert_eq!(Guid::from_bytes(&[0x01; 16]), code_view.guid);
+        assert_eq!(1, code_view.age);
+        assert_eq!("C:\\src\\Test.pdb", code_view.path);
+    }
+
+    #[test]
+    pub fn read_pdb_checksum() {
+        let checksum = PdbChecksum::read(b"SHA256\0\x01\x02\x03").unwrap();
+        assert_eq!("SHA256", checksum.algorithm);
+        assert_eq!(vec![1, 2, 3], checksum.checksum);
+    }
+
+    #[test]
+    pub fn read_embedded_portable_pdb() {
+        let pdb = b"BSJB and then the rest of the PDB".to_vec();
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&pdb).unwrap();
+
+        let mut data = b"MPDB".to_vec();
+        data.extend_from_slice(&(pdb.len() as u32).to_le_bytes());
+        data.extend_from_slice(&encoder.finish().unwrap());
+
+        assert_eq!(pdb, read_embedded_pdb(&data).unwrap());
+
+        data[4] += 1;
+        assert!(read_embedded_pdb(&data).is_err());
+        data[4] -= 2;
+        assert!(read_embedded_pdb(&data).is_err());
+    }
+}
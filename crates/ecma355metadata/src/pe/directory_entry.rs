@@ -3,7 +3,7 @@ use std::io::Read;
 use crate::error::Error;
 use crate::pe::MemoryRange;
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum DirectoryType {
     ExportTable,
     ImportTable,
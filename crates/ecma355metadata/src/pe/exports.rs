@@ -0,0 +1,155 @@
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::error::Error;
+use crate::pe::{DirectoryType, ImageData, MemoryRange, PeImage};
+
+/// Where an exported symbol lives.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExportTarget {
+    /// The RVA of the code or data.
+    Rva(u32),
+
+    /// The export is forwarded to another DLL, as in "NTDLL.RtlAllocateHeap".
+    Forwarder(String),
+}
+
+/// A symbol in the export table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Export {
+    pub ordinal: u32,
+    pub name: Option<String>,
+    pub target: ExportTarget,
+}
+
+/// The export directory of an image.
+#[derive(Debug)]
+pub struct ExportDirectory {
+    pub name: String,
+    pub timestamp: u32,
+    pub major_version: u16,
+    pub minor_version: u16,
+    pub ordinal_base: u32,
+    pub exports: Vec<Export>,
+}
+
+const EXPORT_DIRECTORY_SIZE: u32 = 40;
+
+impl<D: ImageData> PeImage<D> {
+    /// Reads the export table, if the image has one.
+    pub fn exports(&self) -> Result<Option<ExportDirectory>, Error> {
+        let range = match self.directory(DirectoryType::ExportTable) {
+            Some(range) => range,
+            None => return Ok(None),
+        };
+
+        let header = self.read_rva(MemoryRange::new(range.start, EXPORT_DIRECTORY_SIZE))?;
+        let u32_at = |offset: usize| LittleEndian::read_u32(&header[offset..(offset + 4)]);
+        let ordinal_base = u32_at(16);
+        let function_count = u32_at(20);
+        let name_count = u32_at(24);
+
+        // The counts come from the file, so sizes computed from them can overflow
+        let table_size = |count: u32, entry_size: u32| count.checked_mul(entry_size).ok_or(Error::DataOutOfRange);
+        let functions = self.read_rva(MemoryRange::new(u32_at(28), table_size(function_count, 4)?))?;
+        let names = self.read_rva(MemoryRange::new(u32_at(32), table_size(name_count, 4)?))?;
+        let name_ordinals = self.read_rva(MemoryRange::new(u32_at(36), table_size(name_count, 2)?))?;
+
+        // Names are listed separately, and point back into the function table by index
+        let mut function_names = vec![None; function_count as usize];
+        for (name, index) in names.chunks_exact(4).zip(name_ordinals.chunks_exact(2)) {
+            let index = LittleEndian::read_u16(index) as usize;
+            let name = self.read_cstr(LittleEndian::read_u32(name))?;
+            *function_names.get_mut(index).ok_or(Error::DataOutOfRange)? = Some(name);
+        }
+
+        let mut exports = Vec::new();
+        for (index, (rva, name)) in functions.chunks_exact(4).zip(function_names).enumerate() {
+            let rva = LittleEndian::read_u32(rva);
+            if rva == 0 {
+                // Unused ordinal
+                continue;
+            }
+            let target = if rva >= range.start && rva < range.end() {
+                ExportTarget::Forwarder(self.read_cstr(rva)?)
+            } else {
+                ExportTarget::Rva(rva)
+            };
+            exports.push(Export {
+                ordinal: ordinal_base.checked_add(index as u32).ok_or(Error::DataOutOfRange)?,
+                name,
+                target,
+            });
+        }
+
+        Ok(Some(ExportDirectory {
+            name: self.read_cstr(u32_at(12))?,
+            timestamp: u32_at(4),
+            major_version: LittleEndian::read_u16(&header[8..10]),
+            minor_version: LittleEndian::read_u16(&header[10..12]),
+            ordinal_base,
+            exports,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Error;
+    use crate::pe::test_image::{self, SECTION_RVA};
+    use crate::pe::{DirectoryType, Export, ExportTarget, MemoryRange, PeImage};
+
+    #[test]
+    pub fn read_exports() {
+        let mut section = vec![0u8; 0x100];
+        let mut put = |offset: usize, value: u32| section[offset..(offset + 4)].copy_from_slice(&value.to_le_bytes());
+
+        // Directory at 0x00, functions at 0x30, names at 0x40, name ordinals at 0x48, strings from 0x50
+        put(12, SECTION_RVA + 0x50);
+        put(16, 5);
+        put(20, 3);
+        put(24, 1);
+        put(28, SECTION_RVA + 0x30);
+        put(32, SECTION_RVA + 0x40);
+        put(36, SECTION_RVA + 0x48);
+        put(0x30, 0x1234);
+        put(0x34, 0);
+        put(0x38, SECTION_RVA + 0x68);
+        put(0x40, SECTION_RVA + 0x5C);
+        section[0x48..0x4A].copy_from_slice(&2u16.to_le_bytes());
+        section[0x50..0x5C].copy_from_slice(b"native.dll\0\0");
+        section[0x5C..0x64].copy_from_slice(b"Forward\0");
+        section[0x68..0x7B].copy_from_slice(b"NTDLL.RtlAllocHeap\0");
+
+        let image = test_image::build(false, &section, &[(DirectoryType::ExportTable, MemoryRange::new(SECTION_RVA, 0x80))]);
+        let exports = PeImage::load(image).unwrap().exports().unwrap().unwrap();
+        assert_eq!("native.dll", exports.name);
+        assert_eq!(
+            vec![
+                Export { ordinal: 5, name: None, target: ExportTarget::Rva(0x1234) },
+                Export { ordinal: 7, name: Some("Forward".into()), target: ExportTarget::Forwarder("NTDLL.RtlAllocHeap".into()) },
+            ],
+            exports.exports
+        );
+    }
+
+    #[test]
+    pub fn reject_overflowing_counts() {
+        let exports = |ordinal_base: u32, function_count: u32| {
+            let mut section = vec![0u8; 0x40];
+            let mut put = |offset: usize, value: u32| section[offset..(offset + 4)].copy_from_slice(&value.to_le_bytes());
+            put(12, SECTION_RVA + 0x38);
+            put(16, ordinal_base);
+            put(20, function_count);
+            put(28, SECTION_RVA + 0x30);
+            put(32, SECTION_RVA);
+            put(36, SECTION_RVA);
+            put(0x34, 0x1234);
+            section[0x38..0x3A].copy_from_slice(b"a\0");
+            let image = test_image::build(false, &section, &[(DirectoryType::ExportTable, MemoryRange::new(SECTION_RVA, 0x28))]);
+            PeImage::load(image).unwrap().exports()
+        };
+        assert!(matches!(exports(1, 0x4000_0001), Err(Error::DataOutOfRange)));
+        assert_eq!(u32::MAX, exports(u32::MAX - 1, 2).unwrap().unwrap().exports[0].ordinal);
+        assert!(matches!(exports(u32::MAX, 2), Err(Error::DataOutOfRange)));
+    }
+}
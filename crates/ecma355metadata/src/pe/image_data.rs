@@ -0,0 +1,127 @@
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::{Deref, Range};
+use std::sync::Mutex;
+
+use crate::error::Error;
+
+/// A source of the raw bytes of an image file.
+///
+/// All offsets are file offsets, not RVAs.
+pub trait ImageData {
+    /// Gets the bytes in `range`, loading them first if necessary.
+    fn read(&self, range: Range<usize>) -> Result<&[u8], Error>;
+
+    /// Gets the length of the image file, in bytes.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Tells the source which file ranges hold section data, once the section headers have been read.
+    ///
+    /// Sources that load lazily can use this to load a whole section the first time it is touched.
+    fn set_sections(&mut self, _sections: &[Range<usize>]) {}
+}
+
+/// Any in-memory buffer can back an image, including `Vec<u8>`, `&[u8]` and memory-mapped files.
+impl<D: Deref<Target = [u8]>> ImageData for D {
+    fn read(&self, range: Range<usize>) -> Result<&[u8], Error> {
+        self.deref().get(range).ok_or(Error::DataOutOfRange)
+    }
+
+    fn len(&self) -> usize {
+        self.deref().len()
+    }
+}
+
+/// Image data read on demand from a `Read + Seek` source.
+///
+/// Only the headers and the sections that are actually touched are read into memory.
+/// Each section is read in full on first access and kept until the source is dropped.
+pub struct StreamingData<R: Read + Seek> {
+    reader: Mutex<R>,
+    len: usize,
+    sections: Vec<Range<usize>>,
+    loaded: Mutex<Vec<(usize, Box<[u8]>)>>,
+}
+
+impl<R: Read + Seek> StreamingData<R> {
+    pub fn new(mut reader: R) -> Result<StreamingData<R>, Error> {
+        let len = reader.seek(SeekFrom::End(0))? as usize;
+        Ok(StreamingData {
+            reader: Mutex::new(reader),
+            len,
+            sections: Vec::new(),
+            loaded: Mutex::new(Vec::new()),
+        })
+    }
+
+    fn load(&self, range: Range<usize>) -> Result<Box<[u8]>, Error> {
+        let mut buf = vec![0u8; range.end - range.start].into_boxed_slice();
+        let mut reader = self.reader.lock().unwrap();
+        reader.seek(SeekFrom::Start(range.start as u64))?;
+        reader.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+impl<R: Read + Seek> ImageData for StreamingData<R> {
+    fn read(&self, range: Range<usize>) -> Result<&[u8], Error> {
+        if range.start > range.end || range.end > self.len {
+            return Err(Error::DataOutOfRange);
+        }
+
+        let mut loaded = self.loaded.lock().unwrap();
+        let chunk = match loaded.iter().position(|(start, buf)| *start <= range.start && range.end <= start + buf.len()) {
+            Some(index) => index,
+            None => {
+                // Load the whole section if the range falls inside one, otherwise just the range itself.
+                let load_range = self
+                    .sections
+                    .iter()
+                    .find(|s| s.start <= range.start && range.end <= s.end)
+                    .cloned()
+                    .unwrap_or_else(|| range.clone());
+                let buf = self.load(load_range.clone())?;
+                loaded.push((load_range.start, buf));
+                loaded.len() - 1
+            }
+        };
+
+        let (start, buf) = &loaded[chunk];
+        let slice = &buf[(range.start - start)..(range.end - start)];
+
+        // SAFETY: Each chunk is a separate heap allocation that is never modified or freed until `self` is
+        // dropped, so its contents stay put even when the list of chunks grows.
+        Ok(unsafe { std::slice::from_raw_parts(slice.as_ptr(), slice.len()) })
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn set_sections(&mut self, sections: &[Range<usize>]) {
+        self.sections = sections.iter().filter(|s| s.end <= self.len).cloned().collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    pub fn streaming_data_loads_whole_sections() {
+        let bytes: Vec<u8> = (0..=255u8).collect();
+        let mut data = StreamingData::new(Cursor::new(bytes)).unwrap();
+        data.set_sections(&[0x40..0x80]);
+
+        assert_eq!(&[0x50, 0x51], data.read(0x50..0x52).unwrap());
+        assert_eq!(&[0x7E, 0x7F], data.read(0x7E..0x80).unwrap());
+        assert_eq!(&[0xF0], data.read(0xF0..0xF1).unwrap());
+        assert_eq!(2, data.loaded.lock().unwrap().len());
+        assert!(data.read(0xFF..0x101).is_err());
+    }
+}
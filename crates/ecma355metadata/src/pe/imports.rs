@@ -0,0 +1,133 @@
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::error::Error;
+use crate::pe::{DirectoryType, ImageData, MemoryRange, PeImage};
+
+/// A function imported from a DLL.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ImportedFunction {
+    ByName { hint: u16, name: String },
+    ByOrdinal(u16),
+}
+
+impl ::std::fmt::Display for ImportedFunction {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> Result<(), ::std::fmt::Error> {
+        match self {
+            ImportedFunction::ByName { name, .. } => f.write_str(name),
+            ImportedFunction::ByOrdinal(ordinal) => write!(f, "#{}", ordinal),
+        }
+    }
+}
+
+/// The imports from one DLL, from an IMAGE_IMPORT_DESCRIPTOR.
+#[derive(Debug)]
+pub struct ImportedDll {
+    pub name: String,
+    pub timestamp: u32,
+    pub forwarder_chain: u32,
+
+    /// The RVA of the import address table, which the loader fills in with the function addresses.
+    pub import_address_table: u32,
+    pub functions: Vec<ImportedFunction>,
+}
+
+const DESCRIPTOR_SIZE: usize = 20;
+
+impl<D: ImageData> PeImage<D> {
+    /// Reads the import table.
+    pub fn imports(&self) -> Result<Vec<ImportedDll>, Error> {
+        let range = match self.directory(DirectoryType::ImportTable) {
+            Some(range) => range,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut dlls = Vec::new();
+        let mut rva = range.start;
+        loop {
+            let descriptor = self.read_rva(MemoryRange::new(rva, DESCRIPTOR_SIZE as u32))?;
+            let lookup_table = LittleEndian::read_u32(&descriptor[0..4]);
+            let name = LittleEndian::read_u32(&descriptor[12..16]);
+            let import_address_table = LittleEndian::read_u32(&descriptor[16..20]);
+            if name == 0 && import_address_table == 0 {
+                // The table ends with an empty descriptor
+                break;
+            }
+
+            // Old linkers leave the lookup table out, in which case the unbound IAT has the same contents
+            let thunks = if lookup_table != 0 { lookup_table } else { import_address_table };
+            dlls.push(ImportedDll {
+                name: self.read_cstr(name)?,
+                timestamp: LittleEndian::read_u32(&descriptor[4..8]),
+                forwarder_chain: LittleEndian::read_u32(&descriptor[8..12]),
+                import_address_table,
+                functions: self.read_thunks(thunks)?,
+            });
+            rva += DESCRIPTOR_SIZE as u32;
+        }
+        Ok(dlls)
+    }
+
+    fn read_thunks(&self, mut rva: u32) -> Result<Vec<ImportedFunction>, Error> {
+        let (thunk_size, ordinal_flag) = if self.is_pe32plus() { (8, 1u64 << 63) } else { (4, 1u64 << 31) };
+        let mut functions = Vec::new();
+        loop {
+            let thunk = self.read_rva(MemoryRange::new(rva, thunk_size))?;
+            let thunk = if thunk_size == 8 { LittleEndian::read_u64(thunk) } else { LittleEndian::read_u32(thunk) as u64 };
+            if thunk == 0 {
+                break;
+            }
+
+            functions.push(if thunk & ordinal_flag != 0 {
+                ImportedFunction::ByOrdinal(thunk as u16)
+            } else {
+                let hint_rva = (thunk & 0x7FFF_FFFF) as u32;
+                let hint = LittleEndian::read_u16(self.read_rva(MemoryRange::new(hint_rva, 2))?);
+                ImportedFunction::ByName { hint, name: self.read_cstr(hint_rva + 2)? }
+            });
+            rva += thunk_size;
+        }
+        Ok(functions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pe::test_image::{self, SECTION_RVA};
+    use crate::pe::{DirectoryType, ImportedFunction, MemoryRange, PeImage};
+
+    /// Builds the import table of a managed executable, which imports `_CorExeMain` from mscoree.dll.
+    fn image(pe32plus: bool) -> Vec<u8> {
+        let thunk_size = if pe32plus { 8 } else { 4 };
+        let ordinal_flag = if pe32plus { 1u64 << 63 } else { 1u64 << 31 };
+        let mut section = vec![0u8; 0x100];
+
+        // Descriptor at 0x00, terminator at 0x14, lookup table at 0x40, IAT at 0x60, hint/name at 0x80, DLL name at 0xA0
+        section[0x00..0x04].copy_from_slice(&(SECTION_RVA + 0x40).to_le_bytes());
+        section[0x0C..0x10].copy_from_slice(&(SECTION_RVA + 0xA0).to_le_bytes());
+        section[0x10..0x14].copy_from_slice(&(SECTION_RVA + 0x60).to_le_bytes());
+        for (offset, value) in [(0x40, (SECTION_RVA + 0x80) as u64), (0x40 + thunk_size, ordinal_flag | 7)] {
+            section[offset..(offset + thunk_size)].copy_from_slice(&value.to_le_bytes()[..thunk_size]);
+        }
+        section[0x80..0x8D].copy_from_slice(b"\0\0_CorExeMain");
+        section[0xA0..0xAB].copy_from_slice(b"mscoree.dll");
+
+        test_image::build(pe32plus, &section, &[(DirectoryType::ImportTable, MemoryRange::new(SECTION_RVA, 40))])
+    }
+
+    #[test]
+    pub fn read_imports() {
+        for pe32plus in [false, true] {
+            let pe = PeImage::load(image(pe32plus)).unwrap();
+            assert_eq!(pe32plus, pe.is_pe32plus());
+
+            let imports = pe.imports().unwrap();
+            assert_eq!(1, imports.len());
+            assert_eq!("mscoree.dll", imports[0].name);
+            assert_eq!(SECTION_RVA + 0x60, imports[0].import_address_table);
+            assert_eq!(
+                vec![ImportedFunction::ByName { hint: 0, name: "_CorExeMain".into() }, ImportedFunction::ByOrdinal(7)],
+                imports[0].functions
+            );
+        }
+    }
+}
@@ -1,19 +1,39 @@
+mod authenticode;
 mod characteristics;
 mod coff_header;
+mod debug_directory;
 mod directory_entry;
 mod pe_header;
 mod pe_image;
 mod pe_magic;
 mod section_header;
 mod memory_range;
+mod image_data;
+mod resources;
+mod imports;
+mod exports;
+mod relocations;
+#[cfg(test)]
+pub(crate) mod test_image;
+mod version_info;
+mod webcil;
 mod subsystem;
 
+pub use self::authenticode::{certificate_types, read_certificates, Certificate, HashAlgorithm, SignedDigest};
 pub use self::coff_header::CoffHeader;
 pub use self::pe_header::PeHeader;
+pub use self::debug_directory::{read_embedded_pdb, CodeViewData, DebugDirectoryEntry, DebugType, PdbChecksum};
 pub use self::pe_magic::PeMagic;
 pub use self::subsystem::Subsystem;
 pub use self::directory_entry::{DirectoryEntry, DirectoryType};
 pub use self::section_header::SectionHeader;
 pub use self::memory_range::MemoryRange;
 pub use self::pe_image::PeImage;
+pub use self::image_data::{ImageData, StreamingData};
+pub use self::imports::{ImportedDll, ImportedFunction};
+pub use self::exports::{Export, ExportDirectory, ExportTarget};
+pub use self::relocations::{Relocation, RelocationBlock, RelocationBlocks, RelocationType};
+pub use self::resources::{read_resources, resource_types, ResourceEntry, ResourceName};
+pub use self::version_info::{FixedFileInfo, StringTable, VersionInfo};
+pub use self::webcil::{find_webcil_payload, WebcilHeader, WASM_MAGIC, WEBCIL_MAGIC};
 pub use self::characteristics::{FileCharacteristics, SectionCharacteristics};
@@ -1,32 +1,42 @@
-use std::ops::{Deref, Index, Range};
+use std::fs::File;
+use std::ops::Range;
 use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
 
 use byteorder::{LittleEndian, ReadBytesExt};
+use memmap2::Mmap;
 
-use crate::pe::{CoffHeader, MemoryRange, PeHeader, SectionHeader};
+use crate::pe::{self, resource_types, CoffHeader, DebugDirectoryEntry, DirectoryType, FileCharacteristics, ImageData, MemoryRange, PeHeader, ResourceEntry, ResourceName, SectionHeader, StreamingData, VersionInfo, WebcilHeader};
 use crate::error::Error;
 
-// TODO: We could probably use a trait other than Deref in order to
-// allow lazy-loading. But for now, this works.
-
-/// Represents a Portable Executable Image, loaded into memory.
-pub struct PeImage<D: Deref<Target = [u8]>> {
+/// Represents a Portable Executable Image, backed by any source of image data.
+pub struct PeImage<D: ImageData> {
     coff_header: CoffHeader,
     pe_header: Option<PeHeader>,
+    optional_header_offset: usize,
+    webcil_header: Option<WebcilHeader>,
     sections: Vec<SectionHeader>,
     data: D,
 }
 
 const DOS_SIGNATURE: u16 = 0x5A4D;
 const PE_SIGNATURE: u32 = 0x00004550;
+const DOS_HEADER_SIZE: usize = 0x40;
 
 /// Represents a PE Image in memory
-/// 
-/// This type can be indexed or sliced like a [u8], but the offsets are RVAs (relative virtual adresses)
-impl<D: Deref<Target = [u8]>> PeImage<D> {
+///
+/// Data is read by RVA (relative virtual address) with `read_rva` and `read_cstr`, which return an error for RVAs
+/// outside the sections. `physical_range` maps RVAs to file offsets, for reading `data()` with `ImageData::read`.
+impl<D: ImageData> PeImage<D> {
+    /// Loads an image from a PE file, or from a Webcil file or the WebAssembly module that wraps one.
     pub fn load(data: D) -> Result<PeImage<D>, Error> {
-        let (coff_header, pe_header, sections) = {
-            let mut reader = Cursor::new(data.deref());
+        let magic = data.read(0..4.min(data.len()))?;
+        if magic == pe::WEBCIL_MAGIC || magic == pe::WASM_MAGIC {
+            return PeImage::load_webcil(data);
+        }
+
+        let (coff_header, pe_header, optional_header_offset, sections) = {
+            let mut reader = Cursor::new(data.read(0..DOS_HEADER_SIZE.min(data.len()))?);
 
             // Verify the MZ signature
             let mz_sig = reader.read_u16::<LittleEndian>()?;
@@ -37,38 +47,82 @@ impl<D: Deref<Target = [u8]>> PeImage<D> {
                 reader.seek(SeekFrom::Start(0x3C))?;
 
                 // Read the lfanew offset
-                let lfanew = reader.read_u32::<LittleEndian>()?;
-
-                // Seek to the PE header
-                reader.seek(SeekFrom::Start(lfanew as u64))?;
+                let lfanew = reader.read_u32::<LittleEndian>()? as usize;
 
-                // Read the PE signature
+                // Read the PE signature and COFF header, which tells us how big the rest of the headers are
+                let mut reader = Cursor::new(data.read(lfanew..(lfanew + 4 + CoffHeader::SIZE))?);
                 let pe_sig = reader.read_u32::<LittleEndian>()?;
-
-                // Read the COFF header
                 let coff_header = CoffHeader::read(&mut reader)?;
 
+                let optional_header_start = lfanew + 4 + CoffHeader::SIZE;
+                let sections_start = optional_header_start + coff_header.optional_header_size as usize;
+                let sections_end = sections_start + coff_header.number_of_sections as usize * SectionHeader::SIZE;
+
                 // Read the PE header if there is one
                 let pe_header = if pe_sig != PE_SIGNATURE {
                     None
                 } else {
-                    Some(PeHeader::read(&mut reader)?)
+                    Some(PeHeader::read(&mut Cursor::new(data.read(optional_header_start..sections_start)?))?)
                 };
 
                 // Read section headers
+                let mut reader = Cursor::new(data.read(sections_start..sections_end)?);
                 let section_count = coff_header.number_of_sections as usize;
                 let mut sections = Vec::with_capacity(section_count);
                 for _ in 0..section_count {
                     sections.push(SectionHeader::read(&mut reader)?);
                 }
 
-                (coff_header, pe_header, sections)
+                (coff_header, pe_header, optional_header_start, sections)
             }
         };
 
+        PeImage::with_sections(data, coff_header, pe_header, optional_header_offset, None, sections)
+    }
+
+    fn load_webcil(data: D) -> Result<PeImage<D>, Error> {
+        let start = if data.read(0..4)? == pe::WASM_MAGIC {
+            pe::find_webcil_payload(data.read(0..data.len())?)?
+        } else {
+            0
+        };
+        let header = WebcilHeader::read(data.read(start..(start + WebcilHeader::SIZE))?)?;
+        let sections_start = start + WebcilHeader::SIZE;
+        let sections_end = sections_start + header.section_count as usize * WebcilHeader::SECTION_SIZE;
+        let sections = header.read_sections(data.read(sections_start..sections_end)?, start)?;
+
+        // Webcil drops the COFF header, so stand one in that just records the section count
+        let coff_header = CoffHeader {
+            machine: 0,
+            number_of_sections: header.section_count,
+            timestamp: 0,
+            symbol_table_addr: 0,
+            symbol_count: 0,
+            optional_header_size: 0,
+            characteristics: FileCharacteristics::empty(),
+        };
+        PeImage::with_sections(data, coff_header, None, 0, Some(header), sections)
+    }
+
+    fn with_sections(
+        mut data: D,
+        coff_header: CoffHeader,
+        pe_header: Option<PeHeader>,
+        optional_header_offset: usize,
+        webcil_header: Option<WebcilHeader>,
+        sections: Vec<SectionHeader>,
+    ) -> Result<PeImage<D>, Error> {
+        let section_ranges: Vec<Range<usize>> = sections
+            .iter()
+            .map(|s| s.pointer_to_raw_data as usize..(s.pointer_to_raw_data as usize + s.size_of_raw_data as usize))
+            .collect();
+        data.set_sections(&section_ranges);
+
         Ok(PeImage {
             coff_header,
             pe_header,
+            optional_header_offset,
+            webcil_header,
             sections,
             data,
         })
@@ -82,6 +136,16 @@ impl<D: Deref<Target = [u8]>> PeImage<D> {
         self.pe_header.as_ref()
     }
 
+    /// Gets the Webcil header, if the image was loaded from a Webcil file.
+    pub fn webcil_header(&self) -> Option<&WebcilHeader> {
+        self.webcil_header.as_ref()
+    }
+
+    /// Gets the file offset of the optional (PE) header.
+    pub fn optional_header_offset(&self) -> usize {
+        self.optional_header_offset
+    }
+
     pub fn sections(&self) -> &Vec<SectionHeader> {
         &self.sections
     }
@@ -90,10 +154,105 @@ impl<D: Deref<Target = [u8]>> PeImage<D> {
         &self.data
     }
 
+    /// Gets the range of a data directory, if the image has one of that type.
+    ///
+    /// Webcil images only have the CLI header and debug directories.
+    pub fn directory(&self, directory_type: DirectoryType) -> Option<MemoryRange> {
+        if let Some(webcil) = &self.webcil_header {
+            let range = match directory_type {
+                DirectoryType::CliHeader => webcil.cli_header,
+                DirectoryType::DebugData => webcil.debug_directory,
+                _ => return None,
+            };
+            return if range.start != 0 && range.len != 0 { Some(range) } else { None };
+        }
+        self.pe_header
+            .as_ref()?
+            .directories()
+            .iter()
+            .find(|x| x.directory_type == directory_type && x.range.start != 0 && x.range.len != 0)
+            .map(|x| x.range)
+    }
+
+    /// Reads the entries of the debug directory.
+    pub fn debug_directory(&self) -> Result<Vec<DebugDirectoryEntry>, Error> {
+        let range = match self.directory(DirectoryType::DebugData) {
+            Some(range) => range,
+            None => return Ok(Vec::new()),
+        };
+        let mut buf = self.read_rva(range)?;
+        let count = range.len as usize / DebugDirectoryEntry::SIZE;
+        let mut entries = Vec::with_capacity(count);
+        for _ in 0..count {
+            entries.push(DebugDirectoryEntry::read(&mut buf)?);
+        }
+        Ok(entries)
+    }
+
+    /// Gets the data that a debug directory entry points to.
+    pub fn debug_data(&self, entry: &DebugDirectoryEntry) -> Result<&[u8], Error> {
+        let start = entry.pointer_to_raw_data as usize;
+        self.data.read(start..(start + entry.size_of_data as usize))
+    }
+
+    /// Reads the leaves of the Win32 resource tree.
+    pub fn resources(&self) -> Result<Vec<ResourceEntry>, Error> {
+        match self.directory(DirectoryType::ResourceTable) {
+            Some(range) => pe::read_resources(self.read_rva(range)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Gets the data of a Win32 resource.
+    pub fn resource_data(&self, entry: &ResourceEntry) -> Result<&[u8], Error> {
+        self.read_rva(entry.data)
+    }
+
+    /// Decodes the first VERSIONINFO resource, if the image has one.
+    pub fn version_info(&self) -> Result<Option<VersionInfo>, Error> {
+        let resources = self.resources()?;
+        match resources.iter().find(|r| r.resource_type == ResourceName::Id(resource_types::VERSION)) {
+            Some(entry) => VersionInfo::read(self.resource_data(entry)?).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads a range of RVAs, failing if it is not backed by a single section.
+    pub fn read_rva(&self, range: MemoryRange) -> Result<&[u8], Error> {
+        self.data.read(self.physical_range(range).ok_or(Error::DataOutOfRange)?)
+    }
+
+    /// Reads a null-terminated ASCII string at an RVA, such as a DLL or function name.
+    pub fn read_cstr(&self, rva: u32) -> Result<String, Error> {
+        let (phys, size) = self.map_rva(rva as usize).ok_or(Error::DataOutOfRange)?;
+        let buf = self.data.read(phys..(phys + size))?;
+        let len = buf.iter().position(|b| *b == 0).ok_or(Error::DataOutOfRange)?;
+        Ok(String::from_utf8_lossy(&buf[..len]).into_owned())
+    }
+
+    /// Checks if the image is PE32+, which uses 64-bit addresses in its thunks and headers.
+    pub fn is_pe32plus(&self) -> bool {
+        self.pe_header.as_ref().is_some_and(|h| h.magic.is_pe32plus())
+    }
+
+    /// Maps a range of RVAs to the range of file offsets that holds it.
+    ///
+    /// Returns `None` if the range is not fully backed by the raw data of a single section.
+    pub fn physical_range(&self, range: MemoryRange) -> Option<Range<usize>> {
+        let (phys, size) = self.map_rva(range.start as usize)?;
+        if size < range.len as usize {
+            None
+        } else {
+            Some(phys..(phys + range.len as usize))
+        }
+    }
+
     fn map_rva(&self, rva: usize) -> Option<(usize, usize)> {
-        self.sections.iter().find(|x| x.contains_rva(rva as u32)).map(|x| {
+        self.sections.iter().find(|x| x.contains_rva(rva as u32)).and_then(|x| {
             let offset = rva - x.virtual_address as usize;
-            (x.pointer_to_raw_data as usize + offset, x.size_of_raw_data as usize - offset)
+            // An RVA can be inside the section but past its raw data, where the loader fills in zeros
+            let size = (x.size_of_raw_data as usize).checked_sub(offset)?;
+            Some((x.pointer_to_raw_data as usize + offset, size))
         })
     }
 }
@@ -109,34 +268,40 @@ impl PeImage<Vec<u8>> {
     }
 }
 
-impl<D: Deref<Target=[u8]>> Index<usize> for PeImage<D> {
-    type Output = u8;
-    fn index(&self, index: usize) -> &u8 {
-        let (phys, _) = self.map_rva(index).expect("RVA out of range");
-        &self.data()[phys]
+impl PeImage<Mmap> {
+    /// Opens an image by memory-mapping the file at `path`.
+    ///
+    /// The file must not be modified while the image is open.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<PeImage<Mmap>, Error> {
+        let file = File::open(path)?;
+        let map = unsafe { Mmap::map(&file)? };
+        PeImage::load(map)
     }
 }
 
-impl<D: Deref<Target=[u8]>> Index<Range<usize>> for PeImage<D> {
-    type Output = [u8];
-    fn index(&self, index: Range<usize>) -> &[u8] {
-        let requested_size = index.end - index.start;
-        let (phys, size) = self.map_rva(index.start).expect("RVA out of range");
-        if size < requested_size {
-            panic!("RVA range is larger than physical range");
-        }
-        &self.data()[phys..(phys + requested_size)]
+impl<R: Read + Seek> PeImage<StreamingData<R>> {
+    /// Opens an image that reads the headers, and then each section the first time it is touched, from `reader`.
+    pub fn stream(reader: R) -> Result<PeImage<StreamingData<R>>, Error> {
+        PeImage::load(StreamingData::new(reader)?)
     }
 }
 
-impl<D: Deref<Target=[u8]>> Index<MemoryRange> for PeImage<D> {
-    type Output = [u8];
-    fn index(&self, index: MemoryRange) -> &[u8] {
-        let (phys, size) = self.map_rva(index.start as usize)
-            .expect("RVA out of range");
-        if size < index.len as usize {
-            panic!("RVA range is larger than physical range");
-        }
-        &self.data()[phys..(phys + index.len as usize)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pe::test_image::{self, SECTION_RVA};
+
+    #[test]
+    pub fn read_past_raw_data() {
+        let mut image = test_image::build(false, &[0xAB; 0x10], &[]);
+        // Make the section's virtual size larger than its raw data, as for a section with uninitialized data
+        let header = image.windows(8).position(|w| w == b".text\0\0\0").unwrap();
+        image[(header + 8)..(header + 12)].copy_from_slice(&0x1000u32.to_le_bytes());
+        let pe = PeImage::load(image).unwrap();
+
+        assert_eq!(&[0xAB; 4], pe.read_rva(MemoryRange::new(SECTION_RVA, 4)).unwrap());
+        assert!(matches!(pe.read_rva(MemoryRange::new(SECTION_RVA + 0x800, 4)), Err(Error::DataOutOfRange)));
+        assert!(matches!(pe.read_cstr(SECTION_RVA + 0x800), Err(Error::DataOutOfRange)));
+        assert_eq!(None, pe.physical_range(MemoryRange::new(SECTION_RVA + 0x200, 1)));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,134 @@
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::error::Error;
+use crate::pe::{DirectoryType, ImageData, PeImage};
+
+/// The kind of fixup a base relocation applies.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RelocationType {
+    /// Padding, which applies no fixup.
+    Absolute,
+    High,
+    Low,
+
+    /// A 32-bit address, as used by PE32 images.
+    HighLow,
+    HighAdj,
+
+    /// A 64-bit address, as used by PE32+ images.
+    Dir64,
+    Other(u8),
+}
+
+impl RelocationType {
+    pub fn from_u8(value: u8) -> RelocationType {
+        match value {
+            0 => RelocationType::Absolute,
+            1 => RelocationType::High,
+            2 => RelocationType::Low,
+            3 => RelocationType::HighLow,
+            4 => RelocationType::HighAdj,
+            10 => RelocationType::Dir64,
+            x => RelocationType::Other(x),
+        }
+    }
+}
+
+/// A single base relocation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Relocation {
+    pub relocation_type: RelocationType,
+
+    /// The RVA of the value to fix up.
+    pub rva: u32,
+}
+
+/// A block of base relocations for one 4K page.
+pub struct RelocationBlock<'a> {
+    pub page_rva: u32,
+    entries: &'a [u8],
+}
+
+impl<'a> RelocationBlock<'a> {
+    /// Iterates over the relocations in the block.
+    pub fn relocations(&self) -> impl Iterator<Item = Relocation> + 'a {
+        let page_rva = self.page_rva;
+        self.entries.chunks_exact(2).map(move |entry| {
+            let entry = LittleEndian::read_u16(entry);
+            Relocation {
+                relocation_type: RelocationType::from_u8((entry >> 12) as u8),
+                rva: page_rva + (entry & 0x0FFF) as u32,
+            }
+        })
+    }
+}
+
+/// Iterates over the blocks of the base relocation table.
+pub struct RelocationBlocks<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Iterator for RelocationBlocks<'a> {
+    type Item = Result<RelocationBlock<'a>, Error>;
+
+    fn next(&mut self) -> Option<Result<RelocationBlock<'a>, Error>> {
+        if self.buf.len() < 8 {
+            return None;
+        }
+        let page_rva = LittleEndian::read_u32(&self.buf[0..4]);
+        let size = LittleEndian::read_u32(&self.buf[4..8]) as usize;
+        if size < 8 || size > self.buf.len() {
+            self.buf = &[];
+            return Some(Err(Error::DataOutOfRange));
+        }
+        let block = RelocationBlock { page_rva, entries: &self.buf[8..size] };
+        self.buf = &self.buf[size..];
+        Some(Ok(block))
+    }
+}
+
+impl<D: ImageData> PeImage<D> {
+    /// Iterates over the blocks of the base relocation table.
+    pub fn base_relocations(&self) -> Result<RelocationBlocks<'_>, Error> {
+        let buf = match self.directory(DirectoryType::BaseRelocationTable) {
+            Some(range) => self.read_rva(range)?,
+            None => &[],
+        };
+        Ok(RelocationBlocks { buf })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pe::test_image::{self, SECTION_RVA};
+    use crate::pe::{DirectoryType, MemoryRange, PeImage, Relocation, RelocationType};
+
+    #[test]
+    pub fn read_relocations() {
+        let mut section = Vec::new();
+        section.extend_from_slice(&0x2000u32.to_le_bytes());
+        section.extend_from_slice(&12u32.to_le_bytes());
+        section.extend_from_slice(&0x3008u16.to_le_bytes());
+        section.extend_from_slice(&0x0000u16.to_le_bytes());
+        section.extend_from_slice(&0x4000u32.to_le_bytes());
+        section.extend_from_slice(&10u32.to_le_bytes());
+        section.extend_from_slice(&0xA010u16.to_le_bytes());
+
+        let image = test_image::build(false, &section, &[(DirectoryType::BaseRelocationTable, MemoryRange::new(SECTION_RVA, 22))]);
+        let pe = PeImage::load(image).unwrap();
+        let relocations: Vec<Relocation> = pe
+            .base_relocations()
+            .unwrap()
+            .map(|b| b.unwrap().relocations().collect::<Vec<_>>())
+            .flatten()
+            .collect();
+        assert_eq!(
+            vec![
+                Relocation { relocation_type: RelocationType::HighLow, rva: 0x2008 },
+                Relocation { relocation_type: RelocationType::Absolute, rva: 0x2000 },
+                Relocation { relocation_type: RelocationType::Dir64, rva: 0x4010 },
+            ],
+            relocations
+        );
+    }
+}
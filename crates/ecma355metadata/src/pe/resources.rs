@@ -0,0 +1,165 @@
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::error::Error;
+use crate::pe::MemoryRange;
+
+/// Well-known resource type ids.
+pub mod resource_types {
+    pub const CURSOR: u16 = 1;
+    pub const BITMAP: u16 = 2;
+    pub const ICON: u16 = 3;
+    pub const MENU: u16 = 4;
+    pub const DIALOG: u16 = 5;
+    pub const STRING: u16 = 6;
+    pub const RCDATA: u16 = 10;
+    pub const GROUP_CURSOR: u16 = 12;
+    pub const GROUP_ICON: u16 = 14;
+    pub const VERSION: u16 = 16;
+    pub const MANIFEST: u16 = 24;
+}
+
+/// The name of a resource directory entry, which is either an integer id or a string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ResourceName {
+    Id(u16),
+    Name(String),
+}
+
+impl ::std::fmt::Display for ResourceName {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> Result<(), ::std::fmt::Error> {
+        match self {
+            ResourceName::Id(id) => write!(f, "#{}", id),
+            ResourceName::Name(name) => f.write_str(name),
+        }
+    }
+}
+
+/// A leaf of the resource tree, identified by its type, name and language.
+#[derive(Debug)]
+pub struct ResourceEntry {
+    pub resource_type: ResourceName,
+    pub name: ResourceName,
+    pub language: u16,
+
+    /// The RVA range of the resource data.
+    pub data: MemoryRange,
+    pub code_page: u32,
+}
+
+const DIRECTORY_SIZE: usize = 16;
+const ENTRY_SIZE: usize = 8;
+const HIGH_BIT: u32 = 0x8000_0000;
+
+/// Walks the type, name and language levels of a resource directory.
+///
+/// `buf` holds the resource directory, starting at the root. Offsets within the tree are relative to the root, but
+/// the data entries at the leaves hold RVAs.
+pub fn read_resources(buf: &[u8]) -> Result<Vec<ResourceEntry>, Error> {
+    let mut resources = Vec::new();
+    for (resource_type, type_offset) in read_directory(buf, 0)? {
+        let type_offset = subdirectory(type_offset)?;
+        for (name, name_offset) in read_directory(buf, type_offset)? {
+            let name_offset = subdirectory(name_offset)?;
+            for (language, data_offset) in read_directory(buf, name_offset)? {
+                if data_offset & HIGH_BIT != 0 {
+                    return Err(Error::InvalidMetadata("resource tree is deeper than three levels".into()));
+                }
+                let entry = slice(buf, data_offset as usize, 16)?;
+                resources.push(ResourceEntry {
+                    resource_type: resource_type.clone(),
+                    name: name.clone(),
+                    language: match language {
+                        ResourceName::Id(id) => id,
+                        ResourceName::Name(_) => 0,
+                    },
+                    data: MemoryRange::new(LittleEndian::read_u32(&entry[0..4]), LittleEndian::read_u32(&entry[4..8])),
+                    code_page: LittleEndian::read_u32(&entry[8..12]),
+                });
+            }
+        }
+    }
+    Ok(resources)
+}
+
+fn subdirectory(offset: u32) -> Result<usize, Error> {
+    if offset & HIGH_BIT == 0 {
+        Err(Error::InvalidMetadata("resource tree is shallower than three levels".into()))
+    } else {
+        Ok((offset & !HIGH_BIT) as usize)
+    }
+}
+
+fn read_directory(buf: &[u8], offset: usize) -> Result<Vec<(ResourceName, u32)>, Error> {
+    let header = slice(buf, offset, DIRECTORY_SIZE)?;
+    let count = LittleEndian::read_u16(&header[12..14]) as usize + LittleEndian::read_u16(&header[14..16]) as usize;
+    let entries = slice(buf, offset + DIRECTORY_SIZE, count * ENTRY_SIZE)?;
+
+    let mut result = Vec::with_capacity(count);
+    for entry in entries.chunks_exact(ENTRY_SIZE) {
+        let name = LittleEndian::read_u32(&entry[0..4]);
+        let name = if name & HIGH_BIT != 0 {
+            // Names are a length in characters followed by UTF-16 text, with no terminator
+            let name_offset = (name & !HIGH_BIT) as usize;
+            let len = LittleEndian::read_u16(slice(buf, name_offset, 2)?) as usize;
+            let units: Vec<u16> = slice(buf, name_offset + 2, len * 2)?
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect();
+            ResourceName::Name(String::from_utf16_lossy(&units))
+        } else {
+            ResourceName::Id(name as u16)
+        };
+        result.push((name, LittleEndian::read_u32(&entry[4..8])));
+    }
+    Ok(result)
+}
+
+fn slice(buf: &[u8], offset: usize, len: usize) -> Result<&[u8], Error> {
+    buf.get(offset..(offset + len)).ok_or(Error::DataOutOfRange)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directory(entries: &[(u32, u32)]) -> Vec<u8> {
+        let mut buf = vec![0u8; 12];
+        let named = entries.iter().filter(|(name, _)| name & HIGH_BIT != 0).count() as u16;
+        buf.extend_from_slice(&named.to_le_bytes());
+        buf.extend_from_slice(&(entries.len() as u16 - named).to_le_bytes());
+        for (name, offset) in entries {
+            buf.extend_from_slice(&name.to_le_bytes());
+            buf.extend_from_slice(&offset.to_le_bytes());
+        }
+        buf
+    }
+
+    #[test]
+    pub fn read_resource_tree() {
+        // Root at 0x00, names of type VERSION at 0x18, languages of "APP" at 0x30, data entry at 0x48, "APP" string at 0x70
+        let mut buf = directory(&[(16, HIGH_BIT | 0x18)]);
+        buf.extend(directory(&[(HIGH_BIT | 0x70, HIGH_BIT | 0x30)]));
+        buf.extend(directory(&[(1033, 0x48)]));
+        buf.resize(0x48, 0);
+        buf.extend_from_slice(&0x2000u32.to_le_bytes());
+        buf.extend_from_slice(&0x100u32.to_le_bytes());
+        buf.extend_from_slice(&1200u32.to_le_bytes());
+        buf.extend_from_slice(&0u32.to_le_bytes());
+        buf.resize(0x70, 0);
+        buf.extend_from_slice(&[0x03, 0x00, b'A', 0x00, b'P', 0x00, b'P', 0x00]);
+
+        let resources = read_resources(&buf).unwrap();
+        assert_eq!(1, resources.len());
+        assert_eq!(ResourceName::Id(resource_types::VERSION), resources[0].resource_type);
+        assert_eq!(ResourceName::Name("APP".into()), resources[0].name);
+        assert_eq!(1033, resources[0].language);
+        assert_eq!((0x2000, 0x100), (resources[0].data.start, resources[0].data.len));
+        assert_eq!(1200, resources[0].code_page);
+    }
+
+    #[test]
+    pub fn reject_truncated_tree() {
+        let buf = directory(&[(16, HIGH_BIT | 0x18)]);
+        assert!(read_resources(&buf).is_err());
+    }
+}
@@ -20,6 +20,10 @@ impl Subsystem {
     pub fn new(val: u16) -> Subsystem {
         Subsystem(val)
     }
+
+    pub fn value(self) -> u16 {
+        self.0
+    }
 }
 
 impl ::std::fmt::Display for Subsystem {
@@ -0,0 +1,98 @@
+//! Builds minimal PE images for tests.
+
+use crate::pe::{DirectoryType, MemoryRange};
+
+/// The RVA of the single section in a test image.
+pub const SECTION_RVA: u32 = 0x2000;
+
+/// The file offset of the single section in a test image.
+pub const SECTION_OFFSET: usize = 0x200;
+
+const DIRECTORY_TYPES: [DirectoryType; 16] = [
+    DirectoryType::ExportTable,
+    DirectoryType::ImportTable,
+    DirectoryType::ResourceTable,
+    DirectoryType::ExceptionTable,
+    DirectoryType::CertificateTable,
+    DirectoryType::BaseRelocationTable,
+    DirectoryType::DebugData,
+    DirectoryType::CopyrightData,
+    DirectoryType::GlobalPtrData,
+    DirectoryType::TlsTable,
+    DirectoryType::LoadConfigTable,
+    DirectoryType::BoundImport,
+    DirectoryType::ImportAddressTable,
+    DirectoryType::DelayImportDescriptor,
+    DirectoryType::CliHeader,
+    DirectoryType::Reserved,
+];
+
+/// Builds an image with one section at `SECTION_RVA` holding `section`, and the given data directories.
+pub fn build(pe32plus: bool, section: &[u8], directories: &[(DirectoryType, MemoryRange)]) -> Vec<u8> {
+    let mut buf = vec![0u8; 0x40];
+    buf[0..2].copy_from_slice(b"MZ");
+    buf[0x3C..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+
+    // COFF header
+    buf.extend_from_slice(b"PE\0\0");
+    buf.extend_from_slice(&(if pe32plus { 0x8664u16 } else { 0x014Cu16 }).to_le_bytes());
+    buf.extend_from_slice(&1u16.to_le_bytes());
+    buf.extend_from_slice(&[0u8; 12]);
+    buf.extend_from_slice(&(if pe32plus { 240u16 } else { 224u16 }).to_le_bytes());
+    buf.extend_from_slice(&0x2102u16.to_le_bytes());
+
+    // Optional header
+    let raw_size = (section.len() + 0x1FF) & !0x1FF;
+    buf.extend_from_slice(&(if pe32plus { 0x020Bu16 } else { 0x010Bu16 }).to_le_bytes());
+    buf.extend_from_slice(&[0u8; 22]);
+    if !pe32plus {
+        buf.extend_from_slice(&0u32.to_le_bytes());
+        buf.extend_from_slice(&0x0040_0000u32.to_le_bytes());
+    } else {
+        buf.extend_from_slice(&0x0000_0001_4000_0000u64.to_le_bytes());
+    }
+    buf.extend_from_slice(&0x2000u32.to_le_bytes());
+    buf.extend_from_slice(&0x200u32.to_le_bytes());
+    buf.extend_from_slice(&[0u8; 16]);
+    buf.extend_from_slice(&(SECTION_RVA + 0x2000).to_le_bytes());
+    buf.extend_from_slice(&(SECTION_OFFSET as u32).to_le_bytes());
+    buf.extend_from_slice(&[0u8; 8]);
+    buf.extend_from_slice(&vec![0u8; if pe32plus { 32 } else { 16 }]);
+    buf.extend_from_slice(&[0u8; 4]);
+    buf.extend_from_slice(&16u32.to_le_bytes());
+    for directory_type in DIRECTORY_TYPES.iter() {
+        let range = directories
+            .iter()
+            .find(|(t, _)| t == directory_type)
+            .map(|(_, r)| *r)
+            .unwrap_or(MemoryRange::new(0, 0));
+        buf.extend_from_slice(&range.start.to_le_bytes());
+        buf.extend_from_slice(&range.len.to_le_bytes());
+    }
+
+    // Section header
+    buf.extend_from_slice(b".text\0\0\0");
+    buf.extend_from_slice(&(section.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&SECTION_RVA.to_le_bytes());
+    buf.extend_from_slice(&(raw_size as u32).to_le_bytes());
+    buf.extend_from_slice(&(SECTION_OFFSET as u32).to_le_bytes());
+    buf.extend_from_slice(&[0u8; 12]);
+    buf.extend_from_slice(&0x6000_0020u32.to_le_bytes());
+
+    buf.resize(SECTION_OFFSET, 0);
+    buf.extend_from_slice(section);
+    buf.resize(SECTION_OFFSET + raw_size, 0);
+    buf
+}
+
+/// Builds an image whose section holds a CLI header followed by the metadata root `metadata`.
+pub fn cli_image(metadata: &[u8]) -> Vec<u8> {
+    let mut section = Vec::new();
+    section.extend_from_slice(&72u32.to_le_bytes());
+    section.extend_from_slice(&[0x02, 0x00, 0x05, 0x00]);
+    section.extend_from_slice(&(SECTION_RVA + 72).to_le_bytes());
+    section.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+    section.resize(72, 0);
+    section.extend_from_slice(metadata);
+    build(false, &section, &[(DirectoryType::CliHeader, MemoryRange::new(SECTION_RVA, 72))])
+}
@@ -0,0 +1,229 @@
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::error::Error;
+use crate::utils;
+
+/// The fixed part of a version resource (VS_FIXEDFILEINFO).
+#[derive(Debug)]
+pub struct FixedFileInfo {
+    pub file_version: [u16; 4],
+    pub product_version: [u16; 4],
+    pub file_flags_mask: u32,
+    pub file_flags: u32,
+    pub file_os: u32,
+    pub file_type: u32,
+    pub file_subtype: u32,
+    pub file_date: u64,
+}
+
+const FIXED_FILE_INFO_SIGNATURE: u32 = 0xFEEF04BD;
+const FIXED_FILE_INFO_SIZE: usize = 52;
+
+impl FixedFileInfo {
+    pub fn read(buf: &[u8]) -> Result<FixedFileInfo, Error> {
+        if buf.len() < FIXED_FILE_INFO_SIZE {
+            return Err(Error::DataOutOfRange);
+        }
+        if LittleEndian::read_u32(&buf[0..4]) != FIXED_FILE_INFO_SIGNATURE {
+            return Err(Error::InvalidSignature);
+        }
+        let u32_at = |offset: usize| LittleEndian::read_u32(&buf[offset..(offset + 4)]);
+        let version = |ms: u32, ls: u32| [(ms >> 16) as u16, ms as u16, (ls >> 16) as u16, ls as u16];
+        Ok(FixedFileInfo {
+            file_version: version(u32_at(8), u32_at(12)),
+            product_version: version(u32_at(16), u32_at(20)),
+            file_flags_mask: u32_at(24),
+            file_flags: u32_at(28),
+            file_os: u32_at(32),
+            file_type: u32_at(36),
+            file_subtype: u32_at(40),
+            file_date: ((u32_at(44) as u64) << 32) | u32_at(48) as u64,
+        })
+    }
+}
+
+/// The strings for one language and code page, from the StringFileInfo block.
+#[derive(Debug)]
+pub struct StringTable {
+    /// The language and code page, as eight hex digits such as "040904B0".
+    pub key: String,
+    pub strings: Vec<(String, String)>,
+}
+
+/// A decoded VS_VERSIONINFO resource.
+#[derive(Debug)]
+pub struct VersionInfo {
+    pub fixed: Option<FixedFileInfo>,
+    pub string_tables: Vec<StringTable>,
+
+    /// The language and code page pairs from the VarFileInfo block.
+    pub translations: Vec<(u16, u16)>,
+}
+
+impl VersionInfo {
+    pub fn read(buf: &[u8]) -> Result<VersionInfo, Error> {
+        let root = Block::read(buf, 0)?;
+        if root.key != "VS_VERSION_INFO" {
+            return Err(Error::InvalidSignature);
+        }
+
+        let mut info = VersionInfo {
+            fixed: if root.value.is_empty() { None } else { Some(FixedFileInfo::read(root.value)?) },
+            string_tables: Vec::new(),
+            translations: Vec::new(),
+        };
+
+        for child in root.children(buf)? {
+            match child.key.as_str() {
+                "StringFileInfo" => {
+                    for table in child.children(buf)? {
+                        let strings = table
+                            .children(buf)?
+                            .into_iter()
+                            .map(|s| (s.key, utils::read_utf16_nul(s.value).0))
+                            .collect();
+                        info.string_tables.push(StringTable { key: table.key, strings });
+                    }
+                }
+                "VarFileInfo" => {
+                    for var in child.children(buf)? {
+                        if var.key == "Translation" {
+                            info.translations.extend(
+                                var.value.chunks_exact(4).map(|c| (LittleEndian::read_u16(&c[0..2]), LittleEndian::read_u16(&c[2..4]))),
+                            );
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(info)
+    }
+
+    pub fn file_version(&self) -> Option<[u16; 4]> {
+        self.fixed.as_ref().map(|f| f.file_version)
+    }
+
+    pub fn product_version(&self) -> Option<[u16; 4]> {
+        self.fixed.as_ref().map(|f| f.product_version)
+    }
+
+    /// Looks up a string, such as "CompanyName", in the first string table that has it.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.string_tables
+            .iter()
+            .flat_map(|t| t.strings.iter())
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// One node of the version resource tree: a length, a value length, a type, a key, the value and the children.
+///
+/// Every part is aligned to 4 bytes from the start of the resource.
+struct Block<'a> {
+    key: String,
+    value: &'a [u8],
+    children_start: usize,
+    end: usize,
+}
+
+fn align(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+impl<'a> Block<'a> {
+    fn read(buf: &'a [u8], offset: usize) -> Result<Block<'a>, Error> {
+        let header = buf.get(offset..(offset + 6)).ok_or(Error::DataOutOfRange)?;
+        let length = LittleEndian::read_u16(&header[0..2]) as usize;
+        let value_length = LittleEndian::read_u16(&header[2..4]) as usize;
+        let is_text = LittleEndian::read_u16(&header[4..6]) == 1;
+        let end = offset + length;
+        if length < 6 || end > buf.len() {
+            return Err(Error::DataOutOfRange);
+        }
+
+        let (key, key_size) = utils::read_utf16_nul(&buf[(offset + 6)..end]);
+        let value_start = align(offset + 6 + key_size).min(end);
+
+        // Text values are measured in characters, and binary values in bytes
+        let value_size = if is_text { value_length * 2 } else { value_length };
+        let value_end = (value_start + value_size).min(end);
+
+        Ok(Block {
+            key,
+            value: &buf[value_start..value_end],
+            children_start: align(value_end),
+            end,
+        })
+    }
+
+    fn children(&self, buf: &'a [u8]) -> Result<Vec<Block<'a>>, Error> {
+        let mut children = Vec::new();
+        let mut offset = self.children_start;
+        while offset < self.end {
+            let child = Block::read(buf, offset)?;
+            offset = align(child.end);
+            children.push(child);
+        }
+        Ok(children)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(key: &str, value_length: u16, is_text: bool, value: &[u8], children: &[Vec<u8>]) -> Vec<u8> {
+        let mut buf = vec![0, 0];
+        buf.extend_from_slice(&value_length.to_le_bytes());
+        buf.extend_from_slice(&(is_text as u16).to_le_bytes());
+        for unit in key.encode_utf16().chain(std::iter::once(0)) {
+            buf.extend_from_slice(&unit.to_le_bytes());
+        }
+        buf.resize(align(buf.len()), 0);
+        buf.extend_from_slice(value);
+        for child in children {
+            buf.resize(align(buf.len()), 0);
+            buf.extend_from_slice(child);
+        }
+        let len = buf.len() as u16;
+        buf[0..2].copy_from_slice(&len.to_le_bytes());
+        buf
+    }
+
+    fn text(key: &str, value: &str) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for unit in value.encode_utf16().chain(std::iter::once(0)) {
+            bytes.extend_from_slice(&unit.to_le_bytes());
+        }
+        block(key, (bytes.len() / 2) as u16, true, &bytes, &[])
+    }
+
+    #[test]
+    pub fn read_version_info() {
+        let mut fixed = Vec::new();
+        for value in [FIXED_FILE_INFO_SIGNATURE, 0x10000, 0x0001_0002, 0x0003_0004, 0x0005_0006, 0x0007_0008, 0x3F, 0, 4, 2, 0, 0, 0] {
+            fixed.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let strings = block("StringFileInfo", 0, true, &[], &[block(
+            "040904B0",
+            0,
+            true,
+            &[],
+            &[text("CompanyName", "Contoso"), text("FileVersion", "1.2.3.4")],
+        )]);
+        let vars = block("VarFileInfo", 0, true, &[], &[block("Translation", 4, false, &[0x09, 0x04, 0xB0, 0x04], &[])]);
+        let buf = block("VS_VERSION_INFO", FIXED_FILE_INFO_SIZE as u16, false, &fixed, &[strings, vars]);
+
+        let info = VersionInfo::read(&buf).unwrap();
+        assert_eq!(Some([1, 2, 3, 4]), info.file_version());
+        assert_eq!(Some([5, 6, 7, 8]), info.product_version());
+        assert_eq!("040904B0", info.string_tables[0].key);
+        assert_eq!(Some("Contoso"), info.get("CompanyName"));
+        assert_eq!(Some("1.2.3.4"), info.get("FileVersion"));
+        assert_eq!(None, info.get("ProductName"));
+        assert_eq!(vec![(0x0409, 0x04B0)], info.translations);
+    }
+}
@@ -0,0 +1,133 @@
+use std::io::Read;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::error::Error;
+use crate::pe::{MemoryRange, SectionCharacteristics, SectionHeader};
+
+/// The signature at the start of a Webcil payload.
+pub const WEBCIL_MAGIC: [u8; 4] = *b"WbIL";
+
+/// The signature at the start of a WebAssembly module.
+pub const WASM_MAGIC: [u8; 4] = *b"\0asm";
+
+const WASM_DATA_SECTION: u8 = 11;
+
+/// The header of a Webcil image, the container Blazor WebAssembly uses for assemblies.
+///
+/// Webcil keeps the sections of the PE image it was converted from, but replaces the PE headers with this header. Only
+/// the CLI header and debug directories survive.
+#[derive(Debug)]
+pub struct WebcilHeader {
+    pub major_version: u16,
+    pub minor_version: u16,
+    pub section_count: u16,
+    pub cli_header: MemoryRange,
+    pub debug_directory: MemoryRange,
+}
+
+impl WebcilHeader {
+    pub const SIZE: usize = 28;
+    pub const SECTION_SIZE: usize = 16;
+
+    pub fn read<A: Read>(mut buf: A) -> Result<WebcilHeader, Error> {
+        let mut magic = [0u8; 4];
+        buf.read_exact(&mut magic)?;
+        if magic != WEBCIL_MAGIC {
+            return Err(Error::InvalidSignature);
+        }
+        let major_version = buf.read_u16::<LittleEndian>()?;
+        let minor_version = buf.read_u16::<LittleEndian>()?;
+        let section_count = buf.read_u16::<LittleEndian>()?;
+        buf.read_u16::<LittleEndian>()?;
+        Ok(WebcilHeader {
+            major_version,
+            minor_version,
+            section_count,
+            cli_header: MemoryRange::read(&mut buf)?,
+            debug_directory: MemoryRange::read(&mut buf)?,
+        })
+    }
+
+    /// Reads the section table that follows the header.
+    ///
+    /// Webcil sections have no names or characteristics. `base` is the file offset of the Webcil payload, which the
+    /// raw data pointers are relative to.
+    pub fn read_sections<A: Read>(&self, mut buf: A, base: usize) -> Result<Vec<SectionHeader>, Error> {
+        let mut sections = Vec::with_capacity(self.section_count as usize);
+        for _ in 0..self.section_count {
+            sections.push(SectionHeader {
+                name: String::new(),
+                virtual_size: buf.read_u32::<LittleEndian>()?,
+                virtual_address: buf.read_u32::<LittleEndian>()?,
+                size_of_raw_data: buf.read_u32::<LittleEndian>()?,
+                pointer_to_raw_data: buf.read_u32::<LittleEndian>()? + base as u32,
+                pointer_to_relocations: 0,
+                pointer_to_linenumbers: 0,
+                number_of_relocations: 0,
+                number_of_linenumbers: 0,
+                characteristics: SectionCharacteristics::empty(),
+            });
+        }
+        Ok(sections)
+    }
+}
+
+/// Finds the Webcil payload in a WebAssembly module, returning its file offset.
+///
+/// The payload is the second segment of the data section. The first holds its size, so that the runtime can allocate
+/// memory for it before copying it out.
+pub fn find_webcil_payload(buf: &[u8]) -> Result<usize, Error> {
+    if buf.len() < 8 || buf[0..4] != WASM_MAGIC {
+        return Err(Error::InvalidSignature);
+    }
+
+    let mut offset = 8;
+    while offset < buf.len() {
+        let id = buf[offset];
+        offset += 1;
+        let size = read_leb128(buf, &mut offset)?;
+        let end = offset + size as usize;
+        if id != WASM_DATA_SECTION {
+            offset = end;
+            continue;
+        }
+
+        let count = read_leb128(buf, &mut offset)?;
+        for index in 0..count {
+            match read_leb128(buf, &mut offset)? {
+                // Passive segments have no memory index or offset expression
+                1 => {}
+                flags @ (0 | 2) => {
+                    if flags == 2 {
+                        read_leb128(buf, &mut offset)?;
+                    }
+                    // Skip the constant expression up to its end opcode
+                    let len = buf.get(offset..end).and_then(|b| b.iter().position(|x| *x == 0x0B)).ok_or(Error::DataOutOfRange)?;
+                    offset += len + 1;
+                }
+                flags => return Err(Error::InvalidMetadata(format!("unknown wasm data segment kind {}", flags))),
+            }
+            let len = read_leb128(buf, &mut offset)? as usize;
+            if index == 1 {
+                return Ok(offset);
+            }
+            offset += len;
+        }
+        break;
+    }
+    Err(Error::InvalidMetadata("wasm module has no webcil payload".into()))
+}
+
+fn read_leb128(buf: &[u8], offset: &mut usize) -> Result<u32, Error> {
+    let mut value = 0u32;
+    for shift in (0..35).step_by(7) {
+        let byte = *buf.get(*offset).ok_or(Error::DataOutOfRange)?;
+        *offset += 1;
+        value |= ((byte & 0x7F) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(Error::InvalidMetadata("LEB128 integer is too long".into()))
+}
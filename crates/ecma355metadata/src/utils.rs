@@ -1,11 +1,55 @@
 use std::io::Read;
 
-pub fn read_bytes<R: Read>(mut reader: R, count: usize) -> Result<Vec<u8>, ::std::io::Error> {
-    unsafe {
-        let mut vec = Vec::with_capacity(count);
-        vec.reserve_exact(count);
-        vec.set_len(count);
-        reader.read_exact(vec.as_mut_slice())?;
-        Ok(vec)
+use byteorder::ReadBytesExt;
+
+use crate::error::Error;
+
+/// Reads exactly `count` bytes.
+///
+/// The count often comes from the data being read, so the buffer grows as bytes arrive rather than being allocated
+/// up front.
+pub fn read_bytes<R: Read>(reader: R, count: usize) -> Result<Vec<u8>, ::std::io::Error> {
+    let mut vec = Vec::new();
+    reader.take(count as u64).read_to_end(&mut vec)?;
+    if vec.len() != count {
+        return Err(::std::io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(vec)
+}
+
+/// Reads a null-terminated UTF-16LE string, returning it and the number of bytes consumed including the terminator.
+///
+/// If there is no terminator, the whole buffer is read.
+pub fn read_utf16_nul(buf: &[u8]) -> (String, usize) {
+    let units: Vec<u16> = buf
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .take_while(|u| *u != 0)
+        .collect();
+    let consumed = ((units.len() + 1) * 2).min(buf.len() & !1);
+    (String::from_utf16_lossy(&units), consumed)
+}
+
+/// Reads an integer in the 7-bit encoding used by .NET's `BinaryWriter`.
+pub fn read_7bit_encoded_int<R: Read>(mut reader: R) -> Result<u32, Error> {
+    let mut value = 0u32;
+    for shift in (0..35).step_by(7) {
+        let byte = reader.read_u8()?;
+        value |= ((byte & 0x7F) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(Error::InvalidMetadata("7-bit encoded integer is too long".into()))
+}
+
+/// Reads a UTF-8 string prefixed with its 7-bit encoded length in bytes, as written by `BinaryWriter`.
+pub fn read_prefixed_string<R: Read>(mut reader: R) -> Result<String, Error> {
+    let len = read_7bit_encoded_int(&mut reader)? as u64;
+    let mut buf = Vec::new();
+    reader.take(len).read_to_end(&mut buf)?;
+    if buf.len() as u64 != len {
+        return Err(Error::DataOutOfRange);
     }
-}
\ No newline at end of file
+    String::from_utf8(buf).or(Err(Error::InvalidMetadata("invalid UTF-8 string".into())))
+}
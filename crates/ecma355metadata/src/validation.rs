@@ -0,0 +1,744 @@
+//! Validation rules from ECMA-335, Partition II, chapter 22 ("Metadata logical format: tables").
+//!
+//! Only the rules marked [ERROR] that can be checked from the metadata alone are implemented here.
+//! Rules are identified by their section and, where the spec numbers them, their rule number.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::pe::ImageData;
+use crate::cli::signatures::{utils, MethodSignature, SignatureHeader, SignatureKind};
+use crate::cli::tables::{self, CodedIndex, TableHandle, TableIndex, TableRow};
+use crate::cli::{BlobHandle, StringHandle};
+use crate::MetadataImage;
+
+/// Identifies a validation rule in ECMA-335 Partition II.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rule {
+    /// The section of Partition II that defines the rule (for example, "22.37" for the TypeDef table).
+    pub section: &'static str,
+
+    /// The number of the rule within its section, if the spec numbers it.
+    pub number: Option<u8>,
+}
+
+impl Rule {
+    const fn new(section: &'static str, number: u8) -> Rule {
+        Rule {
+            section,
+            number: Some(number),
+        }
+    }
+
+    const fn section(section: &'static str) -> Rule {
+        Rule {
+            section,
+            number: None,
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self.number {
+            Some(number) => write!(f, "II.{} rule {}", self.section, number),
+            None => write!(f, "II.{}", self.section),
+        }
+    }
+}
+
+/// The rules checked by the validator.
+pub mod rules {
+    use super::Rule;
+
+    /// Tables that are required to be sorted shall be sorted by their primary key.
+    pub const SORTED_TABLES: Rule = Rule::section("22");
+
+    pub const ASSEMBLY_SINGLE_ROW: Rule = Rule::new("22.2", 1);
+    pub const ASSEMBLY_NAME: Rule = Rule::new("22.2", 6);
+    pub const ASSEMBLY_REF_NAME: Rule = Rule::new("22.5", 5);
+    pub const CONSTANT_TYPE: Rule = Rule::new("22.9", 1);
+    pub const CONSTANT_PARENT: Rule = Rule::new("22.9", 3);
+    pub const CONSTANT_DUPLICATE: Rule = Rule::new("22.9", 4);
+    pub const CUSTOM_ATTRIBUTE_PARENT: Rule = Rule::new("22.10", 2);
+    pub const CUSTOM_ATTRIBUTE_TYPE: Rule = Rule::new("22.10", 3);
+    pub const FIELD_NAME: Rule = Rule::new("22.15", 7);
+    pub const FIELD_SIGNATURE: Rule = Rule::new("22.15", 9);
+    pub const INTERFACE_IMPL_CLASS: Rule = Rule::new("22.23", 1);
+    pub const MEMBER_REF_CLASS: Rule = Rule::new("22.25", 2);
+    pub const MEMBER_REF_NAME: Rule = Rule::new("22.25", 3);
+    pub const MEMBER_REF_SIGNATURE: Rule = Rule::new("22.25", 5);
+    pub const METHOD_DEF_NAME: Rule = Rule::new("22.26", 17);
+    pub const METHOD_DEF_SIGNATURE: Rule = Rule::new("22.26", 32);
+    pub const METHOD_DEF_PARAM_LIST: Rule = Rule::new("22.26", 33);
+    pub const MODULE_SINGLE_ROW: Rule = Rule::new("22.30", 1);
+    pub const MODULE_NAME: Rule = Rule::new("22.30", 2);
+    pub const MODULE_MVID: Rule = Rule::new("22.30", 3);
+    pub const PARAM_SEQUENCE_ORDER: Rule = Rule::new("22.33", 5);
+    pub const PARAM_NAME: Rule = Rule::new("22.33", 10);
+    pub const TYPE_DEF_NAME: Rule = Rule::new("22.37", 3);
+    pub const TYPE_DEF_NAMESPACE: Rule = Rule::new("22.37", 6);
+    pub const TYPE_DEF_FIELD_LIST: Rule = Rule::new("22.37", 16);
+    pub const TYPE_DEF_METHOD_LIST: Rule = Rule::new("22.37", 18);
+    pub const TYPE_DEF_DUPLICATE: Rule = Rule::new("22.37", 19);
+    pub const TYPE_DEF_NESTED_DUPLICATE: Rule = Rule::new("22.37", 20);
+    pub const TYPE_REF_RESOLUTION_SCOPE: Rule = Rule::new("22.38", 1);
+    pub const TYPE_REF_NAME: Rule = Rule::new("22.38", 2);
+    pub const TYPE_REF_NAMESPACE: Rule = Rule::new("22.38", 5);
+    pub const TYPE_REF_DUPLICATE: Rule = Rule::new("22.38", 7);
+}
+
+/// A single violation of a Partition II validation rule.
+#[derive(Clone, Debug)]
+pub struct Violation {
+    /// The table containing the offending row.
+    pub table: TableIndex,
+
+    /// The 1-based index of the offending row, or 0 if the violation applies to the whole table.
+    pub row: usize,
+
+    /// The rule that was violated.
+    pub rule: Rule,
+
+    /// A description of the violation.
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        if self.row == 0 {
+            write!(f, "{}: {} ({})", self.table, self.message, self.rule)
+        } else {
+            write!(f, "{}[0x{:04X}]: {} ({})", self.table, self.row, self.message, self.rule)
+        }
+    }
+}
+
+/// Validates the metadata tables in `image`, returning every rule violation that was found.
+pub fn validate<D: ImageData>(image: &MetadataImage<D>) -> Vec<Violation> {
+    let mut validator = Validator {
+        image,
+        violations: Vec::new(),
+    };
+    validator.run();
+    validator.violations
+}
+
+struct Validator<'a, D: ImageData> {
+    image: &'a MetadataImage<D>,
+    violations: Vec<Violation>,
+}
+
+impl<'a, D: ImageData> Validator<'a, D> {
+    fn run(&mut self) {
+        let modules = self.check_table::<tables::Module>();
+        self.check_modules(&modules);
+
+        let type_refs = self.check_table::<tables::TypeRef>();
+        self.check_type_refs(&type_refs);
+
+        let type_defs = self.check_table::<tables::TypeDef>();
+        self.check_table::<tables::FieldPtr>();
+        let fields = self.check_table::<tables::Field>();
+        self.check_fields(&fields);
+        self.check_table::<tables::MethodPtr>();
+        let method_defs = self.check_table::<tables::MethodDef>();
+        self.check_method_defs(&method_defs);
+        self.check_table::<tables::ParamPtr>();
+        let params = self.check_table::<tables::Param>();
+        self.check_params(&method_defs, &params);
+
+        let interface_impls = self.check_table::<tables::InterfaceImpl>();
+        self.check_interface_impls(&interface_impls);
+
+        let member_refs = self.check_table::<tables::MemberRef>();
+        self.check_member_refs(&member_refs);
+
+        let constants = self.check_table::<tables::Constant>();
+        self.check_constants(&constants);
+
+        let custom_attributes = self.check_table::<tables::CustomAttribute>();
+        self.check_custom_attributes(&custom_attributes);
+
+        let field_marshals = self.check_table::<tables::FieldMarshal>();
+        self.check_sorted(&field_marshals, |r| tables::HasFieldMarshal::encode(&r.parent));
+
+        let decl_securities = self.check_table::<tables::DeclSecurity>();
+        self.check_sorted(&decl_securities, |r| tables::HasDeclSecurity::encode(&r.parent));
+
+        let class_layouts = self.check_table::<tables::ClassLayout>();
+        self.check_sorted(&class_layouts, |r| r.parent.index());
+
+        let field_layouts = self.check_table::<tables::FieldLayout>();
+        self.check_sorted(&field_layouts, |r| r.field.index());
+
+        self.check_table::<tables::StandAloneSig>();
+        self.check_table::<tables::EventMap>();
+        self.check_table::<tables::EventPtr>();
+        self.check_table::<tables::Event>();
+        self.check_table::<tables::PropertyMap>();
+        self.check_table::<tables::PropertyPtr>();
+        self.check_table::<tables::Property>();
+
+        let method_semantics = self.check_table::<tables::MethodSemantics>();
+        self.check_sorted(&method_semantics, |r| tables::HasSemantics::encode(&r.association));
+
+        let method_impls = self.check_table::<tables::MethodImpl>();
+        self.check_sorted(&method_impls, |r| r.class.index());
+
+        self.check_table::<tables::ModuleRef>();
+        self.check_table::<tables::TypeSpec>();
+
+        let impl_maps = self.check_table::<tables::ImplMap>();
+        self.check_sorted(&impl_maps, |r| tables::MemberForwarded::encode(&r.member_forwarded));
+
+        let field_rvas = self.check_table::<tables::FieldRva>();
+        self.check_sorted(&field_rvas, |r| r.field.index());
+
+        self.check_table::<tables::EncLog>();
+        self.check_table::<tables::EncMap>();
+
+        let assemblies = self.check_table::<tables::Assembly>();
+        self.check_assemblies(&assemblies);
+
+        self.check_table::<tables::AssemblyProcessor>();
+        self.check_table::<tables::AssemblyOS>();
+
+        let assembly_refs = self.check_table::<tables::AssemblyRef>();
+        self.check_assembly_refs(&assembly_refs);
+
+        self.check_table::<tables::AssemblyRefProcessor>();
+        self.check_table::<tables::AssemblyRefOS>();
+        self.check_table::<tables::File>();
+        self.check_table::<tables::ExportedType>();
+        self.check_table::<tables::ManifestResource>();
+
+        let nested_classes = self.check_table::<tables::NestedClass>();
+        self.check_sorted(&nested_classes, |r| r.nested_class.index());
+        self.check_type_defs(&type_defs, &nested_classes, fields.len(), method_defs.len());
+
+        let generic_params = self.check_table::<tables::GenericParam>();
+        self.check_sorted(&generic_params, |r| (tables::TypeOrMethodDef::encode(&r.owner), r.number));
+
+        self.check_table::<tables::MethodSpec>();
+
+        let generic_param_constraints = self.check_table::<tables::GenericParamConstraint>();
+        self.check_sorted(&generic_param_constraints, |r| r.owner.index());
+    }
+
+    fn report(&mut self, table: TableIndex, row: usize, rule: Rule, message: impl Into<String>) {
+        self.violations.push(Violation {
+            table,
+            row,
+            rule,
+            message: message.into(),
+        });
+    }
+
+    /// Decodes every row of a table and checks that its index columns refer to rows that exist.
+    fn check_table<T: TableRow>(&mut self) -> Vec<T> {
+        let table = self.image.table::<T>();
+        let mut rows = Vec::with_capacity(table.len());
+        for (index, row) in table.iter().enumerate() {
+            match row {
+                Ok(row) => rows.push(row),
+                Err(e) => {
+                    // Rows after an undecodable row can't be trusted, so stop here
+                    let rule = Rule::section(section_of(T::INDEX));
+                    self.report(T::INDEX, index + 1, rule, format!("row could not be decoded: {}", e));
+                    return rows;
+                }
+            }
+        }
+
+        for (index, row) in rows.iter().enumerate() {
+            let mut bad_handles = Vec::new();
+            row.for_each_handle(|column, handle| {
+                if !self.handle_in_range(T::INDEX, column, handle) {
+                    bad_handles.push((column, *handle));
+                }
+            });
+            for (column, handle) in bad_handles {
+                let rule = Rule::section(section_of(T::INDEX));
+                self.report(T::INDEX, index + 1, rule, format!("{} refers to a row that does not exist: {}", column, handle));
+            }
+        }
+        rows
+    }
+
+    fn handle_in_range(&self, table: TableIndex, column: &str, handle: &TableHandle) -> bool {
+        if handle.index() == 0 {
+            // Null values are checked by the per-table rules, where they are not allowed.
+            return true;
+        }
+        if handle.table() == TableIndex::NonExistent {
+            return false;
+        }
+
+        // List columns mark the start of a run, and may point one past the end of the target table
+        // when the run is empty.
+        let is_list = matches!(
+            (table, column),
+            (TableIndex::TypeDef, "field_list")
+                | (TableIndex::TypeDef, "method_list")
+                | (TableIndex::MethodDef, "params")
+                | (TableIndex::EventMap, "event_list")
+                | (TableIndex::PropertyMap, "property_list")
+        );
+        let row_count = self.image.row_count(handle.table());
+        if is_list {
+            handle.index() <= row_count + 1
+        } else {
+            handle.index() <= row_count
+        }
+    }
+
+    fn check_sorted<T: TableRow, K: PartialOrd>(&mut self, rows: &[T], key: impl Fn(&T) -> K) {
+        for index in 1..rows.len() {
+            if key(&rows[index - 1]) > key(&rows[index]) {
+                self.report(T::INDEX, index + 1, rules::SORTED_TABLES, format!("{} table is not sorted by its primary key", T::INDEX));
+                return;
+            }
+        }
+    }
+
+    /// Checks that `handle` refers to a non-empty, valid UTF-8 string.
+    fn check_name(&mut self, table: TableIndex, row: usize, rule: Rule, column: &str, handle: StringHandle) -> Option<String> {
+        match self.image.get_string(handle).map(|s| s.to_str()) {
+            None => {
+                self.report(table, row, rule, format!("{} is not a valid string heap index: {}", column, handle));
+                None
+            }
+            Some(Err(_)) => {
+                self.report(table, row, rule, format!("{} is not valid UTF-8", column));
+                None
+            }
+            Some(Ok("")) => {
+                self.report(table, row, rule, format!("{} is empty", column));
+                None
+            }
+            Some(Ok(s)) => Some(s.to_string()),
+        }
+    }
+
+    /// Like `check_name`, but the null string is allowed.
+    fn check_optional_name(&mut self, table: TableIndex, row: usize, rule: Rule, column: &str, handle: StringHandle) -> Option<String> {
+        if handle.0 == 0 {
+            Some(String::new())
+        } else {
+            self.check_name(table, row, rule, column, handle)
+        }
+    }
+
+    fn blob(&self, handle: BlobHandle) -> Option<&'a [u8]> {
+        if handle.0 == 0 {
+            Some(&[])
+        } else {
+            self.image.get_blob(handle)
+        }
+    }
+
+    fn check_modules(&mut self, modules: &[tables::Module]) {
+        if modules.len() != 1 {
+            self.report(TableIndex::Module, 0, rules::MODULE_SINGLE_ROW, format!("expected exactly one row, found {}", modules.len()));
+        }
+        for (index, module) in modules.iter().enumerate() {
+            self.check_name(TableIndex::Module, index + 1, rules::MODULE_NAME, "name", module.name);
+            let has_mvid = self.image.get_guid(module.mvid).map(|g| g.as_bytes() != &[0u8; 16]).unwrap_or(false);
+            if !has_mvid {
+                self.report(TableIndex::Module, index + 1, rules::MODULE_MVID, "mvid does not refer to a non-null GUID");
+            }
+        }
+    }
+
+    fn check_type_refs(&mut self, type_refs: &[tables::TypeRef]) {
+        let mut seen = HashMap::new();
+        for (index, type_ref) in type_refs.iter().enumerate() {
+            let row = index + 1;
+            if type_ref.resolution_scope.table() == TableIndex::TypeRef && type_ref.resolution_scope.index() == row {
+                self.report(TableIndex::TypeRef, row, rules::TYPE_REF_RESOLUTION_SCOPE, "resolution_scope refers to the row itself");
+            }
+            let name = self.check_name(TableIndex::TypeRef, row, rules::TYPE_REF_NAME, "name", type_ref.name);
+            let namespace = self.check_optional_name(TableIndex::TypeRef, row, rules::TYPE_REF_NAMESPACE, "namespace", type_ref.namespace);
+            if let (Some(name), Some(namespace)) = (name, namespace) {
+                let key = (type_ref.resolution_scope, namespace, name);
+                if let Some(first) = seen.insert(key, row) {
+                    self.report(TableIndex::TypeRef, row, rules::TYPE_REF_DUPLICATE, format!("duplicate of row 0x{:04X}", first));
+                }
+            }
+        }
+    }
+
+    fn check_type_defs(&mut self, type_defs: &[tables::TypeDef], nested_classes: &[tables::NestedClass], field_count: usize, method_count: usize) {
+        let enclosing: HashMap<usize, usize> = nested_classes
+            .iter()
+            .map(|n| (n.nested_class.index(), n.enclosing_class.index()))
+            .collect();
+
+        let mut seen = HashMap::new();
+        let mut previous_field = 1;
+        let mut previous_method = 1;
+        for (index, type_def) in type_defs.iter().enumerate() {
+            let row = index + 1;
+            let name = self.check_name(TableIndex::TypeDef, row, rules::TYPE_DEF_NAME, "type_name", type_def.type_name);
+            let namespace = self.check_optional_name(TableIndex::TypeDef, row, rules::TYPE_DEF_NAMESPACE, "type_namespace", type_def.type_namespace);
+
+            if let (Some(name), Some(namespace)) = (name, namespace) {
+                let owner = enclosing.get(&row).copied();
+                if let Some(first) = seen.insert((owner, namespace, name), row) {
+                    let rule = if owner.is_some() {
+                        rules::TYPE_DEF_NESTED_DUPLICATE
+                    } else {
+                        rules::TYPE_DEF_DUPLICATE
+                    };
+                    self.report(TableIndex::TypeDef, row, rule, format!("duplicate of row 0x{:04X}", first));
+                }
+            }
+
+            // The runs of fields and methods owned by each type shall be contiguous and in order.
+            let field = type_def.field_list.index();
+            if field < previous_field || field > field_count + 1 {
+                self.report(TableIndex::TypeDef, row, rules::TYPE_DEF_FIELD_LIST, format!("field_list 0x{:04X} does not continue the previous run", field));
+            } else {
+                previous_field = field;
+            }
+
+            let method = type_def.method_list.index();
+            if method < previous_method || method > method_count + 1 {
+                self.report(TableIndex::TypeDef, row, rules::TYPE_DEF_METHOD_LIST, format!("method_list 0x{:04X} does not continue the previous run", method));
+            } else {
+                previous_method = method;
+            }
+        }
+    }
+
+    fn check_fields(&mut self, fields: &[tables::Field]) {
+        for (index, field) in fields.iter().enumerate() {
+            let row = index + 1;
+            self.check_name(TableIndex::Field, row, rules::FIELD_NAME, "name", field.name);
+
+            let valid = match self.blob(field.signature) {
+                Some(mut sig) => is_field_signature(&mut sig),
+                None => false,
+            };
+            if !valid {
+                self.report(TableIndex::Field, row, rules::FIELD_SIGNATURE, "signature is not a valid field signature");
+            }
+        }
+    }
+
+    fn check_method_defs(&mut self, method_defs: &[tables::MethodDef]) {
+        let param_count = self.image.row_count(TableIndex::Param);
+        let mut previous_param = 1;
+        for (index, method_def) in method_defs.iter().enumerate() {
+            let row = index + 1;
+            self.check_name(TableIndex::MethodDef, row, rules::METHOD_DEF_NAME, "name", method_def.name);
+
+            let valid = match self.blob(method_def.signature) {
+                Some(mut sig) => is_method_signature(&mut sig),
+                None => false,
+            };
+            if !valid {
+                self.report(TableIndex::MethodDef, row, rules::METHOD_DEF_SIGNATURE, "signature is not a valid method signature");
+            }
+
+            let param = method_def.params.index();
+            if param < previous_param || param > param_count + 1 {
+                self.report(TableIndex::MethodDef, row, rules::METHOD_DEF_PARAM_LIST, format!("params 0x{:04X} does not continue the previous run", param));
+            } else {
+                previous_param = param;
+            }
+        }
+    }
+
+    fn check_params(&mut self, method_defs: &[tables::MethodDef], params: &[tables::Param]) {
+        for (index, param) in params.iter().enumerate() {
+            if param.name.0 != 0 {
+                self.check_name(TableIndex::Param, index + 1, rules::PARAM_NAME, "name", param.name);
+            }
+        }
+
+        // Within the run of parameters owned by each method, sequence numbers shall be increasing
+        for (index, method_def) in method_defs.iter().enumerate() {
+            let start = method_def.params.index();
+            let end = method_defs.get(index + 1).map(|m| m.params.index()).unwrap_or(params.len() + 1);
+            if start == 0 || end <= start || end > params.len() + 1 {
+                continue;
+            }
+            for row in (start + 1)..end {
+                if params[row - 1].sequence <= params[row - 2].sequence {
+                    self.report(TableIndex::Param, row, rules::PARAM_SEQUENCE_ORDER, format!("sequence {} does not follow sequence {}", params[row - 1].sequence, params[row - 2].sequence));
+                }
+            }
+        }
+    }
+
+    fn check_interface_impls(&mut self, interface_impls: &[tables::InterfaceImpl]) {
+        for (index, interface_impl) in interface_impls.iter().enumerate() {
+            if interface_impl.class.index() == 0 {
+                self.report(TableIndex::InterfaceImpl, index + 1, rules::INTERFACE_IMPL_CLASS, "class is null");
+            }
+        }
+        self.check_sorted(interface_impls, |r| (r.class.index(), tables::TypeDefOrRef::encode(&r.interface)));
+    }
+
+    fn check_member_refs(&mut self, member_refs: &[tables::MemberRef]) {
+        for (index, member_ref) in member_refs.iter().enumerate() {
+            let row = index + 1;
+            if member_ref.class.index() == 0 {
+                self.report(TableIndex::MemberRef, row, rules::MEMBER_REF_CLASS, "class is null");
+            }
+            self.check_name(TableIndex::MemberRef, row, rules::MEMBER_REF_NAME, "name", member_ref.name);
+
+            let valid = match self.blob(member_ref.signature) {
+                Some(sig) if sig.first() == Some(&0x06) => is_field_signature(&mut &sig[..]),
+                Some(mut sig) => is_method_signature(&mut sig),
+                None => false,
+            };
+            if !valid {
+                self.report(TableIndex::MemberRef, row, rules::MEMBER_REF_SIGNATURE, "signature is not a valid field or method signature");
+            }
+        }
+    }
+
+    fn check_constants(&mut self, constants: &[tables::Constant]) {
+        for (index, constant) in constants.iter().enumerate() {
+            let row = index + 1;
+
+            // BOOLEAN through R8, STRING, or CLASS (for null references)
+            if !matches!(constant.typ, 0x02..=0x0E | 0x12) {
+                self.report(TableIndex::Constant, row, rules::CONSTANT_TYPE, format!("type 0x{:02X} is not a valid constant type", constant.typ));
+            }
+            if constant.parent.index() == 0 {
+                self.report(TableIndex::Constant, row, rules::CONSTANT_PARENT, "parent is null");
+            }
+            if index > 0 && constants[index - 1].parent == constant.parent {
+                self.report(TableIndex::Constant, row, rules::CONSTANT_DUPLICATE, format!("duplicate parent {}", constant.parent));
+            }
+        }
+        self.check_sorted(constants, |r| tables::HasConstant::encode(&r.parent));
+    }
+
+    fn check_custom_attributes(&mut self, custom_attributes: &[tables::CustomAttribute]) {
+        for (index, custom_attribute) in custom_attributes.iter().enumerate() {
+            let row = index + 1;
+            if custom_attribute.parent.index() == 0 || custom_attribute.parent.table() == TableIndex::CustomAttribute {
+                self.report(TableIndex::CustomAttribute, row, rules::CUSTOM_ATTRIBUTE_PARENT, format!("parent {} is not a valid owner", custom_attribute.parent));
+            }
+            if custom_attribute.typ.index() == 0 || !self.is_constructor(&custom_attribute.typ) {
+                self.report(TableIndex::CustomAttribute, row, rules::CUSTOM_ATTRIBUTE_TYPE, format!("type {} is not a constructor", custom_attribute.typ));
+            }
+        }
+        self.check_sorted(custom_attributes, |r| tables::HasCustomAttribute::encode(&r.parent));
+    }
+
+    fn is_constructor(&self, handle: &TableHandle) -> bool {
+        let name = match handle.table() {
+            TableIndex::MethodDef if handle.index() <= self.image.row_count(TableIndex::MethodDef) => {
+                self.image.table::<tables::MethodDef>().row(handle.index() - 1).name().ok()
+            }
+            TableIndex::MemberRef if handle.index() <= self.image.row_count(TableIndex::MemberRef) => {
+                self.image.table::<tables::MemberRef>().row(handle.index() - 1).name().ok()
+            }
+            _ => None,
+        };
+        name.and_then(|n| self.image.get_string(n)).map(|n| n.to_bytes() == b".ctor").unwrap_or(false)
+    }
+
+    fn check_assemblies(&mut self, assemblies: &[tables::Assembly]) {
+        if assemblies.len() > 1 {
+            self.report(TableIndex::Assembly, 0, rules::ASSEMBLY_SINGLE_ROW, format!("expected at most one row, found {}", assemblies.len()));
+        }
+        for (index, assembly) in assemblies.iter().enumerate() {
+            self.check_name(TableIndex::Assembly, index + 1, rules::ASSEMBLY_NAME, "name", assembly.name);
+        }
+    }
+
+    fn check_assembly_refs(&mut self, assembly_refs: &[tables::AssemblyRef]) {
+        for (index, assembly_ref) in assembly_refs.iter().enumerate() {
+            self.check_name(TableIndex::AssemblyRef, index + 1, rules::ASSEMBLY_REF_NAME, "name", assembly_ref.name);
+        }
+    }
+}
+
+fn is_field_signature(sig: &mut &[u8]) -> bool {
+    match SignatureHeader::read(sig) {
+        Ok(header) if header.kind() == SignatureKind::Field => utils::read_modifiers_and_type(sig).is_ok(),
+        _ => false,
+    }
+}
+
+fn is_method_signature(sig: &mut &[u8]) -> bool {
+    match sig.first() {
+        Some(header) if header & 0x0F <= 0x05 => MethodSignature::read(sig).is_ok(),
+        _ => false,
+    }
+}
+
+/// Gets the section of Partition II chapter 22 that describes a table.
+fn section_of(table: TableIndex) -> &'static str {
+    match table {
+        TableIndex::Assembly => "22.2",
+        TableIndex::AssemblyOS => "22.3",
+        TableIndex::AssemblyProcessor => "22.4",
+        TableIndex::AssemblyRef => "22.5",
+        TableIndex::AssemblyRefOS => "22.6",
+        TableIndex::AssemblyRefProcessor => "22.7",
+        TableIndex::ClassLayout => "22.8",
+        TableIndex::Constant => "22.9",
+        TableIndex::CustomAttribute => "22.10",
+        TableIndex::DeclSecurity => "22.11",
+        TableIndex::EventMap => "22.12",
+        TableIndex::Event => "22.13",
+        TableIndex::ExportedType => "22.14",
+        TableIndex::Field => "22.15",
+        TableIndex::FieldLayout => "22.16",
+        TableIndex::FieldMarshal => "22.17",
+        TableIndex::FieldRva => "22.18",
+        TableIndex::File => "22.19",
+        TableIndex::GenericParam => "22.20",
+        TableIndex::GenericParamConstraint => "22.21",
+        TableIndex::ImplMap => "22.22",
+        TableIndex::InterfaceImpl => "22.23",
+        TableIndex::ManifestResource => "22.24",
+        TableIndex::MemberRef => "22.25",
+        TableIndex::MethodDef => "22.26",
+        TableIndex::MethodImpl => "22.27",
+        TableIndex::MethodSemantics => "22.28",
+        TableIndex::MethodSpec => "22.29",
+        TableIndex::Module => "22.30",
+        TableIndex::ModuleRef => "22.31",
+        TableIndex::NestedClass => "22.32",
+        TableIndex::Param => "22.33",
+        TableIndex::Property => "22.34",
+        TableIndex::PropertyMap => "22.35",
+        TableIndex::StandAloneSig => "22.36",
+        TableIndex::TypeDef => "22.37",
+        TableIndex::TypeRef => "22.38",
+        TableIndex::TypeSpec => "22.39",
+        _ => "22",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn field_signature() {
+        assert!(is_field_signature(&mut &[0x06, 0x08][..]));
+        assert!(!is_field_signature(&mut &[0x00, 0x00, 0x01][..]));
+        assert!(!is_field_signature(&mut &[0x06][..]));
+    }
+
+    #[test]
+    pub fn method_signature() {
+        assert!(is_method_signature(&mut &[0x20, 0x01, 0x01, 0x0E][..]));
+        assert!(!is_method_signature(&mut &[0x06, 0x08][..]));
+        assert!(!is_method_signature(&mut &[0x0F, 0x00, 0x01][..]));
+        assert!(!is_method_signature(&mut &[0x00, 0x02, 0x01, 0x08][..]));
+    }
+
+    #[test]
+    pub fn rule_display() {
+        assert_eq!("II.22.37 rule 19", rules::TYPE_DEF_DUPLICATE.to_string());
+        assert_eq!("II.22", rules::SORTED_TABLES.to_string());
+    }
+
+    /// Wraps tables in a minimal PE32 image, with `Broken.dll` and `Run` in the string heap, a field signature in the
+    /// blob heap, and one GUID.
+    fn pe_image(row_counts: &[(TableIndex, u32)], rows: &[u8]) -> Vec<u8> {
+        let mut table_stream = vec![0, 0, 0, 0, 2, 0, 0, 1];
+        let valid = row_counts.iter().fold(0u64, |mask, (table, _)| mask | 1 << *table as u64);
+        table_stream.extend_from_slice(&valid.to_le_bytes());
+        table_stream.extend_from_slice(&0u64.to_le_bytes());
+        for (_, count) in row_counts {
+            table_stream.extend_from_slice(&count.to_le_bytes());
+        }
+        table_stream.extend_from_slice(rows);
+        table_stream.resize((table_stream.len() + 3) & !3, 0);
+
+        let streams: [(&str, Vec<u8>); 4] = [
+            ("#~", table_stream),
+            ("#Strings", b"\0Broken.dll\0Run\0".to_vec()),
+            ("#Blob", vec![0x00, 0x02, 0x06, 0x08]),
+            ("#GUID", vec![1; 16]),
+        ];
+        let mut metadata = b"BSJB\x01\0\x01\0\0\0\0\0\x0C\0\0\0v4.0.30319\0\0\0\0\x04\0".to_vec();
+        let header_size = metadata.len() + streams.iter().map(|(name, _)| 8 + (name.len() + 4) / 4 * 4).sum::<usize>();
+        let mut offset = header_size;
+        for (name, data) in &streams {
+            metadata.extend_from_slice(&(offset as u32).to_le_bytes());
+            metadata.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            metadata.extend_from_slice(name.as_bytes());
+            metadata.resize(metadata.len() + 4 - name.len() % 4, 0);
+            offset += data.len();
+        }
+        for (_, data) in &streams {
+            metadata.extend_from_slice(data);
+        }
+
+        // The DOS header, the PE signature and a COFF header with one section
+        let mut image = vec![0u8; 0x80];
+        image[..2].copy_from_slice(b"MZ");
+        image[0x3C] = 0x80;
+        image.extend_from_slice(b"PE\0\0");
+        image.extend_from_slice(&[0x4C, 0x01, 0x01, 0x00]);
+        image.extend_from_slice(&[0; 12]);
+        image.extend_from_slice(&[0xE0, 0x00, 0x02, 0x21]);
+
+        // The optional header, with only the CLI header directory set
+        let mut optional_header = vec![0u8; 224];
+        optional_header[..2].copy_from_slice(&0x010Bu16.to_le_bytes());
+        optional_header[92..96].copy_from_slice(&16u32.to_le_bytes());
+        optional_header[208..212].copy_from_slice(&0x2000u32.to_le_bytes());
+        optional_header[212..216].copy_from_slice(&72u32.to_le_bytes());
+        image.extend_from_slice(&optional_header);
+
+        // The .text section, holding the CLI header and the metadata just after it
+        let section_size = (72 + metadata.len() as u32 + 0x1FF) & !0x1FF;
+        image.extend_from_slice(b".text\0\0\0");
+        for value in [section_size, 0x2000, section_size, 0x200, 0, 0, 0] {
+            image.extend_from_slice(&value.to_le_bytes());
+        }
+        image.resize(0x200, 0);
+
+        let mut cli_header = vec![0u8; 72];
+        cli_header[..4].copy_from_slice(&72u32.to_le_bytes());
+        cli_header[4..8].copy_from_slice(&[2, 0, 5, 0]);
+        cli_header[8..12].copy_from_slice(&0x2048u32.to_le_bytes());
+        cli_header[12..16].copy_from_slice(&(metadata.len() as u32).to_le_bytes());
+        cli_header[16..20].copy_from_slice(&1u32.to_le_bytes());
+        image.extend_from_slice(&cli_header);
+        image.extend_from_slice(&metadata);
+        image.resize(0x200 + section_size as usize, 0);
+        image
+    }
+
+    #[test]
+    pub fn validate_broken_image() {
+        let rows: &[u8] = &[
+            // Two Module rows, named Broken.dll
+            0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+            // A public class with no name
+            0x01, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00,
+            // A public method Run with no body, a field signature where a method signature belongs and a Param list
+            // past the end of the table
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x06, 0x00, 0x0C, 0x00, 0x01, 0x00, 0x05, 0x00,
+        ];
+        let row_counts = [(TableIndex::Module, 2), (TableIndex::TypeDef, 1), (TableIndex::MethodDef, 1)];
+        let image = MetadataImage::load_data(pe_image(&row_counts, rows)).unwrap();
+        let violations: Vec<_> = image.validate().iter().map(|v| (v.table, v.row, v.rule)).collect();
+        assert_eq!(
+            vec![
+                (TableIndex::Module, 0, rules::MODULE_SINGLE_ROW),
+                (TableIndex::MethodDef, 1, Rule::section("22.26")),
+                (TableIndex::MethodDef, 1, rules::METHOD_DEF_SIGNATURE),
+                (TableIndex::MethodDef, 1, rules::METHOD_DEF_PARAM_LIST),
+                (TableIndex::TypeDef, 1, rules::TYPE_DEF_NAME),
+            ],
+            violations
+        );
+    }
+}
@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+
+use crate::cli::signatures::utils::read_compressed_u32;
+use crate::cli::{BlobHandle, GuidHandle, StringHandle, UserStringHandle};
+use crate::Guid;
+
+/// Appends an unsigned integer in the compressed encoding of ECMA-335 II.23.2, which uses 1, 2 or 4 bytes.
+///
+/// Panics if the value is larger than `0x1FFF_FFFF`, which is the largest value the encoding can hold.
+pub fn write_compressed_u32(buf: &mut Vec<u8>, value: u32) {
+    if value < 0x80 {
+        buf.push(value as u8);
+    } else if value < 0x4000 {
+        buf.extend_from_slice(&(value as u16 | 0x8000).to_be_bytes());
+    } else if value <= 0x1FFF_FFFF {
+        buf.extend_from_slice(&(value | 0xC000_0000).to_be_bytes());
+    } else {
+        panic!("value 0x{:X} is too large to compress", value);
+    }
+}
+
+/// Builds a `#Strings` heap of null-terminated UTF-8 strings, storing each distinct string once.
+pub struct StringHeapBuilder {
+    buf: Vec<u8>,
+    offsets: HashMap<String, StringHandle>,
+}
+
+impl Default for StringHeapBuilder {
+    fn default() -> StringHeapBuilder {
+        StringHeapBuilder::new()
+    }
+}
+
+impl StringHeapBuilder {
+    pub fn new() -> StringHeapBuilder {
+        StringHeapBuilder {
+            buf: vec![0],
+            offsets: HashMap::new(),
+        }
+    }
+
+    /// Creates a builder that starts with the contents of an existing heap, so that its handles stay valid.
+    pub fn from_data(data: &[u8]) -> StringHeapBuilder {
+        let mut builder = StringHeapBuilder::new();
+        if data.is_empty() {
+            return builder;
+        }
+        builder.buf = data.to_vec();
+        let mut offset = 0;
+        for value in data.split(|b| *b == 0) {
+            if let Ok(value) = std::str::from_utf8(value) {
+                builder.offsets.entry(value.to_string()).or_insert(StringHandle(offset));
+            }
+            offset += value.len() + 1;
+        }
+        builder
+    }
+
+    /// Adds a string to the heap, returning the handle of the existing copy if it was added before.
+    ///
+    /// The empty string is always at offset 0.
+    pub fn add(&mut self, value: &str) -> StringHandle {
+        if value.is_empty() {
+            return StringHandle(0);
+        }
+        if let Some(handle) = self.offsets.get(value) {
+            return *handle;
+        }
+        let handle = StringHandle(self.buf.len());
+        self.buf.extend_from_slice(value.as_bytes());
+        self.buf.push(0);
+        self.offsets.insert(value.to_string(), handle);
+        handle
+    }
+
+    /// Gets the contents of the heap, without padding.
+    pub fn data(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+/// Builds a `#Blob` heap of length-prefixed byte sequences, storing each distinct blob once.
+pub struct BlobHeapBuilder {
+    buf: Vec<u8>,
+    offsets: HashMap<Vec<u8>, BlobHandle>,
+}
+
+impl Default for BlobHeapBuilder {
+    fn default() -> BlobHeapBuilder {
+        BlobHeapBuilder::new()
+    }
+}
+
+impl BlobHeapBuilder {
+    pub fn new() -> BlobHeapBuilder {
+        BlobHeapBuilder {
+            buf: vec![0],
+            offsets: HashMap::new(),
+        }
+    }
+
+    /// Creates a builder that starts with the contents of an existing heap, so that its handles stay valid.
+    pub fn from_data(data: &[u8]) -> BlobHeapBuilder {
+        let mut builder = BlobHeapBuilder::new();
+        if data.is_empty() {
+            return builder;
+        }
+        builder.buf = data.to_vec();
+        for (offset, value) in heap_entries(data) {
+            builder.offsets.entry(value.to_vec()).or_insert(BlobHandle(offset));
+        }
+        builder
+    }
+
+    /// Adds a blob to the heap, returning the handle of the existing copy if it was added before.
+    ///
+    /// The empty blob is always at offset 0.
+    pub fn add(&mut self, value: &[u8]) -> BlobHandle {
+        if value.is_empty() {
+            return BlobHandle(0);
+        }
+        if let Some(handle) = self.offsets.get(value) {
+            return *handle;
+        }
+        let handle = BlobHandle(self.buf.len());
+        write_compressed_u32(&mut self.buf, value.len() as u32);
+        self.buf.extend_from_slice(value);
+        self.offsets.insert(value.to_vec(), handle);
+        handle
+    }
+
+    /// Gets the contents of the heap, without padding.
+    pub fn data(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+/// Builds a `#GUID` heap, storing each distinct GUID once.
+pub struct GuidHeapBuilder {
+    buf: Vec<u8>,
+    indexes: HashMap<Guid, GuidHandle>,
+}
+
+impl Default for GuidHeapBuilder {
+    fn default() -> GuidHeapBuilder {
+        GuidHeapBuilder::new()
+    }
+}
+
+impl GuidHeapBuilder {
+    pub fn new() -> GuidHeapBuilder {
+        GuidHeapBuilder {
+            buf: Vec::new(),
+            indexes: HashMap::new(),
+        }
+    }
+
+    /// Creates a builder that starts with the contents of an existing heap, so that its handles stay valid.
+    pub fn from_data(data: &[u8]) -> GuidHeapBuilder {
+        let mut builder = GuidHeapBuilder::new();
+        builder.buf = data[..data.len() / 16 * 16].to_vec();
+        for (index, value) in builder.buf.chunks(16).enumerate() {
+            builder.indexes.entry(Guid::from_bytes(value)).or_insert(GuidHandle(index + 1));
+        }
+        builder
+    }
+
+    /// Adds a GUID to the heap, returning the handle of the existing copy if it was added before.
+    ///
+    /// GUID handles are 1-based, and the empty GUID is given the null handle.
+    pub fn add(&mut self, value: Guid) -> GuidHandle {
+        if value == Guid::EMPTY {
+            return GuidHandle(0);
+        }
+        if let Some(handle) = self.indexes.get(&value) {
+            return *handle;
+        }
+        self.buf.extend_from_slice(value.as_bytes());
+        let handle = GuidHandle(self.buf.len() / 16);
+        self.indexes.insert(value, handle);
+        handle
+    }
+
+    /// Gets the number of GUIDs in the heap.
+    pub fn len(&self) -> usize {
+        self.buf.len() / 16
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Gets the contents of the heap.
+    pub fn data(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+/// Builds a `#US` heap of the UTF-16 string literals loaded by `ldstr`, storing each distinct string once.
+pub struct UserStringHeapBuilder {
+    buf: Vec<u8>,
+    offsets: HashMap<String, UserStringHandle>,
+}
+
+impl Default for UserStringHeapBuilder {
+    fn default() -> UserStringHeapBuilder {
+        UserStringHeapBuilder::new()
+    }
+}
+
+impl UserStringHeapBuilder {
+    pub fn new() -> UserStringHeapBuilder {
+        UserStringHeapBuilder {
+            buf: vec![0],
+            offsets: HashMap::new(),
+        }
+    }
+
+    /// Creates a builder that starts with the contents of an existing heap, so that its handles stay valid.
+    pub fn from_data(data: &[u8]) -> UserStringHeapBuilder {
+        let mut builder = UserStringHeapBuilder::new();
+        if data.is_empty() {
+            return builder;
+        }
+        builder.buf = data.to_vec();
+        for (offset, value) in heap_entries(data) {
+            let units: Vec<u16> = value.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+            if let Ok(value) = String::from_utf16(&units) {
+                builder.offsets.entry(value).or_insert(UserStringHandle(offset));
+            }
+        }
+        builder
+    }
+
+    /// Adds a string literal to the heap, returning the handle of the existing copy if it was added before.
+    pub fn add(&mut self, value: &str) -> UserStringHandle {
+        if let Some(handle) = self.offsets.get(value) {
+            return *handle;
+        }
+        let handle = UserStringHandle(self.buf.len());
+        let units: Vec<u16> = value.encode_utf16().collect();
+        write_compressed_u32(&mut self.buf, units.len() as u32 * 2 + 1);
+        for unit in &units {
+            self.buf.extend_from_slice(&unit.to_le_bytes());
+        }
+
+        // The trailing byte records whether any character needs more than an 8-bit comparison (II.24.2.4)
+        let special = |u: &u16| *u > 0xFF || matches!(*u, 0x01..=0x08 | 0x0E..=0x1F | 0x27 | 0x2D | 0x7F);
+        self.buf.push(units.iter().any(special) as u8);
+        self.offsets.insert(value.to_string(), handle);
+        handle
+    }
+
+    /// Gets the contents of the heap, without padding.
+    pub fn data(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+/// Walks the length-prefixed entries of a `#Blob` or `#US` heap, stopping at the first malformed one.
+fn heap_entries(data: &[u8]) -> Vec<(usize, &[u8])> {
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    while offset < data.len() {
+        let mut reader = &data[offset..];
+        let len = match read_compressed_u32(&mut reader) {
+            Ok(len) if len as usize <= reader.len() => len as usize,
+            _ => break,
+        };
+        let start = data.len() - reader.len();
+        entries.push((offset, &data[start..(start + len)]));
+        offset = start + len;
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::heaps::Heaps;
+
+    #[test]
+    pub fn compressed_integers() {
+        let encode = |value| {
+            let mut buf = Vec::new();
+            write_compressed_u32(&mut buf, value);
+            buf
+        };
+        assert_eq!(vec![0x7F], encode(0x7F));
+        assert_eq!(vec![0xAE, 0x57], encode(0x2E57));
+        assert_eq!(vec![0xC0, 0x00, 0x40, 0x00], encode(0x4000));
+        assert_eq!(vec![0xDF, 0xFF, 0xFF, 0xFF], encode(0x1FFF_FFFF));
+    }
+
+    #[test]
+    pub fn heaps_are_deduplicated() {
+        let mut strings = StringHeapBuilder::new();
+        assert_eq!(StringHandle(0), strings.add(""));
+        let foo = strings.add("Foo");
+        assert_eq!(StringHandle(1), foo);
+        assert_eq!(StringHandle(5), strings.add("Bar"));
+        assert_eq!(foo, strings.add("Foo"));
+        assert_eq!(b"\0Foo\0Bar\0", strings.data());
+
+        let mut blobs = BlobHeapBuilder::new();
+        assert_eq!(BlobHandle(0), blobs.add(&[]));
+        assert_eq!(BlobHandle(1), blobs.add(&[0x20, 0x00, 0x01]));
+        assert_eq!(BlobHandle(1), blobs.add(&[0x20, 0x00, 0x01]));
+        assert_eq!(&[0x00, 0x03, 0x20, 0x00, 0x01], blobs.data());
+
+        let mut guids = GuidHeapBuilder::new();
+        assert_eq!(GuidHandle(0), guids.add(Guid::EMPTY));
+        assert_eq!(GuidHandle(1), guids.add(Guid::from_bytes(&[1; 16])));
+        assert_eq!(GuidHandle(2), guids.add(Guid::from_bytes(&[2; 16])));
+        assert_eq!(GuidHandle(1), guids.add(Guid::from_bytes(&[1; 16])));
+        assert_eq!(2, guids.len());
+    }
+
+    #[test]
+    pub fn user_strings_round_trip() {
+        let mut user_strings = UserStringHeapBuilder::new();
+        let hello = user_strings.add("Hello");
+        let quote = user_strings.add("it's");
+        assert_eq!(hello, user_strings.add("Hello"));
+        assert_eq!(&[0x0B, b'H', 0, b'e', 0, b'l', 0, b'l', 0, b'o', 0, 0x00], &user_strings.data()[1..13]);
+        assert_eq!(0x01, *user_strings.data().last().unwrap());
+
+        let data = user_strings.data();
+        let heaps = Heaps {
+            string_heap: None,
+            userstring_heap: Some(0..data.len()),
+            guid_heap: None,
+            blob_heap: None,
+        };
+        assert_eq!("Hello", heaps.get_user_string(data, hello).unwrap());
+        assert_eq!("it's", heaps.get_user_string(data, quote).unwrap());
+    }
+
+    #[test]
+    pub fn existing_heaps_are_reused() {
+        let mut strings = StringHeapBuilder::from_data(b"\0Foo\0Bar\0\0");
+        assert_eq!(StringHandle(5), strings.add("Bar"));
+        assert_eq!(StringHandle(10), strings.add("Baz"));
+
+        let mut blobs = BlobHeapBuilder::from_data(&[0x00, 0x02, 0x07, 0x08, 0x01, 0x09]);
+        assert_eq!(BlobHandle(4), blobs.add(&[0x09]));
+        assert_eq!(BlobHandle(6), blobs.add(&[0x0A]));
+
+        let mut guids = GuidHeapBuilder::from_data(&[1; 16]);
+        assert_eq!(GuidHandle(1), guids.add(Guid::from_bytes(&[1; 16])));
+
+        let mut user_strings = UserStringHeapBuilder::new();
+        let hello = user_strings.add("Hello");
+        let mut user_strings = UserStringHeapBuilder::from_data(user_strings.data());
+        assert_eq!(hello, user_strings.add("Hello"));
+        assert_eq!(UserStringHandle(13), user_strings.add("World"));
+    }
+}
@@ -0,0 +1,298 @@
+use std::iter;
+
+use crate::cli::tables::{self, RowHandle, TableHandle};
+use crate::cli::AssemblyFlags;
+use crate::error::Error;
+use crate::pe::{DirectoryType, ImageData, MemoryRange, SectionCharacteristics};
+use crate::writer::MetadataBuilder;
+use crate::MetadataImage;
+
+const SECTION_HEADER_SIZE: usize = 40;
+const DEBUG_DIRECTORY_ENTRY_SIZE: usize = 28;
+
+/// Changes the metadata and method bodies of an existing image, and writes it back.
+///
+/// Everything else in the image stays where it is: the new metadata and method bodies go in a section added after
+/// the existing ones, and the CLI header and the RVAs of the changed methods are pointed at them. Untouched method
+/// bodies, mapped field data, managed and Win32 resources and native code keep their RVAs, so nothing that points at
+/// them needs to change.
+///
+/// Rows added to the metadata are appended to their tables, so the tokens of existing rows don't change, except in
+/// sorted tables, which IL never refers to. Authenticode signatures are removed, since they no longer match, and a
+/// strong-named image has to be signed again.
+///
+/// Anything stored after the last section is dropped along with the certificate table: overlays such as installer
+/// payloads or a single-file bundle appended to the image don't survive an edit.
+pub struct ImageEditor<'a, D: ImageData> {
+    image: &'a MetadataImage<D>,
+    metadata: MetadataBuilder,
+    method_bodies: Vec<u8>,
+    method_rvas: Vec<(RowHandle<tables::MethodDef>, usize)>,
+}
+
+impl<'a, D: ImageData> ImageEditor<'a, D> {
+    /// Starts editing an image, which must be a PE file.
+    pub fn new(image: &'a MetadataImage<D>) -> Result<ImageEditor<'a, D>, Error> {
+        if image.pe().and_then(|pe| pe.pe_header()).is_none() {
+            return Err(Error::NotAPortableExecutable);
+        }
+        Ok(ImageEditor {
+            image,
+            metadata: MetadataBuilder::from_image(image)?,
+            method_bodies: Vec::new(),
+            method_rvas: Vec::new(),
+        })
+    }
+
+    /// Gets the metadata of the image, to change rows or add new ones.
+    pub fn metadata(&mut self) -> &mut MetadataBuilder {
+        &mut self.metadata
+    }
+
+    /// Gives a TypeDef a new namespace and name.
+    pub fn rename_type(&mut self, type_def: RowHandle<tables::TypeDef>, namespace: &str, name: &str) -> Result<(), Error> {
+        let namespace = self.metadata.add_string(namespace);
+        let name = self.metadata.add_string(name);
+        let row = self.metadata.row_mut(type_def).ok_or(Error::InvalidMetadata(format!("no TypeDef row {}", type_def.row())))?;
+        row.type_namespace = namespace;
+        row.type_name = name;
+        Ok(())
+    }
+
+    /// Adds a custom attribute, where `value` is the encoded constructor arguments and named arguments.
+    pub fn add_custom_attribute(&mut self, parent: TableHandle, constructor: TableHandle, value: &[u8]) -> RowHandle<tables::CustomAttribute> {
+        let row = tables::CustomAttribute {
+            parent,
+            typ: constructor,
+            value: self.metadata.add_blob(value),
+        };
+        self.metadata.add_row(row)
+    }
+
+    /// Adds a reference to an assembly, with its four-part version and public key token.
+    pub fn add_assembly_ref(&mut self, name: &str, version: [u16; 4], culture: &str, public_key_token: &[u8]) -> RowHandle<tables::AssemblyRef> {
+        let row = tables::AssemblyRef {
+            major_version: version[0],
+            minor_version: version[1],
+            build_number: version[2],
+            revision_number: version[3],
+            flags: AssemblyFlags::empty(),
+            public_key_or_token: self.metadata.add_blob(public_key_token),
+            name: self.metadata.add_string(name),
+            culture: self.metadata.add_string(culture),
+            hash_value: self.metadata.add_blob(&[]),
+        };
+        self.metadata.add_row(row)
+    }
+
+    /// Replaces the body of a method with one that has its tiny or fat header, such as one from `InstructionEncoder`.
+    pub fn set_method_body(&mut self, method: RowHandle<tables::MethodDef>, body: &[u8]) {
+        self.method_bodies.resize((self.method_bodies.len() + 3) & !3, 0);
+        self.method_rvas.push((method, self.method_bodies.len()));
+        self.method_bodies.extend_from_slice(body);
+    }
+
+    /// Writes the changed image.
+    pub fn write(mut self) -> Result<Vec<u8>, Error> {
+        let pe = self.image.pe().ok_or(Error::NotAPortableExecutable)?;
+        let pe_header = pe.pe_header().ok_or(Error::NotAPortableExecutable)?;
+        let data = pe.data().read(0..pe.data().len())?;
+        let section_alignment = pe_header.section_alignment;
+        let file_alignment = pe_header.file_alignment as usize;
+
+        let rva = pe.sections().iter().map(|s| s.virtual_address + s.virtual_size).max().unwrap_or(0);
+        let rva = align(rva as usize, section_alignment as usize) as u32;
+        for (method, offset) in self.method_rvas.iter() {
+            let row = self.metadata.row_mut(*method).ok_or(Error::InvalidMetadata(format!("no MethodDef row {}", method.row())))?;
+            row.rva = rva + *offset as u32;
+        }
+        let metadata_offset = align(self.method_bodies.len(), 4);
+        let mut section = std::mem::take(&mut self.method_bodies);
+        section.resize(metadata_offset, 0);
+        section.extend(std::mem::take(&mut self.metadata).serialize()?);
+        let metadata_len = section.len() - metadata_offset;
+
+        // The section headers must fit before the first section's data, so make room for one more if needed
+        let optional_header = pe.optional_header_offset();
+        let coff_header = optional_header - 20;
+        let section_table = optional_header + pe.coff_header().optional_header_size as usize;
+        let new_header = section_table + pe.sections().len() * SECTION_HEADER_SIZE;
+        let headers_size = pe_header.size_of_headers as usize;
+        let shift = if new_header + SECTION_HEADER_SIZE <= headers_size {
+            0
+        } else {
+            align(new_header + SECTION_HEADER_SIZE - headers_size, file_alignment)
+        };
+        let first_rva = pe.sections().iter().map(|s| s.virtual_address).min().unwrap_or(0) as usize;
+        if headers_size + shift > first_rva {
+            return Err(Error::CannotRewrite("no room for another section header".into()));
+        }
+
+        // Certificates and other data after the last section are dropped
+        let sections_end = pe.sections().iter().map(|s| (s.pointer_to_raw_data + s.size_of_raw_data) as usize).max().unwrap_or(headers_size);
+        let mut buf = data[..sections_end.min(data.len())].to_vec();
+        buf.splice(headers_size..headers_size, iter::repeat_n(0, shift));
+
+        let cli_header = pe.physical_range(pe.directory(DirectoryType::CliHeader).ok_or(Error::CliHeaderNotFound)?).ok_or(Error::DataOutOfRange)?;
+        let debug_entries = match pe.directory(DirectoryType::DebugData) {
+            Some(range) => pe.physical_range(range).ok_or(Error::DataOutOfRange)?,
+            None => 0..0,
+        };
+        if shift > 0 {
+            for i in 0..pe.sections().len() {
+                add_u32(&mut buf, section_table + i * SECTION_HEADER_SIZE + 20, shift as u32)?;
+            }
+            if pe.coff_header().symbol_table_addr != 0 {
+                add_u32(&mut buf, coff_header + 8, shift as u32)?;
+            }
+            for entry in debug_entries.step_by(DEBUG_DIRECTORY_ENTRY_SIZE) {
+                let pointer = entry + shift + 24;
+                if read_u32(&buf, pointer) != 0 {
+                    add_u32(&mut buf, pointer, shift as u32)?;
+                }
+            }
+        }
+
+        let section_offset = align(buf.len(), file_alignment);
+        let section_size = align(section.len(), file_alignment);
+        let mut header = Vec::with_capacity(SECTION_HEADER_SIZE);
+        header.extend_from_slice(b".meta\0\0\0");
+        header.extend_from_slice(&(section.len() as u32).to_le_bytes());
+        header.extend_from_slice(&rva.to_le_bytes());
+        header.extend_from_slice(&(section_size as u32).to_le_bytes());
+        header.extend_from_slice(&(section_offset as u32).to_le_bytes());
+        header.extend_from_slice(&[0u8; 12]);
+        let characteristics = SectionCharacteristics::CNT_INITIALIZED_DATA | SectionCharacteristics::MEM_READ;
+        header.extend_from_slice(&characteristics.bits().to_le_bytes());
+        buf[new_header..(new_header + SECTION_HEADER_SIZE)].copy_from_slice(&header);
+
+        let section_count = pe.sections().len() as u16 + 1;
+        buf[(coff_header + 2)..(coff_header + 4)].copy_from_slice(&section_count.to_le_bytes());
+        add_u32(&mut buf, optional_header + 8, section_size as u32)?;
+        write_u32(&mut buf, optional_header + 56, rva + align(section.len(), section_alignment as usize) as u32);
+        write_u32(&mut buf, optional_header + 60, (headers_size + shift) as u32);
+        write_u32(&mut buf, optional_header + 64, 0);
+        let directories = optional_header + if pe.is_pe32plus() { 112 } else { 96 };
+        let certificates = directories + DirectoryType::CertificateTable as usize * 8;
+        buf[certificates..(certificates + 8)].copy_from_slice(&[0u8; 8]);
+
+        let metadata = MemoryRange::new(rva + metadata_offset as u32, metadata_len as u32);
+        write_u32(&mut buf, cli_header.start + shift + 8, metadata.start);
+        write_u32(&mut buf, cli_header.start + shift + 12, metadata.len);
+
+        buf.resize(section_offset, 0);
+        buf.extend(section);
+        buf.resize(section_offset + section_size, 0);
+        Ok(buf)
+    }
+}
+
+fn align(value: usize, alignment: usize) -> usize {
+    (value + alignment - 1) & !(alignment - 1)
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([buf[offset], buf[offset + 1], buf[offset + 2], buf[offset + 3]])
+}
+
+fn write_u32(buf: &mut [u8], offset: usize, value: u32) {
+    buf[offset..(offset + 4)].copy_from_slice(&value.to_le_bytes());
+}
+
+fn add_u32(buf: &mut [u8], offset: usize, value: u32) -> Result<(), Error> {
+    let current = read_u32(buf, offset);
+    write_u32(buf, offset, current.checked_add(value).ok_or(Error::DataOutOfRange)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::opcodes;
+    use crate::cli::tables::TableIndex;
+    use crate::pe::{resource_types, ResourceName};
+    use crate::writer::pe_builder::tests::hello_world;
+    use crate::writer::{InstructionEncoder, Win32Resource};
+
+    #[test]
+    pub fn edit_and_write_back() {
+        let (mut builder, main, _) = hello_world();
+        builder.add_win32_resource(Win32Resource {
+            resource_type: ResourceName::Id(resource_types::RCDATA),
+            name: ResourceName::Id(1),
+            language: 0,
+            code_page: 0,
+            data: b"native".to_vec(),
+        });
+        let original = MetadataImage::load_data(builder.build().unwrap()).unwrap();
+        let original_main = original.table::<tables::MethodDef>().read(0).unwrap();
+        let original_field_rva = original.table::<tables::FieldRva>().read(0).unwrap();
+        let original_pe = original.pe().unwrap();
+        assert_eq!(3, original_pe.sections().len());
+
+        let mut editor = ImageEditor::new(&original).unwrap();
+        editor.rename_type(RowHandle::new(2), "Renamed", "Program2").unwrap();
+        let runtime = editor.add_assembly_ref("System.Runtime", [8, 0, 0, 0], "", &[0xB0, 0x3F, 0x5F, 0x7F, 0x11, 0xD5, 0x0A, 0x3A]);
+        let attribute = tables::TypeRef {
+            resolution_scope: runtime.handle(),
+            name: editor.metadata().add_string("InternalsVisibleToAttribute"),
+            namespace: editor.metadata().add_string("System.Runtime.CompilerServices"),
+        };
+        let attribute = editor.metadata().add_row(attribute);
+        let constructor = tables::MemberRef {
+            class: attribute.handle(),
+            name: editor.metadata().add_string(".ctor"),
+            signature: editor.metadata().add_blob(&[0x20, 0x01, 0x01, 0x0E]),
+        };
+        let constructor = editor.metadata().add_row(constructor);
+        editor.add_custom_attribute(
+            TableHandle::new(1, TableIndex::Assembly),
+            constructor.handle(),
+            &[0x01, 0x00, 0x05, b'T', b'e', b's', b't', b's', 0x00, 0x00],
+        );
+        editor.add_custom_attribute(main.handle(), constructor.handle(), &[0x01, 0x00, 0x00, 0x00, 0x00]);
+
+        let mut il = InstructionEncoder::new();
+        il.load_constant_i4(42);
+        il.op_code(opcodes::POP);
+        il.op_code(opcodes::RET);
+        editor.set_method_body(main, &il.encode().unwrap());
+
+        let edited = MetadataImage::load_data(editor.write().unwrap()).unwrap();
+        assert!(edited.validate().is_empty(), "{:?}", edited.validate());
+        let pe = edited.pe().unwrap();
+        assert_eq!(vec![".text", ".rsrc", ".reloc", ".meta"], pe.sections().iter().map(|s| s.name.as_str()).collect::<Vec<_>>());
+        assert_eq!(0x400, pe.pe_header().unwrap().size_of_headers);
+
+        let program = edited.table::<tables::TypeDef>().read(1).unwrap();
+        assert_eq!(c"Program2", edited.get_string(program.type_name).unwrap());
+        assert_eq!(c"Renamed", edited.get_string(program.type_namespace).unwrap());
+        assert_eq!(3, edited.row_count(TableIndex::AssemblyRef));
+
+        // The attribute on the method sorts before the one on the assembly
+        let attributes: Vec<_> = edited.table::<tables::CustomAttribute>().iter().map(|r| r.unwrap()).collect();
+        assert_eq!(vec![main.handle(), TableHandle::new(1, TableIndex::Assembly)], attributes.iter().map(|a| a.parent).collect::<Vec<_>>());
+        assert_eq!(Some(&[0x01, 0x00, 0x05, b'T', b'e', b's', b't', b's', 0x00, 0x00][..]), edited.get_blob(attributes[1].value));
+
+        let edited_main = edited.table::<tables::MethodDef>().read(0).unwrap();
+        assert_ne!(original_main.rva, edited_main.rva);
+        assert_eq!(&[0x12, 0x1F, 0x2A, 0x26, 0x2A], pe.read_rva(MemoryRange::new(edited_main.rva, 5)).unwrap());
+        assert_eq!(original_pe.read_rva(MemoryRange::new(original_main.rva, 12)).unwrap()[0], 0x2E);
+
+        // Everything the edit didn't touch is where it was
+        assert_eq!(original_field_rva.rva, edited.table::<tables::FieldRva>().read(0).unwrap().rva);
+        assert_eq!(&42u32.to_le_bytes(), pe.read_rva(MemoryRange::new(original_field_rva.rva, 4)).unwrap());
+        assert_eq!(b"native", pe.resource_data(&pe.resources().unwrap()[0]).unwrap());
+        assert_eq!(original_pe.imports().unwrap()[0].functions, pe.imports().unwrap()[0].functions);
+        assert_eq!(original.get_user_string(crate::cli::UserStringHandle(1)), edited.get_user_string(crate::cli::UserStringHandle(1)));
+        assert_eq!(main.handle(), edited.cli_header().unwrap().entry_point_token);
+    }
+
+    #[test]
+    pub fn reject_fields_that_overflow() {
+        let mut buf = (u32::MAX - 1).to_le_bytes();
+        assert!(add_u32(&mut buf, 0, 1).is_ok());
+        assert!(matches!(add_u32(&mut buf, 0, 1), Err(Error::DataOutOfRange)));
+        assert_eq!(u32::MAX, read_u32(&buf, 0));
+    }
+}
@@ -0,0 +1,549 @@
+use crate::cli::opcodes::{self, FlowControl, OpCode, OperandType, VARIABLE};
+use crate::cli::tables::TableHandle;
+use crate::cli::UserStringHandle;
+use crate::error::Error;
+
+/// A position in the instruction stream, which can be branched to before it is marked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Label(usize);
+
+/// The handler of an exception region.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExceptionHandler {
+    /// Catches exceptions of the given TypeDef, TypeRef or TypeSpec.
+    Catch(TableHandle),
+    /// Catches exceptions that the filter block starting at the label accepts.
+    Filter(Label),
+    Finally,
+    Fault,
+}
+
+struct ExceptionRegion {
+    handler: ExceptionHandler,
+    try_start: Label,
+    try_end: Label,
+    handler_start: Label,
+    handler_end: Label,
+}
+
+enum Operand {
+    Bytes(Vec<u8>),
+    Branch(Label),
+    Switch(Vec<Label>),
+}
+
+struct Instruction {
+    opcode: OpCode,
+    operand: Operand,
+    pops: u32,
+    pushes: u32,
+}
+
+/// Encodes the IL of a method body, with its header and exception handling clauses.
+///
+/// Instructions are appended in order. Branches refer to labels, which are resolved when the body is encoded, and
+/// then use the one-byte form whenever the offset fits. The maximum stack depth is found by following every path
+/// through the method.
+pub struct InstructionEncoder {
+    instructions: Vec<Instruction>,
+    labels: Vec<Option<usize>>,
+    regions: Vec<ExceptionRegion>,
+    local_signature: Option<TableHandle>,
+    init_locals: bool,
+}
+
+impl Default for InstructionEncoder {
+    fn default() -> InstructionEncoder {
+        InstructionEncoder::new()
+    }
+}
+
+impl InstructionEncoder {
+    pub fn new() -> InstructionEncoder {
+        InstructionEncoder {
+            instructions: Vec::new(),
+            labels: Vec::new(),
+            regions: Vec::new(),
+            local_signature: None,
+            init_locals: true,
+        }
+    }
+
+    /// Sets the StandAloneSig row holding the signature of the local variables.
+    pub fn set_local_signature(&mut self, signature: TableHandle) {
+        self.local_signature = Some(signature);
+    }
+
+    /// Sets whether the local variables are zero-initialized, which they are by default.
+    pub fn set_init_locals(&mut self, init_locals: bool) {
+        self.init_locals = init_locals;
+    }
+
+    /// Creates a label that is not yet marked.
+    pub fn define_label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    /// Marks the label at the next instruction.
+    ///
+    /// Panics if the label was already marked.
+    pub fn mark_label(&mut self, label: Label) {
+        assert!(self.labels[label.0].is_none(), "label {:?} is marked twice", label);
+        self.labels[label.0] = Some(self.instructions.len());
+    }
+
+    /// Appends an instruction that has no operand.
+    pub fn op_code(&mut self, opcode: OpCode) {
+        self.raw(opcode, &[]);
+    }
+
+    /// Appends an instruction with an operand that is already encoded, such as the alignment of `unaligned.`.
+    ///
+    /// Panics if the operand has the wrong size, or if the instruction is a branch or a call, which have their own
+    /// methods.
+    pub fn raw(&mut self, opcode: OpCode, operand: &[u8]) {
+        assert!(
+            !matches!(opcode.operand, OperandType::ShortInlineBrTarget | OperandType::InlineBrTarget | OperandType::InlineSwitch),
+            "{} needs a label",
+            opcode
+        );
+        assert!(opcode.pops != VARIABLE || opcode == opcodes::RET, "{} needs the size of the call", opcode);
+        assert_eq!(opcode.operand.size(), operand.len(), "wrong operand size for {}", opcode);
+        self.push(opcode, Operand::Bytes(operand.to_vec()), opcode.pops as u32, opcode.pushes as u32);
+    }
+
+    /// Appends an instruction whose operand is a metadata token, such as `ldfld` or `box`.
+    pub fn token(&mut self, opcode: OpCode, token: TableHandle) {
+        self.raw(opcode, &token.to_metadata_token().to_le_bytes());
+    }
+
+    /// Appends `call`, `callvirt`, `calli` or `newobj`.
+    ///
+    /// `arguments` counts the parameters of the method, including `this` for instance methods other than
+    /// constructors. The function pointer of `calli` is counted automatically.
+    pub fn call(&mut self, opcode: OpCode, method: TableHandle, arguments: u16, returns_value: bool) {
+        assert!(matches!(opcode.value, 0x28 | 0x29 | 0x6F | 0x73), "{} is not a call", opcode);
+        let pops = arguments as u32 + (opcode == opcodes::CALLI) as u32;
+        let pushes = if opcode == opcodes::NEWOBJ { 1 } else { returns_value as u32 };
+        let token = method.to_metadata_token().to_le_bytes().to_vec();
+        self.push(opcode, Operand::Bytes(token), pops, pushes);
+    }
+
+    /// Appends `ldstr` for a string in the `#US` heap.
+    pub fn load_string(&mut self, string: UserStringHandle) {
+        self.raw(opcodes::LDSTR, &(0x7000_0000 | string.0 as u32).to_le_bytes());
+    }
+
+    /// Appends a branch, which may be given in either its long or short form.
+    pub fn branch(&mut self, opcode: OpCode, target: Label) {
+        let opcode = opcode.long_branch().unwrap_or(opcode);
+        assert_eq!(OperandType::InlineBrTarget, opcode.operand, "{} is not a branch", opcode);
+        self.push(opcode, Operand::Branch(target), opcode.pops as u32, 0);
+    }
+
+    /// Appends a `switch` that jumps to the target at the index on the stack.
+    pub fn switch(&mut self, targets: &[Label]) {
+        self.push(opcodes::SWITCH, Operand::Switch(targets.to_vec()), 1, 0);
+    }
+
+    pub fn load_argument(&mut self, index: u16) {
+        let short = [opcodes::LDARG_0, opcodes::LDARG_1, opcodes::LDARG_2, opcodes::LDARG_3];
+        self.variable(&short, opcodes::LDARG_S, opcodes::LDARG, index);
+    }
+
+    pub fn load_argument_address(&mut self, index: u16) {
+        self.variable(&[], opcodes::LDARGA_S, opcodes::LDARGA, index);
+    }
+
+    pub fn store_argument(&mut self, index: u16) {
+        self.variable(&[], opcodes::STARG_S, opcodes::STARG, index);
+    }
+
+    pub fn load_local(&mut self, index: u16) {
+        let short = [opcodes::LDLOC_0, opcodes::LDLOC_1, opcodes::LDLOC_2, opcodes::LDLOC_3];
+        self.variable(&short, opcodes::LDLOC_S, opcodes::LDLOC, index);
+    }
+
+    pub fn load_local_address(&mut self, index: u16) {
+        self.variable(&[], opcodes::LDLOCA_S, opcodes::LDLOCA, index);
+    }
+
+    pub fn store_local(&mut self, index: u16) {
+        let short = [opcodes::STLOC_0, opcodes::STLOC_1, opcodes::STLOC_2, opcodes::STLOC_3];
+        self.variable(&short, opcodes::STLOC_S, opcodes::STLOC, index);
+    }
+
+    fn variable(&mut self, short: &[OpCode], byte: OpCode, long: OpCode, index: u16) {
+        if let Some(opcode) = short.get(index as usize) {
+            self.op_code(*opcode);
+        } else if index <= 0xFF {
+            self.raw(byte, &[index as u8]);
+        } else {
+            self.raw(long, &index.to_le_bytes());
+        }
+    }
+
+    /// Appends the shortest instruction that loads a 32-bit integer.
+    pub fn load_constant_i4(&mut self, value: i32) {
+        match value {
+            -1..=8 => self.op_code(OpCode::from_value((opcodes::LDC_I4_0.value as i32 + value) as u16).unwrap()),
+            -128..=127 => self.raw(opcodes::LDC_I4_S, &[value as i8 as u8]),
+            _ => self.raw(opcodes::LDC_I4, &value.to_le_bytes()),
+        }
+    }
+
+    /// Appends the shortest instruction that loads a 64-bit integer, using `conv.i8` if it fits in 32 bits.
+    pub fn load_constant_i8(&mut self, value: i64) {
+        if let Ok(value) = i32::try_from(value) {
+            self.load_constant_i4(value);
+            self.op_code(opcodes::CONV_I8);
+        } else {
+            self.raw(opcodes::LDC_I8, &value.to_le_bytes());
+        }
+    }
+
+    pub fn load_constant_r4(&mut self, value: f32) {
+        self.raw(opcodes::LDC_R4, &value.to_le_bytes());
+    }
+
+    pub fn load_constant_r8(&mut self, value: f64) {
+        self.raw(opcodes::LDC_R8, &value.to_le_bytes());
+    }
+
+    /// Adds an exception region, with the end labels marked just past the last instruction of each block.
+    ///
+    /// Clauses are written in the order they are added, so regions nested in another must be added first.
+    pub fn add_exception_region(
+        &mut self,
+        handler: ExceptionHandler,
+        try_start: Label,
+        try_end: Label,
+        handler_start: Label,
+        handler_end: Label,
+    ) {
+        self.regions.push(ExceptionRegion { handler, try_start, try_end, handler_start, handler_end });
+    }
+
+    fn push(&mut self, opcode: OpCode, operand: Operand, pops: u32, pushes: u32) {
+        self.instructions.push(Instruction { opcode, operand, pops, pushes });
+    }
+
+    /// Resolves the labels and writes the method body, with a tiny header if the method is small and simple
+    /// enough, and a fat header followed by the exception handling clauses otherwise.
+    pub fn encode(self) -> Result<Vec<u8>, Error> {
+        let labels = self.labels.iter().enumerate()
+            .map(|(i, index)| index.ok_or_else(|| Error::InvalidMethodBody(format!("label {} is never marked", i))))
+            .collect::<Result<Vec<usize>, Error>>()?;
+        let offsets = self.layout(&labels);
+        let max_stack = self.max_stack(&labels, &offsets)?;
+        let code = self.code(&labels, &offsets);
+
+        let mut body = Vec::new();
+        if code.len() < 64 && max_stack <= 8 && self.local_signature.is_none() && self.regions.is_empty() {
+            body.push((code.len() << 2) as u8 | 0x02);
+            body.extend(code);
+            return Ok(body);
+        }
+
+        let mut flags = 0x3003u16;
+        if !self.regions.is_empty() {
+            flags |= 0x08;
+        }
+        if self.init_locals && self.local_signature.is_some() {
+            flags |= 0x10;
+        }
+        body.extend_from_slice(&flags.to_le_bytes());
+        body.extend_from_slice(&(max_stack as u16).to_le_bytes());
+        body.extend_from_slice(&(code.len() as u32).to_le_bytes());
+        body.extend_from_slice(&self.local_signature.map_or(0, |s| s.to_metadata_token()).to_le_bytes());
+        body.extend(code);
+        if !self.regions.is_empty() {
+            body.resize((body.len() + 3) & !3, 0);
+            self.write_exception_clauses(&mut body, &labels, &offsets);
+        }
+        Ok(body)
+    }
+
+    /// Picks the size of every branch, starting with short branches and lengthening those that don't reach until
+    /// none change. Returns the offset of each instruction, followed by the size of the code.
+    fn layout(&self, labels: &[usize]) -> Vec<usize> {
+        let mut short: Vec<bool> = self.instructions.iter().map(|i| matches!(i.operand, Operand::Branch(_))).collect();
+        loop {
+            let mut offsets = Vec::with_capacity(self.instructions.len() + 1);
+            let mut offset = 0;
+            for (instruction, short) in self.instructions.iter().zip(short.iter()) {
+                offsets.push(offset);
+                offset += instruction.opcode.size() + match &instruction.operand {
+                    Operand::Bytes(bytes) => bytes.len(),
+                    Operand::Branch(_) if *short => 1,
+                    Operand::Branch(_) => 4,
+                    Operand::Switch(targets) => 4 + 4 * targets.len(),
+                };
+            }
+            offsets.push(offset);
+
+            let mut changed = false;
+            for (i, instruction) in self.instructions.iter().enumerate() {
+                if let (Operand::Branch(target), true) = (&instruction.operand, short[i]) {
+                    let delta = offsets[labels[target.0]] as i64 - offsets[i + 1] as i64;
+                    if i8::try_from(delta).is_err() {
+                        short[i] = false;
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                return offsets;
+            }
+        }
+    }
+
+    fn code(&self, labels: &[usize], offsets: &[usize]) -> Vec<u8> {
+        let mut code = Vec::with_capacity(offsets[offsets.len() - 1]);
+        for (i, instruction) in self.instructions.iter().enumerate() {
+            let next = offsets[i + 1] as i64;
+            let target = |label: &Label| (offsets[labels[label.0]] as i64 - next) as i32;
+            match &instruction.operand {
+                Operand::Bytes(bytes) => {
+                    instruction.opcode.encode(&mut code);
+                    code.extend_from_slice(bytes);
+                }
+                Operand::Branch(label) if next as usize - offsets[i] == 2 => {
+                    instruction.opcode.short_branch().unwrap().encode(&mut code);
+                    code.push(target(label) as i8 as u8);
+                }
+                Operand::Branch(label) => {
+                    instruction.opcode.encode(&mut code);
+                    code.extend_from_slice(&target(label).to_le_bytes());
+                }
+                Operand::Switch(labels) => {
+                    instruction.opcode.encode(&mut code);
+                    code.extend_from_slice(&(labels.len() as u32).to_le_bytes());
+                    for label in labels {
+                        code.extend_from_slice(&target(label).to_le_bytes());
+                    }
+                }
+            }
+        }
+        code
+    }
+
+    /// Follows every path through the method to find the deepest the stack gets, checking that each instruction is
+    /// always reached with the same depth.
+    fn max_stack(&self, labels: &[usize], offsets: &[usize]) -> Result<u32, Error> {
+        let count = self.instructions.len();
+        let mut depths: Vec<Option<u32>> = vec![None; count + 1];
+        let mut pending = vec![(0, 0)];
+        for region in self.regions.iter() {
+            let caught = match region.handler {
+                ExceptionHandler::Catch(_) => 1,
+                ExceptionHandler::Filter(filter) => {
+                    pending.push((labels[filter.0], 1));
+                    1
+                }
+                ExceptionHandler::Finally | ExceptionHandler::Fault => 0,
+            };
+            pending.push((labels[region.handler_start.0], caught));
+        }
+
+        let mut max = 0;
+        while let Some((index, depth)) = pending.pop() {
+            let error = |message: &str| Err(Error::InvalidMethodBody(format!("{} at IL_{:04X}", message, offsets[index])));
+            match depths[index] {
+                Some(known) if known == depth => continue,
+                Some(_) => return error("inconsistent stack depth"),
+                None => depths[index] = Some(depth),
+            }
+            if index == count {
+                if count == 0 {
+                    continue;
+                }
+                return error("control falls through the end of the method");
+            }
+
+            let instruction = &self.instructions[index];
+            let pops = if instruction.pops == VARIABLE as u32 { depth } else { instruction.pops };
+            if pops > depth {
+                return error("stack underflow");
+            }
+            let after = depth - pops + instruction.pushes;
+            max = max.max(after);
+
+            let is_leave = instruction.opcode == opcodes::LEAVE;
+            match &instruction.operand {
+                Operand::Branch(target) => pending.push((labels[target.0], if is_leave { 0 } else { after })),
+                Operand::Switch(targets) => pending.extend(targets.iter().map(|t| (labels[t.0], after))),
+                Operand::Bytes(_) => {}
+            }
+            if matches!(instruction.opcode.flow, FlowControl::Next | FlowControl::ConditionalBranch | FlowControl::Meta) {
+                pending.push((index + 1, after));
+            }
+        }
+        Ok(max)
+    }
+
+    fn write_exception_clauses(&self, buf: &mut Vec<u8>, labels: &[usize], offsets: &[usize]) {
+        let offset = |label: Label| offsets[labels[label.0]] as u32;
+        let clauses: Vec<[u32; 6]> = self.regions.iter().map(|region| {
+            let (flags, extra) = match region.handler {
+                ExceptionHandler::Catch(class) => (0, class.to_metadata_token()),
+                ExceptionHandler::Filter(filter) => (1, offset(filter)),
+                ExceptionHandler::Finally => (2, 0),
+                ExceptionHandler::Fault => (4, 0),
+            };
+            let try_start = offset(region.try_start);
+            let handler_start = offset(region.handler_start);
+            [flags, try_start, offset(region.try_end) - try_start, handler_start, offset(region.handler_end) - handler_start, extra]
+        }).collect();
+
+        let small_size = 4 + 12 * clauses.len();
+        let small = small_size <= 0xFF && clauses.iter().all(|c| c[1] <= 0xFFFF && c[2] <= 0xFF && c[3] <= 0xFFFF && c[4] <= 0xFF);
+        if small {
+            buf.extend_from_slice(&[0x01, small_size as u8, 0, 0]);
+            for c in clauses.iter() {
+                buf.extend_from_slice(&(c[0] as u16).to_le_bytes());
+                buf.extend_from_slice(&(c[1] as u16).to_le_bytes());
+                buf.push(c[2] as u8);
+                buf.extend_from_slice(&(c[3] as u16).to_le_bytes());
+                buf.push(c[4] as u8);
+                buf.extend_from_slice(&c[5].to_le_bytes());
+            }
+        } else {
+            let size = 4 + 24 * clauses.len() as u32;
+            buf.extend_from_slice(&(0x41 | (size << 8)).to_le_bytes());
+            for value in clauses.iter().flatten() {
+                buf.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::tables::TableIndex;
+
+    #[test]
+    pub fn tiny_body() {
+        let mut il = InstructionEncoder::new();
+        il.load_argument(0);
+        il.load_argument(1);
+        il.op_code(opcodes::ADD);
+        il.op_code(opcodes::RET);
+        assert_eq!(vec![0x12, 0x02, 0x03, 0x58, 0x2A], il.encode().unwrap());
+    }
+
+    #[test]
+    pub fn shortest_encodings() {
+        let mut il = InstructionEncoder::new();
+        il.load_constant_i4(-1);
+        il.load_constant_i4(8);
+        il.load_constant_i4(-100);
+        il.load_constant_i4(1000);
+        il.store_local(3);
+        il.store_local(4);
+        il.store_local(300);
+        il.load_argument_address(1);
+        il.op_code(opcodes::POP);
+        il.op_code(opcodes::RET);
+        let body = il.encode().unwrap();
+        assert_eq!(
+            vec![0x15, 0x1E, 0x1F, 0x9C, 0x20, 0xE8, 0x03, 0x00, 0x00, 0x0D, 0x13, 0x04, 0xFE, 0x0E, 0x2C, 0x01, 0x0F, 0x01, 0x26, 0x2A],
+            body[1..]
+        );
+    }
+
+    #[test]
+    pub fn branches_pick_their_size() {
+        let mut il = InstructionEncoder::new();
+        let start = il.define_label();
+        let far = il.define_label();
+        il.mark_label(start);
+        il.load_argument(0);
+        il.branch(opcodes::BRTRUE, far);
+        il.load_argument(0);
+        il.branch(opcodes::BRFALSE_S, start);
+        for _ in 0..200 {
+            il.op_code(opcodes::NOP);
+        }
+        il.mark_label(far);
+        il.op_code(opcodes::RET);
+
+        let body = il.encode().unwrap();
+        assert_eq!(0x03, body[0] & 0x03);
+        let code = &body[12..];
+        assert_eq!(&[0x02, 0x3A, 0xCB, 0x00, 0x00, 0x00, 0x02, 0x2C, 0xF7], &code[..9]);
+        assert_eq!(1, u16::from_le_bytes([body[2], body[3]]));
+    }
+
+    #[test]
+    pub fn switch_and_exception_regions() {
+        let mut il = InstructionEncoder::new();
+        il.set_local_signature(TableHandle::new(1, TableIndex::StandAloneSig));
+        let cases = [il.define_label(), il.define_label()];
+        let try_start = il.define_label();
+        let try_end = il.define_label();
+        let handler_end = il.define_label();
+        let exit = il.define_label();
+
+        il.load_argument(0);
+        il.switch(&cases);
+        il.branch(opcodes::BR, exit);
+        il.mark_label(cases[0]);
+        il.mark_label(try_start);
+        il.load_argument(0);
+        il.load_argument(0);
+        il.call(opcodes::CALL, TableHandle::new(1, TableIndex::MemberRef), 2, true);
+        il.store_local(0);
+        il.branch(opcodes::LEAVE, exit);
+        il.mark_label(try_end);
+        il.op_code(opcodes::POP);
+        il.branch(opcodes::LEAVE, exit);
+        il.mark_label(handler_end);
+        il.mark_label(cases[1]);
+        il.mark_label(exit);
+        il.op_code(opcodes::RET);
+        il.add_exception_region(
+            ExceptionHandler::Catch(TableHandle::new(1, TableIndex::TypeRef)),
+            try_start,
+            try_end,
+            try_end,
+            handler_end,
+        );
+
+        let body = il.encode().unwrap();
+        assert_eq!(&[0x1B, 0x30, 0x02, 0x00], &body[..4]);
+        assert_eq!(0x1100_0001, u32::from_le_bytes([body[8], body[9], body[10], body[11]]));
+        let code_size = u32::from_le_bytes([body[4], body[5], body[6], body[7]]) as usize;
+        let code = &body[12..12 + code_size];
+        assert_eq!(&[0x02, 0x45, 0x02, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x0F, 0x00, 0x00, 0x00, 0x2B, 0x0D], &code[..16]);
+
+        let eh = &body[(12 + code_size + 3) & !3..];
+        assert_eq!(&[0x01, 16, 0, 0, 0, 0, 16, 0], &eh[..8]);
+        assert_eq!(&[10, 26, 0, 3], &eh[8..12]);
+        assert_eq!(0x0100_0001, u32::from_le_bytes([eh[12], eh[13], eh[14], eh[15]]));
+    }
+
+    #[test]
+    pub fn invalid_bodies() {
+        let mut il = InstructionEncoder::new();
+        let label = il.define_label();
+        il.branch(opcodes::BR, label);
+        assert!(il.encode().is_err());
+
+        let mut il = InstructionEncoder::new();
+        il.op_code(opcodes::POP);
+        il.op_code(opcodes::RET);
+        assert!(il.encode().is_err());
+
+        let mut il = InstructionEncoder::new();
+        let join = il.define_label();
+        il.load_argument(0);
+        il.branch(opcodes::BRTRUE, join);
+        il.op_code(opcodes::LDNULL);
+        il.mark_label(join);
+        il.op_code(opcodes::RET);
+        assert!(il.encode().is_err());
+    }
+}
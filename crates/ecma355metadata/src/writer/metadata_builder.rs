@@ -0,0 +1,518 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+
+use crate::cli::tables::{self, RowDecoder, RowHandle, TableIndex, TableMask, TableRow};
+use crate::cli::{BlobHandle, GuidHandle, HeapSizes, MetadataSizes, StringHandle, UserStringHandle};
+use crate::error::Error;
+use crate::writer::{BlobHeapBuilder, GuidHeapBuilder, StringHeapBuilder, UserStringHeapBuilder};
+use crate::pe::ImageData;
+use crate::{Guid, MetadataImage};
+
+use self::SortKey::{Ascending, Descending};
+
+/// A column of a sort key, and which way it is sorted.
+#[derive(Clone, Copy)]
+enum SortKey {
+    Ascending(usize),
+    Descending(usize),
+}
+
+/// The tables that ECMA-335 II.22 requires to be sorted, with the columns that make up their sort key.
+///
+/// Sorting a table renumbers its rows, so any sorted table that a sort key can point into comes before the tables
+/// whose keys point at it. CustomAttribute parents can be InterfaceImpl, GenericParam, GenericParamConstraint or
+/// DeclSecurity rows, and GenericParamConstraint owners are GenericParam rows.
+const SORTED_TABLES: &[(TableIndex, &[SortKey])] = &[
+    (TableIndex::InterfaceImpl, &[Ascending(0), Ascending(1)]),
+    (TableIndex::GenericParam, &[Ascending(2), Ascending(0)]),
+    (TableIndex::GenericParamConstraint, &[Ascending(0)]),
+    (TableIndex::DeclSecurity, &[Ascending(1)]),
+    (TableIndex::Constant, &[Ascending(2)]),
+    (TableIndex::CustomAttribute, &[Ascending(0)]),
+    (TableIndex::FieldMarshal, &[Ascending(0)]),
+    (TableIndex::ClassLayout, &[Ascending(2)]),
+    (TableIndex::FieldLayout, &[Ascending(1)]),
+    (TableIndex::MethodSemantics, &[Ascending(2)]),
+    (TableIndex::MethodImpl, &[Ascending(0)]),
+    (TableIndex::ImplMap, &[Ascending(1)]),
+    (TableIndex::FieldRva, &[Ascending(1)]),
+    (TableIndex::NestedClass, &[Ascending(0)]),
+    // Scopes that start together are nested, and the outer scope comes first
+    (TableIndex::LocalScope, &[Ascending(0), Ascending(4), Descending(5)]),
+    (TableIndex::StateMachineMethod, &[Ascending(0)]),
+    (TableIndex::CustomDebugInformation, &[Ascending(0)]),
+];
+
+/// The rows of one table, with the row type erased so that every table can be kept in one map.
+trait RowStore {
+    fn len(&self) -> usize;
+    fn raw_rows(&self) -> Result<Vec<Vec<u32>>, Error>;
+    fn reorder(&mut self, order: &[usize]);
+    fn remap(&mut self, table: TableIndex, map: &[usize]);
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: TableRow + 'static> RowStore for Vec<T> {
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn raw_rows(&self) -> Result<Vec<Vec<u32>>, Error> {
+        self.iter().map(|row| row.raw_columns()).collect()
+    }
+
+    fn reorder(&mut self, order: &[usize]) {
+        let mut rows: Vec<Option<T>> = self.drain(..).map(Some).collect();
+        self.extend(order.iter().map(|i| rows[*i].take().expect("order is a permutation")));
+    }
+
+    fn remap(&mut self, table: TableIndex, map: &[usize]) {
+        for row in self.iter_mut() {
+            row.remap(table, map);
+        }
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Builds the metadata of a module, and serializes it as a metadata root.
+///
+/// Rows can be added in any order. Tables that must be sorted are sorted when the metadata is serialized, and
+/// references to the rows they move are updated to match.
+pub struct MetadataBuilder {
+    version: String,
+    strings: StringHeapBuilder,
+    user_strings: UserStringHeapBuilder,
+    guids: GuidHeapBuilder,
+    blobs: BlobHeapBuilder,
+    tables: BTreeMap<TableIndex, Box<dyn RowStore>>,
+}
+
+impl Default for MetadataBuilder {
+    fn default() -> MetadataBuilder {
+        MetadataBuilder::new()
+    }
+}
+
+impl MetadataBuilder {
+    pub fn new() -> MetadataBuilder {
+        MetadataBuilder {
+            version: "v4.0.30319".to_string(),
+            strings: StringHeapBuilder::new(),
+            user_strings: UserStringHeapBuilder::new(),
+            guids: GuidHeapBuilder::new(),
+            blobs: BlobHeapBuilder::new(),
+            tables: BTreeMap::new(),
+        }
+    }
+
+    /// Creates a builder holding the tables and heaps of an existing image, to change it and write it back.
+    ///
+    /// The heaps are copied as they are, so every heap handle in the image stays valid, and rows keep their
+    /// positions, so tokens stay valid too. Streams other than the tables and the four heaps are not copied.
+    pub fn from_image<D: ImageData>(image: &MetadataImage<D>) -> Result<MetadataBuilder, Error> {
+        let metadata = image.metadata();
+        let heaps = image.heaps();
+        let heap = |range: &Option<std::ops::Range<usize>>| range.as_ref().map_or(&[][..], |r| &metadata[r.clone()]);
+        let mut builder = MetadataBuilder {
+            version: image.metadata_header().version.trim_end_matches('\0').to_string(),
+            strings: StringHeapBuilder::from_data(heap(&heaps.string_heap)),
+            user_strings: UserStringHeapBuilder::from_data(heap(&heaps.userstring_heap)),
+            guids: GuidHeapBuilder::from_data(heap(&heaps.guid_heap)),
+            blobs: BlobHeapBuilder::from_data(heap(&heaps.blob_heap)),
+            tables: BTreeMap::new(),
+        };
+
+        macro_rules! copy_tables {
+            ($($table:ident),* $(,)?) => {
+                $(
+                    for row in image.table::<tables::$table>().iter() {
+                        builder.add_row(row?);
+                    }
+                )*
+            };
+        }
+        copy_tables!(
+            Module, TypeRef, TypeDef, FieldPtr, Field, MethodPtr, MethodDef, ParamPtr, Param, InterfaceImpl, MemberRef,
+            Constant, CustomAttribute, FieldMarshal, DeclSecurity, ClassLayout, FieldLayout, StandAloneSig, EventMap,
+            EventPtr, Event, PropertyMap, PropertyPtr, Property, MethodSemantics, MethodImpl, ModuleRef, TypeSpec,
+            ImplMap, FieldRva, EncLog, EncMap, Assembly, AssemblyProcessor, AssemblyOS, AssemblyRef,
+            AssemblyRefProcessor, AssemblyRefOS, File, ExportedType, ManifestResource, NestedClass, GenericParam,
+            MethodSpec, GenericParamConstraint, Document, MethodDebugInformation, LocalScope, LocalVariable,
+            LocalConstant, ImportScope, StateMachineMethod, CustomDebugInformation,
+        );
+        Ok(builder)
+    }
+
+    /// Sets the runtime version string written in the metadata root, which is `v4.0.30319` by default.
+    pub fn set_version(&mut self, version: &str) {
+        self.version = version.to_string();
+    }
+
+    pub fn add_string(&mut self, value: &str) -> StringHandle {
+        self.strings.add(value)
+    }
+
+    pub fn add_user_string(&mut self, value: &str) -> UserStringHandle {
+        self.user_strings.add(value)
+    }
+
+    pub fn add_guid(&mut self, value: Guid) -> GuidHandle {
+        self.guids.add(value)
+    }
+
+    pub fn add_blob(&mut self, value: &[u8]) -> BlobHandle {
+        self.blobs.add(value)
+    }
+
+    /// Appends a row to its table, returning a handle to it.
+    ///
+    /// Handles to rows of the tables that ECMA-335 requires to be sorted, such as InterfaceImpl, GenericParam,
+    /// CustomAttribute, Constant and NestedClass, are only valid until the metadata is serialized, since sorting can
+    /// move the rows. Columns of other rows that point into those tables are updated to match.
+    pub fn add_row<T: TableRow + 'static>(&mut self, row: T) -> RowHandle<T> {
+        let rows = self.rows_mut::<T>();
+        rows.push(row);
+        RowHandle::new(rows.len())
+    }
+
+    /// Gets a handle to the row that the next call to `add_row` for the table will add.
+    ///
+    /// This is what list columns, such as the field and method lists of a TypeDef, should hold when their list starts
+    /// with rows that haven't been added yet.
+    pub fn next_row<T: TableRow>(&self) -> RowHandle<T> {
+        RowHandle::new(self.row_count(T::INDEX) + 1)
+    }
+
+    pub fn row_count(&self, table: TableIndex) -> usize {
+        self.tables.get(&table).map_or(0, |rows| rows.len())
+    }
+
+    /// Gets a row that was added earlier, so it can be changed.
+    pub fn row_mut<T: TableRow + 'static>(&mut self, handle: RowHandle<T>) -> Option<&mut T> {
+        handle.row().checked_sub(1).and_then(|index| self.rows_mut::<T>().get_mut(index))
+    }
+
+    fn rows_mut<T: TableRow + 'static>(&mut self) -> &mut Vec<T> {
+        self.tables
+            .entry(T::INDEX)
+            .or_insert_with(|| Box::new(Vec::<T>::new()))
+            .as_any_mut()
+            .downcast_mut::<Vec<T>>()
+            .expect("each table only holds rows of its own type")
+    }
+
+    /// Sorts the tables and writes the metadata root, with the `#~`, `#Strings`, `#US`, `#GUID` and `#Blob` streams.
+    pub fn serialize(mut self) -> Result<Vec<u8>, Error> {
+        self.sort_tables()?;
+        let tables = self.table_stream()?;
+
+        let streams: [(&str, &[u8]); 5] = [
+            ("#~", &tables),
+            ("#Strings", self.strings.data()),
+            ("#US", self.user_strings.data()),
+            ("#GUID", self.guids.data()),
+            ("#Blob", self.blobs.data()),
+        ];
+
+        let version_size = align4(self.version.len() + 1);
+        let header_size = 20 + version_size + streams.iter().map(|(name, _)| 8 + align4(name.len() + 1)).sum::<usize>();
+
+        let mut buf = Vec::new();
+        buf.extend_from_slice(b"BSJB");
+        buf.extend_from_slice(&1u16.to_le_bytes());
+        buf.extend_from_slice(&1u16.to_le_bytes());
+        buf.extend_from_slice(&0u32.to_le_bytes());
+        buf.extend_from_slice(&(version_size as u32).to_le_bytes());
+        buf.extend_from_slice(self.version.as_bytes());
+        buf.resize(buf.len() + version_size - self.version.len(), 0);
+        buf.extend_from_slice(&0u16.to_le_bytes());
+        buf.extend_from_slice(&(streams.len() as u16).to_le_bytes());
+
+        let mut offset = header_size;
+        for (name, data) in streams.iter() {
+            let size = align4(data.len());
+            buf.extend_from_slice(&(offset as u32).to_le_bytes());
+            buf.extend_from_slice(&(size as u32).to_le_bytes());
+            buf.extend_from_slice(name.as_bytes());
+            buf.resize(buf.len() + align4(name.len() + 1) - name.len(), 0);
+            offset += size;
+        }
+
+        for (_, data) in streams.iter() {
+            buf.extend_from_slice(data);
+            buf.resize(align4(buf.len()), 0);
+        }
+        Ok(buf)
+    }
+
+    /// Sorts the sorted tables by their keys, keeping rows with equal keys in the order they were added.
+    fn sort_tables(&mut self) -> Result<(), Error> {
+        for (table, key) in SORTED_TABLES {
+            let rows = match self.tables.get_mut(table) {
+                Some(rows) => rows,
+                None => continue,
+            };
+            let raw = rows.raw_rows()?;
+            let mut order: Vec<usize> = (0..raw.len()).collect();
+            order.sort_by_key(|i| {
+                key.iter()
+                    .map(|column| match column {
+                        Ascending(column) => raw[*i][*column],
+                        Descending(column) => !raw[*i][*column],
+                    })
+                    .collect::<Vec<_>>()
+            });
+            if order.iter().enumerate().all(|(new, old)| new == *old) {
+                continue;
+            }
+            rows.reorder(&order);
+
+            let mut map = vec![0; order.len()];
+            for (new, old) in order.iter().enumerate() {
+                map[*old] = new + 1;
+            }
+            for rows in self.tables.values_mut() {
+                rows.remap(*table, &map);
+            }
+        }
+        Ok(())
+    }
+
+    fn table_stream(&self) -> Result<Vec<u8>, Error> {
+        let mut heap_sizes = HeapSizes::empty();
+        if self.strings.data().len() > u16::MAX as usize {
+            heap_sizes |= HeapSizes::LARGE_STRINGS;
+        }
+        if self.guids.len() > u16::MAX as usize {
+            heap_sizes |= HeapSizes::LARGE_GUIDS;
+        }
+        if self.blobs.data().len() > u16::MAX as usize {
+            heap_sizes |= HeapSizes::LARGE_BLOBS;
+        }
+        let sorted_mask = SORTED_TABLES.iter().fold(TableMask::empty(), |mask, (table, _)| mask | TableMask::from(*table));
+        let row_counts: Vec<_> = self.tables.iter().map(|(table, rows)| (*table, rows.len())).collect();
+        let sizes = MetadataSizes::new(heap_sizes, sorted_mask, &row_counts);
+        let decoder = RowDecoder::new(&sizes);
+
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&0u32.to_le_bytes());
+        buf.extend_from_slice(&[0x02, 0x00, heap_sizes.bits(), 0x01]);
+        buf.extend_from_slice(&sizes.valid_tables().bits().to_le_bytes());
+        buf.extend_from_slice(&sizes.sorted_tables().bits().to_le_bytes());
+        for table in sizes.valid_tables().tables() {
+            buf.extend_from_slice(&(self.row_count(table) as u32).to_le_bytes());
+        }
+
+        // Index widths come from the row counts, exactly as the reader works them out
+        for (table, rows) in self.tables.iter().filter(|(_, rows)| rows.len() > 0) {
+            let layout = decoder.layout(*table);
+            for row in rows.raw_rows()? {
+                for (column, value) in row.iter().enumerate() {
+                    let (_, size) = layout.column(column);
+                    buf.extend_from_slice(&value.to_le_bytes()[..size]);
+                }
+            }
+        }
+        buf.resize(align4(buf.len()), 0);
+        Ok(buf)
+    }
+}
+
+fn align4(value: usize) -> usize {
+    (value + 3) & !3
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::cli::tables::{self, TableHandle};
+    use crate::cli::{MetadataHeader, MethodAttributes, MethodImplAttributes, TypeAttributes};
+    use crate::MetadataImage;
+
+    fn type_ref(builder: &mut MetadataBuilder, namespace: &str, name: &str) -> RowHandle<tables::TypeRef> {
+        let row = tables::TypeRef {
+            resolution_scope: TableHandle::new(1, TableIndex::Module),
+            name: builder.add_string(name),
+            namespace: builder.add_string(namespace),
+        };
+        builder.add_row(row)
+    }
+
+    fn type_def(builder: &mut MetadataBuilder, name: &str) -> RowHandle<tables::TypeDef> {
+        let row = tables::TypeDef {
+            flags: TypeAttributes::new(0x0010_0001),
+            type_name: builder.add_string(name),
+            type_namespace: builder.add_string("Ns"),
+            extends: TableHandle::new(0, TableIndex::TypeDef),
+            field_list: builder.next_row::<tables::Field>().handle(),
+            method_list: builder.next_row::<tables::MethodDef>().handle(),
+        };
+        builder.add_row(row)
+    }
+
+    #[test]
+    pub fn serialize_round_trips() {
+        let mut builder = MetadataBuilder::new();
+        let module = tables::Module {
+            generation: 0,
+            name: builder.add_string("Test.dll"),
+            mvid: builder.add_guid(Guid::from_bytes(&[7; 16])),
+            enc_id: GuidHandle(0),
+            enc_base_id: GuidHandle(0),
+        };
+        builder.add_row(module);
+        let disposable = type_ref(&mut builder, "System", "IDisposable");
+        let attribute = type_ref(&mut builder, "System", "ObsoleteAttribute");
+        let widget = type_def(&mut builder, "Widget");
+        let gadget = type_def(&mut builder, "Gadget");
+        assert_eq!(TableHandle::new(2, TableIndex::TypeDef), gadget.handle());
+        assert_eq!(TableHandle::new(1, TableIndex::Field), builder.next_row::<tables::Field>().handle());
+
+        // Gadget's interface goes in first, so sorting has to move it, and the attribute that points at it
+        let gadget_impl = builder.add_row(tables::InterfaceImpl { class: gadget.handle(), interface: disposable.handle() });
+        builder.add_row(tables::InterfaceImpl { class: widget.handle(), interface: disposable.handle() });
+        let ctor = tables::MemberRef {
+            class: attribute.handle(),
+            name: builder.add_string(".ctor"),
+            signature: builder.add_blob(&[0x20, 0x00, 0x01]),
+        };
+        let ctor = builder.add_row(ctor);
+        let value = builder.add_blob(&[0x01, 0x00, 0x00, 0x00]);
+        builder.add_row(tables::CustomAttribute { parent: gadget_impl.handle(), typ: ctor.handle(), value });
+        builder.add_row(tables::CustomAttribute { parent: widget.handle(), typ: ctor.handle(), value });
+        let greeting = builder.add_user_string("Hello");
+
+        let metadata = builder.serialize().unwrap();
+        let header = MetadataHeader::read(Cursor::new(&metadata)).unwrap();
+        assert_eq!("v4.0.30319", header.version.trim_end_matches('\0'));
+        let stream = header.get_stream("#~").unwrap();
+        let sizes = MetadataSizes::read(&mut &metadata[stream.offset as usize..]).unwrap();
+        assert_eq!(TableMask::from_bits_truncate(0x1600_3301_FA00) | TableMask::LocalScope | TableMask::StateMachineMethod | TableMask::CustomDebugInformation, sizes.sorted_tables());
+        assert_eq!(2, sizes.row_count(TableIndex::InterfaceImpl));
+
+        let image = MetadataImage::load_metadata(metadata).unwrap();
+        let module = image.table::<tables::Module>().read(0).unwrap();
+        assert_eq!(c"Test.dll", image.get_string(module.name).unwrap());
+        assert_eq!(Guid::from_bytes(&[7; 16]), image.get_guid(module.mvid).unwrap());
+        assert_eq!("Hello", image.get_user_string(greeting).unwrap());
+
+        let impls = image.table::<tables::InterfaceImpl>();
+        assert_eq!(widget.handle(), impls.read(0).unwrap().class);
+        assert_eq!(gadget.handle(), impls.read(1).unwrap().class);
+
+        let attributes = image.table::<tables::CustomAttribute>();
+        assert_eq!(widget.handle(), attributes.read(0).unwrap().parent);
+        assert_eq!(TableHandle::new(2, TableIndex::InterfaceImpl), attributes.read(1).unwrap().parent);
+        assert_eq!(ctor.handle(), attributes.read(1).unwrap().typ);
+        assert!(image.validate().is_empty(), "{:?}", image.validate());
+    }
+
+    #[test]
+    pub fn sort_tables_before_attributes_on_them() {
+        let mut builder = MetadataBuilder::new();
+        let module = tables::Module {
+            generation: 0,
+            name: builder.add_string("Test.dll"),
+            mvid: builder.add_guid(Guid::from_bytes(&[7; 16])),
+            enc_id: GuidHandle(0),
+            enc_base_id: GuidHandle(0),
+        };
+        builder.add_row(module);
+        let disposable = type_ref(&mut builder, "System", "IDisposable");
+        let attribute = type_ref(&mut builder, "System", "ObsoleteAttribute");
+        let widget = type_def(&mut builder, "Widget");
+        let gadget = type_def(&mut builder, "Gadget");
+        let ctor = tables::MemberRef {
+            class: attribute.handle(),
+            name: builder.add_string(".ctor"),
+            signature: builder.add_blob(&[0x20, 0x00, 0x01]),
+        };
+        let ctor = builder.add_row(ctor);
+        let value = builder.add_blob(&[0x01, 0x00, 0x00, 0x00]);
+
+        // Gadget's security declaration and the constraint on Widget's second parameter go in first, so both move
+        let permission_set = builder.add_blob(&[0x2E, 0x00]);
+        let gadget_security = builder.add_row(tables::DeclSecurity { action: 2, parent: gadget.handle(), permission_set });
+        builder.add_row(tables::DeclSecurity { action: 2, parent: widget.handle(), permission_set });
+        let generic_param = |builder: &mut MetadataBuilder, number: u16, name: &str| {
+            let row = tables::GenericParam { number, flags: 0, owner: widget.handle(), name: builder.add_string(name) };
+            builder.add_row(row)
+        };
+        let first = generic_param(&mut builder, 0, "T");
+        let second = generic_param(&mut builder, 1, "U");
+        let second_constraint = builder.add_row(tables::GenericParamConstraint { owner: second.handle(), constraint: disposable.handle() });
+        builder.add_row(tables::GenericParamConstraint { owner: first.handle(), constraint: disposable.handle() });
+        builder.add_row(tables::CustomAttribute { parent: second_constraint.handle(), typ: ctor.handle(), value });
+        builder.add_row(tables::CustomAttribute { parent: gadget_security.handle(), typ: ctor.handle(), value });
+
+        let image = MetadataImage::load_metadata(builder.serialize().unwrap()).unwrap();
+        assert!(image.validate().is_empty(), "{:?}", image.validate());
+
+        let attributes = image.table::<tables::CustomAttribute>();
+        let security = attributes.read(0).unwrap().parent;
+        assert_eq!(TableHandle::new(2, TableIndex::DeclSecurity), security);
+        assert_eq!(gadget.handle(), image.table::<tables::DeclSecurity>().read(1).unwrap().parent);
+        let constraint = attributes.read(1).unwrap().parent;
+        assert_eq!(TableHandle::new(2, TableIndex::GenericParamConstraint), constraint);
+        assert_eq!(second.handle(), image.table::<tables::GenericParamConstraint>().read(1).unwrap().owner);
+    }
+
+    #[test]
+    pub fn nested_scopes_sort_outermost_first() {
+        let mut builder = MetadataBuilder::new();
+        let scope = |start_offset: u32, length: u32| tables::LocalScope {
+            method: TableHandle::new(1, TableIndex::MethodDef),
+            import_scope: TableHandle::new(1, TableIndex::ImportScope),
+            variable_list: TableHandle::new(1, TableIndex::LocalVariable),
+            constant_list: TableHandle::new(1, TableIndex::LocalConstant),
+            start_offset,
+            length,
+        };
+        builder.add_row(scope(0, 4));
+        builder.add_row(scope(0, 10));
+        builder.add_row(scope(2, 1));
+
+        let image = MetadataImage::load_metadata(builder.serialize().unwrap()).unwrap();
+        let scopes = image.table::<tables::LocalScope>();
+        let spans: Vec<_> = (0..3).map(|i| scopes.read(i).map(|s| (s.start_offset, s.length)).unwrap()).collect();
+        assert_eq!(vec![(0, 10), (0, 4), (2, 1)], spans);
+    }
+
+    #[test]
+    pub fn large_heaps_use_large_indexes() {
+        let mut builder = MetadataBuilder::new();
+        for i in 0..10000 {
+            builder.add_string(&format!("Name{}", i));
+        }
+        let name = builder.add_string("Last");
+        builder.add_row(tables::ModuleRef { name });
+
+        let metadata = builder.serialize().unwrap();
+        let image = MetadataImage::load_metadata(metadata).unwrap();
+        assert_eq!(4, image.row_decoder().size_of_string());
+        let row = image.table::<tables::ModuleRef>().read(0).unwrap();
+        assert_eq!(c"Last", image.get_string(row.name).unwrap());
+    }
+
+    #[test]
+    pub fn rows_can_be_changed_after_adding() {
+        let mut builder = MetadataBuilder::new();
+        let method = tables::MethodDef {
+            rva: 0,
+            impl_flags: MethodImplAttributes::new(0),
+            flags: MethodAttributes::new(0x0016),
+            name: builder.add_string("Main"),
+            signature: builder.add_blob(&[0x00, 0x00, 0x01]),
+            params: builder.next_row::<tables::Param>().handle(),
+        };
+        let method = builder.add_row(method);
+        builder.row_mut(method).unwrap().rva = 0x2050;
+        let image = MetadataImage::load_metadata(builder.serialize().unwrap()).unwrap();
+        assert_eq!(0x2050, image.table::<tables::MethodDef>().read(0).unwrap().rva);
+    }
+}
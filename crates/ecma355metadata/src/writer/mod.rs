@@ -0,0 +1,15 @@
+mod heaps;
+mod image_editor;
+mod instruction_encoder;
+mod metadata_builder;
+mod pe_builder;
+mod reference_assembly;
+mod resources;
+
+pub use self::heaps::{write_compressed_u32, BlobHeapBuilder, GuidHeapBuilder, StringHeapBuilder, UserStringHeapBuilder};
+pub use self::image_editor::ImageEditor;
+pub use self::instruction_encoder::{ExceptionHandler, InstructionEncoder, Label};
+pub use self::metadata_builder::MetadataBuilder;
+pub use self::pe_builder::PeBuilder;
+pub use self::reference_assembly::write_reference_assembly;
+pub use self::resources::{write_resources, Win32Resource};
@@ -0,0 +1,571 @@
+use crate::cli::tables::{self, RowHandle, TableHandle};
+use crate::cli::CliFlags;
+use crate::error::Error;
+use crate::pe::{FileCharacteristics, SectionCharacteristics, Subsystem};
+use crate::writer::{write_resources, MetadataBuilder, Win32Resource};
+
+const SECTION_ALIGNMENT: u32 = 0x2000;
+const FILE_ALIGNMENT: u32 = 0x200;
+const CLI_HEADER_SIZE: usize = 72;
+
+/// The MS-DOS header and stub program that every PE file starts with, with `e_lfanew` pointing just past it.
+const DOS_HEADER: [u8; 0x80] = [
+    0x4D, 0x5A, 0x90, 0x00, 0x03, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0x00, 0x00,
+    0xB8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x00, 0x00, 0x00,
+    0x0E, 0x1F, 0xBA, 0x0E, 0x00, 0xB4, 0x09, 0xCD, 0x21, 0xB8, 0x01, 0x4C, 0xCD, 0x21, 0x54, 0x68,
+    0x69, 0x73, 0x20, 0x70, 0x72, 0x6F, 0x67, 0x72, 0x61, 0x6D, 0x20, 0x63, 0x61, 0x6E, 0x6E, 0x6F,
+    0x74, 0x20, 0x62, 0x65, 0x20, 0x72, 0x75, 0x6E, 0x20, 0x69, 0x6E, 0x20, 0x44, 0x4F, 0x53, 0x20,
+    0x6D, 0x6F, 0x64, 0x65, 0x2E, 0x0D, 0x0D, 0x0A, 0x24, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// A section of the image being written.
+struct Section {
+    name: &'static [u8],
+    rva: u32,
+    data: Vec<u8>,
+    characteristics: SectionCharacteristics,
+}
+
+/// Where the pieces of the `.text` section go, relative to the start of the section.
+struct TextLayout {
+    cli_header: usize,
+    method_bodies: usize,
+    field_data: usize,
+    resources: usize,
+    metadata: usize,
+}
+
+/// Builds a managed PE image around the metadata of a module.
+///
+/// The image has a `.text` section holding the CLI header, method bodies, mapped field data, managed resources and
+/// metadata, a `.rsrc` section if there are Win32 resources, and for PE32 images, the import of `_CorExeMain` or
+/// `_CorDllMain` from `mscoree.dll` with the `.reloc` section for its entry point stub. PE32+ images have no stub, as
+/// the loader starts them without one.
+pub struct PeBuilder {
+    metadata: MetadataBuilder,
+    pe32plus: bool,
+    dll: bool,
+    subsystem: Subsystem,
+    cli_flags: CliFlags,
+    entry_point: TableHandle,
+    method_bodies: Vec<u8>,
+    method_rvas: Vec<(RowHandle<tables::MethodDef>, usize)>,
+    field_data: Vec<u8>,
+    field_rvas: Vec<(RowHandle<tables::Field>, usize)>,
+    resources: Vec<u8>,
+    win32_resources: Vec<Win32Resource>,
+}
+
+impl PeBuilder {
+    /// Creates a builder for a 32-bit, IL-only console executable.
+    pub fn new(metadata: MetadataBuilder) -> PeBuilder {
+        PeBuilder {
+            metadata,
+            pe32plus: false,
+            dll: false,
+            subsystem: Subsystem::WINDOWS_CUI,
+            cli_flags: CliFlags::ILONLY,
+            entry_point: TableHandle::new(0, tables::TableIndex::MethodDef),
+            method_bodies: Vec::new(),
+            method_rvas: Vec::new(),
+            field_data: Vec::new(),
+            field_rvas: Vec::new(),
+            resources: Vec::new(),
+            win32_resources: Vec::new(),
+        }
+    }
+
+    /// Gets the metadata of the module, so rows can still be added while method bodies are being written.
+    pub fn metadata(&mut self) -> &mut MetadataBuilder {
+        &mut self.metadata
+    }
+
+    /// Selects PE32+, which targets x64 and has 64-bit headers, instead of PE32.
+    pub fn set_pe32plus(&mut self, pe32plus: bool) {
+        self.pe32plus = pe32plus;
+    }
+
+    /// Marks the image as a library rather than an executable.
+    pub fn set_dll(&mut self, dll: bool) {
+        self.dll = dll;
+    }
+
+    pub fn set_subsystem(&mut self, subsystem: Subsystem) {
+        self.subsystem = subsystem;
+    }
+
+    /// Sets the flags of the CLI header, which are `ILONLY` by default.
+    pub fn set_cli_flags(&mut self, flags: CliFlags) {
+        self.cli_flags = flags;
+    }
+
+    /// Sets the method that the runtime calls to start an executable, or the File row of the module that holds it.
+    pub fn set_entry_point(&mut self, entry_point: TableHandle) {
+        self.entry_point = entry_point;
+    }
+
+    /// Adds the body of a method, with its tiny or fat header, and points the method's RVA at it.
+    pub fn add_method_body(&mut self, method: RowHandle<tables::MethodDef>, body: &[u8]) {
+        // Fat headers must be 4-byte aligned, so align every body
+        self.method_bodies.resize(align(self.method_bodies.len(), 4), 0);
+        self.method_rvas.push((method, self.method_bodies.len()));
+        self.method_bodies.extend_from_slice(body);
+    }
+
+    /// Adds the initial data of a static field, and the FieldRva row that maps the field onto it.
+    pub fn add_field_data(&mut self, field: RowHandle<tables::Field>, data: &[u8]) {
+        self.field_data.resize(align(self.field_data.len(), 8), 0);
+        self.field_rvas.push((field, self.field_data.len()));
+        self.field_data.extend_from_slice(data);
+    }
+
+    /// Adds a managed resource, returning the offset that its ManifestResource row should hold.
+    pub fn add_managed_resource(&mut self, data: &[u8]) -> u32 {
+        self.resources.resize(align(self.resources.len(), 8), 0);
+        let offset = self.resources.len() as u32;
+        self.resources.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        self.resources.extend_from_slice(data);
+        offset
+    }
+
+    /// Adds a resource to the Win32 resource directory.
+    pub fn add_win32_resource(&mut self, resource: Win32Resource) {
+        self.win32_resources.push(resource);
+    }
+
+    /// Lays out the sections and writes the image.
+    pub fn build(mut self) -> Result<Vec<u8>, Error> {
+        let text_rva = SECTION_ALIGNMENT;
+        let iat_size = if self.pe32plus { 0 } else { 8 };
+        let mut layout = TextLayout {
+            cli_header: iat_size,
+            method_bodies: align(iat_size + CLI_HEADER_SIZE, 4),
+            field_data: 0,
+            resources: 0,
+            metadata: 0,
+        };
+        layout.field_data = align(layout.method_bodies + self.method_bodies.len(), 8);
+        layout.resources = align(layout.field_data + self.field_data.len(), 8);
+        layout.metadata = align(layout.resources + self.resources.len(), 4);
+
+        // Bodies and field data come before the metadata, so their RVAs are known before it is serialized
+        let method_bodies_rva = text_rva + layout.method_bodies as u32;
+        for (method, offset) in self.method_rvas.iter() {
+            let row = self.metadata.row_mut(*method).ok_or(Error::InvalidMetadata(format!("{:?} does not exist", method)))?;
+            row.rva = method_bodies_rva + *offset as u32;
+        }
+        let field_data_rva = text_rva + layout.field_data as u32;
+        for (field, offset) in self.field_rvas.iter() {
+            self.metadata.add_row(tables::FieldRva { rva: field_data_rva + *offset as u32, field: field.handle() });
+        }
+        let metadata = std::mem::take(&mut self.metadata).serialize()?;
+
+        let mut text = vec![0u8; layout.metadata];
+        text[layout.method_bodies..(layout.method_bodies + self.method_bodies.len())].copy_from_slice(&self.method_bodies);
+        text[layout.field_data..(layout.field_data + self.field_data.len())].copy_from_slice(&self.field_data);
+        text[layout.resources..(layout.resources + self.resources.len())].copy_from_slice(&self.resources);
+        text.extend_from_slice(&metadata);
+
+        let mut cli_header = Vec::with_capacity(CLI_HEADER_SIZE);
+        cli_header.extend_from_slice(&(CLI_HEADER_SIZE as u32).to_le_bytes());
+        cli_header.extend_from_slice(&2u16.to_le_bytes());
+        cli_header.extend_from_slice(&5u16.to_le_bytes());
+        cli_header.extend_from_slice(&(text_rva + layout.metadata as u32).to_le_bytes());
+        cli_header.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+        cli_header.extend_from_slice(&self.cli_flags.bits().to_le_bytes());
+        let entry_point = if self.entry_point.index() == 0 { 0 } else { self.entry_point.to_metadata_token() };
+        cli_header.extend_from_slice(&entry_point.to_le_bytes());
+        if self.resources.is_empty() {
+            cli_header.extend_from_slice(&[0u8; 8]);
+        } else {
+            cli_header.extend_from_slice(&(text_rva + layout.resources as u32).to_le_bytes());
+            cli_header.extend_from_slice(&(self.resources.len() as u32).to_le_bytes());
+        }
+        cli_header.resize(CLI_HEADER_SIZE, 0);
+        text[layout.cli_header..(layout.cli_header + CLI_HEADER_SIZE)].copy_from_slice(&cli_header);
+
+        let image_base: u64 = match (self.pe32plus, self.dll) {
+            (false, false) => 0x0040_0000,
+            (false, true) => 0x1000_0000,
+            (true, false) => 0x0001_4000_0000,
+            (true, true) => 0x0001_8000_0000,
+        };
+
+        // The import table, and the stub that jumps through the IAT to mscoree's entry point
+        let mut import_table = None;
+        let mut entry_point_stub = 0;
+        if !self.pe32plus {
+            text.resize(align(text.len(), 4), 0);
+            let imports = text.len();
+            let lookup_table = imports + 40;
+            let hint_name = lookup_table + 8;
+            let function: &[u8] = if self.dll { b"_CorDllMain\0" } else { b"_CorExeMain\0" };
+            let dll_name = hint_name + 2 + function.len();
+            let rva = |offset: usize| text_rva + offset as u32;
+
+            text.extend_from_slice(&rva(lookup_table).to_le_bytes());
+            text.extend_from_slice(&[0u8; 8]);
+            text.extend_from_slice(&rva(dll_name).to_le_bytes());
+            text.extend_from_slice(&text_rva.to_le_bytes());
+            text.extend_from_slice(&[0u8; 20]);
+            text.extend_from_slice(&rva(hint_name).to_le_bytes());
+            text.extend_from_slice(&[0u8; 4]);
+            text.extend_from_slice(&[0u8; 2]);
+            text.extend_from_slice(function);
+            text.extend_from_slice(b"mscoree.dll\0");
+            text[0..4].copy_from_slice(&rva(hint_name).to_le_bytes());
+            import_table = Some((rva(imports), (dll_name + 12 - imports) as u32));
+
+            // `jmp [iat]`, with the address operand 4-byte aligned so it can be relocated
+            text.resize(align(text.len() + 2, 4) - 2, 0);
+            entry_point_stub = rva(text.len());
+            text.extend_from_slice(&[0xFF, 0x25]);
+            text.extend_from_slice(&((image_base as u32) + text_rva).to_le_bytes());
+        }
+
+        let mut sections = vec![Section {
+            name: b".text",
+            rva: text_rva,
+            data: text,
+            characteristics: SectionCharacteristics::CNT_CODE | SectionCharacteristics::MEM_EXECUTE | SectionCharacteristics::MEM_READ,
+        }];
+        let next_rva = |sections: &Vec<Section>| {
+            let last = sections.last().unwrap();
+            align(last.rva as usize + last.data.len(), SECTION_ALIGNMENT as usize) as u32
+        };
+
+        let mut resource_table = None;
+        if !self.win32_resources.is_empty() {
+            let rva = next_rva(&sections);
+            let data = write_resources(&self.win32_resources, rva);
+            resource_table = Some((rva, data.len() as u32));
+            sections.push(Section {
+                name: b".rsrc",
+                rva,
+                data,
+                characteristics: SectionCharacteristics::CNT_INITIALIZED_DATA | SectionCharacteristics::MEM_READ,
+            });
+        }
+
+        let mut relocation_table = None;
+        if !self.pe32plus {
+            // A single HIGHLOW fixup for the address in the entry point stub
+            let fixup = entry_point_stub + 2;
+            let mut data = Vec::new();
+            data.extend_from_slice(&(fixup & !0xFFF).to_le_bytes());
+            data.extend_from_slice(&12u32.to_le_bytes());
+            data.extend_from_slice(&((3u16 << 12) | (fixup & 0xFFF) as u16).to_le_bytes());
+            data.extend_from_slice(&0u16.to_le_bytes());
+            let rva = next_rva(&sections);
+            relocation_table = Some((rva, data.len() as u32));
+            sections.push(Section {
+                name: b".reloc",
+                rva,
+                data,
+                characteristics: SectionCharacteristics::CNT_INITIALIZED_DATA
+                    | SectionCharacteristics::MEM_DISCARDABLE
+                    | SectionCharacteristics::MEM_READ,
+            });
+        }
+
+        let optional_header_size = if self.pe32plus { 240 } else { 224 };
+        let headers_size = align(DOS_HEADER.len() + 4 + 20 + optional_header_size + 40 * sections.len(), FILE_ALIGNMENT as usize);
+        let image_size = next_rva(&sections);
+        let raw_size = |section: &Section| align(section.data.len(), FILE_ALIGNMENT as usize) as u32;
+
+        let mut buf = DOS_HEADER.to_vec();
+        buf.extend_from_slice(b"PE\0\0");
+
+        // COFF header
+        let mut characteristics = FileCharacteristics::EXECUTABLE_IMAGE;
+        // Roslyn marks AnyCPU images large address aware for both PE32 and PE32+
+        characteristics |= FileCharacteristics::LARGE_ADDRESS_AWARE;
+        if self.dll {
+            characteristics |= FileCharacteristics::DLL;
+        }
+        buf.extend_from_slice(&(if self.pe32plus { 0x8664u16 } else { 0x014Cu16 }).to_le_bytes());
+        buf.extend_from_slice(&(sections.len() as u16).to_le_bytes());
+        buf.extend_from_slice(&[0u8; 12]);
+        buf.extend_from_slice(&(optional_header_size as u16).to_le_bytes());
+        buf.extend_from_slice(&characteristics.bits().to_le_bytes());
+
+        // Optional header
+        let code_size = raw_size(&sections[0]);
+        let data_size: u32 = sections[1..].iter().map(raw_size).sum();
+        buf.extend_from_slice(&(if self.pe32plus { 0x020Bu16 } else { 0x010Bu16 }).to_le_bytes());
+        buf.extend_from_slice(&[0x30, 0x00]);
+        buf.extend_from_slice(&code_size.to_le_bytes());
+        buf.extend_from_slice(&data_size.to_le_bytes());
+        buf.extend_from_slice(&0u32.to_le_bytes());
+        buf.extend_from_slice(&entry_point_stub.to_le_bytes());
+        buf.extend_from_slice(&text_rva.to_le_bytes());
+        if self.pe32plus {
+            buf.extend_from_slice(&image_base.to_le_bytes());
+        } else {
+            buf.extend_from_slice(&sections.get(1).map_or(0, |s| s.rva).to_le_bytes());
+            buf.extend_from_slice(&(image_base as u32).to_le_bytes());
+        }
+        buf.extend_from_slice(&SECTION_ALIGNMENT.to_le_bytes());
+        buf.extend_from_slice(&FILE_ALIGNMENT.to_le_bytes());
+        for version in [4u16, 0, 0, 0, 4, 0] {
+            buf.extend_from_slice(&version.to_le_bytes());
+        }
+        buf.extend_from_slice(&0u32.to_le_bytes());
+        buf.extend_from_slice(&image_size.to_le_bytes());
+        buf.extend_from_slice(&(headers_size as u32).to_le_bytes());
+        buf.extend_from_slice(&0u32.to_le_bytes());
+        buf.extend_from_slice(&self.subsystem.value().to_le_bytes());
+
+        // Dynamic base, NX compatible, no SEH and terminal server aware, as the C# compiler sets them
+        buf.extend_from_slice(&0x8540u16.to_le_bytes());
+        let (stack_reserve, stack_commit, heap_commit) = if self.pe32plus { (0x40_0000, 0x4000, 0x2000) } else { (0x10_0000, 0x1000, 0x1000) };
+        for value in [stack_reserve, stack_commit, 0x10_0000, heap_commit] {
+            if self.pe32plus {
+                buf.extend_from_slice(&(value as u64).to_le_bytes());
+            } else {
+                buf.extend_from_slice(&(value as u32).to_le_bytes());
+            }
+        }
+        buf.extend_from_slice(&0u32.to_le_bytes());
+        buf.extend_from_slice(&16u32.to_le_bytes());
+
+        // Data directories
+        let iat = if self.pe32plus { None } else { Some((text_rva, 8)) };
+        let cli = Some((text_rva + layout.cli_header as u32, CLI_HEADER_SIZE as u32));
+        let directories = [None, import_table, resource_table, None, None, relocation_table, None, None, None, None, None, None, iat, None, cli, None];
+        for directory in directories {
+            let (rva, size) = directory.unwrap_or((0, 0));
+            buf.extend_from_slice(&rva.to_le_bytes());
+            buf.extend_from_slice(&size.to_le_bytes());
+        }
+
+        // Section headers
+        let mut pointer_to_raw_data = headers_size as u32;
+        for section in sections.iter() {
+            let mut name = [0u8; 8];
+            name[..section.name.len()].copy_from_slice(section.name);
+            buf.extend_from_slice(&name);
+            buf.extend_from_slice(&(section.data.len() as u32).to_le_bytes());
+            buf.extend_from_slice(&section.rva.to_le_bytes());
+            buf.extend_from_slice(&raw_size(section).to_le_bytes());
+            buf.extend_from_slice(&pointer_to_raw_data.to_le_bytes());
+            buf.extend_from_slice(&[0u8; 12]);
+            buf.extend_from_slice(&section.characteristics.bits().to_le_bytes());
+            pointer_to_raw_data += raw_size(section);
+        }
+
+        buf.resize(headers_size, 0);
+        for section in sections.iter() {
+            buf.extend_from_slice(&section.data);
+            buf.resize(align(buf.len(), FILE_ALIGNMENT as usize), 0);
+        }
+        Ok(buf)
+    }
+}
+
+fn align(value: usize, alignment: usize) -> usize {
+    (value + alignment - 1) & !(alignment - 1)
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use crate::cli::tables::TableIndex;
+    use crate::cli::{AssemblyFlags, AssemblyHashAlgorithm, FieldAttributes, GuidHandle, MethodAttributes, MethodImplAttributes, TypeAttributes};
+    use crate::pe::{resource_types, DirectoryType, ImportedFunction, MemoryRange, RelocationType, ResourceName};
+    use crate::{Guid, MetadataImage};
+
+    /// Builds a program whose `Main` method prints "Hello", with a mapped static field.
+    pub fn hello_world() -> (PeBuilder, RowHandle<tables::MethodDef>, RowHandle<tables::Field>) {
+        let mut metadata = MetadataBuilder::new();
+        let module = tables::Module {
+            generation: 0,
+            name: metadata.add_string("Hello.exe"),
+            mvid: metadata.add_guid(Guid::from_bytes(&[0x42; 16])),
+            enc_id: GuidHandle(0),
+            enc_base_id: GuidHandle(0),
+        };
+        metadata.add_row(module);
+        let assembly = tables::Assembly {
+            hash_alg_id: AssemblyHashAlgorithm::SHA1,
+            major_version: 1,
+            minor_version: 0,
+            build_number: 0,
+            revision_number: 0,
+            flags: AssemblyFlags::empty(),
+            public_key: metadata.add_blob(&[]),
+            name: metadata.add_string("Hello"),
+            culture: metadata.add_string(""),
+        };
+        metadata.add_row(assembly);
+        let console = tables::AssemblyRef {
+            major_version: 8,
+            minor_version: 0,
+            build_number: 0,
+            revision_number: 0,
+            flags: AssemblyFlags::empty(),
+            public_key_or_token: metadata.add_blob(&[0xB0, 0x3F, 0x5F, 0x7F, 0x11, 0xD5, 0x0A, 0x3A]),
+            name: metadata.add_string("System.Console"),
+            culture: metadata.add_string(""),
+            hash_value: metadata.add_blob(&[]),
+        };
+        let console = metadata.add_row(console);
+        let console = tables::TypeRef {
+            resolution_scope: console.handle(),
+            name: metadata.add_string("Console"),
+            namespace: metadata.add_string("System"),
+        };
+        let console = metadata.add_row(console);
+        let runtime = tables::AssemblyRef {
+            major_version: 8,
+            minor_version: 0,
+            build_number: 0,
+            revision_number: 0,
+            flags: AssemblyFlags::empty(),
+            public_key_or_token: metadata.add_blob(&[0xB0, 0x3F, 0x5F, 0x7F, 0x11, 0xD5, 0x0A, 0x3A]),
+            name: metadata.add_string("System.Runtime"),
+            culture: metadata.add_string(""),
+            hash_value: metadata.add_blob(&[]),
+        };
+        let runtime = metadata.add_row(runtime);
+        let object = tables::TypeRef {
+            resolution_scope: runtime.handle(),
+            name: metadata.add_string("Object"),
+            namespace: metadata.add_string("System"),
+        };
+        let object = metadata.add_row(object);
+        let write_line = tables::MemberRef {
+            class: console.handle(),
+            name: metadata.add_string("WriteLine"),
+            signature: metadata.add_blob(&[0x00, 0x01, 0x01, 0x0E]),
+        };
+        let write_line = metadata.add_row(write_line);
+
+        for (name, flags, extends) in [("<Module>", 0, TableHandle::new(0, TableIndex::TypeRef)), ("Program", 0x0010_0101, object.handle())] {
+            let row = tables::TypeDef {
+                flags: TypeAttributes::new(flags),
+                type_name: metadata.add_string(name),
+                type_namespace: metadata.add_string(""),
+                extends,
+                field_list: metadata.next_row::<tables::Field>().handle(),
+                method_list: metadata.next_row::<tables::MethodDef>().handle(),
+            };
+            metadata.add_row(row);
+        }
+        let field = tables::Field {
+            flags: FieldAttributes::new(0x0111),
+            name: metadata.add_string("Data"),
+            signature: metadata.add_blob(&[0x06, 0x08]),
+        };
+        let field = metadata.add_row(field);
+        let main = tables::MethodDef {
+            rva: 0,
+            impl_flags: MethodImplAttributes::new(0),
+            flags: MethodAttributes::new(0x0096),
+            name: metadata.add_string("Main"),
+            signature: metadata.add_blob(&[0x00, 0x00, 0x01]),
+            params: metadata.next_row::<tables::Param>().handle(),
+        };
+        let main = metadata.add_row(main);
+        let hello = metadata.add_user_string("Hello");
+
+        // ldstr "Hello"; call void [System.Console]System.Console::WriteLine(string); ret
+        let mut body = vec![0x2E, 0x72];
+        body.extend_from_slice(&(0x7000_0000 | hello.0 as u32).to_le_bytes());
+        body.push(0x28);
+        body.extend_from_slice(&write_line.handle().to_metadata_token().to_le_bytes());
+        body.push(0x2A);
+
+        let mut builder = PeBuilder::new(metadata);
+        builder.add_method_body(main, &body);
+        builder.add_field_data(field, &42u32.to_le_bytes());
+        builder.set_entry_point(main.handle());
+        (builder, main, field)
+    }
+
+    #[test]
+    pub fn build_pe32_executable() {
+        let (mut builder, main, _) = hello_world();
+        let offset = builder.add_managed_resource(b"resource");
+        let name = builder.metadata().add_string("Hello.txt");
+        builder.metadata().add_row(tables::ManifestResource {
+            offset,
+            flags: 1,
+            name,
+            implementation: TableHandle::new(0, TableIndex::File),
+        });
+        builder.add_win32_resource(Win32Resource {
+            resource_type: ResourceName::Id(resource_types::RCDATA),
+            name: ResourceName::Id(1),
+            language: 0,
+            code_page: 0,
+            data: b"native".to_vec(),
+        });
+        let image = builder.build().unwrap();
+
+        let image = MetadataImage::load_data(image).unwrap();
+        let pe = image.pe().unwrap();
+        assert!(!pe.is_pe32plus());
+        assert_eq!(vec![".text", ".rsrc", ".reloc"], pe.sections().iter().map(|s| s.name.as_str()).collect::<Vec<_>>());
+        assert_eq!(main.handle(), image.cli_header().unwrap().entry_point_token);
+
+        let imports = pe.imports().unwrap();
+        assert_eq!("mscoree.dll", imports[0].name);
+        assert_eq!(vec![ImportedFunction::ByName { hint: 0, name: "_CorExeMain".into() }], imports[0].functions);
+        let stub = pe.pe_header().unwrap().entry_point_rva;
+        let relocation = pe.base_relocations().unwrap().next().unwrap().unwrap().relocations().next().unwrap();
+        assert_eq!((RelocationType::HighLow, stub + 2), (relocation.relocation_type, relocation.rva));
+        assert_eq!(Some(MemoryRange::new(0x2000, 8)), pe.directory(DirectoryType::ImportAddressTable));
+
+        let method = image.table::<tables::MethodDef>().read(0).unwrap();
+        let body = pe.read_rva(MemoryRange::new(method.rva, 12)).unwrap();
+        assert_eq!(0x2E, body[0]);
+        assert_eq!(0x2A, body[11]);
+
+        let field_rva = image.table::<tables::FieldRva>().read(0).unwrap();
+        assert_eq!(TableHandle::new(1, TableIndex::Field), field_rva.field);
+        assert_eq!(&42u32.to_le_bytes(), pe.read_rva(MemoryRange::new(field_rva.rva, 4)).unwrap());
+
+        assert_eq!(Some(&b"resource"[..]), image.manifest_resource(0).unwrap());
+        let resources = pe.resources().unwrap();
+        assert_eq!(b"native", pe.resource_data(&resources[0]).unwrap());
+        assert!(image.validate().is_empty(), "{:?}", image.validate());
+    }
+
+    #[test]
+    #[ignore = "needs the dotnet host and an 8.0 or later runtime"]
+    pub fn run_pe32_executable() {
+        let (builder, _, _) = hello_world();
+        let dir = std::env::temp_dir().join(format!("ecma355metadata-run-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Hello.dll"), builder.build().unwrap()).unwrap();
+        std::fs::write(
+            dir.join("Hello.runtimeconfig.json"),
+            r#"{"runtimeOptions":{"tfm":"net8.0","rollForward":"LatestMajor","framework":{"name":"Microsoft.NETCore.App","version":"8.0.0"}}}"#,
+        )
+        .unwrap();
+
+        let output = std::process::Command::new("dotnet").arg(dir.join("Hello.dll")).output().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        assert_eq!("Hello", String::from_utf8_lossy(&output.stdout).trim_end());
+    }
+
+    #[test]
+    pub fn build_pe32plus_library() {
+        let (mut builder, _, _) = hello_world();
+        builder.set_pe32plus(true);
+        builder.set_dll(true);
+        builder.set_entry_point(TableHandle::new(0, TableIndex::MethodDef));
+        let image = MetadataImage::load_data(builder.build().unwrap()).unwrap();
+        let pe = image.pe().unwrap();
+        assert!(pe.is_pe32plus());
+        assert!(pe.coff_header().characteristics.contains(FileCharacteristics::DLL));
+        assert_eq!(1, pe.sections().len());
+        assert!(pe.imports().unwrap().is_empty());
+        assert_eq!(0, image.cli_header().unwrap().entry_point_token.index());
+        assert_eq!(c"Main", image.get_string(image.table::<tables::MethodDef>().read(0).unwrap().name).unwrap());
+    }
+}
@@ -0,0 +1,854 @@
+use std::collections::{HashMap, HashSet};
+
+use sha2::{Digest, Sha256};
+
+use crate::cli::signatures::utils::read_compressed_u32;
+use crate::cli::tables::{self, HeapHandleMut, RowHandle, TableHandle, TableIndex, TableRow};
+use crate::cli::{Access, BlobHandle, CliFlags, FieldAttributes, FieldFlags, GuidHandle, StringHandle};
+use crate::error::Error;
+use crate::pe::{FileCharacteristics, ImageData};
+use crate::writer::{write_compressed_u32, MetadataBuilder, PeBuilder, Win32Resource};
+use crate::{Guid, MetadataImage};
+
+/// The body every method with code gets: a tiny header, then `ldnull; throw`.
+const THROW_NULL: [u8; 3] = [0x0A, 0x14, 0x7A];
+
+/// The tables whose rows are only kept if something in the reference assembly still refers to them.
+const REFERENCE_TABLES: [TableIndex; 3] = [TableIndex::TypeRef, TableIndex::TypeSpec, TableIndex::MemberRef];
+
+/// Names of core libraries, in the order they are preferred when an image references more than one.
+const CORE_LIBRARIES: &[&str] = &["System.Private.CoreLib", "System.Runtime", "netstandard", "mscorlib"];
+
+/// Writes a reference assembly for an assembly, following the rules of the C# compiler's `-refout` option.
+///
+/// Every type is kept, along with the members other assemblies can see. Private members are dropped, and so are
+/// internal ones unless the assembly has `InternalsVisibleToAttribute`. The instance fields of value types are kept
+/// whatever their accessibility, since they decide the layout of the type, and so are methods that explicitly
+/// implement an interface method. Properties and events are kept if any of their accessors are. The entry point is
+/// only kept if its method is, so an executable whose `Main` is private gives a reference assembly with none.
+///
+/// Every method body becomes `throw null`, the TypeRefs, TypeSpecs and MemberRefs that only method bodies used are
+/// dropped, and `ReferenceAssemblyAttribute` is added to the assembly. The MVID is a hash of the written image, so
+/// the same assembly always gives the same reference assembly.
+pub fn write_reference_assembly<D: ImageData>(image: &MetadataImage<D>) -> Result<Vec<u8>, Error> {
+    let selection = Selection::new(image)?;
+    let draft = selection.write(Guid::EMPTY)?;
+    let mut hash = Sha256::digest(&draft)[..16].to_vec();
+    // Mark the hash as a version 4, variant 1 GUID, as the C# compiler does for deterministic MVIDs
+    hash[7] = (hash[7] & 0x0F) | 0x40;
+    hash[8] = (hash[8] & 0x3F) | 0x80;
+    selection.write(Guid::from_bytes(&hash))
+}
+
+/// The rows of an image that go into its reference assembly.
+struct Selection<'a, D: ImageData> {
+    image: &'a MetadataImage<D>,
+    /// Whether each row is kept, for the tables that lose rows.
+    kept: HashMap<TableIndex, Vec<bool>>,
+    /// The new row of each row, and of the row just past the end for list columns, for the tables that lose rows.
+    maps: Vec<(TableIndex, Vec<usize>)>,
+}
+
+impl<'a, D: ImageData> Selection<'a, D> {
+    fn new(image: &'a MetadataImage<D>) -> Result<Selection<'a, D>, Error> {
+        if image.pe().and_then(|pe| pe.pe_header()).is_none() {
+            return Err(Error::NotAPortableExecutable);
+        }
+        let unsupported = [
+            TableIndex::FieldPtr, TableIndex::MethodPtr, TableIndex::ParamPtr, TableIndex::EventPtr,
+            TableIndex::PropertyPtr, TableIndex::EncLog, TableIndex::EncMap,
+        ];
+        if unsupported.iter().any(|&table| image.row_count(table) > 0) {
+            return Err(Error::CannotRewrite("uncompressed and edit-and-continue metadata aren't supported".to_string()));
+        }
+        if image.row_count(TableIndex::Assembly) == 0 {
+            return Err(Error::CannotRewrite("only assemblies have reference assemblies".to_string()));
+        }
+
+        let mut selection = Selection { image, kept: HashMap::new(), maps: Vec::new() };
+        selection.select_members()?;
+        selection.select_attached_rows()?;
+        selection.select_references()?;
+        for (&table, kept) in &selection.kept {
+            let mut map = vec![1];
+            for &keep in kept {
+                map.push(map[map.len() - 1] + keep as usize);
+            }
+            selection.maps.push((table, map));
+        }
+        Ok(selection)
+    }
+
+    /// Picks the fields, methods, parameters, properties and events to keep.
+    fn select_members(&mut self) -> Result<(), Error> {
+        let image = self.image;
+        let internals_visible = self.has_assembly_attribute("System.Runtime.CompilerServices", "InternalsVisibleToAttribute")?;
+        let visible = |access: Access| match access {
+            Access::Public | Access::Family | Access::FamORAssem => true,
+            Access::Assembly | Access::FamANDAssem => internals_visible,
+            Access::Private | Access::CompilerControlled => false,
+        };
+
+        let type_defs = read_rows::<tables::TypeDef, D>(image)?;
+        let mut value_types = Vec::with_capacity(type_defs.len());
+        for type_def in &type_defs {
+            let base = self.type_name(type_def.extends)?;
+            value_types.push(base.as_ref().is_some_and(|(namespace, name)| namespace == "System" && (name == "ValueType" || name == "Enum")));
+        }
+
+        let field_owners = owners(&type_defs, image.row_count(TableIndex::Field), |t| t.field_list.index());
+        let mut fields = Vec::new();
+        for (row, owner) in image.table::<tables::Field>().iter().zip(field_owners) {
+            let row = row?;
+            let instance = !row.flags.flags().contains(FieldFlags::Static);
+            fields.push(visible(row.flags.access()) || (instance && owner.is_some_and(|owner| value_types[owner])));
+        }
+
+        let implementations: HashSet<usize> = read_rows::<tables::MethodImpl, D>(image)?.iter()
+            .filter(|row| row.method_body.table() == TableIndex::MethodDef)
+            .map(|row| row.method_body.index())
+            .collect();
+        let methods = read_rows::<tables::MethodDef, D>(image)?;
+        let kept_methods: Vec<bool> = methods.iter().enumerate()
+            .map(|(i, row)| visible(row.flags.access()) || implementations.contains(&(i + 1)))
+            .collect();
+        let param_owners = owners(&methods, image.row_count(TableIndex::Param), |m| m.params.index());
+        let params = param_owners.iter().map(|owner| owner.is_some_and(|owner| kept_methods[owner])).collect();
+
+        // Properties and events go with their accessors
+        let semantics = read_rows::<tables::MethodSemantics, D>(image)?;
+        let mut properties = vec![None; image.row_count(TableIndex::Property)];
+        let mut events = vec![None; image.row_count(TableIndex::Event)];
+        for row in &semantics {
+            let kept = kept_methods.get(row.method.index().wrapping_sub(1)).copied().unwrap_or(false);
+            let associated = match row.association.table() {
+                TableIndex::Property => properties.get_mut(row.association.index().wrapping_sub(1)),
+                _ => events.get_mut(row.association.index().wrapping_sub(1)),
+            };
+            if let Some(associated) = associated {
+                *associated = Some(associated.unwrap_or(false) || kept);
+            }
+        }
+        let properties: Vec<bool> = properties.into_iter().map(|kept| kept.unwrap_or(true)).collect();
+        let events: Vec<bool> = events.into_iter().map(|kept| kept.unwrap_or(true)).collect();
+
+        let property_maps = read_rows::<tables::PropertyMap, D>(image)?;
+        let property_maps = any_kept_in_lists(&property_maps, &properties, |m| m.property_list.index());
+        let event_maps = read_rows::<tables::EventMap, D>(image)?;
+        let event_maps = any_kept_in_lists(&event_maps, &events, |m| m.event_list.index());
+
+        self.kept.insert(TableIndex::Field, fields);
+        self.kept.insert(TableIndex::MethodDef, kept_methods);
+        self.kept.insert(TableIndex::Param, params);
+        self.kept.insert(TableIndex::Property, properties);
+        self.kept.insert(TableIndex::Event, events);
+        self.kept.insert(TableIndex::PropertyMap, property_maps);
+        self.kept.insert(TableIndex::EventMap, event_maps);
+        Ok(())
+    }
+
+    /// Picks the rows that describe members, which go wherever their members go.
+    fn select_attached_rows(&mut self) -> Result<(), Error> {
+        let image = self.image;
+        // Mapped field data, local signatures and generic method instantiations are only used by method bodies
+        for table in [TableIndex::FieldRva, TableIndex::StandAloneSig, TableIndex::MethodSpec] {
+            self.kept.insert(table, vec![false; image.row_count(table)]);
+        }
+
+        let semantics = self.select::<tables::MethodSemantics>(|s, row| s.is_kept(row.method) && s.is_kept(row.association))?;
+        self.kept.insert(TableIndex::MethodSemantics, semantics);
+        let method_impls = self.select::<tables::MethodImpl>(|s, row| s.is_kept(row.method_body))?;
+        self.kept.insert(TableIndex::MethodImpl, method_impls);
+        let constants = self.select::<tables::Constant>(|s, row| s.is_kept(row.parent))?;
+        self.kept.insert(TableIndex::Constant, constants);
+        let marshals = self.select::<tables::FieldMarshal>(|s, row| s.is_kept(row.parent))?;
+        self.kept.insert(TableIndex::FieldMarshal, marshals);
+        let security = self.select::<tables::DeclSecurity>(|s, row| s.is_kept(row.parent))?;
+        self.kept.insert(TableIndex::DeclSecurity, security);
+        let layouts = self.select::<tables::FieldLayout>(|s, row| s.is_kept(row.field))?;
+        self.kept.insert(TableIndex::FieldLayout, layouts);
+        let imports = self.select::<tables::ImplMap>(|s, row| s.is_kept(row.member_forwarded))?;
+        self.kept.insert(TableIndex::ImplMap, imports);
+        let generic_params = self.select::<tables::GenericParam>(|s, row| s.is_kept(row.owner))?;
+        self.kept.insert(TableIndex::GenericParam, generic_params);
+        let constraints = self.select::<tables::GenericParamConstraint>(|s, row| s.is_kept(row.owner))?;
+        self.kept.insert(TableIndex::GenericParamConstraint, constraints);
+
+        // Attributes on references only matter to the IL that used them
+        let attributes = self.select::<tables::CustomAttribute>(|s, row| {
+            !REFERENCE_TABLES.contains(&row.parent.table()) && s.is_kept(row.parent) && s.is_kept(row.typ)
+        })?;
+        self.kept.insert(TableIndex::CustomAttribute, attributes);
+        Ok(())
+    }
+
+    /// Picks the TypeRefs, TypeSpecs and MemberRefs that the kept rows still refer to.
+    fn select_references(&mut self) -> Result<(), Error> {
+        let image = self.image;
+        let mut references = References::default();
+        for row in image.table::<tables::TypeDef>().iter() {
+            references.add(row?.extends);
+        }
+        for row in image.table::<tables::InterfaceImpl>().iter() {
+            references.add(row?.interface);
+        }
+        for row in self.kept_rows::<tables::Event>()? {
+            references.add(row.event_type);
+        }
+        for row in self.kept_rows::<tables::GenericParamConstraint>()? {
+            references.add(row.constraint);
+        }
+        for row in self.kept_rows::<tables::CustomAttribute>()? {
+            references.add(row.typ);
+        }
+        for row in self.kept_rows::<tables::MethodImpl>()? {
+            references.add(row.method_declaration);
+        }
+        for row in self.kept_rows::<tables::Field>()? {
+            references.add_signature(self.blob(row.signature)?, false)?;
+        }
+        for row in self.kept_rows::<tables::MethodDef>()? {
+            references.add_signature(self.blob(row.signature)?, false)?;
+        }
+        for row in self.kept_rows::<tables::Property>()? {
+            references.add_signature(self.blob(row.typ)?, false)?;
+        }
+
+        while let Some(handle) = references.pending.pop() {
+            match handle.table() {
+                TableIndex::TypeRef => references.add(image.table::<tables::TypeRef>().read(handle.index() - 1)?.resolution_scope),
+                TableIndex::TypeSpec => {
+                    let row = image.table::<tables::TypeSpec>().read(handle.index() - 1)?;
+                    references.add_signature(self.blob(row.signature)?, true)?;
+                }
+                _ => {
+                    let row = image.table::<tables::MemberRef>().read(handle.index() - 1)?;
+                    references.add(row.class);
+                    references.add_signature(self.blob(row.signature)?, false)?;
+                }
+            }
+        }
+
+        for table in REFERENCE_TABLES {
+            let kept = (1..=image.row_count(table)).map(|row| references.reachable.contains(&TableHandle::new(row, table))).collect();
+            self.kept.insert(table, kept);
+        }
+        Ok(())
+    }
+
+    /// Writes the reference assembly with the given MVID.
+    fn write(&self, mvid: Guid) -> Result<Vec<u8>, Error> {
+        let image = self.image;
+        let original = image.pe().ok_or(Error::NotAPortableExecutable)?;
+        let cli_header = image.cli_header().ok_or(Error::CliHeaderNotFound)?;
+        let has_attribute = self.has_assembly_attribute("System.Runtime.CompilerServices", "ReferenceAssemblyAttribute")?;
+        let attribute_scope = if has_attribute { None } else { Some(self.core_library()?) };
+
+        let mut metadata = MetadataBuilder::new();
+        metadata.set_version(image.metadata_header().version.trim_end_matches('\0'));
+        let mut pe = PeBuilder::new(metadata);
+        pe.set_pe32plus(original.is_pe32plus());
+        pe.set_dll(original.coff_header().characteristics.contains(FileCharacteristics::DLL));
+        if let Some(header) = original.pe_header() {
+            pe.set_subsystem(header.subsystem);
+        }
+        pe.set_cli_flags(CliFlags::from_bits_truncate(cli_header.flags.bits()) - CliFlags::STRONGNAMESIGNED - CliFlags::NATIVEENTRYPOINT);
+        let entry_point = cli_header.entry_point_token;
+        if entry_point.table() == TableIndex::MethodDef && entry_point.index() > 0 && self.is_kept(entry_point) {
+            pe.set_entry_point(self.new_handle(entry_point));
+        }
+        for entry in original.resources()? {
+            pe.add_win32_resource(Win32Resource {
+                data: original.resource_data(&entry)?.to_vec(),
+                resource_type: entry.resource_type,
+                name: entry.name,
+                language: entry.language,
+                code_page: entry.code_page,
+            });
+        }
+        let mut resource_offsets = Vec::new();
+        for index in 0..image.row_count(TableIndex::ManifestResource) {
+            resource_offsets.push(image.manifest_resource(index)?.map(|data| pe.add_managed_resource(data)));
+        }
+
+        let metadata = pe.metadata();
+        self.copy::<tables::Module>(metadata, |row, _, metadata| row.mvid = metadata.add_guid(mvid))?;
+        self.copy::<tables::TypeRef>(metadata, |_, _, _| ())?;
+        self.copy::<tables::TypeDef>(metadata, |_, _, _| ())?;
+        self.copy::<tables::Field>(metadata, |row, _, _| {
+            row.flags = FieldAttributes::new(row.flags.bits() & !FieldFlags::HasFieldRVA.bits());
+        })?;
+        let mut bodies = Vec::new();
+        self.copy::<tables::MethodDef>(metadata, |row, index, _| {
+            if row.rva != 0 {
+                bodies.push(RowHandle::new(self.new_handle(TableHandle::new(index + 1, TableIndex::MethodDef)).index()));
+            }
+            row.rva = 0;
+        })?;
+        self.copy::<tables::Param>(metadata, |_, _, _| ())?;
+        self.copy::<tables::InterfaceImpl>(metadata, |_, _, _| ())?;
+        self.copy::<tables::MemberRef>(metadata, |_, _, _| ())?;
+        self.copy::<tables::Constant>(metadata, |_, _, _| ())?;
+        self.copy::<tables::CustomAttribute>(metadata, |_, _, _| ())?;
+        self.copy::<tables::FieldMarshal>(metadata, |_, _, _| ())?;
+        self.copy::<tables::DeclSecurity>(metadata, |_, _, _| ())?;
+        self.copy::<tables::ClassLayout>(metadata, |_, _, _| ())?;
+        self.copy::<tables::FieldLayout>(metadata, |_, _, _| ())?;
+        self.copy::<tables::EventMap>(metadata, |_, _, _| ())?;
+        self.copy::<tables::Event>(metadata, |_, _, _| ())?;
+        self.copy::<tables::PropertyMap>(metadata, |_, _, _| ())?;
+        self.copy::<tables::Property>(metadata, |_, _, _| ())?;
+        self.copy::<tables::MethodSemantics>(metadata, |_, _, _| ())?;
+        self.copy::<tables::MethodImpl>(metadata, |_, _, _| ())?;
+        self.copy::<tables::ModuleRef>(metadata, |_, _, _| ())?;
+        self.copy::<tables::TypeSpec>(metadata, |_, _, _| ())?;
+        self.copy::<tables::ImplMap>(metadata, |_, _, _| ())?;
+        self.copy::<tables::Assembly>(metadata, |_, _, _| ())?;
+        self.copy::<tables::AssemblyProcessor>(metadata, |_, _, _| ())?;
+        self.copy::<tables::AssemblyOS>(metadata, |_, _, _| ())?;
+        self.copy::<tables::AssemblyRef>(metadata, |_, _, _| ())?;
+        self.copy::<tables::AssemblyRefProcessor>(metadata, |_, _, _| ())?;
+        self.copy::<tables::AssemblyRefOS>(metadata, |_, _, _| ())?;
+        self.copy::<tables::File>(metadata, |_, _, _| ())?;
+        self.copy::<tables::ExportedType>(metadata, |_, _, _| ())?;
+        self.copy::<tables::ManifestResource>(metadata, |row, index, _| {
+            if let Some(offset) = resource_offsets[index] {
+                row.offset = offset;
+            }
+        })?;
+        self.copy::<tables::NestedClass>(metadata, |_, _, _| ())?;
+        self.copy::<tables::GenericParam>(metadata, |_, _, _| ())?;
+        self.copy::<tables::GenericParamConstraint>(metadata, |_, _, _| ())?;
+
+        if let Some(scope) = attribute_scope {
+            let attribute = tables::TypeRef {
+                resolution_scope: scope,
+                name: metadata.add_string("ReferenceAssemblyAttribute"),
+                namespace: metadata.add_string("System.Runtime.CompilerServices"),
+            };
+            let attribute = metadata.add_row(attribute);
+            let constructor = tables::MemberRef {
+                class: attribute.handle(),
+                name: metadata.add_string(".ctor"),
+                signature: metadata.add_blob(&[0x20, 0x00, 0x01]),
+            };
+            let constructor = metadata.add_row(constructor);
+            let custom_attribute = tables::CustomAttribute {
+                parent: TableHandle::new(1, TableIndex::Assembly),
+                typ: constructor.handle(),
+                value: metadata.add_blob(&[0x01, 0x00, 0x00, 0x00]),
+            };
+            metadata.add_row(custom_attribute);
+        }
+
+        for method in bodies {
+            pe.add_method_body(method, &THROW_NULL);
+        }
+        pe.build()
+    }
+
+    /// Copies the kept rows of a table, pointing their references at the new rows and their heap handles at the
+    /// new heaps. `fix` gets each row with its 0-based index in the original table, to change anything else.
+    fn copy<T: TableRow + 'static>(&self, metadata: &mut MetadataBuilder, mut fix: impl FnMut(&mut T, usize, &mut MetadataBuilder)) -> Result<(), Error> {
+        let kept = self.kept.get(&T::INDEX);
+        let signature = match T::INDEX {
+            TableIndex::TypeSpec => Some(true),
+            TableIndex::Field | TableIndex::MethodDef | TableIndex::MemberRef | TableIndex::Property => Some(false),
+            _ => None,
+        };
+        for (index, row) in self.image.table::<T>().iter().enumerate() {
+            if kept.is_some_and(|kept| !kept[index]) {
+                continue;
+            }
+            let mut row = row?;
+            for (table, map) in &self.maps {
+                row.remap(*table, map);
+            }
+            row.for_each_heap_handle(|handle| {
+                match handle {
+                    HeapHandleMut::String(handle) => {
+                        *handle = metadata.add_string(&self.string(*handle)?);
+                    }
+                    HeapHandleMut::Blob(handle) => {
+                        let value = self.blob(*handle)?;
+                        *handle = match signature {
+                            Some(is_type) => metadata.add_blob(&rewrite_signature(value, is_type, |h| self.new_handle(h))?),
+                            None => metadata.add_blob(value),
+                        };
+                    }
+                    // The only GUIDs are the MVID, which `fix` sets, and edit-and-continue ids, which are dropped
+                    HeapHandleMut::Guid(handle) => *handle = GuidHandle(0),
+                }
+                Ok(())
+            })?;
+            fix(&mut row, index, metadata);
+            metadata.add_row(row);
+        }
+        Ok(())
+    }
+
+    /// Works out which rows of a table to keep.
+    fn select<T: TableRow>(&self, mut keep: impl FnMut(&Self, &T) -> bool) -> Result<Vec<bool>, Error> {
+        self.image.table::<T>().iter().map(|row| Ok(keep(self, &row?))).collect()
+    }
+
+    fn kept_rows<T: TableRow>(&self) -> Result<Vec<T>, Error> {
+        let kept = self.kept.get(&T::INDEX);
+        let mut rows = Vec::new();
+        for (index, row) in self.image.table::<T>().iter().enumerate() {
+            if kept.is_none_or(|kept| kept[index]) {
+                rows.push(row?);
+            }
+        }
+        Ok(rows)
+    }
+
+    /// Whether the row a handle refers to is kept, which it is for tables that keep every row.
+    fn is_kept(&self, handle: TableHandle) -> bool {
+        match self.kept.get(&handle.table()) {
+            Some(kept) => kept.get(handle.index().wrapping_sub(1)).copied().unwrap_or(false),
+            None => true,
+        }
+    }
+
+    /// Gets the handle of a kept row in the reference assembly.
+    fn new_handle(&self, handle: TableHandle) -> TableHandle {
+        match self.maps.iter().find(|(table, _)| *table == handle.table()) {
+            Some((_, map)) if handle.index() > 0 => TableHandle::new(map[handle.index() - 1], handle.table()),
+            _ => handle,
+        }
+    }
+
+    /// Gets a string, which is empty for the null handle even if the image has no string heap.
+    fn string(&self, handle: StringHandle) -> Result<String, Error> {
+        match handle.0 {
+            0 => Ok(String::new()),
+            _ => self.image.get_string(handle).map(|s| s.to_string_lossy().into_owned()).ok_or(Error::InvalidHeapReference),
+        }
+    }
+
+    /// Gets a blob, which is empty for the null handle even if the image has no blob heap.
+    fn blob(&self, handle: BlobHandle) -> Result<&'a [u8], Error> {
+        match handle.0 {
+            0 => Ok(&[]),
+            _ => self.image.get_blob(handle).ok_or(Error::InvalidHeapReference),
+        }
+    }
+
+    /// Gets the namespace and name of a TypeDef or TypeRef, or `None` for anything else.
+    fn type_name(&self, handle: TableHandle) -> Result<Option<(String, String)>, Error> {
+        let image = self.image;
+        let (namespace, name) = match handle.table() {
+            _ if handle.index() == 0 => return Ok(None),
+            TableIndex::TypeRef => {
+                let row = image.table::<tables::TypeRef>().read(handle.index() - 1)?;
+                (row.namespace, row.name)
+            }
+            TableIndex::TypeDef => {
+                let row = image.table::<tables::TypeDef>().read(handle.index() - 1)?;
+                (row.type_namespace, row.type_name)
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some((self.string(namespace)?, self.string(name)?)))
+    }
+
+    /// Whether the assembly has a custom attribute of the given type.
+    fn has_assembly_attribute(&self, namespace: &str, name: &str) -> Result<bool, Error> {
+        let image = self.image;
+        for row in image.table::<tables::CustomAttribute>().iter() {
+            let row = row?;
+            if row.parent.table() != TableIndex::Assembly || row.typ.table() != TableIndex::MemberRef {
+                continue;
+            }
+            let constructor = image.table::<tables::MemberRef>().read(row.typ.index() - 1)?;
+            if self.type_name(constructor.class)?.is_some_and(|(ns, n)| ns == namespace && n == name) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Gets the scope of the image's references to the core library, which is where the attributes the compiler adds
+    /// live.
+    ///
+    /// `System.Object` is the best guide, but an image of only interfaces and enums never refers to it, so other
+    /// `System` types that come from the core library will do, and failing that an assembly with a core library name.
+    /// An image that defines `System.Object` is the core library, so its own module is the scope.
+    fn core_library(&self) -> Result<TableHandle, Error> {
+        let image = self.image;
+        if image.find_type_def("System", "Object").is_some() {
+            return Ok(TableHandle::new(1, TableIndex::Module));
+        }
+        let mut system_scope = None;
+        for row in image.table::<tables::TypeRef>().iter() {
+            let row = row?;
+            if self.string(row.namespace)? != "System" {
+                continue;
+            }
+            match self.string(row.name)?.as_str() {
+                "Object" => return Ok(row.resolution_scope),
+                "ValueType" | "Enum" | "Attribute" => {
+                    system_scope.get_or_insert(row.resolution_scope);
+                }
+                _ => {}
+            }
+        }
+        if let Some(scope) = system_scope {
+            return Ok(scope);
+        }
+
+        let mut core_libraries = Vec::new();
+        for (index, row) in image.table::<tables::AssemblyRef>().iter().enumerate() {
+            let name = self.string(row?.name)?;
+            if let Some(rank) = CORE_LIBRARIES.iter().position(|n| *n == name) {
+                core_libraries.push((rank, index));
+            }
+        }
+        match core_libraries.into_iter().min() {
+            Some((_, index)) => Ok(TableHandle::new(index + 1, TableIndex::AssemblyRef)),
+            None => Err(Error::CannotRewrite("the image doesn't reference its core library".to_string())),
+        }
+    }
+}
+
+/// The TypeRefs, TypeSpecs and MemberRefs found so far, with the ones whose own references haven't been followed.
+#[derive(Default)]
+struct References {
+    reachable: HashSet<TableHandle>,
+    pending: Vec<TableHandle>,
+}
+
+impl References {
+    fn add(&mut self, handle: TableHandle) {
+        if handle.index() > 0 && REFERENCE_TABLES.contains(&handle.table()) && self.reachable.insert(handle) {
+            self.pending.push(handle);
+        }
+    }
+
+    fn add_signature(&mut self, signature: &[u8], is_type: bool) -> Result<(), Error> {
+        rewrite_signature(signature, is_type, |handle| {
+            self.add(handle);
+            handle
+        })?;
+        Ok(())
+    }
+}
+
+fn read_rows<T: TableRow, D: ImageData>(image: &MetadataImage<D>) -> Result<Vec<T>, Error> {
+    image.table::<T>().iter().collect()
+}
+
+/// Finds the 0-based owner of each of `count` rows in a list column, such as the TypeDef of each field.
+fn owners<T>(rows: &[T], count: usize, list: impl Fn(&T) -> usize) -> Vec<Option<usize>> {
+    let mut owners = vec![None; count];
+    for (owner, row) in rows.iter().enumerate() {
+        let start = list(row).max(1);
+        let end = rows.get(owner + 1).map_or(count + 1, |next| list(next).max(1));
+        for slot in owners.iter_mut().take(end.saturating_sub(1)).skip(start - 1) {
+            *slot = Some(owner);
+        }
+    }
+    owners
+}
+
+/// Whether each row's list, such as the properties of a PropertyMap, still has a kept row in it.
+fn any_kept_in_lists<T>(rows: &[T], kept: &[bool], list: impl Fn(&T) -> usize) -> Vec<bool> {
+    let owners = owners(rows, kept.len(), list);
+    let mut any = vec![false; rows.len()];
+    for (owner, &kept) in owners.iter().zip(kept) {
+        if let (Some(owner), true) = (owner, kept) {
+            any[*owner] = true;
+        }
+    }
+    any
+}
+
+/// Copies a type or member signature, passing each TypeDef, TypeRef or TypeSpec it refers to through `f`.
+fn rewrite_signature(signature: &[u8], is_type: bool, f: impl FnMut(TableHandle) -> TableHandle) -> Result<Vec<u8>, Error> {
+    if signature.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut rewriter = SignatureRewriter { input: signature, output: Vec::with_capacity(signature.len()), f };
+    if is_type {
+        rewriter.type_sig()?;
+    } else {
+        rewriter.member_sig()?;
+    }
+    rewriter.output.extend_from_slice(rewriter.input);
+    Ok(rewriter.output)
+}
+
+struct SignatureRewriter<'a, F> {
+    input: &'a [u8],
+    output: Vec<u8>,
+    f: F,
+}
+
+impl<F: FnMut(TableHandle) -> TableHandle> SignatureRewriter<'_, F> {
+    fn byte(&mut self) -> Result<u8, Error> {
+        let (&byte, rest) = self.input.split_first().ok_or_else(|| Error::InvalidMetadata("truncated signature".to_string()))?;
+        self.input = rest;
+        self.output.push(byte);
+        Ok(byte)
+    }
+
+    fn compressed(&mut self) -> Result<u32, Error> {
+        let value = read_compressed_u32(&mut self.input)?;
+        write_compressed_u32(&mut self.output, value);
+        Ok(value)
+    }
+
+    /// Rewrites a TypeDefOrRefEncoded type.
+    fn type_handle(&mut self) -> Result<(), Error> {
+        let coded = read_compressed_u32(&mut self.input)?;
+        let table = match coded & 0x03 {
+            0 => TableIndex::TypeDef,
+            1 => TableIndex::TypeRef,
+            2 => TableIndex::TypeSpec,
+            _ => return Err(Error::InvalidCodedIndex),
+        };
+        let handle = (self.f)(TableHandle::new((coded >> 2) as usize, table));
+        let tag = match handle.table() {
+            TableIndex::TypeDef => 0,
+            TableIndex::TypeRef => 1,
+            _ => 2,
+        };
+        write_compressed_u32(&mut self.output, ((handle.index() as u32) << 2) | tag);
+        Ok(())
+    }
+
+    fn type_sig(&mut self) -> Result<(), Error> {
+        match self.byte()? {
+            0x01..=0x0E | 0x16 | 0x18 | 0x19 | 0x1C => {}
+            // PTR, BYREF, SZARRAY and PINNED wrap another type
+            0x0F | 0x10 | 0x1D | 0x45 => self.type_sig()?,
+            // VALUETYPE and CLASS
+            0x11 | 0x12 => self.type_handle()?,
+            // VAR and MVAR
+            0x13 | 0x1E => {
+                self.compressed()?;
+            }
+            // ARRAY: the element type, rank, sizes and lower bounds
+            0x14 => {
+                self.type_sig()?;
+                self.compressed()?;
+                for _ in 0..2 {
+                    let count = self.compressed()?;
+                    for _ in 0..count {
+                        self.compressed()?;
+                    }
+                }
+            }
+            // GENERICINST: the generic type and its arguments
+            0x15 => {
+                self.type_sig()?;
+                let count = self.compressed()?;
+                for _ in 0..count {
+                    self.type_sig()?;
+                }
+            }
+            0x1B => self.member_sig()?,
+            // CMOD_REQD and CMOD_OPT come before the type they modify
+            0x1F | 0x20 => {
+                self.type_handle()?;
+                self.type_sig()?;
+            }
+            other => return Err(Error::InvalidMetadata(format!("unknown element type 0x{:02X} in signature", other))),
+        }
+        Ok(())
+    }
+
+    /// Rewrites a field, method, property, local variable or method instantiation signature.
+    fn member_sig(&mut self) -> Result<(), Error> {
+        let header = self.byte()?;
+        match header & 0x0F {
+            0x06 => self.type_sig(),
+            0x07 | 0x0A => {
+                let count = self.compressed()?;
+                for _ in 0..count {
+                    self.type_sig()?;
+                }
+                Ok(())
+            }
+            _ => {
+                if header & 0x10 != 0 {
+                    self.compressed()?;
+                }
+                let count = self.compressed()?;
+                self.type_sig()?;
+                for _ in 0..count {
+                    // The SENTINEL before the vararg parameters of a call site
+                    if self.input.first() == Some(&0x41) {
+                        self.byte()?;
+                    }
+                    self.type_sig()?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ilasm::assemble;
+    use crate::pe::MemoryRange;
+
+    const LIBRARY: &str = r#"
+        .assembly extern System.Runtime { .publickeytoken = (B0 3F 5F 7F 11 D5 0A 3A) .ver 8:0:0:0 }
+        .assembly extern System.Console { .publickeytoken = (B0 3F 5F 7F 11 D5 0A 3A) .ver 8:0:0:0 }
+        .assembly Library { .ver 1:0:0:0 }
+
+        .class public auto ansi beforefieldinit Lib.Widget extends [System.Runtime]System.Object implements [System.Runtime]System.IDisposable {
+            .field public int32 Size
+            .field private string name
+            .method public hidebysig specialname rtspecialname instance void .ctor() cil managed {
+                ldarg.0
+                call instance void [System.Runtime]System.Object::.ctor()
+                ret
+            }
+            .method public hidebysig specialname instance int32 get_Size() cil managed {
+                ldarg.0
+                ldfld int32 Lib.Widget::Size
+                ret
+            }
+            .method private hidebysig specialname instance string get_Name() cil managed {
+                ldarg.0
+                ldfld string Lib.Widget::name
+                ret
+            }
+            .method assembly hidebysig instance void Log() cil managed {
+                ldstr "log"
+                call void [System.Console]System.Console::WriteLine(string)
+                ret
+            }
+            .method private hidebysig newslot virtual final instance void System.IDisposable.Dispose() cil managed {
+                .override [System.Runtime]System.IDisposable::Dispose
+                ret
+            }
+            .property instance int32 Size() {
+                .get instance int32 Lib.Widget::get_Size()
+            }
+            .property instance string Name() {
+                .get instance string Lib.Widget::get_Name()
+            }
+            .method private hidebysig specialname instance void add_Changed(class [System.Runtime]System.EventHandler 'value') cil managed {
+                ret
+            }
+            .method private hidebysig specialname instance void remove_Changed(class [System.Runtime]System.EventHandler 'value') cil managed {
+                ret
+            }
+            .event [System.Runtime]System.EventHandler Changed {
+                .addon instance void Lib.Widget::add_Changed(class [System.Runtime]System.EventHandler)
+                .removeon instance void Lib.Widget::remove_Changed(class [System.Runtime]System.EventHandler)
+            }
+        }
+
+        .class public sequential ansi sealed beforefieldinit Lib.Point extends [System.Runtime]System.ValueType {
+            .field private int32 x
+            .field private int32 y
+            .field private static int32 count
+        }
+    "#;
+
+    fn names<T: TableRow, D: ImageData>(image: &MetadataImage<D>, name: impl Fn(&T) -> crate::cli::StringHandle) -> Vec<String> {
+        image.table::<T>().iter()
+            .map(|row| image.get_string(name(&row.unwrap())).unwrap().to_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    pub fn write_reference_assembly_of_library() {
+        let original = MetadataImage::load_data(assemble(LIBRARY).unwrap()).unwrap();
+        let reference = write_reference_assembly(&original).unwrap();
+        let image = MetadataImage::load_data(&reference[..]).unwrap();
+        assert!(image.validate().is_empty(), "{:?}", image.validate());
+
+        // Private members go, except the instance fields of the struct and the explicit interface implementation
+        assert_eq!(vec!["Size", "x", "y"], names::<tables::Field, _>(&image, |f| f.name));
+        assert_eq!(
+            vec![".ctor", "get_Size", "System.IDisposable.Dispose"],
+            names::<tables::MethodDef, _>(&image, |m| m.name)
+        );
+        assert_eq!(vec!["Size"], names::<tables::Property, _>(&image, |p| p.name));
+        assert_eq!(1, image.row_count(TableIndex::MethodSemantics));
+        assert_eq!(1, image.row_count(TableIndex::MethodImpl));
+
+        // Console was only used by a method body, EventHandler only by the private event, and the attribute's type is new
+        assert_eq!(
+            vec!["Object", "ValueType", "IDisposable", "ReferenceAssemblyAttribute"],
+            names::<tables::TypeRef, _>(&image, |t| t.name)
+        );
+        let attribute = image.table::<tables::CustomAttribute>().read(0).unwrap();
+        assert_eq!(TableHandle::new(1, TableIndex::Assembly), attribute.parent);
+        assert_eq!(Some(&[0x01, 0x00, 0x00, 0x00][..]), image.get_blob(attribute.value));
+
+        let pe = image.pe().unwrap();
+        for row in image.table::<tables::MethodDef>().iter() {
+            let row = row.unwrap();
+            assert_eq!(&THROW_NULL, pe.read_rva(MemoryRange::new(row.rva, 3)).unwrap());
+        }
+
+        // The MVID comes from the content, so writing again gives the same image
+        assert!(reference == write_reference_assembly(&original).unwrap());
+        let module = image.table::<tables::Module>().read(0).unwrap();
+        let mvid = image.get_guid(module.mvid).unwrap();
+        assert_ne!(Guid::EMPTY, mvid);
+        assert_ne!(original.get_guid(original.table::<tables::Module>().read(0).unwrap().mvid), Some(mvid));
+
+        // A reference assembly already has the attribute, so it isn't added twice
+        let again = MetadataImage::load_data(write_reference_assembly(&image).unwrap()).unwrap();
+        assert_eq!(1, again.row_count(TableIndex::CustomAttribute));
+        assert_eq!(3, again.row_count(TableIndex::MethodDef));
+    }
+
+    #[test]
+    pub fn find_core_library_without_object() {
+        let source = r#"
+            .assembly extern System.Private.Uri { .publickeytoken = (B0 3F 5F 7F 11 D5 0A 3A) .ver 8:0:0:0 }
+            .assembly extern System.Runtime { .publickeytoken = (B0 3F 5F 7F 11 D5 0A 3A) .ver 8:0:0:0 }
+            .assembly Shapes { .ver 1:0:0:0 }
+
+            .class interface public abstract auto ansi Shapes.IShape {
+                .method public hidebysig newslot abstract virtual instance class [System.Private.Uri]System.Uri Source() cil managed {}
+            }
+            .class public auto ansi sealed enum Shapes.Kind {
+                .field public specialname rtspecialname int32 value__
+            }
+        "#;
+        let original = MetadataImage::load_data(assemble(source).unwrap()).unwrap();
+        let image = MetadataImage::load_data(write_reference_assembly(&original).unwrap()).unwrap();
+        assert!(image.validate().is_empty(), "{:?}", image.validate());
+        assert_eq!(vec!["Uri", "Enum", "ReferenceAssemblyAttribute"], names::<tables::TypeRef, _>(&image, |t| t.name));
+
+        // The attribute comes from the scope of System.Enum, not from the first assembly referenced
+        let attribute_type = image.table::<tables::TypeRef>().read(2).unwrap();
+        assert_eq!(TableHandle::new(2, TableIndex::AssemblyRef), attribute_type.resolution_scope);
+        let scope = image.table::<tables::AssemblyRef>().read(1).unwrap();
+        assert_eq!(c"System.Runtime", image.get_string(scope.name).unwrap());
+    }
+
+    #[test]
+    pub fn write_reference_assembly_of_core_library() {
+        let source = r#"
+            .assembly Core { .ver 1:0:0:0 }
+
+            .class public auto ansi beforefieldinit System.Object {
+                .method public hidebysig specialname rtspecialname instance void .ctor() cil managed {
+                    ret
+                }
+            }
+        "#;
+        let original = MetadataImage::load_data(assemble(source).unwrap()).unwrap();
+        let image = MetadataImage::load_data(write_reference_assembly(&original).unwrap()).unwrap();
+        assert!(image.validate().is_empty(), "{:?}", image.validate());
+        assert_eq!(0, image.row_count(TableIndex::AssemblyRef));
+
+        // The attribute is expected in the module itself
+        let attribute_type = image.table::<tables::TypeRef>().read(0).unwrap();
+        assert_eq!(c"ReferenceAssemblyAttribute", image.get_string(attribute_type.name).unwrap());
+        assert_eq!(TableHandle::new(1, TableIndex::Module), attribute_type.resolution_scope);
+    }
+}
@@ -0,0 +1,188 @@
+use crate::pe::ResourceName;
+
+/// A Win32 resource to write to the `.rsrc` section.
+pub struct Win32Resource {
+    pub resource_type: ResourceName,
+    pub name: ResourceName,
+    pub language: u16,
+    pub code_page: u32,
+    pub data: Vec<u8>,
+}
+
+/// The resources under each name, grouped under each type.
+type ResourceTree<'a> = Vec<(&'a ResourceName, Vec<(&'a ResourceName, Vec<&'a Win32Resource>)>)>;
+
+const DIRECTORY_SIZE: usize = 16;
+const ENTRY_SIZE: usize = 8;
+const DATA_ENTRY_SIZE: usize = 16;
+const HIGH_BIT: u32 = 0x8000_0000;
+
+/// Writes the type, name and language levels of a resource directory, for a section loaded at `rva`.
+///
+/// The directories come first, then the data entries, the names, and finally the resource data. Named entries are
+/// sorted before numbered ones in each directory, as the loader expects.
+pub fn write_resources(resources: &[Win32Resource], rva: u32) -> Vec<u8> {
+    let mut sorted: Vec<&Win32Resource> = resources.iter().collect();
+    sorted.sort_by(|a, b| {
+        sort_key(&a.resource_type).cmp(&sort_key(&b.resource_type))
+            .then_with(|| sort_key(&a.name).cmp(&sort_key(&b.name)))
+            .then_with(|| a.language.cmp(&b.language))
+    });
+
+    // Group the leaves into a tree of types, then names, then languages
+    let mut tree: ResourceTree = Vec::new();
+    for resource in sorted {
+        if tree.last().is_none_or(|(t, _)| **t != resource.resource_type) {
+            tree.push((&resource.resource_type, Vec::new()));
+        }
+        let names = &mut tree.last_mut().unwrap().1;
+        if names.last().is_none_or(|(n, _)| **n != resource.name) {
+            names.push((&resource.name, Vec::new()));
+        }
+        names.last_mut().unwrap().1.push(resource);
+    }
+
+    // Work out where every piece goes before writing anything
+    let type_dirs_start = DIRECTORY_SIZE + ENTRY_SIZE * tree.len();
+    let name_dirs_start = type_dirs_start + tree.iter().map(|(_, names)| DIRECTORY_SIZE + ENTRY_SIZE * names.len()).sum::<usize>();
+    let data_entries_start = name_dirs_start + tree.iter()
+        .flat_map(|(_, names)| names.iter())
+        .map(|(_, languages)| DIRECTORY_SIZE + ENTRY_SIZE * languages.len())
+        .sum::<usize>();
+    let leaf_count: usize = tree.iter().flat_map(|(_, names)| names.iter()).map(|(_, languages)| languages.len()).sum();
+    let strings_start = data_entries_start + DATA_ENTRY_SIZE * leaf_count;
+
+    let mut strings = Vec::new();
+    let mut name_entry = |name: &ResourceName| match name {
+        ResourceName::Id(id) => *id as u32,
+        ResourceName::Name(name) => {
+            let offset = strings_start + strings.len();
+            let units: Vec<u16> = name.encode_utf16().collect();
+            strings.extend_from_slice(&(units.len() as u16).to_le_bytes());
+            for unit in units {
+                strings.extend_from_slice(&unit.to_le_bytes());
+            }
+            HIGH_BIT | offset as u32
+        }
+    };
+
+    let mut type_dir = type_dirs_start;
+    let mut name_dir = name_dirs_start;
+    let mut data_entry = data_entries_start;
+    let mut root = directory_header(tree.iter().map(|(t, _)| *t));
+    let mut type_dirs = Vec::new();
+    let mut name_dirs = Vec::new();
+    let mut leaves = Vec::new();
+    for (resource_type, names) in tree.iter() {
+        root.extend_from_slice(&name_entry(resource_type).to_le_bytes());
+        root.extend_from_slice(&(HIGH_BIT | type_dir as u32).to_le_bytes());
+        type_dir += DIRECTORY_SIZE + ENTRY_SIZE * names.len();
+
+        type_dirs.extend(directory_header(names.iter().map(|(n, _)| *n)));
+        for (name, languages) in names.iter() {
+            type_dirs.extend_from_slice(&name_entry(name).to_le_bytes());
+            type_dirs.extend_from_slice(&(HIGH_BIT | name_dir as u32).to_le_bytes());
+            name_dir += DIRECTORY_SIZE + ENTRY_SIZE * languages.len();
+
+            let language_names: Vec<_> = languages.iter().map(|r| ResourceName::Id(r.language)).collect();
+            name_dirs.extend(directory_header(language_names.iter()));
+            for resource in languages.iter() {
+                name_dirs.extend_from_slice(&(resource.language as u32).to_le_bytes());
+                name_dirs.extend_from_slice(&(data_entry as u32).to_le_bytes());
+                data_entry += DATA_ENTRY_SIZE;
+                leaves.push(*resource);
+            }
+        }
+    }
+    let mut directories = root;
+    directories.extend(type_dirs);
+    directories.extend(name_dirs);
+
+    // The data entries hold RVAs, unlike every other offset in the tree
+    let mut data = Vec::new();
+    let data_start = align8(strings_start + strings.len());
+    for resource in leaves.iter() {
+        let offset = data_start + data.len();
+        directories.extend_from_slice(&(rva + offset as u32).to_le_bytes());
+        directories.extend_from_slice(&(resource.data.len() as u32).to_le_bytes());
+        directories.extend_from_slice(&resource.code_page.to_le_bytes());
+        directories.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&resource.data);
+        data.resize(align8(data.len()), 0);
+    }
+
+    directories.extend(strings);
+    directories.resize(data_start, 0);
+    directories.extend(data);
+    directories
+}
+
+/// Writes the header of a directory whose entries have the given names.
+fn directory_header<'a>(names: impl Iterator<Item = &'a ResourceName>) -> Vec<u8> {
+    let (named, ids) = names.fold((0u16, 0u16), |(named, ids), name| match name {
+        ResourceName::Name(_) => (named + 1, ids),
+        ResourceName::Id(_) => (named, ids + 1),
+    });
+    let mut buf = vec![0u8; 12];
+    buf.extend_from_slice(&named.to_le_bytes());
+    buf.extend_from_slice(&ids.to_le_bytes());
+    buf
+}
+
+fn sort_key(name: &ResourceName) -> (bool, String, u16) {
+    match name {
+        ResourceName::Name(name) => (false, name.to_uppercase(), 0),
+        ResourceName::Id(id) => (true, String::new(), *id),
+    }
+}
+
+fn align8(value: usize) -> usize {
+    (value + 7) & !7
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pe::{read_resources, resource_types};
+
+    #[test]
+    pub fn resources_round_trip() {
+        let resources = vec![
+            Win32Resource {
+                resource_type: ResourceName::Id(resource_types::VERSION),
+                name: ResourceName::Id(1),
+                language: 0,
+                code_page: 0,
+                data: vec![1, 2, 3],
+            },
+            Win32Resource {
+                resource_type: ResourceName::Id(resource_types::RCDATA),
+                name: ResourceName::Name("APP".into()),
+                language: 1033,
+                code_page: 1200,
+                data: vec![4, 5],
+            },
+            Win32Resource {
+                resource_type: ResourceName::Id(resource_types::RCDATA),
+                name: ResourceName::Name("APP".into()),
+                language: 1031,
+                code_page: 0,
+                data: vec![6],
+            },
+        ];
+        let buf = write_resources(&resources, 0x4000);
+        let entries = read_resources(&buf).unwrap();
+        assert_eq!(3, entries.len());
+
+        let data = |i: usize| {
+            let start = (entries[i].data.start - 0x4000) as usize;
+            &buf[start..(start + entries[i].data.len as usize)]
+        };
+        assert_eq!(ResourceName::Id(resource_types::RCDATA), entries[0].resource_type);
+        assert_eq!(ResourceName::Name("APP".into()), entries[0].name);
+        assert_eq!((1031, &[6u8][..]), (entries[0].language, data(0)));
+        assert_eq!((1033, 1200, &[4u8, 5][..]), (entries[1].language, entries[1].code_page, data(1)));
+        assert_eq!(ResourceName::Id(resource_types::VERSION), entries[2].resource_type);
+        assert_eq!(&[1u8, 2, 3][..], data(2));
+    }
+}